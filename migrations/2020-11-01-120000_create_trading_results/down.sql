@@ -0,0 +1 @@
+DROP TABLE trading_results
\ No newline at end of file
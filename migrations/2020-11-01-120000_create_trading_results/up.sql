@@ -0,0 +1,6 @@
+CREATE TABLE trading_results (
+  portfolio TEXT NOT NULL,
+  year INTEGER NOT NULL,
+  profit TEXT NOT NULL,
+  PRIMARY KEY (portfolio, year)
+) WITHOUT ROWID
\ No newline at end of file
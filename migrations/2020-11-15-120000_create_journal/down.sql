@@ -0,0 +1 @@
+DROP TABLE journal
\ No newline at end of file
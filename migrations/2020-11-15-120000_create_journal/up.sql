@@ -0,0 +1,13 @@
+CREATE TABLE journal (
+  id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+  portfolio TEXT NOT NULL,
+  time DATETIME NOT NULL,
+  action TEXT CHECK(action IN ('sync', 'buy', 'sell', 'cash', 'undo')) NOT NULL,
+  symbol TEXT,
+  shares TEXT,
+  price TEXT,
+  cash_assets TEXT,
+  note TEXT,
+  undone_id INTEGER,
+  previous_assets TEXT NOT NULL
+)
\ No newline at end of file
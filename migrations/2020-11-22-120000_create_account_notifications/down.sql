@@ -0,0 +1 @@
+DROP TABLE account_notifications
\ No newline at end of file
@@ -0,0 +1,8 @@
+CREATE TABLE account_notifications (
+  portfolio TEXT NOT NULL,
+  notification_type TEXT CHECK(notification_type IN ('opening', 'change', 'closing')) NOT NULL,
+  account_number TEXT NOT NULL,
+  date DATE NOT NULL,
+  filing_date DATE NOT NULL,
+  PRIMARY KEY (portfolio, notification_type, account_number)
+) WITHOUT ROWID
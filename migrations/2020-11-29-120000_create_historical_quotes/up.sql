@@ -0,0 +1,7 @@
+CREATE TABLE historical_quotes (
+  symbol TEXT NOT NULL,
+  date DATE NOT NULL,
+  currency TEXT,
+  price TEXT,
+  PRIMARY KEY (symbol, date)
+) WITHOUT ROWID
\ No newline at end of file
@@ -0,0 +1,11 @@
+CREATE TABLE quotes_backup (
+  symbol TEXT NOT NULL,
+  time DATETIME NOT NULL,
+  currency TEXT NOT NULL,
+  price TEXT NOT NULL,
+  PRIMARY KEY (symbol)
+) WITHOUT ROWID;
+
+INSERT INTO quotes_backup SELECT symbol, time, currency, price FROM quotes;
+DROP TABLE quotes;
+ALTER TABLE quotes_backup RENAME TO quotes
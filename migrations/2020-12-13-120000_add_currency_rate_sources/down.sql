@@ -0,0 +1,10 @@
+CREATE TABLE currency_rates_backup (
+  currency TEXT NOT NULL,
+  date DATE NOT NULL,
+  price TEXT,
+  PRIMARY KEY (currency, date)
+) WITHOUT ROWID;
+
+INSERT INTO currency_rates_backup SELECT currency, date, price FROM currency_rates WHERE source = 'cbr';
+DROP TABLE currency_rates;
+ALTER TABLE currency_rates_backup RENAME TO currency_rates
\ No newline at end of file
@@ -0,0 +1,11 @@
+CREATE TABLE currency_rates_new (
+  source TEXT CHECK(source IN ('cbr', 'ecb', 'fed')) NOT NULL,
+  currency TEXT NOT NULL,
+  date DATE NOT NULL,
+  price TEXT,
+  PRIMARY KEY (source, currency, date)
+) WITHOUT ROWID;
+
+INSERT INTO currency_rates_new SELECT 'cbr', currency, date, price FROM currency_rates;
+DROP TABLE currency_rates;
+ALTER TABLE currency_rates_new RENAME TO currency_rates
\ No newline at end of file
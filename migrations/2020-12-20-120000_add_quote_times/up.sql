@@ -0,0 +1 @@
+ALTER TABLE quotes ADD COLUMN quote_time DATETIME
\ No newline at end of file
@@ -0,0 +1,10 @@
+CREATE TABLE trading_results_backup (
+  portfolio TEXT NOT NULL,
+  year INTEGER NOT NULL,
+  profit TEXT NOT NULL,
+  PRIMARY KEY (portfolio, year)
+) WITHOUT ROWID;
+
+INSERT INTO trading_results_backup SELECT portfolio, year, profit FROM trading_results;
+DROP TABLE trading_results;
+ALTER TABLE trading_results_backup RENAME TO trading_results
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE trading_results ADD COLUMN applied_loss TEXT NOT NULL DEFAULT '0'
\ No newline at end of file
@@ -5,11 +5,14 @@ use crate::broker_statement::BrokerStatement;
 use crate::commissions::CommissionCalc;
 use crate::config::{Config, PortfolioConfig, PerformanceMergingConfig};
 use crate::core::{GenericResult, EmptyResult};
+use crate::currency::Cash;
 use crate::currency::converter::CurrencyConverter;
-use crate::db;
+use crate::db::{self, schema::CurrencyRateSource};
 use crate::localities;
 use crate::quotes::Quotes;
+use crate::taxes::iia;
 use crate::types::Decimal;
+use crate::util;
 
 use self::portfolio_analysis::PortfolioPerformanceAnalysis;
 use self::portfolio_performance::PortfolioPerformanceAnalyser;
@@ -25,11 +28,11 @@ pub struct PortfolioStatistics {
 }
 
 impl PortfolioStatistics {
-    fn new() -> PortfolioStatistics {
+    fn new(currencies: Vec<String>) -> PortfolioStatistics {
         PortfolioStatistics {
-            currencies: ["USD", "RUB"].iter().map(|&currency| (
+            currencies: currencies.into_iter().map(|currency| (
                 PortfolioCurrencyStatistics {
-                    currency: currency.to_owned(),
+                    currency: currency,
 
                     assets: BTreeMap::new(),
                     performance: None,
@@ -81,9 +84,10 @@ pub fn analyse(
 ) -> GenericResult<(PortfolioStatistics, CurrencyConverter)> {
     let mut portfolios = load_portfolios(config, portfolio_name)?;
 
+    let today = util::today();
     let country = localities::russia();
-    let (converter, quotes) = load_tools(config)?;
-    let mut statistics = PortfolioStatistics::new();
+    let (converter, quotes) = load_tools(config, get_currency_rate_source(&portfolios)?)?;
+    let mut statistics = PortfolioStatistics::new(get_statistics_currencies(&portfolios));
 
     for (_, statement) in &mut portfolios {
         statement.batch_quotes(&quotes);
@@ -101,6 +105,14 @@ pub fn analyse(
             Ok(statistics.add_assets("Cash", cash_assets))
         })?;
 
+        // There are no quotes for derivatives, so we can't emulate their closing
+        if !statement.derivative_positions.is_empty() {
+            let symbols = statement.derivative_positions.keys().cloned().collect::<Vec<_>>();
+            return Err!(
+                "Portfolio analysis is not supported for portfolios with open derivative positions: {}",
+                symbols.join(", "));
+        }
+
         let mut commission_calc = CommissionCalc::new(statement.broker.commission_spec.clone());
 
         for (symbol, quantity) in statement.open_positions.clone() {
@@ -119,6 +131,11 @@ pub fn analyse(
 
         statement.process_trades()?;
 
+        // Assume that individual investment account is going to be closed right now
+        let trading_income_exempt = portfolio.iia.as_ref().map_or(false, |iia| {
+            iia::is_trading_income_exempt(iia, iia.close_date.unwrap_or(today))
+        });
+
         for trade in statement.stock_sells.iter().rev() {
             if !trade.emulation {
                 break;
@@ -134,12 +151,54 @@ pub fn analyse(
 
                 statistics.add_assets(&trade.symbol, volume);
                 statistics.projected_commissions += commission;
-                statistics.projected_taxes += tax_to_pay;
+                if !trading_income_exempt {
+                    statistics.projected_taxes += tax_to_pay;
+                }
+
+                Ok(())
+            })?;
+        }
+
+        for trade in statement.bond_sells.iter().rev() {
+            if !trade.emulation {
+                break;
+            }
+
+            let details = trade.calculate(&country, &converter)?;
+
+            statistics.process(|statistics| {
+                let currency = &statistics.currency;
+                let volume = converter.real_time_convert_to(trade.volume, currency)?;
+                let commission = converter.real_time_convert_to(trade.commission, currency)?;
+                let tax_to_pay = converter.real_time_convert_to(details.tax_to_pay, currency)?;
+
+                statistics.add_assets(&trade.symbol, volume);
+                statistics.projected_commissions += commission;
+                if !trading_income_exempt {
+                    statistics.projected_taxes += tax_to_pay;
+                }
 
                 Ok(())
             })?;
         }
 
+        // Type A deductions which haven't been received yet reduce the taxes
+        if let Some(ref iia) = portfolio.iia {
+            for deduction in iia::get_contribution_deductions(iia, &country, &statement.cash_flows)? {
+                if deduction.get_payment_date() <= today {
+                    continue;
+                }
+
+                let deduction = Cash::new(country.currency, deduction.deduction);
+
+                statistics.process(|statistics| {
+                    statistics.projected_taxes -= converter.real_time_convert_to(
+                        deduction, &statistics.currency)?;
+                    Ok(())
+                })?;
+            }
+        }
+
         if !portfolio.merge_performance.is_empty() {
             statement.merge_symbols(&portfolio.merge_performance, true).map_err(|e| format!(
                 "Invalid performance merging configuration: {}", e))?;
@@ -170,7 +229,7 @@ pub fn analyse(
 pub fn simulate_sell(config: &Config, portfolio_name: &str, positions: &[(String, Option<Decimal>)]) -> EmptyResult {
     let portfolio = config.get_portfolio(portfolio_name)?;
     let statement = load_portfolio(config, portfolio, true)?;
-    let (converter, quotes) = load_tools(config)?;
+    let (converter, quotes) = load_tools(config, portfolio.get_currency_rate_source())?;
     sell_simulation::simulate_sell(portfolio, statement, &converter, &quotes, positions)
 }
 
@@ -195,6 +254,20 @@ fn load_portfolios<'a>(config: &'a Config, name: Option<&str>) -> GenericResult<
     Ok(portfolios)
 }
 
+fn get_statistics_currencies(portfolios: &[(&PortfolioConfig, BrokerStatement)]) -> Vec<String> {
+    let mut currencies = vec![s!("USD"), s!("RUB")];
+
+    for (portfolio, _) in portfolios {
+        if let Some(ref currency) = portfolio.currency {
+            if !currencies.contains(currency) {
+                currencies.push(currency.clone());
+            }
+        }
+    }
+
+    currencies
+}
+
 fn load_portfolio(config: &Config, portfolio: &PortfolioConfig, strict_mode: bool) -> GenericResult<BrokerStatement> {
     let broker = portfolio.broker.get_info(config, portfolio.plan.as_ref())?;
     BrokerStatement::read(
@@ -202,9 +275,28 @@ fn load_portfolio(config: &Config, portfolio: &PortfolioConfig, strict_mode: boo
         portfolio.get_tax_remapping()?, strict_mode)
 }
 
-fn load_tools(config: &Config) -> GenericResult<(CurrencyConverter, Rc<Quotes>)> {
+// All portfolios are analysed using a single currency converter
+fn get_currency_rate_source(
+    portfolios: &[(&PortfolioConfig, BrokerStatement)],
+) -> GenericResult<CurrencyRateSource> {
+    let mut source = None;
+
+    for (portfolio, _) in portfolios {
+        let portfolio_source = portfolio.get_currency_rate_source();
+
+        match source {
+            Some(source) if source != portfolio_source => return Err!(
+                "Portfolios with different currency rate sources can't be analysed together"),
+            _ => source = Some(portfolio_source),
+        }
+    }
+
+    Ok(source.unwrap_or_else(|| localities::russia().currency_rate_source))
+}
+
+fn load_tools(config: &Config, source: CurrencyRateSource) -> GenericResult<(CurrencyConverter, Rc<Quotes>)> {
     let database = db::connect(&config.db_path)?;
     let quotes = Rc::new(Quotes::new(&config, database.clone())?);
-    let converter = CurrencyConverter::new(database, Some(quotes.clone()), false);
+    let converter = CurrencyConverter::new(database, source, Some(quotes.clone()), false);
     Ok((converter, quotes))
 }
\ No newline at end of file
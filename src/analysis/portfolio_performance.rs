@@ -10,7 +10,7 @@ use crate::currency::Cash;
 use crate::currency::converter::CurrencyConverter;
 use crate::formatting;
 use crate::localities::Country;
-use crate::taxes::NetTaxCalculator;
+use crate::taxes::{NetTaxCalculator, iia};
 use crate::types::{Date, Decimal};
 use crate::util;
 
@@ -63,9 +63,11 @@ impl <'a> PortfolioPerformanceAnalyser<'a> {
         trace!("Deposit emulator transactions for {:?}:", portfolio.name);
         self.process_deposits_and_withdrawals(statement)?;
         self.process_positions(statement, portfolio)?;
+        self.process_derivatives(statement, portfolio)?;
         self.process_dividends(statement, portfolio)?;
+        self.process_coupons(statement, portfolio)?;
         self.process_interest(statement, portfolio)?;
-        self.process_tax_deductions(portfolio)?;
+        self.process_tax_deductions(statement, portfolio)?;
         self.process_cash_assets(statement)?;
 
         for (symbol, deposit_view) in self.instruments.as_mut().unwrap().iter_mut() {
@@ -259,9 +261,17 @@ impl <'a> PortfolioPerformanceAnalyser<'a> {
         let mut taxes = NetTaxCalculator::new(self.country, portfolio.tax_payment_day);
         let mut stock_taxes = HashMap::new();
 
+        // Income from trading operations on type B individual investment account is exempt from
+        // taxation (here we assume that the account is going to be closed right now)
+        let trading_income_exempt = portfolio.iia.as_ref().map_or(false, |iia| {
+            iia::is_trading_income_exempt(iia, iia.close_date.unwrap_or(self.today))
+        });
+
         for stock_buy in &statement.stock_buys {
-            let multiplier = statement.stock_splits.get_multiplier(
+            let symbol = statement.stock_splits.get_symbol(
                 &stock_buy.symbol, stock_buy.conclusion_date, self.today);
+            let quantity = statement.stock_splits.convert_quantity(
+                &stock_buy.symbol, stock_buy.conclusion_date, self.today, stock_buy.quantity);
 
             let commission = self.converter.convert_to(
                 stock_buy.conclusion_date, stock_buy.commission, self.currency)?;
@@ -271,14 +281,16 @@ impl <'a> PortfolioPerformanceAnalyser<'a> {
                 stock_buy.execution_date, stock_buy.volume, self.currency)?;
             assets += commission;
 
-            let deposit_view = self.get_deposit_view(&stock_buy.symbol);
-            deposit_view.trade(stock_buy.conclusion_date, multiplier * stock_buy.quantity);
+            let deposit_view = self.get_deposit_view(&symbol);
+            deposit_view.trade(stock_buy.conclusion_date, quantity);
             deposit_view.transaction(stock_buy.conclusion_date, assets);
         }
 
         for stock_sell in &statement.stock_sells {
-            let multiplier = statement.stock_splits.get_multiplier(
+            let symbol = statement.stock_splits.get_symbol(
                 &stock_sell.symbol, stock_sell.conclusion_date, self.today);
+            let quantity = statement.stock_splits.convert_quantity(
+                &stock_sell.symbol, stock_sell.conclusion_date, self.today, stock_sell.quantity);
 
             let assets = self.converter.convert_to(
                 stock_sell.execution_date, stock_sell.volume, self.currency)?;
@@ -288,9 +300,9 @@ impl <'a> PortfolioPerformanceAnalyser<'a> {
             self.income_structure.commissions += commission;
 
             {
-                let deposit_view = self.get_deposit_view(&stock_sell.symbol);
+                let deposit_view = self.get_deposit_view(&symbol);
 
-                deposit_view.trade(stock_sell.conclusion_date, multiplier * -stock_sell.quantity);
+                deposit_view.trade(stock_sell.conclusion_date, -quantity);
                 deposit_view.transaction(stock_sell.conclusion_date, -assets);
                 deposit_view.transaction(stock_sell.conclusion_date, commission);
 
@@ -300,16 +312,73 @@ impl <'a> PortfolioPerformanceAnalyser<'a> {
                 }
             }
 
+            if trading_income_exempt {
+                continue;
+            }
+
             let local_profit = stock_sell.calculate(&self.country, self.converter)?.local_profit.amount;
 
-            stock_taxes.entry(&stock_sell.symbol)
+            stock_taxes.entry(symbol)
                 .or_insert_with(|| NetTaxCalculator::new(self.country, portfolio.tax_payment_day))
                 .add_profit(stock_sell.execution_date, local_profit);
 
             taxes.add_profit(stock_sell.execution_date, local_profit);
         }
 
-        for (&symbol, symbol_taxes) in stock_taxes.iter() {
+        // Accrued coupon interest is a part of the trade volume for performance analysis purposes
+        for bond_buy in &statement.bond_buys {
+            let commission = self.converter.convert_to(
+                bond_buy.conclusion_date, bond_buy.commission, self.currency)?;
+            self.income_structure.commissions += commission;
+
+            let mut assets = self.converter.convert_to(
+                bond_buy.execution_date, bond_buy.volume, self.currency)?;
+            assets += self.converter.convert_to(
+                bond_buy.execution_date, bond_buy.accrued_interest, self.currency)?;
+            assets += commission;
+
+            let deposit_view = self.get_deposit_view(&bond_buy.symbol);
+            deposit_view.trade(bond_buy.conclusion_date, bond_buy.quantity);
+            deposit_view.transaction(bond_buy.conclusion_date, assets);
+        }
+
+        for bond_sell in &statement.bond_sells {
+            let mut assets = self.converter.convert_to(
+                bond_sell.execution_date, bond_sell.volume, self.currency)?;
+            assets += self.converter.convert_to(
+                bond_sell.execution_date, bond_sell.accrued_interest, self.currency)?;
+
+            let commission = self.converter.convert_to(
+                bond_sell.conclusion_date, bond_sell.commission, self.currency)?;
+            self.income_structure.commissions += commission;
+
+            {
+                let deposit_view = self.get_deposit_view(&bond_sell.symbol);
+
+                deposit_view.trade(bond_sell.conclusion_date, -bond_sell.quantity);
+                deposit_view.transaction(bond_sell.conclusion_date, -assets);
+                deposit_view.transaction(bond_sell.conclusion_date, commission);
+
+                deposit_view.last_sell_volume.replace(assets);
+                if bond_sell.emulation {
+                    deposit_view.closed = false;
+                }
+            }
+
+            if trading_income_exempt {
+                continue;
+            }
+
+            let local_profit = bond_sell.calculate(&self.country, self.converter)?.local_profit.amount;
+
+            stock_taxes.entry(bond_sell.symbol.clone())
+                .or_insert_with(|| NetTaxCalculator::new(self.country, portfolio.tax_payment_day))
+                .add_profit(bond_sell.execution_date, local_profit);
+
+            taxes.add_profit(bond_sell.execution_date, local_profit);
+        }
+
+        for (symbol, symbol_taxes) in stock_taxes.iter() {
             for (&tax_payment_date, &tax_to_pay) in symbol_taxes.get_taxes().iter() {
                 if let Some(amount) = self.map_tax_to_deposit_amount(tax_payment_date, tax_to_pay)? {
                     trace!("* {} selling {} tax: {}",
@@ -331,6 +400,40 @@ impl <'a> PortfolioPerformanceAnalyser<'a> {
         Ok(())
     }
 
+    // Derivatives don't require investments (futures) or may be opened by short position which
+    // brings money instead of spending them (options), so they can't be represented as deposits.
+    // Their financial result is already in the cash assets, so only commissions and taxes (which
+    // form a separate tax base) are accounted here at portfolio level.
+    fn process_derivatives(&mut self, statement: &BrokerStatement, portfolio: &PortfolioConfig) -> EmptyResult {
+        let mut taxes = NetTaxCalculator::new(self.country, portfolio.tax_payment_day);
+
+        let trading_income_exempt = portfolio.iia.as_ref().map_or(false, |iia| {
+            iia::is_trading_income_exempt(iia, iia.close_date.unwrap_or(self.today))
+        });
+
+        for trade in &statement.derivative_trades {
+            self.income_structure.commissions += self.converter.convert_to(
+                trade.conclusion_date, trade.commission, self.currency)?;
+
+            if trading_income_exempt || trade.get_closed().is_zero() {
+                continue;
+            }
+
+            let local_profit = trade.calculate(&self.country, self.converter)?.local_profit.amount;
+            taxes.add_profit(trade.execution_date, local_profit);
+        }
+
+        for (&tax_payment_date, &tax_to_pay) in taxes.get_taxes().iter() {
+            if let Some(amount) = self.map_tax_to_deposit_amount(tax_payment_date, tax_to_pay)? {
+                trace!("* Derivatives {} tax: {}", formatting::format_date(tax_payment_date), amount);
+                self.transaction(tax_payment_date, amount);
+                self.income_structure.taxes += amount;
+            }
+        }
+
+        Ok(())
+    }
+
     fn process_dividends(&mut self, statement: &BrokerStatement, portfolio: &PortfolioConfig) -> EmptyResult {
         for dividend in &statement.dividends {
             let income = dividend.amount.sub(dividend.paid_tax).map_err(|e| format!(
@@ -358,6 +461,33 @@ impl <'a> PortfolioPerformanceAnalyser<'a> {
         Ok(())
     }
 
+    fn process_coupons(&mut self, statement: &BrokerStatement, portfolio: &PortfolioConfig) -> EmptyResult {
+        for coupon in &statement.coupons {
+            let income = coupon.amount.sub(coupon.paid_tax).map_err(|e| format!(
+                "{}: The tax is paid in currency different from the coupon currency: {}",
+                coupon.description(), e))?;
+
+            let income = self.converter.convert_to(coupon.date, income, self.currency)?;
+            self.get_deposit_view(&coupon.symbol).transaction(coupon.date, -income);
+            self.income_structure.interest += income;
+
+            let tax_to_pay = coupon.tax_to_pay(&self.country, self.converter)?;
+            let tax_payment_date = portfolio.tax_payment_day.get(coupon.date);
+
+            if let Some(amount) = self.map_tax_to_deposit_amount(tax_payment_date, tax_to_pay)? {
+                trace!("* {} {} coupon {} tax: {}",
+                       coupon.symbol, formatting::format_date(coupon.date),
+                       formatting::format_date(tax_payment_date), amount);
+
+                self.get_deposit_view(&coupon.symbol).transaction(tax_payment_date, amount);
+                self.transaction(tax_payment_date, amount);
+                self.income_structure.taxes += amount;
+            }
+        }
+
+        Ok(())
+    }
+
     fn process_interest(&mut self, statement: &BrokerStatement, portfolio: &PortfolioConfig) -> EmptyResult {
         for interest in &statement.idle_cash_interest {
             self.income_structure.interest += self.converter.convert_to(
@@ -379,8 +509,22 @@ impl <'a> PortfolioPerformanceAnalyser<'a> {
         Ok(())
     }
 
-    fn process_tax_deductions(&mut self, portfolio: &PortfolioConfig) -> EmptyResult {
-        for &(date, amount) in &portfolio.tax_deductions {
+    fn process_tax_deductions(&mut self, statement: &BrokerStatement, portfolio: &PortfolioConfig) -> EmptyResult {
+        let mut deductions = portfolio.tax_deductions.clone();
+
+        if let Some(ref iia) = portfolio.iia {
+            for deduction in iia::get_contribution_deductions(iia, &self.country, &statement.cash_flows)? {
+                deductions.push((deduction.get_payment_date(), deduction.deduction));
+            }
+        }
+
+        for (date, amount) in deductions {
+            // Deductions which are going to be received in the future haven't affected the
+            // portfolio performance yet
+            if date > self.today {
+                continue;
+            }
+
             let amount = self.converter.convert(self.country.currency, self.currency, date, amount)?;
             trace!("* Tax deduction {}: {}", formatting::format_date(date), -amount);
             self.transaction(date, -amount);
@@ -9,7 +9,8 @@ use crate::currency::converter::CurrencyConverter;
 use crate::formatting::table::Cell;
 use crate::localities::Country;
 use crate::quotes::Quotes;
-use crate::types::Decimal;
+use crate::taxes::{self, LongTermOwnershipDeduction};
+use crate::types::{Date, Decimal};
 use crate::util;
 
 pub fn simulate_sell(
@@ -23,6 +24,10 @@ pub fn simulate_sell(
             return Err!("The portfolio has no open {:?} positions", symbol);
         }
 
+        if statement.bond_buys.iter().any(|trade| &trade.symbol == symbol) {
+            return Err!("Sell simulation is not supported for bonds yet: {}", symbol);
+        }
+
         quotes.batch(&symbol);
     }
 
@@ -87,6 +92,23 @@ struct FifoRow {
     quantity: Decimal,
     #[column(name="Price")]
     price: Cash,
+    #[column(name="Buy date")]
+    buy_date: Date,
+    #[column(name="LTO")]
+    long_term_ownership: Option<String>,
+}
+
+#[derive(StaticTable)]
+#[table(name="LongTermOwnershipTable")]
+struct LongTermOwnershipRow {
+    #[column(name="Symbol")]
+    symbol: String,
+    #[column(name="Quantity")]
+    quantity: Decimal,
+    #[column(name="Buy date")]
+    buy_date: Date,
+    #[column(name="Eligible from")]
+    eligibility_date: Date,
 }
 
 fn print_results(
@@ -126,6 +148,9 @@ fn print_results(
 
     let mut fifo_table = FifoTable::new();
 
+    let mut long_term_ownership = LongTermOwnershipDeduction::default();
+    let mut long_term_ownership_table = LongTermOwnershipTable::new();
+
     for trade in stock_sells {
         let commission = trade.commission.round();
         let details = trade.calculate(&country, &converter)?;
@@ -136,8 +161,24 @@ fn print_results(
         total_local_revenue.add_assign(details.local_revenue).unwrap();
         total_profit.deposit(details.profit);
         total_local_profit.add_assign(details.local_profit).unwrap();
+        long_term_ownership.merge(&details.long_term_ownership);
 
         for (index, buy_trade) in details.fifo.iter().enumerate() {
+            let quantity = (buy_trade.quantity * buy_trade.multiplier).normalize();
+
+            // Suggest to postpone the sell of the lots which are going to be eligible for
+            // long-term ownership deduction
+            if !buy_trade.long_term_ownership {
+                if let Some(eligibility_date) = taxes::get_long_term_ownership_date(buy_trade.execution_date) {
+                    long_term_ownership_table.add_row(LongTermOwnershipRow {
+                        symbol: trade.symbol.clone(),
+                        quantity: quantity,
+                        buy_date: buy_trade.execution_date,
+                        eligibility_date: eligibility_date,
+                    });
+                }
+            }
+
             purchase_cost.amount += converter.convert_to_rounding(
                 buy_trade.execution_date, buy_trade.price * buy_trade.quantity,
                 purchase_cost.currency)?;
@@ -148,8 +189,14 @@ fn print_results(
                 } else {
                    None
                 },
-                quantity: (buy_trade.quantity * buy_trade.multiplier).normalize(),
+                quantity: quantity,
                 price: (buy_trade.price / buy_trade.multiplier).normalize(),
+                buy_date: buy_trade.execution_date,
+                long_term_ownership: if buy_trade.long_term_ownership {
+                    Some(format!("{} years", buy_trade.ownership_years))
+                } else {
+                    None
+                },
             });
         }
 
@@ -170,6 +217,21 @@ fn print_results(
         });
     }
 
+    // Long-term ownership deduction is calculated for the whole tax year, so here we assume that
+    // there will be no other sells in this year
+    if long_term_ownership.is_empty() {
+        fifo_table.hide_long_term_ownership();
+    } else if total_local_profit.is_positive() {
+        let deduction = std::cmp::min(long_term_ownership.deduction(), total_local_profit.amount);
+
+        let mut row = trades_table.add_empty_row();
+        row.set_symbol(format!(
+            "LTO deduction (limit {})", Cash::new(country.currency, long_term_ownership.limit())));
+        row.set_local_profit(Cash::new(country.currency, -deduction));
+
+        total_local_profit.amount -= deduction;
+    }
+
     let tax_to_pay = Cash::new(country.currency, country.tax_to_pay(total_local_profit.amount, None));
 
     let mut totals = trades_table.add_empty_row();
@@ -183,5 +245,9 @@ fn print_results(
     trades_table.print("Sell simulation results");
     fifo_table.print("FIFO details");
 
+    if !long_term_ownership_table.is_empty() {
+        long_term_ownership_table.print("Lots which will be eligible for long-term ownership deduction");
+    }
+
     Ok(())
 }
@@ -27,14 +27,24 @@ pub enum Action {
         shares: Decimal,
         symbol: String,
         cash_assets: Decimal,
+        price: Option<Decimal>,
+        note: Option<String>,
     },
     Sell {
         name: String,
         shares: Decimal,
         symbol: String,
         cash_assets: Decimal,
+        price: Option<Decimal>,
+        note: Option<String>,
     },
-    SetCashAssets(String, Decimal),
+    SetCashAssets {
+        name: String,
+        cash_assets: Decimal,
+        note: Option<String>,
+    },
+    History(String),
+    Undo(String),
 
     Show {
         name: String,
@@ -42,17 +52,27 @@ pub enum Action {
     },
     Rebalance {
         name: String,
+        cash_flow: Option<Decimal>,
+        account: Option<String>,
         flat: bool,
     },
+    Check(String),
 
     TaxStatement {
         name: String,
         year: Option<i32>,
         tax_statement_path: Option<String>,
+        create_tax_statement: bool,
     },
     CashFlow {
         name: String,
         year: Option<i32>,
+        xml_path: Option<String>,
+    },
+    ForeignAccount {
+        name: String,
+        xml_dir: Option<String>,
+        mark_as_filed: bool,
     },
 
     Deposits {
@@ -61,6 +81,7 @@ pub enum Action {
     },
 
     Metrics(String),
+    Quotes(Vec<String>),
 }
 
 pub fn initialize() -> (Action, Config) {
@@ -111,23 +132,60 @@ pub fn initialize() -> (Action, Config) {
             .arg(portfolio::arg())
             .arg(shares::arg())
             .arg(symbol::arg())
-            .arg(cash_assets::arg()))
+            .arg(cash_assets::arg())
+            .arg(price_arg())
+            .arg(note_arg()))
         .subcommand(SubCommand::with_name("sell")
             .about("Remove the specified stock shares from the portfolio")
             .arg(portfolio::arg())
             .arg(shares::arg())
             .arg(symbol::arg())
-            .arg(cash_assets::arg()))
+            .arg(cash_assets::arg())
+            .arg(price_arg())
+            .arg(note_arg()))
         .subcommand(SubCommand::with_name("cash")
             .about("Set current cash assets")
             .arg(portfolio::arg())
-            .arg(cash_assets::arg()))
+            .arg(cash_assets::arg())
+            .arg(note_arg()))
+        .subcommand(SubCommand::with_name("history")
+            .about("Show history of portfolio changes")
+            .arg(portfolio::arg()))
+        .subcommand(SubCommand::with_name("undo")
+            .about("Undo the last portfolio change")
+            .long_about(concat!(
+                "\nReverts the last change made by sync, buy, sell or cash command which hasn't ",
+                "been undone yet."))
+            .arg(portfolio::arg()))
         .subcommand(SubCommand::with_name("rebalance")
             .about("Rebalance the portfolio according to the asset allocation configuration")
             .arg(Arg::with_name("flat")
                 .short("f")
                 .long("flat")
                 .help("Flat view"))
+            .arg(Arg::with_name("contribute")
+                .long("contribute")
+                .value_name("AMOUNT")
+                .help("Distribute the contribution between the assets by buying only")
+                .takes_value(true))
+            .arg(Arg::with_name("withdraw")
+                .long("withdraw")
+                .value_name("AMOUNT")
+                .help("Gather the withdrawal from the assets by selling only")
+                .takes_value(true)
+                .conflicts_with("contribute"))
+            .arg(Arg::with_name("account")
+                .long("account")
+                .value_name("NAME")
+                .help("Virtual portfolio's account to apply the contribution or withdrawal to")
+                .takes_value(true))
+            .arg(portfolio::arg()))
+        .subcommand(SubCommand::with_name("check")
+            .about("Check whether portfolio assets have drifted out of their tolerance bands")
+            .long_about(concat!(
+                "\nPrints nothing and exits with zero status if all assets are within their ",
+                "tolerance bands. Otherwise prints the drifted assets and exits with status 2, so ",
+                "the command may be used for cron notifications."))
             .arg(portfolio::arg()))
         .subcommand(SubCommand::with_name("simulate-sell")
             .about("Simulates stock selling (calculates revenue, profit and taxes)")
@@ -142,18 +200,53 @@ pub fn initialize() -> (Action, Config) {
                 "named Декларация) by adding all required information about income from stock ",
                 "selling, paid dividends and idle cash interest.\n",
                 "\nIf tax statement file is not specified only outputs the data which is going to ",
-                "be declared."))
+                "be declared.\n",
+                "\nWith --new flag the tax statement file is created from scratch using taxpayer ",
+                "profile from the configuration file. *.xml tax statement (3-НДФЛ XML format ",
+                "accepted by the tax service) is always created from scratch."))
+            .arg(Arg::with_name("new")
+                .short("n")
+                .long("new")
+                .requires("TAX_STATEMENT")
+                .help("Create a new tax statement file instead of altering an existing one"))
             .arg(portfolio::arg())
             .arg(Arg::with_name("YEAR")
                 .help("Year to generate the statement for"))
             .arg(Arg::with_name("TAX_STATEMENT")
-                .help("Path to tax statement *.dcX file")))
+                .help("Path to tax statement *.dcX or 3-НДФЛ *.xml file")))
         .subcommand(SubCommand::with_name("cash-flow")
             .about("Generate cash flow report")
-            .long_about("Generates cash flow report for tax inspection notification")
+            .long_about(concat!(
+                "\nGenerates cash flow report for tax inspection notification.\n",
+                "\nThe report may be also exported in XML format accepted by the tax service (requires ",
+                "taxpayer profile and account number in the configuration file)."))
+            .arg(Arg::with_name("xml")
+                .short("x")
+                .long("xml")
+                .value_name("PATH")
+                .requires("YEAR")
+                .help("Export the report to the specified XML file"))
             .arg(portfolio::arg())
             .arg(Arg::with_name("YEAR")
                 .help("Year to generate the report for")))
+        .subcommand(SubCommand::with_name("foreign-account")
+            .about("Prepare foreign account notifications")
+            .long_about(concat!(
+                "\nPrepares notifications about foreign broker account opening, changing of its ",
+                "details and closing which must be filed to the tax inspection.\n",
+                "\nFiled notifications are tracked in the database, so only the required ones are ",
+                "prepared. They may be also exported in XML format accepted by the tax service ",
+                "(requires taxpayer profile in the configuration file)."))
+            .arg(Arg::with_name("xml")
+                .short("x")
+                .long("xml")
+                .value_name("DIR")
+                .help("Export the required notifications to XML files in the specified directory"))
+            .arg(Arg::with_name("filed")
+                .short("f")
+                .long("filed")
+                .help("Mark the required notifications as filed today"))
+            .arg(portfolio::arg()))
         .subcommand(SubCommand::with_name("deposits")
             .about("List deposits")
             .arg(Arg::with_name("date")
@@ -170,6 +263,15 @@ pub fn initialize() -> (Action, Config) {
             .arg(Arg::with_name("PATH")
                 .help("Path to write the metrics to")
                 .required(true)))
+        .subcommand(SubCommand::with_name("quotes")
+            .about("Show current quotes")
+            .long_about(concat!(
+                "\nGets quotes for the specified symbols and shows which provider has served each of ",
+                "them and how old the quote is. Providers which have failed are listed separately."))
+            .arg(Arg::with_name("SYMBOLS")
+                .help("Stock symbols or currency pairs (in $base/$quote format)")
+                .multiple(true)
+                .required(true)))
         .global_setting(AppSettings::DisableVersion)
         .global_setting(AppSettings::DisableHelpSubcommand)
         .global_setting(AppSettings::DeriveDisplayOrder)
@@ -242,6 +344,10 @@ fn parse_arguments(config: &mut Config, matches: &ArgMatches) -> GenericResult<A
             let path = matches.value_of("PATH").unwrap().to_owned();
             return Ok(Action::Metrics(path))
         },
+        "quotes" => {
+            let symbols = matches.values_of("SYMBOLS").unwrap().map(ToOwned::to_owned).collect();
+            return Ok(Action::Quotes(symbols))
+        },
         _ => {},
     }
 
@@ -260,31 +366,66 @@ fn parse_arguments(config: &mut Config, matches: &ArgMatches) -> GenericResult<A
         "buy" | "sell" | "cash" => {
             let cash_assets = Decimal::from_str(&cash_assets::get(matches))
                 .map_err(|_| "Invalid cash assets value")?;
+            let note = matches.value_of("note").map(|note| note.to_owned());
 
             if command == "cash" {
-                Action::SetCashAssets(name, cash_assets)
+                Action::SetCashAssets {name, cash_assets, note}
             } else {
                 let shares = util::parse_decimal(
                     &shares::get(matches), DecimalRestrictions::StrictlyPositive)
                     .map_err(|_| "Invalid shares number")?.normalize();
                 let symbol = symbol::get(matches);
 
+                let price = match matches.value_of("price") {
+                    Some(price) => Some(util::parse_decimal(
+                        price, DecimalRestrictions::StrictlyPositive,
+                    ).map_err(|_| format!("Invalid price: {:?}", price))?.normalize()),
+                    None => None,
+                };
+
                 match command {
-                    "buy" => Action::Buy {name, shares, symbol, cash_assets},
-                    "sell" => Action::Sell {name, shares, symbol, cash_assets},
+                    "buy" => Action::Buy {name, shares, symbol, cash_assets, price, note},
+                    "sell" => Action::Sell {name, shares, symbol, cash_assets, price, note},
                     _ => unreachable!(),
                 }
             }
         },
+        "history" => Action::History(name),
+        "undo" => Action::Undo(name),
 
         "show" => Action::Show {
             name,
             flat: matches.is_present("flat"),
         },
-        "rebalance" => Action::Rebalance {
-            name,
-            flat: matches.is_present("flat"),
+        "rebalance" => {
+            let parse_amount = |name: &str| -> GenericResult<Option<Decimal>> {
+                Ok(match matches.value_of(name) {
+                    Some(amount) => Some(util::parse_decimal(
+                        amount, DecimalRestrictions::StrictlyPositive,
+                    ).map_err(|_| format!("Invalid {} amount: {:?}", name, amount))?),
+                    None => None,
+                })
+            };
+
+            let cash_flow = match (parse_amount("contribute")?, parse_amount("withdraw")?) {
+                (Some(contribution), _) => Some(contribution),
+                (_, Some(withdrawal)) => Some(-withdrawal),
+                _ => None,
+            };
+
+            let account = matches.value_of("account").map(ToOwned::to_owned);
+            if account.is_some() && cash_flow.is_none() {
+                return Err("The account may be specified only for a contribution or withdrawal".into());
+            }
+
+            Action::Rebalance {
+                name,
+                cash_flow,
+                account,
+                flat: matches.is_present("flat"),
+            }
         },
+        "check" => Action::Check(name),
         "simulate-sell" => {
             let mut positions = Vec::new();
             let mut positions_spec_iter = matches.values_of("POSITIONS").unwrap();
@@ -316,12 +457,21 @@ fn parse_arguments(config: &mut Config, matches: &ArgMatches) -> GenericResult<A
                 name,
                 year: get_year(matches)?,
                 tax_statement_path: tax_statement_path,
+                create_tax_statement: matches.is_present("new"),
             }
         },
         "cash-flow" => {
             Action::CashFlow {
                 name,
                 year: get_year(matches)?,
+                xml_path: matches.value_of("xml").map(|path| path.to_owned()),
+            }
+        },
+        "foreign-account" => {
+            Action::ForeignAccount {
+                name,
+                xml_dir: matches.value_of("xml").map(|path| path.to_owned()),
+                mark_as_filed: matches.is_present("filed"),
             }
         },
 
@@ -340,6 +490,24 @@ fn get_year(matches: &ArgMatches) -> GenericResult<Option<i32>> {
     })
 }
 
+fn price_arg() -> Arg<'static, 'static> {
+    Arg::with_name("price")
+        .short("p")
+        .long("price")
+        .value_name("PRICE")
+        .help("Trade price (estimated by cash assets change if not specified)")
+        .takes_value(true)
+}
+
+fn note_arg() -> Arg<'static, 'static> {
+    Arg::with_name("note")
+        .short("n")
+        .long("note")
+        .value_name("NOTE")
+        .help("A note to save in portfolio history")
+        .takes_value(true)
+}
+
 macro_rules! arg {
     ($id:ident, $name:expr, $help:expr) => {
         mod $id {
@@ -9,8 +9,10 @@ use investments::cash_flow;
 use investments::config::Config;
 use investments::core::EmptyResult;
 use investments::deposits;
+use investments::foreign_accounts;
 use investments::metrics;
 use investments::portfolio;
+use investments::quotes;
 use investments::tax_statement;
 
 use self::init::{Action, initialize};
@@ -44,26 +46,35 @@ fn run(action: Action, config: Config) -> EmptyResult {
             &config, &name, &positions)?,
 
         Action::Sync(name) => portfolio::sync(&config, &name)?,
-        Action::Buy {name, shares, symbol, cash_assets} =>
-            portfolio::buy(&config, &name, shares, &symbol, cash_assets)?,
-        Action::Sell {name, shares, symbol, cash_assets} =>
-            portfolio::sell(&config, &name, shares, &symbol, cash_assets)?,
-        Action::SetCashAssets(name, cash_assets) =>
-            portfolio::set_cash_assets(&config, &name, cash_assets)?,
+        Action::Buy {name, shares, symbol, cash_assets, price, note} =>
+            portfolio::buy(&config, &name, shares, &symbol, cash_assets, price, note.as_deref())?,
+        Action::Sell {name, shares, symbol, cash_assets, price, note} =>
+            portfolio::sell(&config, &name, shares, &symbol, cash_assets, price, note.as_deref())?,
+        Action::SetCashAssets {name, cash_assets, note} =>
+            portfolio::set_cash_assets(&config, &name, cash_assets, note.as_deref())?,
+        Action::History(name) => portfolio::history(&config, &name)?,
+        Action::Undo(name) => portfolio::undo(&config, &name)?,
 
         Action::Show {name, flat} => portfolio::show(&config, &name, flat)?,
-        Action::Rebalance {name, flat} => portfolio::rebalance(&config, &name, flat)?,
+        Action::Rebalance {name, cash_flow, account, flat} =>
+            portfolio::rebalance(&config, &name, cash_flow, account.as_deref(), flat)?,
+        Action::Check(name) => if !portfolio::check(&config, &name)? {
+            process::exit(2);
+        },
 
-        Action::TaxStatement {name, year, tax_statement_path} =>
+        Action::TaxStatement {name, year, tax_statement_path, create_tax_statement} =>
             tax_statement::generate_tax_statement(
-                &config, &name, year, tax_statement_path.as_deref())?,
-        Action::CashFlow {name, year} =>
-            cash_flow::generate_cash_flow_report(&config, &name, year)?,
+                &config, &name, year, tax_statement_path.as_deref(), create_tax_statement)?,
+        Action::CashFlow {name, year, xml_path} =>
+            cash_flow::generate_cash_flow_report(&config, &name, year, xml_path.as_deref())?,
+        Action::ForeignAccount {name, xml_dir, mark_as_filed} =>
+            foreign_accounts::generate_notifications(&config, &name, xml_dir.as_deref(), mark_as_filed)?,
 
         Action::Deposits { date, cron_mode } => deposits::list(
             config.deposits, date, cron_mode, config.notify_deposit_closing_days),
 
         Action::Metrics(path) => metrics::collect(&config, &path)?,
+        Action::Quotes(symbols) => quotes::print_quotes(&config, &symbols)?,
     };
 
     Ok(())
@@ -1,15 +1,39 @@
 extern crate investments;
 
+use std::fs::File;
+use std::io::Read;
 use std::process;
 
-use clap::{App, Arg, AppSettings};
+use clap::{App, Arg, ArgGroup, AppSettings};
 
 use investments::core::EmptyResult;
-use investments::tax_statement::TaxStatement;
+use investments::tax_statement::{TaxStatement, ExportedStatement};
 
 pub fn run() -> EmptyResult {
     let matches = App::new("Tax statement parser")
         .about("\nParses *.dcX file and prints its contents to stdout")
+        .long_about(concat!(
+            "\nParses *.dcX file and prints its contents to stdout.\n",
+            "\nThe statement records may be exported to JSON/YAML, edited and written back to ",
+            "a new *.dcX file via --import option."))
+        .arg(Arg::with_name("export")
+            .short("e")
+            .long("export")
+            .value_name("FORMAT")
+            .possible_values(&["json", "yaml"])
+            .help("Export the statement records in the specified format"))
+        .arg(Arg::with_name("import")
+            .short("i")
+            .long("import")
+            .value_name("PATH")
+            .help("Write the statement from the exported JSON/YAML file to a new *.dcX file"))
+        .arg(Arg::with_name("diff")
+            .short("d")
+            .long("diff")
+            .value_name("PATH")
+            .help("Compare the statement to the specified one record by record"))
+        .group(ArgGroup::with_name("action")
+            .args(&["export", "import", "diff"]))
         .arg(Arg::with_name("TAX_STATEMENT")
             .help("Path to tax statement *.dcX file")
             .required(true))
@@ -18,9 +42,35 @@ pub fn run() -> EmptyResult {
         .get_matches();
 
     let path = matches.value_of("TAX_STATEMENT").unwrap();
-    let statement = TaxStatement::read(path)?;
 
-    println!("{:#?}", statement);
+    if let Some(format) = matches.value_of("export") {
+        let exported = TaxStatement::export(path)?;
+
+        match format {
+            "json" => println!("{}", serde_json::to_string_pretty(&exported)?),
+            "yaml" => println!("{}", serde_yaml::to_string(&exported)?),
+            _ => unreachable!(),
+        }
+    } else if let Some(import_path) = matches.value_of("import") {
+        let mut data = Vec::new();
+        File::open(import_path)?.read_to_end(&mut data)?;
+
+        // JSON is a subset of YAML, so the both formats are handled by YAML parser
+        let exported: ExportedStatement = serde_yaml::from_slice(&data).map_err(|e| format!(
+            "Error while reading {:?}: {}", import_path, e))?;
+
+        TaxStatement::import(path, &exported)?.save()?;
+    } else if let Some(other_path) = matches.value_of("diff") {
+        let statement = TaxStatement::export(path)?;
+        let other_statement = TaxStatement::export(other_path)?;
+
+        for difference in statement.diff(&other_statement) {
+            println!("{}", difference);
+        }
+    } else {
+        let statement = TaxStatement::read(path)?;
+        println!("{:#?}", statement);
+    }
 
     Ok(())
 }
@@ -30,4 +80,4 @@ fn main() {
         eprintln!("Error: {}.", e);
         process::exit(1);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,201 @@
+use crate::core::GenericResult;
+use crate::currency::Cash;
+use crate::currency::converter::CurrencyConverter;
+use crate::formatting;
+use crate::localities::{Country, CountryCode};
+use crate::types::{Date, Decimal};
+
+#[derive(Debug)]
+pub struct BondBuy {
+    pub symbol: String,
+    pub quantity: Decimal,
+    pub price: Cash,
+    pub volume: Cash, // May be slightly different from price * quantity due to rounding on broker side
+    pub accrued_interest: Cash, // НКД paid to the seller
+    pub commission: Cash,
+
+    pub conclusion_date: Date,
+    pub execution_date: Date,
+
+    sold: Decimal,
+}
+
+impl BondBuy {
+    pub fn new(
+        symbol: &str, quantity: Decimal, price: Cash, volume: Cash, accrued_interest: Cash,
+        commission: Cash, conclusion_date: Date, execution_date: Date,
+    ) -> BondBuy {
+        BondBuy {
+            symbol: symbol.to_owned(), quantity, price, volume, accrued_interest, commission,
+            conclusion_date, execution_date, sold: dec!(0),
+        }
+    }
+
+    pub fn is_sold(&self) -> bool {
+        self.sold == self.quantity
+    }
+
+    pub fn get_unsold(&self) -> Decimal {
+        self.quantity - self.sold
+    }
+
+    pub fn sell(&mut self, quantity: Decimal) {
+        assert!(self.get_unsold() >= quantity);
+        self.sold += quantity;
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BondSell {
+    pub symbol: String,
+    pub quantity: Decimal,
+    pub price: Cash,
+    pub volume: Cash, // May be slightly different from price * quantity due to rounding on broker side
+    pub accrued_interest: Cash, // НКД received from the buyer
+    pub commission: Cash,
+
+    pub conclusion_date: Date,
+    pub execution_date: Date,
+
+    pub redemption: bool,
+    pub emulation: bool,
+    sources: Vec<BondSellSource>,
+}
+
+impl BondSell {
+    pub fn new(
+        symbol: &str, quantity: Decimal, price: Cash, volume: Cash, accrued_interest: Cash,
+        commission: Cash, conclusion_date: Date, execution_date: Date, redemption: bool,
+        emulation: bool,
+    ) -> BondSell {
+        BondSell {
+            symbol: symbol.to_owned(), quantity, price, volume, accrued_interest, commission,
+            conclusion_date, execution_date, redemption, emulation, sources: Vec::new(),
+        }
+    }
+
+    pub fn is_processed(&self) -> bool {
+        !self.sources.is_empty()
+    }
+
+    pub fn process(&mut self, sources: Vec<BondSellSource>) {
+        assert!(!self.is_processed());
+        assert_eq!(sources.iter().map(|source| source.quantity).sum::<Decimal>(), self.quantity);
+        self.sources = sources;
+    }
+
+    pub fn calculate(&self, country: &Country, converter: &CurrencyConverter) -> GenericResult<BondSellDetails> {
+        Ok(self.calculate_impl(country, converter).map_err(|e| format!(
+            "Failed to calculate results of {} {} from {}: {}",
+            self.symbol, if self.redemption { "redemption" } else { "selling order" },
+            formatting::format_date(self.conclusion_date), e))?)
+    }
+
+    fn calculate_impl(&self, country: &Country, converter: &CurrencyConverter) -> GenericResult<BondSellDetails> {
+        // Accrued coupon interest received on sell is a part of the revenue and accrued coupon
+        // interest paid on buy is a part of the expenses
+        let revenue = self.volume.add(self.accrued_interest).map_err(|e| format!(
+            "Trade and accrued interest have different currency: {}", e))?.round();
+        let local_revenue = converter.convert_to_cash_rounding(
+            self.execution_date, revenue, country.currency)?;
+
+        let commission = self.commission.round();
+        let local_commission = converter.convert_to_cash_rounding(
+            self.conclusion_date, commission, country.currency)?;
+
+        let mut purchase_cost = Cash::new(revenue.currency, dec!(0));
+        let mut purchase_local_cost = Cash::new(country.currency, dec!(0));
+
+        for source in &self.sources {
+            let (cost, local_cost) = source.calculate(country, converter)?;
+
+            purchase_cost.add_assign(cost).map_err(|e| format!(
+                "Sell and buy trades have different currency: {}", e))?;
+            purchase_local_cost.add_assign(local_cost).unwrap();
+        }
+
+        let total_cost = purchase_cost.add(commission).map_err(|e| format!(
+            "Trade and commission have different currency: {}", e))?;
+        let total_local_cost = purchase_local_cost.add(local_commission).unwrap();
+
+        let profit = revenue.sub(total_cost)?;
+        let local_profit = local_revenue.sub(total_local_cost).unwrap();
+        let tax_to_pay = Cash::new(country.currency, country.tax_to_pay(local_profit.amount, None));
+
+        Ok(BondSellDetails {
+            revenue,
+            local_revenue,
+            local_commission,
+
+            purchase_local_cost,
+            total_local_cost,
+
+            profit,
+            local_profit,
+            tax_to_pay,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BondSellSource {
+    pub quantity: Decimal,
+    pub price: Cash,
+    pub accrued_interest: Cash,
+    pub commission: Cash,
+
+    pub conclusion_date: Date,
+    pub execution_date: Date,
+}
+
+impl BondSellSource {
+    fn calculate(&self, country: &Country, converter: &CurrencyConverter) -> GenericResult<(Cash, Cash)> {
+        let cost = (self.price * self.quantity).add(self.accrued_interest).map_err(|e| format!(
+            "Trade and accrued interest have different currency: {}", e))?.round();
+        let local_cost = converter.convert_to_cash_rounding(
+            self.execution_date, cost, country.currency)?;
+
+        let commission = self.commission.round();
+        let local_commission = converter.convert_to_cash_rounding(
+            self.conclusion_date, commission, country.currency)?;
+
+        let total_cost = cost.add(commission).map_err(|e| format!(
+            "Trade and commission have different currency: {}", e))?;
+
+        Ok((total_cost, local_cost.add(local_commission).unwrap()))
+    }
+}
+
+pub struct BondSellDetails {
+    pub revenue: Cash,
+    pub local_revenue: Cash,
+    pub local_commission: Cash,
+
+    pub purchase_local_cost: Cash,
+    pub total_local_cost: Cash,
+
+    pub profit: Cash,
+    pub local_profit: Cash,
+    pub tax_to_pay: Cash,
+}
+
+#[derive(Debug)]
+pub struct CouponPayment {
+    pub date: Date,
+    pub symbol: String,
+    pub issuer_country: CountryCode,
+    pub amount: Cash,
+    pub paid_tax: Cash,
+}
+
+impl CouponPayment {
+    pub fn tax_to_pay(&self, country: &Country, converter: &CurrencyConverter) -> GenericResult<Decimal> {
+        let amount = converter.convert_to_rounding(self.date, self.amount, country.currency)?;
+        let paid_tax = converter.convert_to_rounding(self.date, self.paid_tax, country.currency)?;
+        Ok(country.tax_to_pay(amount, Some(paid_tax)))
+    }
+
+    pub fn description(&self) -> String {
+        format!("{} coupon from {}", self.symbol, formatting::format_date(self.date))
+    }
+}
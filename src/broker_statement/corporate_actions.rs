@@ -1,8 +1,10 @@
 use std::collections::{HashMap, BTreeMap, btree_map};
 
 use crate::core::EmptyResult;
+use crate::currency::Cash;
 use crate::formatting::format_date;
 use crate::types::{Date, Decimal};
+use crate::util;
 
 #[derive(Debug)]
 #[cfg_attr(test, derive(PartialEq))]
@@ -15,65 +17,269 @@ pub struct CorporateAction {
 #[derive(Debug)]
 #[cfg_attr(test, derive(PartialEq))]
 pub enum CorporateActionType {
-    StockSplit(u32),
+    StockSplit(StockSplitRatio),
+
+    // The stock is renamed to the specified symbol without any changes in the position
+    SymbolChange(String),
+
+    // The stock holders receive the specified quantity of the new company stocks. Cost basis of
+    // the original position is split between the original and the received stocks proportionally
+    // to their quantities.
+    SpinOff {
+        symbol: String,
+        quantity: Decimal,
+        currency: String,
+    },
+
+    // The stock is exchanged to the specified stock with the specified ratio. Cost basis of the
+    // original position is carried over to the new one.
+    StockMerger {
+        symbol: String,
+        ratio: StockSplitRatio,
+    },
+
+    // The stock position is closed with the specified cash payout per stock which is taxed as an
+    // ordinary stock sell.
+    CashMerger {
+        quantity: Decimal,
+        price: Cash,
+    },
+
+    // Fractional stocks which are left after stock split are paid out in cash. The payout is taxed
+    // as an ordinary stock sell.
+    CashInLieu {
+        quantity: Decimal,
+        price: Cash,
+    },
+
+    // The bond is redeemed by the issuer at the specified price which is taxed as an ordinary bond
+    // sell.
+    BondRedemption {
+        quantity: Decimal,
+        price: Cash,
+    },
+}
+
+/// Describes a stock split where `from` stocks are turned into `to` stocks.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StockSplitRatio {
+    pub from: u32,
+    pub to: u32,
+}
+
+impl StockSplitRatio {
+    pub fn new(from: u32, to: u32) -> StockSplitRatio {
+        assert!(from > 0 && to > 0);
+        StockSplitRatio {from, to}
+    }
+}
+
+// Stock quantity precision which is used when quantities are converted between stock splits. It's
+// way above any precision that is used by brokers for fractional shares.
+const QUANTITY_PRECISION: u32 = 8;
+
+/// Returns true if the quantity is a leftover of rounding errors which have been accumulated during
+/// quantity conversions between stock splits.
+pub fn is_quantity_dust(quantity: Decimal) -> bool {
+    util::round(quantity, QUANTITY_PRECISION - 2).is_zero()
 }
 
 #[derive(Default, Debug)]
 pub struct StockSplitController {
-    symbols: HashMap<String, BTreeMap<Date, u32>>
+    symbols: HashMap<String, BTreeMap<Date, StockSplitRatio>>,
+    symbol_changes: HashMap<String, BTreeMap<Date, String>>,
 }
 
 impl StockSplitController {
-    pub fn add(&mut self, date: Date, symbol: &str, divisor: u32) -> EmptyResult {
+    pub fn add(&mut self, date: Date, symbol: &str, ratio: StockSplitRatio) -> EmptyResult {
         let splits = self.symbols.entry(symbol.to_owned()).or_default();
 
         match splits.entry(date) {
-            btree_map::Entry::Vacant(entry) => entry.insert(divisor),
+            btree_map::Entry::Vacant(entry) => entry.insert(ratio),
             btree_map::Entry::Occupied(_) => return Err!(
                 "Got a duplicated {} stock split for {}",
                  symbol, format_date(date),
             ),
         };
 
-        let mut full_divisor = dec!(1);
+        Ok(())
+    }
 
-        for cur_divisor in splits.values().rev().copied() {
-            full_divisor *= Decimal::from(cur_divisor);
+    pub fn add_symbol_change(&mut self, date: Date, symbol: &str, new_symbol: &str) -> EmptyResult {
+        if symbol == new_symbol {
+            return Err!("Got an invalid {} symbol change for {}", symbol, format_date(date));
+        }
 
-            if dec!(1) / full_divisor * full_divisor != dec!(1) {
-                splits.remove(&date).unwrap();
-                return Err!("Got an unsupported stock split result divisor: {}", full_divisor);
-            }
+        // Symbol changes are followed in a chain, so changes of the same day mustn't form a cycle
+        let mut next_symbol = new_symbol;
+        loop {
+            next_symbol = match self.symbol_changes.get(next_symbol).and_then(|changes| changes.get(&date)) {
+                Some(changed_symbol) if changed_symbol == symbol => return Err!(
+                    "Got a cyclic {} -> {} symbol change for {}",
+                    symbol, new_symbol, format_date(date),
+                ),
+                Some(changed_symbol) => changed_symbol,
+                None => break,
+            };
         }
 
+        match self.symbol_changes.entry(symbol.to_owned()).or_default().entry(date) {
+            btree_map::Entry::Vacant(entry) => entry.insert(new_symbol.to_owned()),
+            btree_map::Entry::Occupied(_) => return Err!(
+                "Got a duplicated {} symbol change for {}",
+                symbol, format_date(date),
+            ),
+        };
+
         Ok(())
     }
 
+    /// Returns the symbol under which the position opened at `from_date` with the specified symbol
+    /// is known at `to_date`.
+    pub fn get_symbol(&self, symbol: &str, from_date: Date, to_date: Date) -> String {
+        let mut symbol = symbol;
+        let mut cur_date = from_date;
+
+        while let Some((date, new_symbol)) = self.get_symbol_change(symbol, cur_date, to_date) {
+            symbol = new_symbol;
+            cur_date = date;
+        }
+
+        symbol.to_owned()
+    }
+
+    /// Returns stock quantity multiplier for the position. The symbol must be specified as it's
+    /// known at the earliest of the dates.
+    ///
+    /// Please note that the multiplier may be inexact for some split ratios, so use
+    /// `convert_quantity()` for quantity calculations.
     pub fn get_multiplier(&self, symbol: &str, from_date: Date, to_date: Date) -> Decimal {
-        let mut multiplier = dec!(1);
+        let (numerator, denominator) = self.get_ratio(symbol, from_date, to_date);
+        (numerator / denominator).normalize()
+    }
+
+    /// Converts stock quantity from `from_date` to `to_date` taking into account all stock splits
+    /// that have been occurred between the dates. The symbol must be specified as it's known at the
+    /// earliest of the dates.
+    pub fn convert_quantity(&self, symbol: &str, from_date: Date, to_date: Date, quantity: Decimal) -> Decimal {
+        let (numerator, denominator) = self.get_ratio(symbol, from_date, to_date);
+        util::round(quantity * numerator / denominator, QUANTITY_PRECISION)
+    }
+
+    fn get_ratio(&self, symbol: &str, from_date: Date, to_date: Date) -> (Decimal, Decimal) {
+        let mut numerator = dec!(1);
+        let mut denominator = dec!(1);
 
         let (start, end, divide) = if from_date < to_date {
-            (from_date.succ(), to_date, false)
+            (from_date, to_date, false)
         } else if to_date < from_date {
-            (to_date.succ(), from_date, true)
+            (to_date, from_date, true)
         } else {
-            return multiplier;
+            return (numerator, denominator);
         };
 
-        let splits = match self.symbols.get(symbol) {
-            Some(splits) => splits,
-            None => return multiplier,
-        };
+        let mut symbol = symbol;
+        let mut cur_date = start;
+
+        loop {
+            let symbol_change = self.get_symbol_change(symbol, cur_date, end);
+            let period_end = symbol_change.map(|(date, _)| date).unwrap_or(end);
 
-        for (_, &divisor) in splits.range(start..=end) {
-            multiplier *= Decimal::from(divisor);
+            if let Some(splits) = self.symbols.get(symbol) {
+                if cur_date < period_end {
+                    for (_, ratio) in splits.range(cur_date.succ()..=period_end) {
+                        numerator *= Decimal::from(ratio.to);
+                        denominator *= Decimal::from(ratio.from);
+                    }
+                }
+            }
+
+            match symbol_change {
+                Some((date, new_symbol)) => {
+                    symbol = new_symbol;
+                    cur_date = date;
+                },
+                None => break,
+            };
         }
 
         if divide {
-            multiplier = dec!(1) / multiplier;
-            assert_eq!(dec!(1) * multiplier / multiplier, dec!(1));
+            (denominator, numerator)
+        } else {
+            (numerator, denominator)
         }
+    }
+
+    fn get_symbol_change(&self, symbol: &str, from_date: Date, to_date: Date) -> Option<(Date, &str)> {
+        if from_date > to_date {
+            return None;
+        }
+
+        self.symbol_changes.get(symbol).and_then(|changes| {
+            changes.range(from_date..=to_date).next().map(|(&date, new_symbol)| {
+                (date, new_symbol.as_str())
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        multiplier
+    #[test]
+    fn symbol_changes() {
+        let mut controller = StockSplitController::default();
+
+        controller.add(date!(1, 2, 2020), "OLD", StockSplitRatio::new(1, 4)).unwrap();
+        controller.add(date!(1, 3, 2020), "OLD", StockSplitRatio::new(2, 1)).unwrap();
+        controller.add_symbol_change(date!(1, 4, 2020), "OLD", "NEW").unwrap();
+        controller.add(date!(1, 5, 2020), "NEW", StockSplitRatio::new(10, 1)).unwrap();
+
+        assert_eq!(controller.get_symbol("OLD", date!(1, 1, 2020), date!(31, 3, 2020)), "OLD");
+        assert_eq!(controller.get_symbol("OLD", date!(1, 1, 2020), date!(1, 4, 2020)), "NEW");
+        assert_eq!(controller.get_symbol("NEW", date!(1, 1, 2020), date!(1, 6, 2020)), "NEW");
+
+        assert_eq!(controller.get_multiplier("OLD", date!(1, 1, 2020), date!(1, 2, 2020)), dec!(4));
+        assert_eq!(controller.get_multiplier("OLD", date!(1, 1, 2020), date!(1, 4, 2020)), dec!(2));
+        assert_eq!(controller.get_multiplier("OLD", date!(1, 1, 2020), date!(1, 6, 2020)), dec!(0.2));
+        assert_eq!(controller.get_multiplier("OLD", date!(1, 6, 2020), date!(1, 1, 2020)), dec!(5));
+        assert_eq!(controller.get_multiplier("NEW", date!(1, 4, 2020), date!(1, 6, 2020)), dec!(0.1));
+    }
+
+    #[test]
+    fn fractional_splits() {
+        let mut controller = StockSplitController::default();
+
+        controller.add(date!(1, 2, 2020), "SYMBOL", StockSplitRatio::new(2, 3)).unwrap();
+        controller.add(date!(1, 3, 2020), "SYMBOL", StockSplitRatio::new(3, 1)).unwrap();
+
+        assert_eq!(controller.get_multiplier("SYMBOL", date!(1, 1, 2020), date!(1, 2, 2020)), dec!(1.5));
+        assert_eq!(controller.get_multiplier("SYMBOL", date!(1, 1, 2020), date!(1, 3, 2020)), dec!(0.5));
+
+        assert_eq!(controller.convert_quantity(
+            "SYMBOL", date!(1, 1, 2020), date!(1, 2, 2020), dec!(7)), dec!(10.5));
+        assert_eq!(controller.convert_quantity(
+            "SYMBOL", date!(1, 2, 2020), date!(1, 1, 2020), dec!(0.5)), dec!(0.33333333));
+        assert_eq!(controller.convert_quantity(
+            "SYMBOL", date!(1, 1, 2020), date!(1, 3, 2020), dec!(7)), dec!(3.5));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn cyclic_symbol_changes() {
+        let mut controller = StockSplitController::default();
+
+        controller.add_symbol_change(date!(1, 2, 2020), "A", "B").unwrap();
+        controller.add_symbol_change(date!(1, 3, 2020), "B", "A").unwrap();
+        controller.add_symbol_change(date!(1, 4, 2020), "A", "B").unwrap();
+        controller.add_symbol_change(date!(1, 4, 2020), "B", "C").unwrap();
+
+        assert_eq!(
+            controller.add_symbol_change(date!(1, 4, 2020), "C", "A").unwrap_err().to_string(),
+            "Got a cyclic C -> A symbol change for 01.04.2020");
+
+        assert_eq!(controller.get_symbol("A", date!(1, 1, 2020), date!(1, 2, 2020)), "B");
+        assert_eq!(controller.get_symbol("A", date!(1, 1, 2020), date!(1, 3, 2020)), "A");
+        assert_eq!(controller.get_symbol("A", date!(1, 1, 2020), date!(1, 5, 2020)), "C");
+    }
+}
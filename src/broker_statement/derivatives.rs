@@ -0,0 +1,290 @@
+use crate::core::GenericResult;
+use crate::currency::Cash;
+use crate::currency::converter::CurrencyConverter;
+use crate::formatting;
+use crate::localities::Country;
+use crate::types::{Date, Decimal};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DerivativeType {
+    Option,
+    Future,
+}
+
+#[derive(Debug)]
+pub struct DerivativeTrade {
+    pub symbol: String,
+    pub type_: DerivativeType,
+    pub quantity: Decimal, // Positive for buys and negative for sells
+    pub price: Cash,
+    pub volume: Cash, // Negative for buys and positive for sells (zero on option expiration)
+    pub commission: Cash,
+
+    pub conclusion_date: Date,
+    pub execution_date: Date,
+
+    processed: bool,
+    open: Decimal,
+    sources: Vec<DerivativeTradeSource>,
+}
+
+impl DerivativeTrade {
+    pub fn new(
+        symbol: &str, type_: DerivativeType, quantity: Decimal, price: Cash, volume: Cash,
+        commission: Cash, conclusion_date: Date, execution_date: Date,
+    ) -> DerivativeTrade {
+        DerivativeTrade {
+            symbol: symbol.to_owned(), type_, quantity, price, volume, commission,
+            conclusion_date, execution_date, processed: false, open: quantity, sources: Vec::new(),
+        }
+    }
+
+    pub fn is_processed(&self) -> bool {
+        self.processed
+    }
+
+    /// Returns the part of the trade which is still open (has the same sign as trade quantity).
+    pub fn get_open(&self) -> Decimal {
+        self.open
+    }
+
+    /// Returns the quantity of the positions that have been closed by this trade.
+    pub fn get_closed(&self) -> Decimal {
+        assert!(self.processed);
+        self.sources.iter().map(|source| source.quantity).sum()
+    }
+
+    pub fn close(&mut self, quantity: Decimal) {
+        assert!(quantity <= self.open.abs());
+
+        if self.open.is_sign_positive() {
+            self.open -= quantity;
+        } else {
+            self.open += quantity;
+        }
+    }
+
+    pub fn process(&mut self, sources: Vec<DerivativeTradeSource>) {
+        assert!(!self.processed);
+
+        let closed: Decimal = sources.iter().map(|source| source.quantity).sum();
+        self.close(closed);
+
+        self.sources = sources;
+        self.processed = true;
+    }
+
+    /// Returns the actual cash flow of the trade: option premium or futures financial result on
+    /// position closing.
+    pub fn get_cash_flow(&self) -> Cash {
+        match self.type_ {
+            DerivativeType::Option => self.volume,
+            DerivativeType::Future => {
+                let closed = self.get_closed();
+                let mut result = self.volume * (closed / self.quantity.abs());
+
+                for source in &self.sources {
+                    result.add_assign(source.volume).unwrap();
+                }
+
+                result
+            },
+        }
+    }
+
+    pub fn calculate(&self, country: &Country, converter: &CurrencyConverter) -> GenericResult<DerivativeDetails> {
+        Ok(self.calculate_impl(country, converter).map_err(|e| format!(
+            "Failed to calculate results of {} closing trade from {}: {}",
+            self.symbol, formatting::format_date(self.conclusion_date), e))?)
+    }
+
+    fn calculate_impl(&self, country: &Country, converter: &CurrencyConverter) -> GenericResult<DerivativeDetails> {
+        let closed = self.get_closed();
+        assert!(!closed.is_zero());
+        let share = closed / self.quantity.abs();
+
+        // Cash flows of the position opening and closing trades
+        let mut legs = vec![(self.execution_date, self.volume * share)];
+        let mut commissions = vec![(self.conclusion_date, self.commission * share)];
+
+        for source in &self.sources {
+            legs.push((source.execution_date, source.volume));
+            commissions.push((source.conclusion_date, source.commission));
+        }
+
+        let currency = self.volume.currency;
+        let mut revenue = Cash::new(currency, dec!(0));
+        let mut local_revenue = Cash::new(country.currency, dec!(0));
+        let mut cost = Cash::new(currency, dec!(0));
+        let mut local_cost = Cash::new(country.currency, dec!(0));
+
+        match self.type_ {
+            // Option premiums are income and expenses of the dates when they have been received and
+            // paid
+            DerivativeType::Option => {
+                for (date, amount) in legs {
+                    let amount = amount.round();
+                    let local_amount = converter.convert_to_cash_rounding(
+                        date, amount, country.currency)?;
+
+                    if amount.is_negative() {
+                        cost.sub_assign(amount).map_err(|e| format!(
+                            "Position opening and closing trades have different currency: {}", e))?;
+                        local_cost.sub_assign(local_amount).unwrap();
+                    } else {
+                        revenue.add_assign(amount).map_err(|e| format!(
+                            "Position opening and closing trades have different currency: {}", e))?;
+                        local_revenue.add_assign(local_amount).unwrap();
+                    }
+                }
+            },
+
+            // Futures don't require the notional payment, so only the financial result is
+            // accounted at position closing date
+            DerivativeType::Future => {
+                let mut result = Cash::new(currency, dec!(0));
+
+                for (_, amount) in legs {
+                    result.add_assign(amount).map_err(|e| format!(
+                        "Position opening and closing trades have different currency: {}", e))?;
+                }
+
+                let result = result.round();
+                let local_result = converter.convert_to_cash_rounding(
+                    self.execution_date, result, country.currency)?;
+
+                if result.is_negative() {
+                    cost.sub_assign(result).unwrap();
+                    local_cost.sub_assign(local_result).unwrap();
+                } else {
+                    revenue.add_assign(result).unwrap();
+                    local_revenue.add_assign(local_result).unwrap();
+                }
+            },
+        };
+
+        let mut local_commission = Cash::new(country.currency, dec!(0));
+
+        for (date, commission) in commissions {
+            let commission = commission.round();
+            cost.add_assign(commission).map_err(|e| format!(
+                "Trade and commission have different currency: {}", e))?;
+            local_commission.add_assign(converter.convert_to_cash_rounding(
+                date, commission, country.currency)?).unwrap();
+        }
+
+        let total_local_cost = local_cost.add(local_commission).unwrap();
+
+        let profit = revenue.sub(cost).unwrap();
+        let local_profit = local_revenue.sub(total_local_cost).unwrap();
+        let tax_to_pay = Cash::new(country.currency, country.tax_to_pay(local_profit.amount, None));
+
+        Ok(DerivativeDetails {
+            revenue,
+            local_revenue,
+            local_commission,
+            total_local_cost,
+
+            profit,
+            local_profit,
+            tax_to_pay,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct DerivativeTradeSource {
+    pub quantity: Decimal,
+    pub volume: Cash,
+    pub commission: Cash,
+
+    pub conclusion_date: Date,
+    pub execution_date: Date,
+}
+
+pub struct DerivativeDetails {
+    pub revenue: Cash,
+    pub local_revenue: Cash,
+    pub local_commission: Cash,
+    pub total_local_cost: Cash,
+
+    pub profit: Cash,
+    pub local_profit: Cash,
+    pub tax_to_pay: Cash,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use chrono::Datelike;
+
+    use crate::broker_statement::BrokerStatement;
+    use crate::brokers::Broker;
+    use crate::config::Config;
+    use crate::currency::converter::CurrencyConverterBackend;
+    use crate::localities;
+    use crate::taxes::TaxRemapping;
+
+    use super::*;
+
+    struct TestBackend;
+
+    impl CurrencyConverterBackend for TestBackend {
+        fn convert(&self, from: &str, to: &str, date: Date, amount: Decimal) -> GenericResult<Decimal> {
+            assert_eq!((from, to, date.year()), ("USD", "RUB", 2020));
+
+            let rate = match (date.day(), date.month()) {
+                (3, 8) => dec!(73),
+                (5, 8) => dec!(74),
+                (20, 8) => dec!(75),
+                (1, 9) => dec!(76),
+                (10, 9) => dec!(77),
+                (18, 9) => dec!(78),
+                _ => unreachable!(),
+            };
+
+            Ok(amount * rate)
+        }
+    }
+
+    #[test]
+    fn calculate() {
+        let broker = Broker::InteractiveBrokers.get_info(&Config::mock(), None).unwrap();
+        let path = Path::new(file!()).parent().unwrap().join("ib/testdata/derivatives");
+        let statement = BrokerStatement::read(
+            broker, path.to_str().unwrap(), &hashmap!{}, &hashmap!{}, TaxRemapping::new(), true).unwrap();
+
+        let country = localities::russia();
+        let converter = CurrencyConverter::new_with_backend(Box::new(TestBackend));
+
+        let closing_trades: Vec<&DerivativeTrade> = statement.derivative_trades.iter()
+            .filter(|trade| !trade.get_closed().is_zero())
+            .collect();
+        assert_eq!(closing_trades.len(), 3);
+
+        for &(trade, revenue, local_revenue, local_commission, total_local_cost, profit, local_profit, tax_to_pay) in &[
+            // Short option: the premium is received on opening and paid on closing
+            (closing_trades[0], dec!(300), dec!(21900), dec!(155.40), dec!(7655.40),
+             dec!(197.90), dec!(14244.60), dec!(1852)),
+
+            // Futures: the financial result is converted at position closing date
+            (closing_trades[1], dec!(500), dec!(38500), dec!(324.36), dec!(324.36),
+             dec!(495.76), dec!(38175.64), dec!(4963)),
+
+            // Long option expiration: the paid premium is a loss
+            (closing_trades[2], dec!(0), dec!(0), dec!(77.70), dec!(3777.70),
+             dec!(-51.05), dec!(-3777.70), dec!(0)),
+        ] {
+            let details = trade.calculate(&country, &converter).unwrap();
+
+            assert_eq!(details.revenue, Cash::new("USD", revenue));
+            assert_eq!(details.local_revenue, Cash::new("RUB", local_revenue));
+            assert_eq!(details.local_commission, Cash::new("RUB", local_commission));
+            assert_eq!(details.total_local_cost, Cash::new("RUB", total_local_cost));
+            assert_eq!(details.profit, Cash::new("USD", profit));
+            assert_eq!(details.local_profit, Cash::new("RUB", local_profit));
+            assert_eq!(details.tax_to_pay, Cash::new("RUB", tax_to_pay));
+        }
+    }
+}
@@ -4,7 +4,7 @@ use crate::core::GenericResult;
 use crate::currency::Cash;
 use crate::currency::converter::CurrencyConverter;
 use crate::formatting;
-use crate::localities::Country;
+use crate::localities::{Country, CountryCode};
 use crate::types::{Date, Decimal};
 
 use super::payments::Payments;
@@ -14,6 +14,7 @@ use super::taxes::{TaxId, TaxAccruals};
 pub struct Dividend {
     pub date: Date,
     pub issuer: String,
+    pub issuer_country: CountryCode,
     pub amount: Cash,
     pub paid_tax: Cash,
 }
@@ -26,10 +27,35 @@ impl Dividend {
 
     pub fn tax_to_pay(&self, country: &Country, converter: &CurrencyConverter) -> GenericResult<Decimal> {
         let amount = converter.convert_to_rounding(self.date, self.amount, country.currency)?;
-        let paid_tax = converter.convert_to_rounding(self.date, self.paid_tax, country.currency)?;
+        let paid_tax = converter.convert_to_rounding(self.date, self.creditable_tax()?, country.currency)?;
         Ok(country.tax_to_pay(amount, Some(paid_tax)))
     }
 
+    /// Returns the part of the paid tax which may be credited against the local tax: it's limited by
+    /// the rate established by the double taxation treaty with the issuer country (if the program
+    /// knows it).
+    pub fn creditable_tax(&self) -> GenericResult<Cash> {
+        let amount = self.amount.round();
+        let paid_tax = self.paid_tax.round();
+
+        if paid_tax.currency != amount.currency {
+            return Err!(
+                "{}: Tax currency is different from dividend currency: {} vs {}",
+                self.description(), paid_tax.currency, amount.currency);
+        }
+
+        let max_tax = match self.issuer_country.dividend_treaty_tax_rate() {
+            Some(treaty_tax_rate) => (amount * treaty_tax_rate).round(),
+            None => return Ok(paid_tax),
+        };
+
+        Ok(if paid_tax.amount > max_tax.amount {
+            max_tax
+        } else {
+            paid_tax
+        })
+    }
+
     pub fn description(&self) -> String {
         format!("{} dividend from {}", self.issuer, formatting::format_date(self.date))
     }
@@ -39,6 +65,7 @@ impl Dividend {
 pub struct DividendId {
     pub date: Date,
     pub issuer: String,
+    pub issuer_country: CountryCode,
 }
 
 pub type DividendAccruals = Payments;
@@ -71,7 +98,39 @@ pub fn process_dividend_accruals(
     Ok(Some(Dividend {
         date: dividend.date,
         issuer: dividend.issuer,
+        issuer_country: dividend.issuer_country,
         amount: amount,
         paid_tax: paid_tax.unwrap_or_else(|| Cash::new(amount.currency, dec!(0))),
     }))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::localities::Jurisdiction;
+
+    use super::*;
+
+    #[test]
+    fn creditable_tax() {
+        let mut dividend = Dividend {
+            date: date!(1, 6, 2020),
+            issuer: s!("ALV"),
+            issuer_country: CountryCode::Known(Jurisdiction::Germany),
+            amount: Cash::new("EUR", dec!(100)),
+            paid_tax: Cash::new("EUR", dec!(26.38)),
+        };
+        assert_eq!(dividend.creditable_tax().unwrap(), Cash::new("EUR", dec!(15)));
+
+        dividend.issuer_country = CountryCode::Known(Jurisdiction::Ireland);
+        dividend.paid_tax = Cash::new("EUR", dec!(0));
+        assert_eq!(dividend.creditable_tax().unwrap(), Cash::new("EUR", dec!(0)));
+
+        dividend.issuer_country = CountryCode::Unknown(124);
+        dividend.paid_tax = Cash::new("EUR", dec!(25));
+        assert_eq!(dividend.creditable_tax().unwrap(), Cash::new("EUR", dec!(25)));
+
+        dividend.issuer_country = CountryCode::Known(Jurisdiction::Usa);
+        dividend.paid_tax = Cash::new("USD", dec!(10));
+        assert!(dividend.creditable_tax().is_err());
+    }
+}
@@ -67,7 +67,7 @@ struct Report {
     #[serde(rename = "CURDEF")]
     currency: String,
     #[serde(rename = "INVACCTFROM")]
-    _account: Ignore,
+    account: Account,
     #[serde(rename = "INVTRANLIST")]
     transactions: Transactions,
     #[serde(rename = "INVPOSLIST")]
@@ -76,6 +76,15 @@ struct Report {
     balance: Balance,
 }
 
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Account {
+    #[serde(rename = "BROKERID")]
+    _broker_id: Ignore,
+    #[serde(rename = "ACCTID")]
+    id: String,
+}
+
 impl OFX {
     pub fn parse(self, parser: &mut StatementParser) -> EmptyResult {
         let report = self.statement.response.report;
@@ -94,6 +103,7 @@ impl OFX {
         }
 
         parser.statement.set_period((start_date, end_date))?;
+        parser.statement.set_account_number(&report.account.id)?;
 
         parser.statement.set_starting_assets(false)?;
         report.balance.parse(parser, &currency)?;
@@ -6,7 +6,7 @@ use crate::broker_statement::{StockBuy, StockSell, IdleCashInterest, Dividend};
 use crate::core::EmptyResult;
 use crate::currency::{Cash, CashAssets};
 use crate::formatting;
-use crate::localities;
+use crate::localities::{self, CountryCode, Jurisdiction};
 use crate::types::{Date, Decimal};
 use crate::util::{self, DecimalRestrictions};
 
@@ -320,6 +320,7 @@ impl IncomeInfo {
         parser.statement.dividends.push(Dividend {
             date: date,
             issuer: issuer.to_owned(),
+            issuer_country: CountryCode::Known(Jurisdiction::Usa),
             amount: Cash::new(currency, amount),
             paid_tax: Cash::new(currency, paid_tax),
         });
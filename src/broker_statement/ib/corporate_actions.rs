@@ -1,9 +1,11 @@
 use lazy_static::lazy_static;
-use regex::Regex;
+use regex::{Captures, Regex};
 
-use crate::broker_statement::corporate_actions::{CorporateAction, CorporateActionType};
+use crate::broker_statement::corporate_actions::{CorporateAction, CorporateActionType, StockSplitRatio};
 use crate::core::{EmptyResult, GenericResult};
-use crate::types::Date;
+use crate::currency::Cash;
+use crate::types::{Date, Decimal};
+use crate::util::{self, DecimalRestrictions};
 
 use super::StatementParser;
 use super::common::{Record, RecordParser};
@@ -19,43 +21,194 @@ impl RecordParser for CorporateActionsParser {
         let asset_category = record.get_value("Asset Category")?;
         let date = record.parse_date("Report Date")?;
         let description = record.get_value("Description")?;
+        let currency = record.get_value("Currency")?;
+        let quantity = record.parse_amount("Quantity", DecimalRestrictions::No)?;
+        let proceeds = record.parse_amount("Proceeds", DecimalRestrictions::No)?;
 
-        let corporate_action = parse(date, asset_category, description)?;
-        parser.statement.corporate_actions.push(corporate_action);
+        if let Some(corporate_action) = parse(
+            date, asset_category, description, currency, quantity, proceeds,
+        )? {
+            parser.statement.corporate_actions.push(corporate_action);
+        }
 
         Ok(())
     }
 }
 
-fn parse(date: Date, asset_category: &str, description: &str) -> GenericResult<CorporateAction> {
+// Interactive Brokers reports most of corporate actions as a pair of records: the first one
+// removes the old position (negative quantity) and the second one adds the new position (positive
+// quantity). The actions are parsed from one of them and the other one is skipped.
+fn parse(
+    date: Date, asset_category: &str, description: &str, currency: &str, quantity: Decimal,
+    proceeds: Decimal,
+) -> GenericResult<Option<CorporateAction>> {
     lazy_static! {
         static ref STOCK_SPLIT_REGEX: Regex = Regex::new(
-            r"^(?P<symbol>[A-Z]+) ?\([A-Z0-9]+\) Split (?P<divisor>\d+) for (?P<dividend>\d+) \([^)]+\)$",
+            r"^(?P<symbol>[A-Z]+)(?:\.OLD)? ?\([A-Z0-9]+\) Split (?P<divisor>\d+) for (?P<dividend>\d+) \([^)]+\)$",
         ).unwrap();
+
+        static ref SYMBOL_CHANGE_REGEX: Regex = Regex::new(concat!(
+            r"^(?P<symbol>[A-Z]+)(?:\.OLD)? ?\([A-Z0-9]+\) (?:CUSIP/ISIN|Symbol) Change to ",
+            r"\([A-Z0-9]+\) \((?P<new_symbol>[A-Z]+), [^)]+\)$",
+        )).unwrap();
+
+        static ref SPIN_OFF_REGEX: Regex = Regex::new(concat!(
+            r"^(?P<symbol>[A-Z]+) ?\([A-Z0-9]+\) Spinoff +\d+ for \d+ ",
+            r"\((?P<new_symbol>[A-Z]+), [^)]+\)$",
+        )).unwrap();
+
+        static ref STOCK_MERGER_REGEX: Regex = Regex::new(concat!(
+            r"^(?P<symbol>[A-Z]+) ?\([A-Z0-9]+\) Merged\(Acquisition\) WITH [A-Z0-9]+ ",
+            r"(?P<divisor>\d+) FOR (?P<dividend>\d+) \((?P<new_symbol>[A-Z]+), [^)]+\)$",
+        )).unwrap();
+
+        static ref CASH_MERGER_REGEX: Regex = Regex::new(concat!(
+            r"^(?P<symbol>[A-Z]+) ?\([A-Z0-9]+\) Merged\(Acquisition\) FOR ",
+            r"(?P<currency>[A-Z]{3}) (?P<price>\d+(?:\.\d+)?) PER SHARE \([^)]+\)$",
+        )).unwrap();
+
+        static ref CASH_IN_LIEU_REGEX: Regex = Regex::new(
+            r"^(?P<symbol>[A-Z]+) ?\([A-Z0-9]+\) Cash in Lieu of Fractional Shares? \([^)]+\)$",
+        ).unwrap();
+
+        static ref BOND_REDEMPTION_REGEX: Regex = Regex::new(concat!(
+            r"^\([A-Z0-9]+\) (?:Bond Maturity|Full Call / Early Redemption) for ",
+            r"(?P<currency>[A-Z]{3}) (?P<price>\d+(?:\.\d+)?) per Bond \((?P<symbol>[^,]+), [^)]+\)$",
+        )).unwrap();
     }
 
-    Ok(match (asset_category, STOCK_SPLIT_REGEX.captures(description)) {
-        ("Stocks", Some(captures)) => {
-            let symbol = captures.name("symbol").unwrap().as_str();
-            let divisor: u32 = captures.name("divisor").unwrap().as_str().parse()?;
+    let get_symbol = |captures: &Captures, name: &str| -> String {
+        captures.name(name).unwrap().as_str().to_owned()
+    };
 
-            if divisor == 0 || captures.name("dividend").unwrap().as_str() != "1" {
-                return Err!("Unsupported stock split: {:?}", description);
-            }
+    if asset_category == "Bonds" {
+        let captures = BOND_REDEMPTION_REGEX.captures(description).ok_or_else(|| format!(
+            "Unsupported corporate action: {:?}", description))?;
 
-            CorporateAction {
-                date,
-                symbol: symbol.to_owned(),
-                action: CorporateActionType::StockSplit(divisor),
-            }
-        },
-        _ => return Err!("Unsupported corporate action: {:?}", description),
-    })
+        let quantity = util::validate_named_decimal(
+            "bond redemption quantity", -quantity, DecimalRestrictions::StrictlyPositive)?;
+
+        let price = util::parse_decimal(
+            captures.name("price").unwrap().as_str(), DecimalRestrictions::StrictlyPositive)?;
+
+        return Ok(Some(CorporateAction {
+            date,
+            symbol: get_symbol(&captures, "symbol"),
+            action: CorporateActionType::BondRedemption {
+                quantity: quantity.normalize(),
+                price: Cash::new(captures.name("currency").unwrap().as_str(), price),
+            },
+        }));
+    } else if asset_category != "Stocks" {
+        return Err!("Unsupported corporate action: {:?}", description);
+    }
+
+    // "N for M" means that M old stocks are turned into N new stocks
+    let parse_ratio = |captures: &Captures| -> GenericResult<StockSplitRatio> {
+        let divisor: u32 = captures.name("divisor").unwrap().as_str().parse()?;
+        let dividend: u32 = captures.name("dividend").unwrap().as_str().parse()?;
+
+        if divisor == 0 || dividend == 0 {
+            return Err!("Unsupported corporate action: {:?}", description);
+        }
+
+        Ok(StockSplitRatio::new(dividend, divisor))
+    };
+
+    let (symbol, action) = if let Some(captures) = STOCK_SPLIT_REGEX.captures(description) {
+        let ratio = parse_ratio(&captures)?;
+
+        // Reverse stock splits and the splits with CUSIP change are reported as a pair of records
+        // where the first one removes the old (*.OLD) position
+        if quantity.is_sign_negative() {
+            return Ok(None);
+        }
+
+        (get_symbol(&captures, "symbol"), CorporateActionType::StockSplit(ratio))
+    } else if let Some(captures) = SYMBOL_CHANGE_REGEX.captures(description) {
+        if quantity.is_sign_negative() {
+            return Ok(None);
+        }
+
+        (get_symbol(&captures, "symbol"), CorporateActionType::SymbolChange(
+            get_symbol(&captures, "new_symbol")))
+    } else if let Some(captures) = SPIN_OFF_REGEX.captures(description) {
+        let quantity = util::validate_named_decimal(
+            "spin-off quantity", quantity, DecimalRestrictions::StrictlyPositive)?;
+
+        (get_symbol(&captures, "symbol"), CorporateActionType::SpinOff {
+            symbol: get_symbol(&captures, "new_symbol"),
+            quantity: quantity.normalize(),
+            currency: currency.to_owned(),
+        })
+    } else if let Some(captures) = STOCK_MERGER_REGEX.captures(description) {
+        if quantity.is_sign_negative() {
+            return Ok(None);
+        }
+
+        (get_symbol(&captures, "symbol"), CorporateActionType::StockMerger {
+            symbol: get_symbol(&captures, "new_symbol"),
+            ratio: parse_ratio(&captures)?,
+        })
+    } else if let Some(captures) = CASH_MERGER_REGEX.captures(description) {
+        let quantity = util::validate_named_decimal(
+            "cash merger quantity", -quantity, DecimalRestrictions::StrictlyPositive)?;
+
+        let price = util::parse_decimal(
+            captures.name("price").unwrap().as_str(), DecimalRestrictions::StrictlyPositive)?;
+
+        (get_symbol(&captures, "symbol"), CorporateActionType::CashMerger {
+            quantity: quantity.normalize(),
+            price: Cash::new(captures.name("currency").unwrap().as_str(), price),
+        })
+    } else if let Some(captures) = CASH_IN_LIEU_REGEX.captures(description) {
+        let quantity = util::validate_named_decimal(
+            "cash in lieu quantity", -quantity, DecimalRestrictions::StrictlyPositive)?;
+
+        let proceeds = util::validate_named_decimal(
+            "cash in lieu proceeds", proceeds, DecimalRestrictions::StrictlyPositive)?;
+
+        (get_symbol(&captures, "symbol"), CorporateActionType::CashInLieu {
+            quantity: quantity.normalize(),
+            price: Cash::new(currency, proceeds / quantity),
+        })
+    } else {
+        return Err!("Unsupported corporate action: {:?}", description);
+    };
+
+    Ok(Some(CorporateAction {date, symbol, action}))
 }
 
 #[cfg(test)]
 mod tests {
+    use std::path::Path;
+
+    use crate::broker_statement::BrokerStatementReader;
+    use crate::taxes::TaxRemapping;
+
     use super::*;
+    use super::super::StatementReader;
+
+    #[test]
+    fn paired_records() {
+        let path = Path::new(file!()).parent().unwrap().join("testdata/corporate-actions.csv");
+        let statement = StatementReader::new(TaxRemapping::new(), true).unwrap()
+            .read(path.to_str().unwrap()).unwrap();
+
+        assert_eq!(statement.corporate_actions, vec![CorporateAction {
+            date: date!(31, 8, 2020),
+            symbol: s!("AAPL"),
+            action: CorporateActionType::StockSplit(StockSplitRatio::new(1, 4)),
+        }, CorporateAction {
+            date: date!(15, 10, 2020),
+            symbol: s!("SIVR"),
+            action: CorporateActionType::StockSplit(StockSplitRatio::new(1, 2)),
+        }, CorporateAction {
+            date: date!(16, 12, 2020),
+            symbol: s!("VXX"),
+            action: CorporateActionType::StockSplit(StockSplitRatio::new(4, 1)),
+        }]);
+    }
 
     #[test]
     fn parsing() {
@@ -64,13 +217,142 @@ mod tests {
         assert_eq!(
             parse(
                 date, "Stocks", "AAPL(US0378331005) Split 4 for 1 (AAPL, APPLE INC, US0378331005)",
-            ).unwrap(),
+                "USD", dec!(300), dec!(0),
+            ).unwrap().unwrap(),
 
             CorporateAction {
                 date,
                 symbol: s!("AAPL"),
-                action: CorporateActionType::StockSplit(4),
+                action: CorporateActionType::StockSplit(StockSplitRatio::new(1, 4)),
+            }
+        );
+
+        assert_eq!(
+            parse(
+                date, "Stocks", "VXX(US06742E7114) Split 1 for 4 (VXX, IPATH SERIES B S&P 500 VIX, US06748F1075)",
+                "USD", dec!(25), dec!(0),
+            ).unwrap().unwrap(),
+
+            CorporateAction {
+                date,
+                symbol: s!("VXX"),
+                action: CorporateActionType::StockSplit(StockSplitRatio::new(4, 1)),
+            }
+        );
+
+        assert_eq!(
+            parse(
+                date, "Stocks", "VXX.OLD(US06742E7114) Split 1 for 4 (VXX.OLD, IPATH SERIES B S&P 500 VIX, US06742E7114)",
+                "USD", dec!(-100), dec!(0),
+            ).unwrap(),
+            None,
+        );
+
+        assert_eq!(
+            parse(
+                date, "Stocks", "FB(US30303M1027) CUSIP/ISIN Change to (US30303M1027) (META, META PLATFORMS INC-CLASS A, US30303M1027)",
+                "USD", dec!(10), dec!(0),
+            ).unwrap().unwrap(),
+
+            CorporateAction {
+                date,
+                symbol: s!("FB"),
+                action: CorporateActionType::SymbolChange(s!("META")),
+            }
+        );
+
+        assert_eq!(
+            parse(
+                date, "Stocks", "T(US00206R1023) Spinoff  1000000 for 1000000 (WBD, WARNER BROS DISCOVERY INC, US9344231041)",
+                "USD", dec!(2.4), dec!(0),
+            ).unwrap().unwrap(),
+
+            CorporateAction {
+                date,
+                symbol: s!("T"),
+                action: CorporateActionType::SpinOff {
+                    symbol: s!("WBD"),
+                    quantity: dec!(2.4),
+                    currency: s!("USD"),
+                },
+            }
+        );
+
+        assert_eq!(
+            parse(
+                date, "Stocks", "XLNX(US9839191015) Merged(Acquisition) WITH US0079031078 1722 FOR 1000 (AMD, ADVANCED MICRO DEVICES, US0079031078)",
+                "USD", dec!(17), dec!(0),
+            ).unwrap().unwrap(),
+
+            CorporateAction {
+                date,
+                symbol: s!("XLNX"),
+                action: CorporateActionType::StockMerger {
+                    symbol: s!("AMD"),
+                    ratio: StockSplitRatio::new(1000, 1722),
+                },
+            }
+        );
+
+        assert_eq!(
+            parse(
+                date, "Stocks", "CXO(US20605P1012) Merged(Acquisition) FOR USD 65.50 PER SHARE (CXO, CONCHO RESOURCES INC, US20605P1012)",
+                "USD", dec!(-10), dec!(655),
+            ).unwrap().unwrap(),
+
+            CorporateAction {
+                date,
+                symbol: s!("CXO"),
+                action: CorporateActionType::CashMerger {
+                    quantity: dec!(10),
+                    price: Cash::new("USD", dec!(65.50)),
+                },
+            }
+        );
+
+        assert_eq!(
+            parse(
+                date, "Stocks", "NVDA(US67066G1040) Split 3 for 2 (NVDA, NVIDIA CORP, US67066G1040)",
+                "USD", dec!(5), dec!(0),
+            ).unwrap().unwrap(),
+
+            CorporateAction {
+                date,
+                symbol: s!("NVDA"),
+                action: CorporateActionType::StockSplit(StockSplitRatio::new(2, 3)),
+            }
+        );
+
+        assert_eq!(
+            parse(
+                date, "Stocks", "NVDA(US67066G1040) Cash in Lieu of Fractional Shares (NVDA, NVIDIA CORP, US67066G1040)",
+                "USD", dec!(-0.5), dec!(100.5),
+            ).unwrap().unwrap(),
+
+            CorporateAction {
+                date,
+                symbol: s!("NVDA"),
+                action: CorporateActionType::CashInLieu {
+                    quantity: dec!(0.5),
+                    price: Cash::new("USD", dec!(201)),
+                },
+            }
+        );
+
+        assert_eq!(
+            parse(
+                date, "Bonds", "(US912828ZX16) Bond Maturity for USD 1.00 per Bond (T 0 1/4 06/30/22, US TREASURY N/B, US912828ZX16)",
+                "USD", dec!(-2000), dec!(2000),
+            ).unwrap().unwrap(),
+
+            CorporateAction {
+                date,
+                symbol: s!("T 0 1/4 06/30/22"),
+                action: CorporateActionType::BondRedemption {
+                    quantity: dec!(2000),
+                    price: Cash::new("USD", dec!(1)),
+                },
             }
         );
     }
-}
\ No newline at end of file
+}
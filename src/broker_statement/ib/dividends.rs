@@ -3,6 +3,7 @@ use regex::Regex;
 
 use crate::core::{EmptyResult, GenericResult};
 use crate::broker_statement::dividends::{DividendId, DividendAccruals};
+use crate::localities::CountryCode;
 use crate::util::DecimalRestrictions;
 
 use super::StatementParser;
@@ -18,12 +19,13 @@ impl RecordParser for DividendsParser {
     fn parse(&self, parser: &mut StatementParser, record: &Record) -> EmptyResult {
         let currency = record.get_value("Currency")?;
         let date = record.parse_date("Date")?;
-        let issuer = parse_dividend_description(record.get_value("Description")?)?;
+        let (issuer, issuer_country) = parse_dividend_description(record.get_value("Description")?)?;
         let amount = record.parse_cash("Amount", currency, DecimalRestrictions::NonZero)?;
 
         let accruals = parser.statement.dividend_accruals.entry(DividendId {
             date: date,
             issuer: issuer,
+            issuer_country: issuer_country,
         }).or_insert_with(DividendAccruals::new);
 
         if amount.is_negative() {
@@ -36,20 +38,25 @@ impl RecordParser for DividendsParser {
     }
 }
 
-fn parse_dividend_description(description: &str) -> GenericResult<String> {
+fn parse_dividend_description(description: &str) -> GenericResult<(String, CountryCode)> {
     lazy_static! {
         static ref DESCRIPTION_REGEX: Regex = Regex::new(
-            r"^(?P<issuer>[A-Z]+) ?\([A-Z0-9]+\) ").unwrap();
+            r"^(?P<issuer>[A-Z]+) ?\((?P<isin>[A-Z0-9]+)\) ").unwrap();
     }
 
     let captures = DESCRIPTION_REGEX.captures(description).ok_or_else(|| format!(
         "Unexpected dividend description: {:?}", description))?;
 
-    Ok(captures.name("issuer").unwrap().as_str().to_owned())
+    let issuer = captures.name("issuer").unwrap().as_str().to_owned();
+    let issuer_country = CountryCode::from_isin(captures.name("isin").unwrap().as_str())?;
+
+    Ok((issuer, issuer_country))
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::localities::Jurisdiction;
+
     use super::*;
 
     #[test]
@@ -66,7 +73,18 @@ mod tests {
         test_parsing("UNIT(US91325V1089) Payment in Lieu of Dividend (Ordinary Dividend)", "UNIT");
     }
 
+    #[test]
+    fn dividend_issuer_country() {
+        assert_eq!(
+            parse_dividend_description("IGLN(IE00B4ND3602) Cash Dividend USD 0.05 per Share (Ordinary Dividend)").unwrap(),
+            (s!("IGLN"), CountryCode::Known(Jurisdiction::Ireland)));
+        assert_eq!(
+            parse_dividend_description("RY(CA7800871021) Cash Dividend USD 0.81 per Share (Ordinary Dividend)").unwrap(),
+            (s!("RY"), CountryCode::Unknown(124)));
+        assert!(parse_dividend_description("XXX(XX0000000000) Cash Dividend USD 0.05 per Share (Ordinary Dividend)").is_err());
+    }
+
     fn test_parsing(description: &str, symbol: &str) {
-        assert_eq!(parse_dividend_description(description).unwrap(), symbol.to_owned());
+        assert_eq!(parse_dividend_description(description).unwrap(), (symbol.to_owned(), CountryCode::Known(Jurisdiction::Usa)));
     }
 }
\ No newline at end of file
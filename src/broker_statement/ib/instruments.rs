@@ -1,4 +1,5 @@
 use crate::core::EmptyResult;
+use crate::localities::CountryCode;
 use crate::util::{self, DecimalRestrictions};
 
 use super::StatementParser;
@@ -12,20 +13,31 @@ impl RecordParser for OpenPositionsParser {
     }
 
     fn parse(&self, parser: &mut StatementParser, record: &Record) -> EmptyResult {
-        record.check_values(&[
-            ("DataDiscriminator", "Summary"),
-            ("Asset Category", "Stocks"),
-            ("Mult", "1"),
-        ])?;
+        record.check_value("DataDiscriminator", "Summary")?;
 
+        let asset_category = record.get_value("Asset Category")?;
         let symbol = record.get_value("Symbol")?;
-
         let quantity = record.get_value("Quantity")?;
-        let quantity = util::parse_decimal(
-            quantity, DecimalRestrictions::StrictlyPositive
-        ).map_err(|_| format!("Got an unexpected {} quantity: {}", symbol, quantity))?;
 
-        parser.statement.add_open_position(symbol, quantity)
+        match asset_category {
+            "Stocks" | "Bonds" => {
+                record.check_value("Mult", "1")?;
+
+                let quantity = util::parse_decimal(
+                    &quantity.replace(',', ""), DecimalRestrictions::StrictlyPositive
+                ).map_err(|_| format!("Got an unexpected {} quantity: {}", symbol, quantity))?;
+
+                parser.statement.add_open_position(symbol, quantity)
+            },
+            "Equity and Index Options" | "Futures" => {
+                let quantity = util::parse_decimal(
+                    &quantity.replace(',', ""), DecimalRestrictions::NonZero
+                ).map_err(|_| format!("Got an unexpected {} quantity: {}", symbol, quantity))?;
+
+                parser.statement.add_derivative_position(symbol, quantity)
+            },
+            _ => Err!("Unsupported asset category: {}", asset_category),
+        }
     }
 }
 
@@ -36,6 +48,11 @@ impl RecordParser for FinancialInstrumentInformationParser {
     fn parse(&self, parser: &mut StatementParser, record: &Record) -> EmptyResult {
         let symbol = record.get_value("Symbol")?;
 
+        if let Ok("Bonds") = record.get_value("Asset Category") {
+            let issuer_country = CountryCode::from_isin(record.get_value("Security ID")?)?;
+            parser.statement.bond_issuers.insert(symbol.to_owned(), issuer_country);
+        }
+
         if parser.statement.instrument_names.insert(
             symbol.to_owned(), record.get_value("Description")?.to_owned()).is_some() {
             return Err!("Duplicated symbol: {}", symbol);
@@ -1,3 +1,6 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
 use crate::core::EmptyResult;
 use crate::broker_statement::interest::IdleCashInterest;
 use crate::util::DecimalRestrictions;
@@ -13,10 +16,44 @@ impl RecordParser for InterestParser {
     }
 
     fn parse(&self, parser: &mut StatementParser, record: &Record) -> EmptyResult {
+        lazy_static! {
+            static ref BOND_INTEREST_REGEX: Regex = Regex::new(
+                r"^Bond (?P<type>Coupon Payment|Interest Paid|Interest Received) for (?P<symbol>.+)$",
+            ).unwrap();
+        }
+
         let currency = record.get_value("Currency")?;
         let date = record.parse_date("Date")?;
         let amount = record.parse_cash("Amount", currency, DecimalRestrictions::NonZero)?;
+
+        if let Some(captures) = BOND_INTEREST_REGEX.captures(record.get_value("Description")?) {
+            let symbol = captures.name("symbol").unwrap().as_str().to_owned();
+
+            match captures.name("type").unwrap().as_str() {
+                // Accrued interest paid on bond buying is reported as negative bond interest. It's
+                // a part of the purchase expenses, so it's attached to the trade.
+                "Interest Paid" => {
+                    if !amount.is_negative() {
+                        return Err!("Got an unexpected paid accrued interest amount: {}", amount);
+                    }
+                    parser.paid_accrued_interest.push((symbol, date, -amount));
+                },
+
+                // Accrued interest received on bond selling is a part of the sale revenue
+                "Interest Received" => {
+                    if !amount.is_positive() {
+                        return Err!("Got an unexpected received accrued interest amount: {}", amount);
+                    }
+                    parser.received_accrued_interest.push((symbol, date, amount));
+                },
+
+                _ => parser.bond_coupons.push((symbol, date, amount)),
+            };
+
+            return Ok(());
+        }
+
         parser.statement.idle_cash_interest.push(IdleCashInterest::new(date, amount));
         Ok(())
     }
-}
\ No newline at end of file
+}
@@ -23,10 +23,11 @@ use crate::core::{GenericResult, EmptyResult};
 use crate::currency::Cash;
 use crate::formatting::format_date;
 use crate::taxes::TaxRemapping;
-use crate::types::Date;
+use crate::types::{Date, Decimal};
 
 #[cfg(test)] use super::{BrokerStatement};
 use super::{BrokerStatementReader, PartialBrokerStatement};
+use super::bonds::CouponPayment;
 
 use self::common::{RecordSpec, Record, RecordParser, format_record};
 use self::confirmation::{TradeExecutionDates, OrderId};
@@ -74,6 +75,9 @@ impl BrokerStatementReader for StatementReader {
 
             base_currency: None,
             base_currency_summary: None,
+            bond_coupons: Vec::new(),
+            paid_accrued_interest: Vec::new(),
+            received_accrued_interest: Vec::new(),
 
             tax_remapping: &mut self.tax_remapping.borrow_mut(),
             trade_execution_dates: &self.trade_execution_dates.borrow(),
@@ -99,6 +103,9 @@ pub struct StatementParser<'a> {
 
     base_currency: Option<String>,
     base_currency_summary: Option<Cash>,
+    bond_coupons: Vec<(String, Date, Cash)>,
+    paid_accrued_interest: Vec<(String, Date, Cash)>,
+    received_accrued_interest: Vec<(String, Date, Cash)>,
 
     tax_remapping: &'a mut TaxRemapping,
     trade_execution_dates: &'a TradeExecutionDates,
@@ -223,9 +230,56 @@ impl<'a> StatementParser<'a> {
             self.statement.cash_assets.deposit(amount);
         }
 
+        self.process_bond_coupons()?;
+        self.attach_accrued_interest()?;
         self.statement.validate()
     }
 
+    // Bond issuer is determined by its ISIN from "Financial Instrument Information" section which
+    // goes after "Interest" section, so coupons are processed after the whole statement is parsed.
+    fn process_bond_coupons(&mut self) -> EmptyResult {
+        for (symbol, date, amount) in self.bond_coupons.drain(..) {
+            let issuer_country = *self.statement.bond_issuers.get(&symbol).ok_or_else(|| format!(
+                "Unable to determine {} bond issuer country", symbol))?;
+
+            self.statement.coupons.push(CouponPayment {
+                date,
+                symbol,
+                issuer_country,
+                amount,
+                paid_tax: Cash::new(amount.currency, dec!(0)),
+            });
+        }
+
+        Ok(())
+    }
+
+    // Accrued interest paid on bond buying and received on bond selling is reported in "Interest"
+    // section separately from the trade itself, so match it with the trade by bond symbol and date.
+    fn attach_accrued_interest(&mut self) -> EmptyResult {
+        for (symbol, date, amount) in self.paid_accrued_interest.drain(..) {
+            let trades = self.statement.bond_buys.iter_mut().filter(|trade| {
+                trade.symbol == symbol && (trade.execution_date == date || trade.conclusion_date == date)
+            }).map(|trade| (trade.quantity, &mut trade.accrued_interest)).collect();
+
+            distribute_accrued_interest(trades, amount).map_err(|_| format!(
+                "Unable to find {} bond purchase for {} accrued interest paid on {}",
+                symbol, amount, format_date(date)))?;
+        }
+
+        for (symbol, date, amount) in self.received_accrued_interest.drain(..) {
+            let trades = self.statement.bond_sells.iter_mut().filter(|trade| {
+                trade.symbol == symbol && (trade.execution_date == date || trade.conclusion_date == date)
+            }).map(|trade| (trade.quantity, &mut trade.accrued_interest)).collect();
+
+            distribute_accrued_interest(trades, amount).map_err(|_| format!(
+                "Unable to find {} bond sale for {} accrued interest received on {}",
+                symbol, amount, format_date(date)))?;
+        }
+
+        Ok(())
+    }
+
     fn base_currency(&self) -> GenericResult<&str> {
         Ok(self.base_currency.as_deref().ok_or_else(||
             "Unable to determine account base currency")?)
@@ -252,6 +306,30 @@ impl<'a> StatementParser<'a> {
     }
 }
 
+// Distributes the accrued interest between the trades proportionally to their quantity
+fn distribute_accrued_interest(mut trades: Vec<(Decimal, &mut Cash)>, amount: Cash) -> EmptyResult {
+    if trades.is_empty() || trades.iter().any(|(_, accrued_interest)| accrued_interest.currency != amount.currency) {
+        return Err!("Unable to find the matching trades");
+    }
+
+    let total_quantity: Decimal = trades.iter().map(|&(quantity, _)| quantity).sum();
+    let mut remaining = amount;
+    let last_index = trades.len() - 1;
+
+    for (index, (quantity, trade_accrued_interest)) in trades.iter_mut().enumerate() {
+        let accrued_interest = if index == last_index {
+            remaining
+        } else {
+            (amount * *quantity / total_quantity).round()
+        };
+
+        remaining.sub_assign(accrued_interest).unwrap();
+        trade_accrued_interest.add_assign(accrued_interest).unwrap();
+    }
+
+    Ok(())
+}
+
 fn parse_header(record: &StringRecord) -> RecordSpec {
     let offset = 2;
     let name = record.get(0).unwrap();
@@ -262,7 +340,13 @@ fn parse_header(record: &StringRecord) -> RecordSpec {
 
 #[cfg(test)]
 mod tests {
+    use std::path::Path;
+
     use rstest::rstest;
+
+    use crate::broker_statement::{DerivativeTrade, DerivativeType};
+    use crate::localities::{CountryCode, Jurisdiction};
+
     use super::*;
 
     #[test]
@@ -278,12 +362,103 @@ mod tests {
         assert!(statement.forex_trades.is_empty());
         assert!(statement.stock_buys.is_empty());
         assert!(statement.stock_sells.is_empty());
+        assert!(statement.bond_buys.is_empty());
+        assert!(statement.bond_sells.is_empty());
         assert!(statement.dividends.is_empty());
+        assert!(statement.coupons.is_empty());
 
         assert!(statement.open_positions.is_empty());
         assert!(statement.instrument_names.is_empty());
     }
 
+    #[test]
+    fn parse_bonds() {
+        let path = Path::new(file!()).parent().unwrap().join("testdata/bonds.csv");
+        let statement = StatementReader::new(TaxRemapping::new(), true).unwrap()
+            .read(path.to_str().unwrap()).unwrap();
+
+        let symbol = "AAPL 3.85 05/04/43";
+        assert_eq!(statement.bond_issuers.get(symbol), Some(&CountryCode::Known(Jurisdiction::Usa)));
+
+        // Accrued interest is reported in "Interest" section and attached to the trades
+        assert_eq!(statement.bond_buys.len(), 1);
+        let trade = &statement.bond_buys[0];
+        assert_eq!(trade.quantity, dec!(2000));
+        assert_eq!(trade.price, Cash::new("USD", dec!(1.055)));
+        assert_eq!(trade.volume, Cash::new("USD", dec!(2110)));
+        assert_eq!(trade.accrued_interest, Cash::new("USD", dec!(25.03)));
+        assert_eq!(trade.commission, Cash::new("USD", dec!(2)));
+
+        assert_eq!(statement.bond_sells.len(), 1);
+        let trade = &statement.bond_sells[0];
+        assert_eq!(trade.quantity, dec!(1000));
+        assert_eq!(trade.price, Cash::new("USD", dec!(1.08)));
+        assert_eq!(trade.volume, Cash::new("USD", dec!(1080)));
+        assert_eq!(trade.accrued_interest, Cash::new("USD", dec!(2.89)));
+        assert_eq!(trade.commission, Cash::new("USD", dec!(1)));
+
+        assert_eq!(statement.coupons.len(), 1);
+        let coupon = &statement.coupons[0];
+        assert_eq!(coupon.date, date!(4, 5, 2020));
+        assert_eq!(coupon.symbol, symbol);
+        assert_eq!(coupon.issuer_country, CountryCode::Known(Jurisdiction::Usa));
+        assert_eq!(coupon.amount, Cash::new("USD", dec!(38.5)));
+        assert_eq!(coupon.paid_tax, Cash::new("USD", dec!(0)));
+
+        assert!(statement.idle_cash_interest.is_empty());
+    }
+
+    #[test]
+    fn parse_derivatives() {
+        let broker = Broker::InteractiveBrokers.get_info(&Config::mock(), None).unwrap();
+        let path = Path::new(file!()).parent().unwrap().join("testdata/derivatives");
+        let statement = BrokerStatement::read(
+            broker, path.to_str().unwrap(), &hashmap!{}, &hashmap!{}, TaxRemapping::new(), true).unwrap();
+
+        assert!(statement.stock_buys.is_empty());
+        assert!(statement.stock_sells.is_empty());
+        assert!(statement.open_positions.is_empty());
+        assert_eq!(statement.derivative_positions, hashmap!{s!("ESZ0") => dec!(1)});
+
+        let trades = &statement.derivative_trades;
+        assert_eq!(trades.len(), 6);
+        assert!(trades.iter().all(DerivativeTrade::is_processed));
+
+        // Short option position opening and closing
+        let (open, close) = (&trades[0], &trades[2]);
+        assert_eq!((open.symbol.as_str(), open.type_), ("AAPL 18SEP20 130 C", DerivativeType::Option));
+        assert_eq!((open.quantity, open.get_open(), open.get_closed()), (dec!(-1), dec!(0), dec!(0)));
+        assert_eq!(open.volume, Cash::new("USD", dec!(300)));
+        assert_eq!(open.commission, Cash::new("USD", dec!(1.05)));
+
+        assert_eq!(close.symbol, open.symbol);
+        assert_eq!((close.quantity, close.get_open(), close.get_closed()), (dec!(1), dec!(0), dec!(1)));
+        assert_eq!(close.volume, Cash::new("USD", dec!(-100)));
+        assert_eq!(close.get_cash_flow(), Cash::new("USD", dec!(-100)));
+
+        // Long option position expiration
+        let (open, close) = (&trades[1], &trades[5]);
+        assert_eq!(open.symbol, "AAPL 18SEP20 100 P");
+        assert_eq!((open.quantity, open.volume), (dec!(1), Cash::new("USD", dec!(-50))));
+
+        assert_eq!(close.symbol, open.symbol);
+        assert_eq!(close.conclusion_date, date!(18, 9, 2020));
+        assert_eq!((close.quantity, close.get_closed()), (dec!(-1), dec!(1)));
+        assert_eq!(close.volume, Cash::new("USD", dec!(0)));
+        assert_eq!(close.commission, Cash::new("USD", dec!(0)));
+
+        // Futures position partial closing
+        let (open, close) = (&trades[3], &trades[4]);
+        assert_eq!((open.symbol.as_str(), open.type_), ("ESZ0", DerivativeType::Future));
+        assert_eq!((open.quantity, open.get_open(), open.get_closed()), (dec!(2), dec!(1), dec!(0)));
+        assert_eq!(open.get_cash_flow(), Cash::new("USD", dec!(0)));
+
+        assert_eq!((close.quantity, close.get_open(), close.get_closed()), (dec!(-1), dec!(0), dec!(1)));
+        assert_eq!(close.volume, Cash::new("USD", dec!(175500)));
+        assert_eq!(close.commission, Cash::new("USD", dec!(2.12)));
+        assert_eq!(close.get_cash_flow(), Cash::new("USD", dec!(500)));
+    }
+
     #[test]
     fn parse_real_current() {
         let tax_remapping = config::load_config("testdata/config/config.yaml").unwrap()
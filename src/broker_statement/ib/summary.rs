@@ -46,6 +46,8 @@ impl RecordParser for AccountInformationParser {
                 },
                 _ => return Err!("Unsupported account type: {}", value),
             }
+        } else if name == "Account" {
+            parser.statement.set_account_number(value)?;
         } else if name == "Base Currency" {
             parser.base_currency.replace(value.to_owned());
         }
@@ -1,6 +1,9 @@
 use std::ops::Deref;
 
+use crate::broker_statement::bonds::{BondBuy, BondSell};
+use crate::broker_statement::derivatives::{DerivativeTrade, DerivativeType};
 use crate::broker_statement::trades::{ForexTrade, StockBuy, StockSell};
+use crate::currency::Cash;
 use crate::core::EmptyResult;
 use crate::types::Date;
 use crate::util::{self, DecimalRestrictions};
@@ -25,6 +28,11 @@ impl RecordParser for TradesParser {
         match asset_category {
             "Forex" => parse_forex_record(parser, record, symbol, conclusion_date),
             "Stocks" => parse_stock_record(parser, record, symbol, conclusion_date),
+            "Bonds" => parse_bond_record(parser, record, symbol, conclusion_date),
+            "Equity and Index Options" => parse_derivative_record(
+                parser, record, symbol, DerivativeType::Option, conclusion_date),
+            "Futures" => parse_derivative_record(
+                parser, record, symbol, DerivativeType::Future, conclusion_date),
             _ => return Err!("Unsupported asset category: {}", asset_category)
         }
     }
@@ -99,4 +107,79 @@ fn parse_stock_record(
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+// Bond quantity is specified in face value units and price - in percents of face value. Accrued
+// interest is reported separately in "Interest" section and is attached to the trade after the
+// whole statement is parsed.
+fn parse_bond_record(
+    parser: &mut StatementParser, record: &Record, symbol: &str, conclusion_date: Date,
+) -> EmptyResult {
+    let currency = record.get_value("Currency")?;
+    let price = record.parse_amount("T. Price", DecimalRestrictions::StrictlyPositive)?;
+    let price = Cash::new(currency, price / dec!(100));
+    let commission = -record.parse_cash("Comm/Fee", currency, DecimalRestrictions::NegativeOrZero)?;
+    let accrued_interest = Cash::new(currency, dec!(0));
+    let execution_date = parser.get_execution_date(symbol, conclusion_date);
+
+    let quantity = record.get_value("Quantity")?;
+    let quantity = util::parse_decimal(
+        &quantity.replace(',', ""), DecimalRestrictions::NonZero,
+    ).map_err(|_| format!("Got an unexpected {} trade quantity: {}", symbol, quantity))?.normalize();
+
+    let volume = record.parse_cash("Proceeds", currency, if quantity.is_sign_positive() {
+        DecimalRestrictions::StrictlyNegative
+    } else {
+        DecimalRestrictions::StrictlyPositive
+    })?;
+
+    if quantity.is_sign_positive() {
+        parser.statement.bond_buys.push(BondBuy::new(
+            symbol, quantity, price, -volume, accrued_interest, commission,
+            conclusion_date, execution_date));
+    } else {
+        parser.statement.bond_sells.push(BondSell::new(
+            symbol, -quantity, price, volume, accrued_interest, commission,
+            conclusion_date, execution_date, false, false));
+    }
+
+    Ok(())
+}
+
+// Option assignment, exercise and expiration are reported as ordinary trades with zero proceeds
+// which close the option position. The underlying stock trades in case of assignment or exercise
+// are reported separately as ordinary stock trades.
+fn parse_derivative_record(
+    parser: &mut StatementParser, record: &Record, symbol: &str, type_: DerivativeType,
+    conclusion_date: Date,
+) -> EmptyResult {
+    // On assignment or exercise the option premium must be included into cost or revenue of the
+    // underlying stock trade, which isn't supported yet
+    if type_ == DerivativeType::Option {
+        let codes = record.get_value("Code")?;
+        if codes.split(';').any(|code| code == "A" || code == "Ex") {
+            return Err!("{} option assignment and exercise are not supported yet", symbol);
+        }
+    }
+
+    let currency = record.get_value("Currency")?;
+    let price = record.parse_cash("T. Price", currency, DecimalRestrictions::PositiveOrZero)?;
+    let commission = -record.parse_cash("Comm/Fee", currency, DecimalRestrictions::NegativeOrZero)?;
+    let execution_date = parser.get_execution_date(symbol, conclusion_date);
+
+    let quantity = record.get_value("Quantity")?;
+    let quantity = util::parse_decimal(
+        &quantity.replace(',', ""), DecimalRestrictions::NonZero,
+    ).map_err(|_| format!("Got an unexpected {} trade quantity: {}", symbol, quantity))?.normalize();
+
+    let volume = record.parse_cash("Proceeds", currency, if quantity.is_sign_positive() {
+        DecimalRestrictions::NegativeOrZero
+    } else {
+        DecimalRestrictions::PositiveOrZero
+    })?;
+
+    parser.statement.derivative_trades.push(DerivativeTrade::new(
+        symbol, type_, quantity, price, volume, commission, conclusion_date, execution_date));
+
+    Ok(())
+}
@@ -1,4 +1,6 @@
+mod bonds;
 mod corporate_actions;
+mod derivatives;
 mod dividends;
 mod fees;
 mod interest;
@@ -16,7 +18,7 @@ mod open;
 mod tinkoff;
 
 use std::{self, fs};
-use std::collections::{HashMap, HashSet, BTreeMap, BTreeSet};
+use std::collections::{HashMap, HashSet, BTreeMap, BTreeSet, VecDeque};
 use std::collections::hash_map::Entry;
 use std::path::Path;
 
@@ -28,7 +30,7 @@ use crate::commissions::CommissionCalc;
 use crate::core::{EmptyResult, GenericResult};
 use crate::currency::{Cash, CashAssets, MultiCurrencyCashAccount};
 use crate::formatting;
-use crate::localities;
+use crate::localities::{self, CountryCode};
 use crate::quotes::Quotes;
 use crate::taxes::TaxRemapping;
 use crate::types::{Date, Decimal, TradeType};
@@ -38,7 +40,9 @@ use self::dividends::{DividendAccruals, process_dividend_accruals};
 use self::partial::PartialBrokerStatement;
 use self::taxes::{TaxId, TaxAccruals};
 
+pub use self::bonds::{BondBuy, BondSell, BondSellSource, BondSellDetails, CouponPayment};
 pub use self::corporate_actions::{CorporateAction, CorporateActionType, StockSplitController};
+pub use self::derivatives::{DerivativeTrade, DerivativeTradeSource, DerivativeType, DerivativeDetails};
 pub use self::dividends::Dividend;
 pub use self::fees::Fee;
 pub use self::interest::IdleCashInterest;
@@ -49,6 +53,9 @@ pub use self::trades::{ForexTrade, StockBuy, StockSell, StockSellSource, SellDet
 pub struct BrokerStatement {
     pub broker: BrokerInfo,
     pub period: (Date, Date),
+    pub account_number: Option<String>,
+    // Start date of the first statement with the current account number
+    pub account_number_date: Option<Date>,
 
     pub cash_assets: MultiCurrencyCashAccount,
     pub historical_cash_assets: BTreeMap<Date, MultiCurrencyCashAccount>,
@@ -60,13 +67,19 @@ pub struct BrokerStatement {
     pub forex_trades: Vec<ForexTrade>,
     pub stock_buys: Vec<StockBuy>,
     pub stock_sells: Vec<StockSell>,
+    pub bond_buys: Vec<BondBuy>,
+    pub bond_sells: Vec<BondSell>,
+    pub derivative_trades: Vec<DerivativeTrade>,
     pub dividends: Vec<Dividend>,
+    pub coupons: Vec<CouponPayment>,
 
     corporate_actions: Vec<CorporateAction>,
     pub stock_splits: StockSplitController,
 
     pub open_positions: HashMap<String, Decimal>,
+    pub derivative_positions: HashMap<String, Decimal>,
     instrument_names: HashMap<String, String>,
+    bond_issuers: HashMap<String, CountryCode>,
 }
 
 impl BrokerStatement {
@@ -182,6 +195,8 @@ impl BrokerStatement {
         Ok(BrokerStatement {
             broker,
             period: period,
+            account_number: None,
+            account_number_date: None,
 
             cash_assets: MultiCurrencyCashAccount::new(),
             historical_cash_assets: BTreeMap::new(),
@@ -193,13 +208,19 @@ impl BrokerStatement {
             forex_trades: Vec::new(),
             stock_buys: Vec::new(),
             stock_sells: Vec::new(),
+            bond_buys: Vec::new(),
+            bond_sells: Vec::new(),
+            derivative_trades: Vec::new(),
             dividends: Vec::new(),
+            coupons: Vec::new(),
 
             corporate_actions: Vec::new(),
             stock_splits: StockSplitController::default(),
 
             open_positions: HashMap::new(),
+            derivative_positions: HashMap::new(),
             instrument_names: HashMap::new(),
+            bond_issuers: HashMap::new(),
         })
     }
 
@@ -246,6 +267,11 @@ impl BrokerStatement {
         }
     }
 
+    pub fn get_bond_issuer(&self, symbol: &str) -> GenericResult<CountryCode> {
+        Ok(*self.bond_issuers.get(symbol).ok_or_else(|| format!(
+            "Unable to determine {} bond issuer country", symbol))?)
+    }
+
     pub fn batch_quotes(&self, quotes: &Quotes) {
         for symbol in self.open_positions.keys() {
             quotes.batch(&symbol);
@@ -264,13 +290,15 @@ impl BrokerStatement {
             }
         }
 
+        if let Some(last_trade) = self.bond_sells.last() {
+            if last_trade.execution_date > execution_date {
+                execution_date = last_trade.execution_date;
+            }
+        }
+
         let commission = commission_calc.add_trade(
             conclusion_date, TradeType::Sell, quantity, price)?;
 
-        let stock_cell = StockSell::new(
-            symbol, quantity, price, price * quantity, commission,
-            conclusion_date, execution_date, true);
-
         if let Entry::Occupied(mut open_position) = self.open_positions.entry(symbol.to_owned()) {
             let available = open_position.get_mut();
 
@@ -285,13 +313,63 @@ impl BrokerStatement {
             return Err!("The portfolio has no open {} position", symbol);
         }
 
-        self.stock_sells.push(stock_cell);
+        // Bond quotes include accrued coupon interest, so it's separated from the trade volume to
+        // get the same trade structure as for the real trades
+        if self.bond_buys.iter().any(|trade| trade.symbol == symbol) {
+            let accrued_interest = self.estimate_accrued_interest(symbol, quantity, price.currency, execution_date);
+            let volume = (price * quantity).sub(accrued_interest)?;
+
+            self.bond_sells.push(BondSell::new(
+                symbol, quantity, volume / quantity, volume, accrued_interest,
+                commission, conclusion_date, execution_date, false, true));
+        } else {
+            self.stock_sells.push(StockSell::new(
+                symbol, quantity, price, price * quantity, commission,
+                conclusion_date, execution_date, true));
+        }
+
         self.cash_assets.deposit(price * quantity);
         self.cash_assets.withdraw(commission);
 
         Ok(())
     }
 
+    // Accrued coupon interest is estimated by the last coupon payments assuming that the coupon
+    // accrues linearly between them
+    fn estimate_accrued_interest(&self, symbol: &str, quantity: Decimal, currency: &str, date: Date) -> Cash {
+        let zero = Cash::new(currency, dec!(0));
+
+        let coupons: Vec<&CouponPayment> = self.coupons.iter().filter(|coupon| {
+            coupon.symbol == symbol && coupon.amount.currency == currency
+        }).collect();
+
+        let (prev_coupon, last_coupon) = match coupons.len() {
+            count if count >= 2 => (coupons[count - 2], coupons[count - 1]),
+            _ => return zero,
+        };
+
+        let period = (last_coupon.date - prev_coupon.date).num_days();
+        if period <= 0 || date <= last_coupon.date {
+            return zero;
+        }
+
+        let bought: Decimal = self.bond_buys.iter()
+            .filter(|trade| trade.symbol == symbol && trade.execution_date <= last_coupon.date)
+            .map(|trade| trade.quantity).sum();
+
+        let sold: Decimal = self.bond_sells.iter()
+            .filter(|trade| trade.symbol == symbol && trade.execution_date <= last_coupon.date)
+            .map(|trade| trade.quantity).sum();
+
+        let holding = bought - sold;
+        if holding <= dec!(0) {
+            return zero;
+        }
+
+        let days = std::cmp::min((date - last_coupon.date).num_days(), period);
+        (last_coupon.amount / holding * quantity * Decimal::from(days) / Decimal::from(period)).round()
+    }
+
     pub fn emulate_commissions(&mut self, commission_calc: CommissionCalc) -> MultiCurrencyCashAccount {
         let mut total = MultiCurrencyCashAccount::new();
 
@@ -304,19 +382,20 @@ impl BrokerStatement {
     }
 
     pub fn process_trades(&mut self) -> EmptyResult {
+        let last_date = self.last_date();
         let mut unsold_buys: HashMap<String, Vec<usize>> = HashMap::new();
 
+        // Symbols may be changed by corporate actions, so trades are matched by the symbol under
+        // which the position is known at the end of the statement period.
         for (index, stock_buy) in self.stock_buys.iter().enumerate().rev() {
             if stock_buy.is_sold() {
                 continue;
             }
 
-            let symbol_buys = match unsold_buys.get_mut(&stock_buy.symbol) {
-                Some(symbol_buys) => symbol_buys,
-                None => unsold_buys.entry(stock_buy.symbol.clone()).or_insert_with(Vec::new),
-            };
+            let symbol = self.stock_splits.get_symbol(
+                &stock_buy.symbol, stock_buy.conclusion_date, last_date);
 
-            symbol_buys.push(index);
+            unsold_buys.entry(symbol).or_insert_with(Vec::new).push(index);
         }
 
         for stock_sell in &mut self.stock_sells {
@@ -324,10 +403,17 @@ impl BrokerStatement {
                 continue;
             }
 
+            allocate_spin_off_cost_basis(
+                &mut self.stock_buys, &self.stock_splits, &unsold_buys, Some(stock_sell.conclusion_date),
+                last_date)?;
+
             let mut remaining_quantity = stock_sell.quantity;
             let mut sources = Vec::new();
 
-            let symbol_buys = unsold_buys.get_mut(&stock_sell.symbol).ok_or_else(|| format!(
+            let symbol = self.stock_splits.get_symbol(
+                &stock_sell.symbol, stock_sell.conclusion_date, last_date);
+
+            let symbol_buys = unsold_buys.get_mut(&symbol).ok_or_else(|| format!(
                 "Error while processing {} position closing: There are no open positions for it",
                 stock_sell.symbol
             ))?;
@@ -340,14 +426,33 @@ impl BrokerStatement {
 
                 let stock_buy = &mut self.stock_buys[index];
                 let multiplier = self.stock_splits.get_multiplier(
-                    &stock_sell.symbol, stock_buy.conclusion_date, stock_sell.conclusion_date);
+                    &stock_buy.symbol, stock_buy.conclusion_date, stock_sell.conclusion_date);
+
+                let unsold_quantity = self.stock_splits.convert_quantity(
+                    &stock_buy.symbol, stock_buy.conclusion_date, stock_sell.conclusion_date,
+                    stock_buy.get_unsold());
 
-                let unsold_quantity = multiplier * stock_buy.get_unsold();
                 let sell_quantity = std::cmp::min(remaining_quantity, unsold_quantity);
                 assert!(sell_quantity > dec!(0));
 
-                let source_quantity = (sell_quantity / multiplier).normalize();
-                assert_eq!(source_quantity * multiplier, sell_quantity);
+                let mut source_quantity = if sell_quantity == unsold_quantity {
+                    stock_buy.get_unsold()
+                } else {
+                    std::cmp::min(stock_buy.get_unsold(), self.stock_splits.convert_quantity(
+                        &stock_buy.symbol, stock_sell.conclusion_date, stock_buy.conclusion_date,
+                        sell_quantity))
+                };
+
+                // Quantity conversion between stock splits is rounded, so it may leave dust which
+                // is indistinguishable from zero at the time of the sell. The lot is closed in this
+                // case to not leave positions that can never be sold.
+                let only_dust_left = corporate_actions::is_quantity_dust(self.stock_splits.convert_quantity(
+                    &stock_buy.symbol, stock_buy.conclusion_date, stock_sell.conclusion_date,
+                    stock_buy.get_unsold() - source_quantity));
+
+                if only_dust_left {
+                    source_quantity = stock_buy.get_unsold();
+                }
 
                 sources.push(StockSellSource {
                     quantity: source_quantity,
@@ -370,15 +475,172 @@ impl BrokerStatement {
             stock_sell.process(sources);
         }
 
+        allocate_spin_off_cost_basis(
+            &mut self.stock_buys, &self.stock_splits, &unsold_buys, None, last_date)?;
+
+        self.process_bond_trades()?;
+        self.process_derivative_trades()?;
         self.validate_open_positions()
     }
 
+    fn process_bond_trades(&mut self) -> EmptyResult {
+        let mut unsold_buys: HashMap<String, Vec<usize>> = HashMap::new();
+
+        for (index, bond_buy) in self.bond_buys.iter().enumerate().rev() {
+            if !bond_buy.is_sold() {
+                unsold_buys.entry(bond_buy.symbol.clone()).or_insert_with(Vec::new).push(index);
+            }
+        }
+
+        for bond_sell in &mut self.bond_sells {
+            if bond_sell.is_processed() {
+                continue;
+            }
+
+            let mut remaining_quantity = bond_sell.quantity;
+            let mut sources = Vec::new();
+
+            let symbol_buys = unsold_buys.get_mut(&bond_sell.symbol).ok_or_else(|| format!(
+                "Error while processing {} position closing: There are no open positions for it",
+                bond_sell.symbol
+            ))?;
+
+            while !remaining_quantity.is_zero() {
+                let index = symbol_buys.last().copied().ok_or_else(|| format!(
+                    "Error while processing {} position closing: There are no open positions for it",
+                    bond_sell.symbol
+                ))?;
+
+                let bond_buy = &mut self.bond_buys[index];
+                let sell_quantity = std::cmp::min(remaining_quantity, bond_buy.get_unsold());
+                assert!(sell_quantity > dec!(0));
+
+                sources.push(BondSellSource {
+                    quantity: sell_quantity,
+                    price: bond_buy.price,
+                    accrued_interest: bond_buy.accrued_interest / bond_buy.quantity * sell_quantity,
+                    commission: bond_buy.commission / bond_buy.quantity * sell_quantity,
+
+                    conclusion_date: bond_buy.conclusion_date,
+                    execution_date: bond_buy.execution_date,
+                });
+
+                remaining_quantity -= sell_quantity;
+                bond_buy.sell(sell_quantity);
+
+                if bond_buy.is_sold() {
+                    symbol_buys.pop();
+                }
+            }
+
+            bond_sell.process(sources);
+        }
+
+        Ok(())
+    }
+
+    // Derivative positions may be both long and short, so each trade either closes the opposite
+    // positions in FIFO order or opens a new position in its direction (or both).
+    fn process_derivative_trades(&mut self) -> EmptyResult {
+        let mut open_trades: HashMap<String, VecDeque<usize>> = HashMap::new();
+
+        for (index, trade) in self.derivative_trades.iter().enumerate() {
+            if trade.is_processed() && !trade.get_open().is_zero() {
+                open_trades.entry(trade.symbol.clone()).or_default().push_back(index);
+            }
+        }
+
+        for index in 0..self.derivative_trades.len() {
+            if self.derivative_trades[index].is_processed() {
+                continue;
+            }
+
+            let symbol = self.derivative_trades[index].symbol.clone();
+            let mut remaining_quantity = self.derivative_trades[index].quantity;
+            let mut sources = Vec::new();
+
+            let symbol_trades = open_trades.entry(symbol).or_default();
+
+            while !remaining_quantity.is_zero() {
+                let open_trade = match symbol_trades.front() {
+                    Some(&open_index) => &mut self.derivative_trades[open_index],
+                    None => break,
+                };
+
+                let open_quantity = open_trade.get_open();
+                if open_quantity.is_sign_positive() == remaining_quantity.is_sign_positive() {
+                    break;
+                }
+
+                let quantity = std::cmp::min(remaining_quantity.abs(), open_quantity.abs());
+                let share = quantity / open_trade.quantity.abs();
+
+                sources.push(DerivativeTradeSource {
+                    quantity,
+                    volume: open_trade.volume * share,
+                    commission: open_trade.commission * share,
+
+                    conclusion_date: open_trade.conclusion_date,
+                    execution_date: open_trade.execution_date,
+                });
+
+                open_trade.close(quantity);
+                if open_trade.get_open().is_zero() {
+                    symbol_trades.pop_front();
+                }
+
+                if remaining_quantity.is_sign_positive() {
+                    remaining_quantity -= quantity;
+                } else {
+                    remaining_quantity += quantity;
+                }
+            }
+
+            let trade = &mut self.derivative_trades[index];
+            trade.process(sources);
+
+            if !trade.get_open().is_zero() {
+                symbol_trades.push_back(index);
+            }
+        }
+
+        let mut positions: HashMap<&str, Decimal> = HashMap::new();
+
+        for trade in &self.derivative_trades {
+            let quantity = trade.get_open();
+            if !quantity.is_zero() {
+                *positions.entry(&trade.symbol).or_default() += quantity;
+            }
+        }
+
+        let symbols: BTreeSet<&str> = self.derivative_positions.keys().map(String::as_str)
+            .chain(positions.keys().copied())
+            .collect();
+
+        for &symbol in &symbols {
+            let calculated = positions.get(symbol).copied().unwrap_or_default();
+            let actual = self.derivative_positions.get(symbol).copied().unwrap_or_default();
+
+            if calculated != actual {
+                return Err!(concat!(
+                    "Calculated derivative positions don't match declared ones in the statement: ",
+                    "{}: {} vs {}"
+                ), symbol, calculated, actual);
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn merge_symbols(
         &mut self, symbols_to_merge: &HashMap<String, HashSet<String>>, strict: bool,
     ) -> EmptyResult {
         assert!(self.open_positions.is_empty());
         assert!(!self.stock_buys.iter().any(|stock_buy| !stock_buy.is_sold()));
         assert!(!self.stock_sells.iter().any(|stock_sell| !stock_sell.is_processed()));
+        assert!(!self.bond_buys.iter().any(|bond_buy| !bond_buy.is_sold()));
+        assert!(!self.bond_sells.iter().any(|bond_sell| !bond_sell.is_processed()));
+        assert!(!self.derivative_trades.iter().any(|trade| !trade.is_processed()));
 
         let mut symbol_mapping: HashMap<&String, &String> = HashMap::new();
 
@@ -388,10 +650,14 @@ impl BrokerStatement {
             }
         }
 
-        for &symbol in symbol_mapping.keys() {
+        for (&symbol, &master_symbol) in &symbol_mapping {
             if strict && self.instrument_names.remove(symbol).is_none() {
                 return Err!("The broker statement has no any activity for {:?} symbol", symbol);
             }
+
+            if let Some(issuer_country) = self.bond_issuers.remove(symbol) {
+                self.bond_issuers.entry(master_symbol.clone()).or_insert(issuer_country);
+            }
         }
 
         for stock_buy in &mut self.stock_buys {
@@ -406,6 +672,30 @@ impl BrokerStatement {
             }
         }
 
+        for bond_buy in &mut self.bond_buys {
+            if let Some(&symbol) = symbol_mapping.get(&bond_buy.symbol) {
+                bond_buy.symbol = symbol.clone();
+            }
+        }
+
+        for bond_sell in &mut self.bond_sells {
+            if let Some(&symbol) = symbol_mapping.get(&bond_sell.symbol) {
+                bond_sell.symbol = symbol.clone();
+            }
+        }
+
+        for coupon in &mut self.coupons {
+            if let Some(&symbol) = symbol_mapping.get(&coupon.symbol) {
+                coupon.symbol = symbol.clone();
+            }
+        }
+
+        for trade in &mut self.derivative_trades {
+            if let Some(&symbol) = symbol_mapping.get(&trade.symbol) {
+                trade.symbol = symbol.clone();
+            }
+        }
+
         for dividend in &mut self.dividends {
             if let Some(&issuer) = symbol_mapping.get(&dividend.issuer) {
                 dividend.issuer = issuer.clone();
@@ -420,6 +710,13 @@ impl BrokerStatement {
         self.broker.statements_merging_strategy.validate(self.period, period)?;
         self.period.1 = period.1;
 
+        if let Some(number) = statement.account_number.take() {
+            if self.account_number.as_ref() != Some(&number) {
+                self.account_number.replace(number);
+                self.account_number_date.replace(period.0);
+            }
+        }
+
         self.cash_assets = statement.cash_assets.clone();
         assert!(self.historical_cash_assets.insert(self.last_date(), statement.cash_assets).is_none());
 
@@ -430,19 +727,67 @@ impl BrokerStatement {
         self.forex_trades.extend(statement.forex_trades.drain(..));
         self.stock_buys.extend(statement.stock_buys.drain(..));
         self.stock_sells.extend(statement.stock_sells.drain(..));
+        self.bond_buys.extend(statement.bond_buys.drain(..));
+        self.bond_sells.extend(statement.bond_sells.drain(..));
+        self.derivative_trades.extend(statement.derivative_trades.drain(..));
         self.dividends.extend(statement.dividends.drain(..));
+        self.coupons.extend(statement.coupons.drain(..));
 
         for action in statement.corporate_actions.drain(..) {
-            match action.action {
-                CorporateActionType::StockSplit(divisor) => {
-                    self.stock_splits.add(action.date, &action.symbol, divisor)?;
-                }
-            };
+            self.process_corporate_action(&action)?;
             self.corporate_actions.push(action);
         }
 
         self.open_positions = statement.open_positions;
+        self.derivative_positions = statement.derivative_positions;
         self.instrument_names.extend(statement.instrument_names.drain());
+        self.bond_issuers.extend(statement.bond_issuers.drain());
+
+        Ok(())
+    }
+
+    fn process_corporate_action(&mut self, action: &CorporateAction) -> EmptyResult {
+        let date = action.date;
+        let symbol = &action.symbol;
+
+        match action.action {
+            CorporateActionType::StockSplit(ratio) => {
+                self.stock_splits.add(date, symbol, ratio)?;
+            },
+
+            CorporateActionType::SymbolChange(ref new_symbol) => {
+                self.stock_splits.add_symbol_change(date, symbol, new_symbol)?;
+            },
+
+            CorporateActionType::SpinOff {symbol: ref new_symbol, quantity, ref currency} => {
+                let conclusion_date = get_corporate_action_conclusion_date(
+                    date, self.stock_buys.iter().map(|trade| (trade.conclusion_date, trade.execution_date)));
+
+                self.stock_buys.push(StockBuy::new_spin_off(
+                    new_symbol, quantity, currency, symbol, conclusion_date, date));
+            },
+
+            CorporateActionType::StockMerger {symbol: ref new_symbol, ratio} => {
+                self.stock_splits.add(date, symbol, ratio)?;
+                self.stock_splits.add_symbol_change(date, symbol, new_symbol)?;
+            },
+
+            CorporateActionType::CashMerger {quantity, price} |
+            CorporateActionType::CashInLieu {quantity, price} => {
+                let conclusion_date = get_corporate_action_conclusion_date(
+                    date, self.stock_sells.iter().map(|trade| (trade.conclusion_date, trade.execution_date)));
+
+                let commission = Cash::new(price.currency, dec!(0));
+                self.stock_sells.push(StockSell::new(
+                    symbol, quantity, price, price * quantity, commission, conclusion_date, date, false));
+            },
+
+            CorporateActionType::BondRedemption {quantity, price} => {
+                let zero = Cash::new(price.currency, dec!(0));
+                self.bond_sells.push(BondSell::new(
+                    symbol, quantity, price, price * quantity, zero, zero, date, date, true, false));
+            },
+        };
 
         Ok(())
     }
@@ -459,9 +804,17 @@ impl BrokerStatement {
                 self.open_positions.insert(mapping.to_owned(), quantity);
             }
 
+            if let Some(quantity) = self.derivative_positions.remove(symbol) {
+                self.derivative_positions.insert(mapping.to_owned(), quantity);
+            }
+
             if let Some(name) = self.instrument_names.remove(symbol) {
                 self.instrument_names.insert(mapping.to_owned(), name);
             }
+
+            if let Some(issuer_country) = self.bond_issuers.remove(symbol) {
+                self.bond_issuers.insert(mapping.to_owned(), issuer_country);
+            }
         }
 
         for stock_buy in &mut self.stock_buys {
@@ -476,6 +829,30 @@ impl BrokerStatement {
             }
         }
 
+        for bond_buy in &mut self.bond_buys {
+            if let Some(mapping) = remapping.get(&bond_buy.symbol) {
+                bond_buy.symbol = mapping.to_owned();
+            }
+        }
+
+        for bond_sell in &mut self.bond_sells {
+            if let Some(mapping) = remapping.get(&bond_sell.symbol) {
+                bond_sell.symbol = mapping.to_owned();
+            }
+        }
+
+        for coupon in &mut self.coupons {
+            if let Some(mapping) = remapping.get(&coupon.symbol) {
+                coupon.symbol = mapping.to_owned();
+            }
+        }
+
+        for trade in &mut self.derivative_trades {
+            if let Some(mapping) = remapping.get(&trade.symbol) {
+                trade.symbol = mapping.to_owned();
+            }
+        }
+
         for dividend in &mut self.dividends {
             if let Some(mapping) = remapping.get(&dividend.issuer) {
                 dividend.issuer = mapping.to_owned();
@@ -517,11 +894,34 @@ impl BrokerStatement {
                 "stock sell", &self.stock_sells, |trade| trade.conclusion_date)?;
         }
 
+        if !self.bond_buys.is_empty() {
+            self.bond_buys.sort_by_key(|trade| (trade.conclusion_date, trade.execution_date));
+            date_validator.validate(
+                "bond buy", &self.bond_buys, |trade| trade.conclusion_date)?;
+        }
+
+        if !self.bond_sells.is_empty() {
+            self.bond_sells.sort_by_key(|trade| (trade.conclusion_date, trade.execution_date));
+            date_validator.validate(
+                "bond sell", &self.bond_sells, |trade| trade.conclusion_date)?;
+        }
+
+        if !self.derivative_trades.is_empty() {
+            self.derivative_trades.sort_by_key(|trade| (trade.conclusion_date, trade.execution_date));
+            date_validator.validate(
+                "derivative trade", &self.derivative_trades, |trade| trade.conclusion_date)?;
+        }
+
         if !self.dividends.is_empty() {
             self.dividends.sort_by(|a, b| (a.date, &a.issuer).cmp(&(b.date, &b.issuer)));
             date_validator.validate("dividend", &self.dividends, |dividend| dividend.date)?;
         }
 
+        if !self.coupons.is_empty() {
+            self.coupons.sort_by(|a, b| (a.date, &a.symbol).cmp(&(b.date, &b.symbol)));
+            date_validator.validate("coupon", &self.coupons, |coupon| coupon.date)?;
+        }
+
         date_validator.sort_and_validate(
             "corporate action", &mut self.corporate_actions, |action| action.date)?;
 
@@ -577,35 +977,48 @@ impl BrokerStatement {
     }
 
     fn validate_open_positions(&self) -> EmptyResult {
-        let mut open_positions: HashMap<&str, Decimal> = HashMap::new();
+        let mut open_positions: HashMap<String, Decimal> = HashMap::new();
 
         for stock_buy in &self.stock_buys {
             if stock_buy.is_sold() {
                 continue;
             }
 
-            let multiplier = self.stock_splits.get_multiplier(
+            let symbol = self.stock_splits.get_symbol(
                 &stock_buy.symbol, stock_buy.conclusion_date, self.last_date());
 
-            let quantity = multiplier * stock_buy.get_unsold();
+            let quantity = self.stock_splits.convert_quantity(
+                &stock_buy.symbol, stock_buy.conclusion_date, self.last_date(),
+                stock_buy.get_unsold());
+
+            open_positions.entry(symbol)
+                .and_modify(|position| *position += quantity)
+                .or_insert(quantity);
+        }
+
+        for bond_buy in &self.bond_buys {
+            if bond_buy.is_sold() {
+                continue;
+            }
+
+            let quantity = bond_buy.get_unsold();
 
-            open_positions.entry(&stock_buy.symbol)
+            open_positions.entry(bond_buy.symbol.clone())
                 .and_modify(|position| *position += quantity)
                 .or_insert(quantity);
         }
 
         let symbols: BTreeSet<&str> = self.open_positions.keys().map(String::as_str)
-            .chain(open_positions.keys().copied())
+            .chain(open_positions.keys().map(String::as_str))
             .collect();
 
         for &symbol in &symbols {
-            let calculated = open_positions.get(symbol);
-            let actual = self.open_positions.get(symbol);
-
-            if calculated != actual {
-                let calculated = calculated.copied().unwrap_or_default();
-                let actual = actual.copied().unwrap_or_default();
+            let calculated = open_positions.get(symbol).copied().unwrap_or_default();
+            let actual = self.open_positions.get(symbol).copied().unwrap_or_default();
 
+            // Calculated positions may have rounding errors due to quantity conversions between
+            // stock splits
+            if !corporate_actions::is_quantity_dust(calculated - actual) {
                 return Err!(concat!(
                     "Calculated open positions don't match declared ones in the statement: ",
                     "{}: {} vs {}"
@@ -617,6 +1030,82 @@ impl BrokerStatement {
     }
 }
 
+/// Allocates a part of the parent position cost basis to the stocks received in spin-offs which
+/// have occurred before the specified date (all pending spin-offs if it's not specified).
+///
+/// Must be called during trades processing when all sells preceding the spin-off are already
+/// matched against the buys, so the unsold buys contain the parent position as it was at the
+/// moment of the spin-off. The cost basis is split proportionally to the stock quantities.
+fn allocate_spin_off_cost_basis(
+    stock_buys: &mut [StockBuy], stock_splits: &StockSplitController,
+    unsold_buys: &HashMap<String, Vec<usize>>, before: Option<Date>, last_date: Date,
+) -> EmptyResult {
+    for index in 0..stock_buys.len() {
+        let spin_off = &stock_buys[index];
+        let date = spin_off.execution_date;
+
+        let parent_symbol = match spin_off.spin_off_parent {
+            Some(ref symbol) if before.map_or(true, |before| date <= before) => symbol.clone(),
+            _ => continue,
+        };
+
+        let mut parent_buys = Vec::new();
+        let mut parent_quantity = dec!(0);
+        let mut parent_cost = Cash::new(spin_off.price.currency, dec!(0));
+
+        let parent_final_symbol = stock_splits.get_symbol(&parent_symbol, date, last_date);
+
+        for &parent_index in unsold_buys.get(&parent_final_symbol).map(Vec::as_slice).unwrap_or_default() {
+            let parent_buy = &stock_buys[parent_index];
+            if parent_buy.conclusion_date >= date || parent_buy.is_sold() {
+                continue;
+            }
+
+            parent_quantity += stock_splits.convert_quantity(
+                &parent_buy.symbol, parent_buy.conclusion_date, date, parent_buy.get_unsold());
+
+            parent_cost.add_assign(parent_buy.price * parent_buy.get_unsold()).map_err(|e| format!(
+                "Unable to allocate {} spin-off cost basis: {}", spin_off.symbol, e))?;
+
+            parent_buys.push(parent_index);
+        }
+
+        // The parent position might be closed between the record and the distribution dates
+        if parent_quantity.is_zero() {
+            warn!("Unable to allocate {} spin-off cost basis: there is no open {} position at {}.",
+                  spin_off.symbol, parent_symbol, formatting::format_date(date));
+            stock_buys[index].spin_off_parent = None;
+            continue;
+        }
+
+        let spin_off_quantity = spin_off.quantity;
+
+        let retained_ratio = parent_quantity / (parent_quantity + spin_off_quantity);
+        for parent_index in parent_buys {
+            let parent_buy = &mut stock_buys[parent_index];
+            parent_buy.price = parent_buy.price * retained_ratio;
+        }
+
+        let spin_off = &mut stock_buys[index];
+        spin_off.price = parent_cost / (parent_quantity + spin_off_quantity);
+        spin_off.spin_off_parent = None;
+    }
+
+    Ok(())
+}
+
+/// Corporate actions take effect on their date, but trades concluded before it may be executed
+/// after it. Trades emulating corporate actions are concluded before such trades to preserve the
+/// execution order that trades validation expects.
+fn get_corporate_action_conclusion_date<I>(date: Date, trades: I) -> Date
+    where I: Iterator<Item=(Date, Date)>
+{
+    trades
+        .filter(|&(_, execution_date)| execution_date > date)
+        .map(|(conclusion_date, _)| conclusion_date)
+        .fold(date, std::cmp::min)
+}
+
 struct DateValidator {
     min_date: Date,
     max_date: Date,
@@ -679,4 +1168,93 @@ pub trait BrokerStatementReader {
     fn read(&mut self, path: &str) -> GenericResult<PartialBrokerStatement>;
     #[allow(clippy::boxed_local)]
     fn close(self: Box<Self>) -> EmptyResult { Ok(()) }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use super::*;
+    use super::corporate_actions::StockSplitRatio;
+
+    #[test]
+    fn fractional_fifo() {
+        let mut statement = PartialBrokerStatement::new();
+        statement.set_period((date!(1, 1, 2020), date!(1, 1, 2021))).unwrap();
+        statement.set_starting_assets(false).unwrap();
+        statement.cash_assets.deposit(Cash::new("USD", dec!(0)));
+
+        let buy = |quantity, price, date| {
+            let price = Cash::new("USD", price);
+            let commission = Cash::new("USD", dec!(1));
+            StockBuy::new("XYZ", quantity, price, price * quantity, commission, date, date)
+        };
+
+        let sell = |quantity, price, date| {
+            let price = Cash::new("USD", price);
+            let commission = Cash::new("USD", dec!(1));
+            StockSell::new("XYZ", quantity, price, price * quantity, commission, date, date, false)
+        };
+
+        statement.stock_buys.push(buy(dec!(1), dec!(90), date!(3, 2, 2020)));
+        statement.corporate_actions.push(CorporateAction {
+            date: date!(2, 3, 2020),
+            symbol: s!("XYZ"),
+            action: CorporateActionType::StockSplit(StockSplitRatio::new(1, 3)),
+        });
+
+        // Each sell converts the quantity to the pre-split one with rounding
+        statement.stock_sells.push(sell(dec!(1), dec!(40), date!(1, 4, 2020)));
+        statement.stock_sells.push(sell(dec!(1), dec!(40), date!(4, 5, 2020)));
+        statement.stock_sells.push(sell(dec!(1), dec!(40), date!(1, 6, 2020)));
+
+        statement.stock_buys.push(buy(dec!(2.5), dec!(30), date!(1, 7, 2020)));
+        statement.stock_sells.push(sell(dec!(0.5), dec!(35), date!(3, 8, 2020)));
+        statement.add_open_position("XYZ", dec!(2)).unwrap();
+
+        let broker = Broker::InteractiveBrokers.get_info(&Config::mock(), None).unwrap();
+        let statement = BrokerStatement::new_from(broker, vec![statement], &hashmap!{}, &hashmap!{}).unwrap();
+
+        assert_eq!(statement.stock_buys.len(), 2);
+        assert!(statement.stock_buys[0].is_sold());
+        assert_eq!(statement.stock_buys[1].get_unsold(), dec!(2));
+        assert!(statement.stock_sells.iter().all(StockSell::is_processed));
+    }
+
+    #[test]
+    fn spin_off_cost_basis() {
+        let buy = |symbol, quantity, price, date| {
+            let price = Cash::new("USD", price);
+            let zero = Cash::new("USD", dec!(0));
+            StockBuy::new(symbol, quantity, price, price * quantity, zero, date, date)
+        };
+
+        let mut stock_buys = vec![
+            buy("PARENT", dec!(10), dec!(100), date!(1, 1, 2020)),
+            buy("PARENT", dec!(10), dec!(200), date!(1, 2, 2020)),
+            StockBuy::new_spin_off("CHILD", dec!(15), "USD", "PARENT", date!(1, 4, 2020), date!(1, 4, 2020)),
+            buy("PARENT", dec!(10), dec!(300), date!(1, 5, 2020)),
+        ];
+        stock_buys[0].sell(dec!(5));
+
+        let stock_splits = StockSplitController::default();
+        let unsold_buys = hashmap!{
+            s!("PARENT") => vec![3, 1, 0],
+            s!("CHILD") => vec![2],
+        };
+        let last_date = date!(31, 12, 2020);
+
+        allocate_spin_off_cost_basis(
+            &mut stock_buys, &stock_splits, &unsold_buys, Some(date!(31, 3, 2020)), last_date).unwrap();
+        assert_eq!(stock_buys[2].price, Cash::new("USD", dec!(0)));
+        assert_eq!(stock_buys[2].spin_off_parent, Some(s!("PARENT")));
+
+        allocate_spin_off_cost_basis(
+            &mut stock_buys, &stock_splits, &unsold_buys, Some(date!(1, 4, 2020)), last_date).unwrap();
+        assert_eq!(stock_buys[2].spin_off_parent, None);
+
+        // 15 parent stocks with 2500 cost basis are held at the moment of spin-off
+        assert_eq!(stock_buys[0].price, Cash::new("USD", dec!(50)));
+        assert_eq!(stock_buys[1].price, Cash::new("USD", dec!(100)));
+        assert_eq!((stock_buys[2].price * dec!(15)).round(), Cash::new("USD", dec!(1250)));
+        assert_eq!(stock_buys[3].price, Cash::new("USD", dec!(300)));
+    }
+}
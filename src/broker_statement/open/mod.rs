@@ -45,6 +45,15 @@ fn read_statement(path: &str) -> GenericResult<BrokerReport> {
 
 #[cfg(test)]
 mod tests {
+    use std::path::Path;
+
+    use crate::commissions::CommissionCalc;
+    use crate::currency::Cash;
+    use crate::currency::converter::CurrencyConverter;
+    use crate::db::{self, schema::CurrencyRateSource};
+    use crate::localities::{self, CountryCode};
+    use crate::types::Date;
+
     use super::*;
 
     #[test]
@@ -63,9 +72,59 @@ mod tests {
         assert!(statement.forex_trades.is_empty());
         assert!(!statement.stock_buys.is_empty());
         assert!(!statement.stock_sells.is_empty());
+        assert!(statement.bond_buys.is_empty());
+        assert!(statement.bond_sells.is_empty());
         assert!(statement.dividends.is_empty());
+        assert!(statement.coupons.is_empty());
 
         assert!(!statement.open_positions.is_empty());
         assert!(!statement.instrument_names.is_empty());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn parse_bonds() {
+        let broker = Broker::Open.get_info(&Config::mock(), None).unwrap();
+        let path = Path::new(file!()).parent().unwrap().join("testdata/bonds");
+
+        let mut statement = BrokerStatement::read(
+            broker, path.to_str().unwrap(), &hashmap!{}, &hashmap!{}, TaxRemapping::new(), true).unwrap();
+
+        let symbol = "SU26209RMFS5";
+        assert_eq!(statement.get_bond_issuer(symbol).unwrap(), CountryCode::from_oksm_code(643));
+
+        let accrued_interest: Vec<Cash> = statement.bond_buys.iter()
+            .map(|trade| trade.accrued_interest).collect();
+        assert_eq!(accrued_interest, vec![Cash::new("RUB", dec!(150)), Cash::new("RUB", dec!(120))]);
+
+        let coupons: Vec<(Date, Cash, Cash)> = statement.coupons.iter()
+            .map(|coupon| (coupon.date, coupon.amount, coupon.paid_tax)).collect();
+        assert_eq!(coupons, vec![
+            (date!(22, 1, 2020), Cash::new("RUB", dec!(381.50)), Cash::new("RUB", dec!(49.60))),
+            (date!(22, 7, 2020), Cash::new("RUB", dec!(572.25)), Cash::new("RUB", dec!(74.39))),
+        ]);
+
+        assert_eq!(statement.bond_sells.len(), 1);
+        let trade = &statement.bond_sells[0];
+        assert_eq!(trade.accrued_interest, Cash::new("RUB", dec!(240)));
+
+        // The sold bonds are taken from the first purchase and the rest from the second one
+        let (_database_file, database) = db::new_temporary();
+        let converter = CurrencyConverter::new(database, CurrencyRateSource::Cbr, None, false);
+        let details = trade.calculate(&localities::russia(), &converter).unwrap();
+
+        assert_eq!(details.revenue, Cash::new("RUB", dec!(12600)));
+        assert_eq!(details.purchase_local_cost, Cash::new("RUB", dec!(12400.19)));
+        assert_eq!(details.total_local_cost, Cash::new("RUB", dec!(12412.55)));
+        assert_eq!(details.local_profit, Cash::new("RUB", dec!(187.45)));
+        assert_eq!(details.tax_to_pay, Cash::new("RUB", dec!(24)));
+
+        // Bond quotes include accrued interest, so it's separated from the emulated trade volume
+        let mut commission_calc = CommissionCalc::new(statement.broker.commission_spec.clone());
+        statement.emulate_sell(symbol, dec!(3), Cash::new("RUB", dec!(1050)), &mut commission_calc).unwrap();
+
+        let trade = statement.bond_sells.last().unwrap();
+        assert!(trade.emulation);
+        assert_eq!(trade.accrued_interest, Cash::new("RUB", dec!(114.45)));
+        assert_eq!(trade.volume, Cash::new("RUB", dec!(3035.55)));
+    }
+}
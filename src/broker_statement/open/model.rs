@@ -4,11 +4,13 @@ use log::{warn, error};
 use num_traits::Zero;
 use serde::Deserialize;
 
+use crate::broker_statement::bonds::{BondBuy, BondSell, CouponPayment};
 use crate::broker_statement::fees::Fee;
 use crate::broker_statement::partial::PartialBrokerStatement;
 use crate::broker_statement::trades::{StockBuy, StockSell};
 use crate::core::{EmptyResult, GenericResult};
 use crate::currency::{Cash, CashAssets};
+use crate::localities::CountryCode;
 use crate::types::{Date, Decimal};
 use crate::util::{self, DecimalRestrictions};
 
@@ -60,7 +62,7 @@ impl BrokerReport {
         }
 
         if let Some(ref cash_flow) = self.cash_flow {
-            cash_flow.parse(statement)?;
+            cash_flow.parse(statement, &securities)?;
         }
 
         // Actually, we should check trade execution dates on statements merging stage when we have
@@ -140,7 +142,7 @@ impl Assets {
             has_starting_assets |= !asset.start_amount.is_zero();
 
             match asset.type_.as_str() {
-                "Акции" | "ПАИ" => {
+                "Акции" | "ПАИ" | "Облигации" => {
                     let symbol = get_symbol(securities, &asset.name)?;
                     let amount = parse_quantity(asset.end_amount, true)?;
                     if amount != 0 {
@@ -200,6 +202,10 @@ struct ConcludedTrade {
 
     #[serde(rename = "broker_commission")]
     commission: Decimal,
+
+    // Accrued coupon interest (НКД) which is specified for bond trades only
+    #[serde(rename = "nkd")]
+    accrued_interest: Option<Decimal>,
 }
 
 impl ConcludedTrades {
@@ -238,6 +244,34 @@ impl ConcludedTrades {
                 None => trade.execution_date,
             };
 
+            if let Some(accrued_interest) = trade.accrued_interest {
+                let accrued_interest = util::validate_named_decimal(
+                    "accrued interest", accrued_interest, DecimalRestrictions::PositiveOrZero)?;
+                let accrued_interest = Cash::new(&trade.currency, accrued_interest);
+                statement.bond_issuers.insert(symbol.to_owned(), get_bond_issuer());
+
+                // Bond price is specified in percents of face value, so calculate it from volume
+                match (trade.buy_quantity, trade.sell_quantity) {
+                    (Some(quantity), None) => {
+                        let quantity = Decimal::from(parse_quantity(quantity, false)?);
+
+                        statement.bond_buys.push(BondBuy::new(
+                            symbol, quantity, volume / quantity, volume, accrued_interest,
+                            commission, trade.conclusion_date, execution_date));
+                    },
+                    (None, Some(quantity)) => {
+                        let quantity = Decimal::from(parse_quantity(quantity, false)?);
+
+                        statement.bond_sells.push(BondSell::new(
+                            symbol, quantity, volume / quantity, volume, accrued_interest,
+                            commission, trade.conclusion_date, execution_date, false, false));
+                    },
+                    _ => return Err!("Got an unexpected trade: Can't match it as buy or sell trade")
+                };
+
+                continue;
+            }
+
             match (trade.buy_quantity, trade.sell_quantity) {
                 (Some(quantity), None) => {
                     let quantity = parse_quantity(quantity, false)?;
@@ -318,7 +352,7 @@ struct CashFlow {
 }
 
 impl CashFlows {
-    fn parse(&self, statement: &mut PartialBrokerStatement) -> EmptyResult {
+    fn parse(&self, statement: &mut PartialBrokerStatement, securities: &HashMap<String, String>) -> EmptyResult {
         for cash_flow in &self.cash_flows {
             let date = cash_flow.date;
             let currency = &cash_flow.currency;
@@ -345,6 +379,24 @@ impl CashFlows {
                         description: Some(description),
                     });
                 },
+                CashFlowType::Coupon(security_name, withheld_tax) => {
+                    let symbol = get_symbol(securities, &security_name)?;
+                    let amount = util::validate_named_decimal(
+                        "coupon amount", amount, DecimalRestrictions::StrictlyPositive)?;
+
+                    // The coupon is paid net of the tax withheld by the broker. The broker is a tax
+                    // agent, so the coupon isn't declared and issuer country is informational only.
+                    let paid_tax = withheld_tax.unwrap_or_else(|| dec!(0));
+                    statement.bond_issuers.insert(symbol.to_owned(), get_bond_issuer());
+
+                    statement.coupons.push(CouponPayment {
+                        date,
+                        symbol: symbol.to_owned(),
+                        issuer_country: get_bond_issuer(),
+                        amount: Cash::new(currency, amount + paid_tax),
+                        paid_tax: Cash::new(currency, paid_tax),
+                    });
+                },
             };
         }
 
@@ -389,6 +441,11 @@ impl Securities {
     }
 }
 
+// The statements have no bond ISIN, so all bonds are considered as issued in Russia
+fn get_bond_issuer() -> CountryCode {
+    CountryCode::from_oksm_code(643)
+}
+
 fn get_symbol<'a>(securities: &'a HashMap<String, String>, name: &str) -> GenericResult<&'a String> {
     Ok(securities.get(name).ok_or_else(|| format!(
         "Unable to find security info by its name ({:?})", name))?)
@@ -1,11 +1,13 @@
+use lazy_static::lazy_static;
 #[cfg(test)] use matches::assert_matches;
 use num_traits::{FromPrimitive, ToPrimitive};
+use regex::Regex;
 use serde::{Deserialize, Deserializer};
 use serde::de::Error;
 
 use crate::core::GenericResult;
 use crate::types::{Date, Decimal};
-use crate::util;
+use crate::util::{self, DecimalRestrictions};
 
 fn parse_date(date: &str) -> GenericResult<Date> {
     util::parse_date(date, "%Y-%m-%dT00:00:00")
@@ -49,6 +51,7 @@ pub enum CashFlowType {
     Deposit,
     Commission,
     Fee(String),
+    Coupon(String, Option<Decimal>),
 }
 
 impl CashFlowType {
@@ -72,6 +75,29 @@ impl CashFlowType {
             return Ok(CashFlowType::Deposit);
         }
 
+        lazy_static! {
+            static ref COUPON_REGEX: Regex = Regex::new(
+                r"^Выплата купонного дохода по ЦБ (?P<name>[^;,]+?)(?:[;,].*)?$").unwrap();
+            static ref COUPON_TAX_REGEX: Regex = Regex::new(
+                r"НДФЛ удержан в (?:размере|сумме) (?P<amount>\d+(?:[.,]\d+)?)").unwrap();
+        }
+
+        if let Some(captures) = COUPON_REGEX.captures(description) {
+            let name = captures.name("name").unwrap().as_str().to_owned();
+
+            // The broker is a tax agent and withholds the tax from the coupon. The withheld amount
+            // is specified in the description (if any).
+            let withheld_tax = match COUPON_TAX_REGEX.captures(description) {
+                Some(captures) => {
+                    let amount = captures.name("amount").unwrap().as_str().replace(',', ".");
+                    Some(util::parse_decimal(&amount, DecimalRestrictions::PositiveOrZero)?)
+                },
+                None => None,
+            };
+
+            return Ok(CashFlowType::Coupon(name, withheld_tax));
+        }
+
         return Err!("Unable to determine cash flow type by its description: {:?}", description);
     }
 }
@@ -123,5 +149,15 @@ mod tests {
             CashFlowType::parse("Вознаграждение Брокера за предоставление информации по движению и учету ценных бумаг/ИФИ в портфеле Фондовый Рынок Московской биржи за январь 2020").unwrap(),
             CashFlowType::Fee(d) if d == "Вознаграждение Брокера за предоставление информации по движению и учету ценных бумаг"
         );
+
+        assert_matches!(
+            CashFlowType::parse("Выплата купонного дохода по ЦБ ОФЗ 26209; НДФЛ удержан").unwrap(),
+            CashFlowType::Coupon(name, None) if name == "ОФЗ 26209"
+        );
+
+        assert_matches!(
+            CashFlowType::parse("Выплата купонного дохода по ЦБ ОФЗ 26209; НДФЛ удержан в размере 51.48 руб.").unwrap(),
+            CashFlowType::Coupon(name, Some(tax)) if name == "ОФЗ 26209" && tax == dec!(51.48)
+        );
     }
 }
\ No newline at end of file
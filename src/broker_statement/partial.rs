@@ -4,10 +4,13 @@ use std::collections::hash_map::Entry;
 use crate::core::{EmptyResult, GenericResult};
 use crate::currency::{CashAssets, MultiCurrencyCashAccount};
 use crate::formatting;
+use crate::localities::CountryCode;
 use crate::types::{Date, Decimal};
 use crate::util::{DecimalRestrictions, validate_named_decimal};
 
+use super::bonds::{BondBuy, BondSell, CouponPayment};
 use super::corporate_actions::CorporateAction;
+use super::derivatives::DerivativeTrade;
 use super::dividends::{Dividend, DividendId, DividendAccruals};
 use super::fees::Fee;
 use super::interest::IdleCashInterest;
@@ -16,6 +19,7 @@ use super::taxes::{TaxId, TaxAccruals};
 
 pub struct PartialBrokerStatement {
     pub period: Option<(Date, Date)>,
+    pub account_number: Option<String>,
 
     pub starting_assets: Option<bool>,
     pub cash_flows: Vec<CashAssets>,
@@ -27,20 +31,27 @@ pub struct PartialBrokerStatement {
     pub forex_trades: Vec<ForexTrade>,
     pub stock_buys: Vec<StockBuy>,
     pub stock_sells: Vec<StockSell>,
+    pub bond_buys: Vec<BondBuy>,
+    pub bond_sells: Vec<BondSell>,
+    pub derivative_trades: Vec<DerivativeTrade>,
     pub dividends: Vec<Dividend>,
+    pub coupons: Vec<CouponPayment>,
 
     pub corporate_actions: Vec<CorporateAction>,
     pub dividend_accruals: HashMap<DividendId, DividendAccruals>,
     pub tax_accruals: HashMap<TaxId, TaxAccruals>,
 
     pub open_positions: HashMap<String, Decimal>,
+    pub derivative_positions: HashMap<String, Decimal>,
     pub instrument_names: HashMap<String, String>,
+    pub bond_issuers: HashMap<String, CountryCode>,
 }
 
 impl PartialBrokerStatement {
     pub fn new() -> PartialBrokerStatement {
         PartialBrokerStatement {
             period: None,
+            account_number: None,
 
             starting_assets: None,
             cash_flows: Vec::new(),
@@ -52,14 +63,20 @@ impl PartialBrokerStatement {
             forex_trades: Vec::new(),
             stock_buys: Vec::new(),
             stock_sells: Vec::new(),
+            bond_buys: Vec::new(),
+            bond_sells: Vec::new(),
+            derivative_trades: Vec::new(),
             dividends: Vec::new(),
+            coupons: Vec::new(),
 
             corporate_actions: Vec::new(),
             dividend_accruals: HashMap::new(),
             tax_accruals: HashMap::new(),
 
             open_positions: HashMap::new(),
+            derivative_positions: HashMap::new(),
             instrument_names: HashMap::new(),
+            bond_issuers: HashMap::new(),
         }
     }
 
@@ -71,6 +88,10 @@ impl PartialBrokerStatement {
         get_option("statement period", self.period)
     }
 
+    pub fn set_account_number(&mut self, number: &str) -> EmptyResult {
+        set_option("account number", &mut self.account_number, number.to_owned())
+    }
+
     pub fn set_starting_assets(&mut self, exists: bool) -> EmptyResult {
         set_option("starting assets", &mut self.starting_assets, exists)
     }
@@ -91,6 +112,18 @@ impl PartialBrokerStatement {
         Ok(())
     }
 
+    pub fn add_derivative_position(&mut self, symbol: &str, quantity: Decimal) -> EmptyResult {
+        validate_named_decimal(
+            &format!("{} derivative position", symbol), quantity, DecimalRestrictions::NonZero)?;
+
+        match self.derivative_positions.entry(symbol.to_owned()) {
+            Entry::Vacant(entry) => entry.insert(quantity),
+            Entry::Occupied(_) => return Err!("Got a duplicated derivative position for {}", symbol),
+        };
+
+        Ok(())
+    }
+
     pub fn validate(self) -> GenericResult<PartialBrokerStatement> {
         let period = self.get_period()?;
         if period.0 >= period.1 {
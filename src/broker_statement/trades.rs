@@ -1,10 +1,13 @@
 use crate::core::GenericResult;
-use crate::currency::Cash;
+use crate::currency::{self, Cash};
 use crate::currency::converter::CurrencyConverter;
 use crate::formatting;
 use crate::localities::Country;
+use crate::taxes::{self, LongTermOwnershipDeduction};
 use crate::types::{Date, Decimal};
 
+use super::corporate_actions;
+
 #[derive(Debug)]
 pub struct ForexTrade {
     pub from: Cash,
@@ -24,6 +27,10 @@ pub struct StockBuy {
     pub conclusion_date: Date,
     pub execution_date: Date,
 
+    // Stocks received in a spin-off get a part of the parent position cost basis which is
+    // allocated on trades processing
+    pub spin_off_parent: Option<String>,
+
     sold: Decimal,
 }
 
@@ -34,7 +41,19 @@ impl StockBuy {
     ) -> StockBuy {
         StockBuy {
             symbol: symbol.to_owned(), quantity, price, volume, commission,
-            conclusion_date, execution_date, sold: dec!(0),
+            conclusion_date, execution_date, spin_off_parent: None, sold: dec!(0),
+        }
+    }
+
+    pub fn new_spin_off(
+        symbol: &str, quantity: Decimal, currency: &str, parent_symbol: &str,
+        conclusion_date: Date, execution_date: Date,
+    ) -> StockBuy {
+        let zero = Cash::new(currency, dec!(0));
+        StockBuy {
+            symbol: symbol.to_owned(), quantity, price: zero, volume: zero, commission: zero,
+            conclusion_date, execution_date, spin_off_parent: Some(parent_symbol.to_owned()),
+            sold: dec!(0),
         }
     }
 
@@ -84,12 +103,17 @@ impl StockSell {
 
     pub fn process(&mut self, sources: Vec<StockSellSource>) {
         assert!(!self.is_processed());
-        assert_eq!(
-            sources.iter()
-                .map(|source| source.multiplier * source.quantity)
-                .sum::<Decimal>(),
-            self.quantity,
-        );
+        assert!(!sources.is_empty());
+
+        // Quantities are rounded when converted between stock splits, so the sources may have a
+        // rounding error
+        let quantity: Decimal = sources.iter()
+            .map(|source| source.multiplier * source.quantity)
+            .sum();
+        assert!(corporate_actions::is_quantity_dust(quantity - self.quantity),
+                "{} sell sources quantity doesn't match the sold quantity: {} vs {}",
+                self.symbol, quantity, self.quantity);
+
         self.sources = sources;
     }
 
@@ -115,14 +139,26 @@ impl StockSell {
         let mut purchase_local_cost = Cash::new(total_local_cost.currency, dec!(0));
 
         let mut fifo = Vec::new();
+        let mut long_term_ownership = LongTermOwnershipDeduction::default();
 
         for source in &self.sources {
-            let fifo_details = source.calculate(country, converter)?;
+            let fifo_details = source.calculate(country, converter, self.execution_date)?;
 
             purchase_cost.add_assign(fifo_details.total_cost).map_err(|e| format!(
                 "Sell and buy trades have different currency: {}", e))?;
             purchase_local_cost.add_assign(fifo_details.total_local_cost).unwrap();
 
+            if fifo_details.long_term_ownership {
+                let quantity = fifo_details.quantity * fifo_details.multiplier;
+                let share = quantity / self.quantity;
+                let revenue = local_revenue.amount * share;
+                let profit = (local_revenue.amount - local_commission.amount) * share
+                    - fifo_details.total_local_cost.amount;
+
+                long_term_ownership.add(
+                    currency::round(revenue), fifo_details.ownership_years, currency::round(profit));
+            }
+
             fifo.push(fifo_details);
         }
 
@@ -168,6 +204,7 @@ impl StockSell {
             real_profit_ratio,
             real_local_profit_ratio,
 
+            long_term_ownership,
             fifo: fifo,
         })
     }
@@ -185,7 +222,9 @@ pub struct StockSellSource {
 }
 
 impl StockSellSource {
-    fn calculate(&self, country: &Country, converter: &CurrencyConverter) -> GenericResult<FifoDetails> {
+    fn calculate(
+        &self, country: &Country, converter: &CurrencyConverter, sell_date: Date,
+    ) -> GenericResult<FifoDetails> {
         let cost = (self.price * self.quantity).round();
         let local_cost = converter.convert_to_rounding(
             self.execution_date, cost, country.currency)?;
@@ -217,6 +256,9 @@ impl StockSellSource {
 
             total_cost,
             total_local_cost: Cash::new(country.currency, total_local_cost),
+
+            ownership_years: taxes::get_ownership_years(self.execution_date, sell_date),
+            long_term_ownership: taxes::is_long_term_ownership(self.execution_date, sell_date),
         })
     }
 }
@@ -240,6 +282,8 @@ pub struct SellDetails {
     pub real_profit_ratio: Decimal,
     pub real_local_profit_ratio: Decimal,
 
+    // Information for long-term ownership deduction (ЛДВ) which is applied to the whole tax year
+    pub long_term_ownership: LongTermOwnershipDeduction,
     pub fifo: Vec<FifoDetails>,
 }
 
@@ -259,4 +303,7 @@ pub struct FifoDetails {
 
     pub total_cost: Cash,
     pub total_local_cost: Cash,
+
+    pub ownership_years: u32,
+    pub long_term_ownership: bool,
 }
\ No newline at end of file
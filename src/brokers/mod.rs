@@ -11,6 +11,7 @@ use crate::commissions::CommissionSpec;
 use crate::config::{Config, BrokersConfig, BrokerConfig};
 use crate::core::GenericResult;
 use crate::currency::CashAssets;
+use crate::localities::Jurisdiction;
 use crate::types::Decimal;
 
 #[derive(Debug, Clone, Copy)]
@@ -57,6 +58,29 @@ impl Broker {
         }
     }
 
+    /// Returns jurisdiction of the foreign brokers (None for Russian ones).
+    pub fn get_jurisdiction(self) -> Option<Jurisdiction> {
+        match self {
+            Broker::Firstrade | Broker::InteractiveBrokers => Some(Jurisdiction::Usa),
+            Broker::Bcs | Broker::Open | Broker::Tinkoff => None,
+        }
+    }
+
+    /// Returns registered office address of the foreign brokers (None for Russian ones).
+    pub fn get_address(self) -> Option<&'static str> {
+        match self {
+            Broker::Firstrade => Some("30-50 Main Street, Flushing, NY 11354, USA"),
+            Broker::InteractiveBrokers => Some("One Pickwick Plaza, Greenwich, CT 06830, USA"),
+            Broker::Bcs | Broker::Open | Broker::Tinkoff => None,
+        }
+    }
+
+    /// Russian brokers are tax agents: they calculate, withhold and pay the tax themselves, so the
+    /// income mustn't be declared in the tax statement.
+    pub fn is_tax_agent(self) -> bool {
+        self.get_jurisdiction().is_none()
+    }
+
     fn get_config(self, config: &BrokersConfig) -> Option<&BrokerConfig> {
         match self {
             Broker::Bcs => &config.bcs,
@@ -3,7 +3,7 @@ use std::collections::BTreeMap;
 use log::warn;
 
 use crate::broker_statement::BrokerStatement;
-use crate::currency::{Cash, MultiCurrencyCashAccount};
+use crate::currency::{self, Cash, MultiCurrencyCashAccount};
 use crate::formatting::format_date;
 use crate::types::{Date, Decimal};
 
@@ -17,6 +17,20 @@ pub struct CashFlowSummary {
     pub ending: Decimal,
 }
 
+impl CashFlowSummary {
+    /// Rounds the summary to the currency precision keeping ending assets consistent with the
+    /// other rounded values.
+    pub fn round(&self) -> CashFlowSummary {
+        let starting = currency::round(self.starting);
+        let deposits = currency::round(self.deposits);
+        let withdrawals = currency::round(self.withdrawals);
+        let ending = starting + deposits - withdrawals;
+        assert!(self.ending - dec!(0.015) <= ending && ending <= self.ending + dec!(0.015));
+
+        CashFlowSummary {starting, deposits, withdrawals, ending}
+    }
+}
+
 pub fn calculate(statement: &BrokerStatement, start_date: Date, end_date: Date) -> (
     BTreeMap<&'static str, CashFlowSummary>, Vec<CashFlow>
 ) {
@@ -1,5 +1,6 @@
 use crate::broker_statement::{
-    BrokerStatement, ForexTrade, StockBuy, StockSell, Dividend, Fee, IdleCashInterest};
+    BrokerStatement, ForexTrade, StockBuy, StockSell, BondBuy, BondSell, DerivativeTrade, Dividend,
+    CouponPayment, Fee, IdleCashInterest};
 use crate::currency::{Cash, CashAssets};
 use crate::types::Date;
 
@@ -34,6 +35,10 @@ impl CashFlowMapper {
             self.dividend(&statement.get_instrument_name(&dividend.issuer), dividend);
         }
 
+        for coupon in &statement.coupons {
+            self.coupon(&statement.get_instrument_name(&coupon.symbol), coupon);
+        }
+
         for trade in &statement.forex_trades {
             self.forex_trade(trade);
         }
@@ -46,6 +51,18 @@ impl CashFlowMapper {
             self.stock_buy(&statement.get_instrument_name(&trade.symbol), trade);
         }
 
+        for trade in &statement.bond_sells {
+            self.bond_sell(&statement.get_instrument_name(&trade.symbol), trade);
+        }
+
+        for trade in &statement.bond_buys {
+            self.bond_buy(&statement.get_instrument_name(&trade.symbol), trade);
+        }
+
+        for trade in &statement.derivative_trades {
+            self.derivative_trade(&statement.get_instrument_name(&trade.symbol), trade);
+        }
+
         for fee in &statement.fees {
             self.fee(fee);
         }
@@ -114,6 +131,71 @@ impl CashFlowMapper {
         };
     }
 
+    fn bond_buy(&mut self, name: &str, trade: &BondBuy) {
+        let description = format!("Покупка {} {}", trade.quantity, name);
+        self.add(trade.conclusion_date, -trade.volume, description);
+
+        if !trade.accrued_interest.is_zero() {
+            let description = format!("НКД при покупке {} {}", trade.quantity, name);
+            self.add(trade.conclusion_date, -trade.accrued_interest, description);
+        };
+
+        if !trade.commission.is_zero() {
+            let description = format!("Комиссия за покупку {} {}", trade.quantity, name);
+            self.add(trade.conclusion_date, -trade.commission, description);
+        };
+    }
+
+    fn bond_sell(&mut self, name: &str, trade: &BondSell) {
+        let description = if trade.redemption {
+            format!("Погашение {} {}", trade.quantity, name)
+        } else {
+            format!("Продажа {} {}", trade.quantity, name)
+        };
+        self.add(trade.conclusion_date, trade.volume, description);
+
+        if !trade.accrued_interest.is_zero() {
+            let description = format!("НКД при продаже {} {}", trade.quantity, name);
+            self.add(trade.conclusion_date, trade.accrued_interest, description);
+        };
+
+        if !trade.commission.is_zero() {
+            let description = format!("Комиссия за продажу {} {}", trade.quantity, name);
+            self.add(trade.conclusion_date, -trade.commission, description);
+        };
+    }
+
+    fn derivative_trade(&mut self, name: &str, trade: &DerivativeTrade) {
+        let cash_flow = trade.get_cash_flow();
+        if !cash_flow.is_zero() {
+            let description = format!("{} {} {}", if trade.quantity.is_sign_positive() {
+                "Покупка"
+            } else {
+                "Продажа"
+            }, trade.quantity.abs(), name);
+            self.add(trade.conclusion_date, cash_flow, description);
+        }
+
+        if !trade.commission.is_zero() {
+            let description = format!("Комиссия за сделку с {} {}", trade.quantity.abs(), name);
+            self.add(trade.conclusion_date, -trade.commission, description);
+        };
+    }
+
+    fn coupon(&mut self, name: &str, coupon: &CouponPayment) {
+        let description = if coupon.amount.is_negative() {
+            format!("НКД, уплаченный по {}", name)
+        } else {
+            format!("Купон по {}", name)
+        };
+        self.add(coupon.date, coupon.amount, description);
+
+        if !coupon.paid_tax.is_zero() {
+            let description = format!("Налог, удержанный с купона по {}", name);
+            self.add(coupon.date, -coupon.paid_tax, description);
+        };
+    }
+
     fn dividend(&mut self, name: &str, dividend: &Dividend) {
         let description = format!("Дивиденд от {}", name);
         self.add(dividend.date, dividend.amount, description);
@@ -1,20 +1,25 @@
 mod calculator;
 mod comparator;
 mod mapper;
+mod xml;
 
 use std::collections::BTreeMap;
 
 use crate::broker_statement::BrokerStatement;
 use crate::config::Config;
 use crate::core::EmptyResult;
-use crate::currency::{self, Cash};
+use crate::currency::Cash;
+use crate::foreign_accounts::AccountInfo;
 use crate::formatting::table::{Table, Column, Cell};
 use crate::types::Date;
+use crate::util;
 
 use self::calculator::CashFlowSummary;
 use self::mapper::CashFlow;
 
-pub fn generate_cash_flow_report(config: &Config, portfolio_name: &str, year: Option<i32>) -> EmptyResult {
+pub fn generate_cash_flow_report(
+    config: &Config, portfolio_name: &str, year: Option<i32>, xml_path: Option<&str>,
+) -> EmptyResult {
     let portfolio = config.get_portfolio(portfolio_name)?;
     let broker = portfolio.broker.get_info(config, portfolio.plan.as_ref())?;
 
@@ -42,6 +47,16 @@ pub fn generate_cash_flow_report(config: &Config, portfolio_name: &str, year: Op
     };
 
     let (summaries, cash_flows) = calculator::calculate(&statement, start_date, end_date);
+
+    if let Some(path) = xml_path {
+        let year = year.ok_or("Year must be specified to export the report in XML format")?;
+        let taxpayer = config.taxpayer.as_ref().ok_or(
+            "Taxpayer profile must be configured to export the report in XML format")?;
+
+        let account = AccountInfo::new(portfolio, &statement)?;
+        xml::write(path, taxpayer, &account, year, &summaries, util::today())?;
+    }
+
     generate_summary_report(&summary_title, start_date, end_date, &summaries);
     generate_details_report(&details_title, &summaries, cash_flows);
 
@@ -61,17 +76,13 @@ fn generate_summary_report(
     for (&currency, summary) in summaries {
         columns.push(Column::new(currency));
 
-        let starting = currency::round(summary.starting);
-        let deposits = currency::round(summary.deposits);
-        let withdrawals = currency::round(summary.withdrawals);
-        let ending = starting + deposits - withdrawals;
-        assert!(summary.ending - dec!(0.015) <= ending && ending <= summary.ending + dec!(0.015));
+        let summary = summary.round();
 
         let add_cell = |row: &mut Vec<Cell>, amount| row.push(Cash::new(currency, amount).into());
-        add_cell(&mut starting_assets_row, starting);
-        add_cell(&mut deposits_row, deposits);
-        add_cell(&mut withdrawals_row, -withdrawals);
-        add_cell(&mut ending_assets_row, ending);
+        add_cell(&mut starting_assets_row, summary.starting);
+        add_cell(&mut deposits_row, summary.deposits);
+        add_cell(&mut withdrawals_row, -summary.withdrawals);
+        add_cell(&mut ending_assets_row, summary.ending);
     }
 
     let mut table = Table::new(columns);
@@ -0,0 +1,237 @@
+use std::collections::BTreeMap;
+
+use crate::config::TaxpayerConfig;
+use crate::core::{EmptyResult, GenericResult};
+use crate::currency;
+use crate::foreign_accounts::AccountInfo;
+use crate::formatting::{self, xml::{self, XmlWriter}};
+use crate::types::{Date, Decimal};
+
+use super::calculator::CashFlowSummary;
+
+// Form code by КНД (Russian classification of tax documentation) of the report on cash flows in
+// foreign bank (financial organization) accounts
+const FORM_CODE: &str = "1112521";
+const FORM_VERSION: &str = "5.01";
+
+// Document type prefix of the file ID
+const FILE_TYPE: &str = "UT_OTCHDVDSFL";
+
+/// Writes the cash flow report in XML format which is accepted by the tax service.
+pub fn write(
+    path: &str, taxpayer: &TaxpayerConfig, account: &AccountInfo, year: i32,
+    summaries: &BTreeMap<&'static str, CashFlowSummary>, date: Date,
+) -> EmptyResult {
+    let data = generate(taxpayer, account, year, summaries, date)?;
+    xml::save(&data, path)
+}
+
+fn generate(
+    taxpayer: &TaxpayerConfig, account: &AccountInfo, year: i32,
+    summaries: &BTreeMap<&'static str, CashFlowSummary>, date: Date,
+) -> GenericResult<String> {
+    let mut writer = XmlWriter::new()?;
+
+    writer.start("Файл", &[
+        ("ИдФайл", xml::get_file_id(FILE_TYPE, taxpayer, date)),
+        ("ВерсПрог", format!("investments {}", env!("CARGO_PKG_VERSION"))),
+        ("ВерсФорм", s!(FORM_VERSION)),
+    ])?;
+
+    writer.start("Документ", &[
+        ("КНД", s!(FORM_CODE)),
+        ("ДатаДок", formatting::format_date(date)),
+        ("НомКорр", s!("0")),
+        ("ОтчетГод", year.to_string()),
+        ("КодНО", taxpayer.inspection_code.clone()),
+    ])?;
+
+    writer.start("СвФЛ", &[("ИННФЛ", taxpayer.inn.clone())])?;
+    writer.empty("ФИО", &xml::get_taxpayer_name(taxpayer))?;
+    writer.end("СвФЛ")?;
+
+    writer.empty("СвОрг", &[
+        ("НаимОрг", s!(account.organization)),
+        ("КодСтр", format!("{:03}", account.jurisdiction.oksm_code())),
+        ("АдрОрг", s!(account.address)),
+    ])?;
+
+    writer.start("СвСчет", &[
+        ("НомСчет", account.number.clone()),
+        ("ДатаОткр", formatting::format_date(account.open_date)),
+    ])?;
+
+    for (&currency, summary) in summaries {
+        let code = currency::get_numeric_code(currency).ok_or_else(|| format!(
+            "Unable to determine {} currency code", currency))?;
+        let summary = summary.round();
+
+        writer.empty("ДвижСредств", &[
+            ("КодВал", format!("{:03}", code)),
+            ("ОстНачПер", format_amount(summary.starting)),
+            ("Зачислено", format_amount(summary.deposits)),
+            ("Списано", format_amount(summary.withdrawals)),
+            ("ОстКонПер", format_amount(summary.ending)),
+        ])?;
+    }
+
+    writer.end("СвСчет")?;
+    writer.end("Документ")?;
+    writer.end("Файл")?;
+
+    writer.finish()
+}
+
+fn format_amount(amount: Decimal) -> String {
+    amount.normalize().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::formatting::xml::{
+        ElementSchema, AMOUNT, DATE, INN, INSPECTION_CODE, NAME, OKSM_CODE, OKV_CODE,
+        PROGRAM_VERSION, YEAR};
+    use crate::localities::Jurisdiction;
+
+    use super::*;
+
+    // Elements and attributes of the report format (КНД 1112521, version 5.01) which may be emitted
+    // by the writer with their value restrictions. Transcribed from the published XSD which can't be
+    // downloaded by the tests.
+    const SCHEMA: &[ElementSchema] = &[
+        ElementSchema {
+            name: "Файл", parent: None, required: true, multiple: false,
+            attributes: &[
+                ("ИдФайл", true, concat!(
+                    r"UT_OTCHDVDSFL_\d{4}_\d{4}_\d{12}_\d{8}_",
+                    r"[0-9A-F]{8}-[0-9A-F]{4}-[0-9A-F]{4}-[0-9A-F]{4}-[0-9A-F]{12}")),
+                ("ВерсПрог", true, PROGRAM_VERSION),
+                ("ВерсФорм", true, r"5\.01"),
+            ],
+        },
+        ElementSchema {
+            name: "Документ", parent: Some("Файл"), required: true, multiple: false,
+            attributes: &[
+                ("КНД", true, "1112521"),
+                ("ДатаДок", true, DATE),
+                ("НомКорр", true, r"\d{1,3}"),
+                ("ОтчетГод", true, YEAR),
+                ("КодНО", true, INSPECTION_CODE),
+            ],
+        },
+        ElementSchema {
+            name: "СвФЛ", parent: Some("Документ"), required: true, multiple: false,
+            attributes: &[
+                ("ИННФЛ", true, INN),
+            ],
+        },
+        ElementSchema {
+            name: "ФИО", parent: Some("СвФЛ"), required: true, multiple: false,
+            attributes: &[
+                ("Фамилия", true, NAME),
+                ("Имя", true, NAME),
+                ("Отчество", false, NAME),
+            ],
+        },
+        ElementSchema {
+            name: "СвОрг", parent: Some("Документ"), required: true, multiple: false,
+            attributes: &[
+                ("НаимОрг", true, r"[^\s].{0,999}"),
+                ("КодСтр", true, OKSM_CODE),
+                ("АдрОрг", true, r"[^\s].{0,999}"),
+            ],
+        },
+        ElementSchema {
+            name: "СвСчет", parent: Some("Документ"), required: true, multiple: false,
+            attributes: &[
+                ("НомСчет", true, r"[^\s].{0,99}"),
+                ("ДатаОткр", true, DATE),
+            ],
+        },
+        ElementSchema {
+            name: "ДвижСредств", parent: Some("СвСчет"), required: false, multiple: true,
+            attributes: &[
+                ("КодВал", true, OKV_CODE),
+                ("ОстНачПер", true, AMOUNT),
+                ("Зачислено", true, AMOUNT),
+                ("Списано", true, AMOUNT),
+                ("ОстКонПер", true, AMOUNT),
+            ],
+        },
+    ];
+
+    #[test]
+    fn generation() {
+        let taxpayer = TaxpayerConfig {
+            last_name: s!("Иванов"),
+            first_name: s!("Иван"),
+            middle_name: Some(s!("Иванович")),
+            inn: s!("123456789012"),
+            inspection_code: s!("7701"),
+            oktmo: None,
+        };
+
+        let account = AccountInfo {
+            organization: "Interactive Brokers LLC",
+            address: "One Pickwick Plaza, Greenwich, CT 06830, USA",
+            jurisdiction: Jurisdiction::Usa,
+            number: s!("U1234567"),
+            open_date: date!(1, 2, 2019),
+        };
+
+        let mut summaries = BTreeMap::new();
+        summaries.insert("EUR", CashFlowSummary {
+            starting: dec!(0),
+            deposits: dec!(100.004),
+            withdrawals: dec!(0),
+            ending: dec!(100.004),
+        });
+        summaries.insert("USD", CashFlowSummary {
+            starting: dec!(1000),
+            deposits: dec!(500.5),
+            withdrawals: dec!(1200),
+            ending: dec!(300.5),
+        });
+
+        let data = generate(&taxpayer, &account, 2019, &summaries, date!(15, 1, 2020)).unwrap();
+        let elements = xml::parse(&data);
+        xml::validate(&elements, SCHEMA);
+
+        let get = |name: &str, index: usize| xml::get_attributes(&elements, name, index);
+
+        assert!(get("Файл", 0)["ИдФайл"].starts_with("UT_OTCHDVDSFL_7701_7701_123456789012_20200115_"));
+        assert_eq!(get("Документ", 0)["КНД"], "1112521");
+        assert_eq!(get("Документ", 0)["ОтчетГод"], "2019");
+        assert_eq!(get("ФИО", 0)["Отчество"], "Иванович");
+        assert_eq!(get("СвОрг", 0)["КодСтр"], "840");
+        assert_eq!(get("СвОрг", 0)["АдрОрг"], "One Pickwick Plaza, Greenwich, CT 06830, USA");
+        assert_eq!(get("СвСчет", 0)["НомСчет"], "U1234567");
+        assert_eq!(get("СвСчет", 0)["ДатаОткр"], "01.02.2019");
+
+        let eur = get("ДвижСредств", 0);
+        assert_eq!(eur["КодВал"], "978");
+        assert_eq!(eur["ОстНачПер"], "0");
+        assert_eq!(eur["Зачислено"], "100");
+        assert_eq!(eur["ОстКонПер"], "100");
+
+        let usd = get("ДвижСредств", 1);
+        assert_eq!(usd["КодВал"], "840");
+        assert_eq!(usd["ОстНачПер"], "1000");
+        assert_eq!(usd["Зачислено"], "500.5");
+        assert_eq!(usd["Списано"], "1200");
+        assert_eq!(usd["ОстКонПер"], "300.5");
+
+        // The tax service requires a unique ID for each submitted file
+        let other_data = generate(&taxpayer, &account, 2019, &summaries, date!(15, 1, 2020)).unwrap();
+        let other_elements = xml::parse(&other_data);
+        assert_ne!(xml::get_attributes(&other_elements, "Файл", 0)["ИдФайл"], get("Файл", 0)["ИдФайл"]);
+
+        summaries.insert("XXX", CashFlowSummary {
+            starting: dec!(0),
+            deposits: dec!(1),
+            withdrawals: dec!(0),
+            ending: dec!(1),
+        });
+        assert!(generate(&taxpayer, &account, 2019, &summaries, date!(15, 1, 2020)).is_err());
+    }
+}
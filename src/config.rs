@@ -3,16 +3,18 @@ use std::fs::File;
 use std::io::Read;
 
 use chrono::{Duration, Datelike};
-use num_traits::FromPrimitive;
 use regex::Regex;
 use serde::Deserialize;
 use serde::de::{Deserializer, Error};
 
 use crate::brokers::Broker;
 use crate::core::{GenericResult, EmptyResult};
+use crate::currency;
+use crate::db::schema::CurrencyRateSource;
 use crate::formatting;
 use crate::localities::{self, Country};
 use crate::taxes::{TaxPaymentDay, TaxRemapping};
+use crate::taxes::iia::IiaType;
 use crate::types::{Date, Decimal};
 use crate::util::{self, DecimalRestrictions};
 
@@ -30,10 +32,15 @@ pub struct Config {
 
     #[serde(default)]
     pub portfolios: Vec<PortfolioConfig>,
+    #[serde(default)]
+    pub virtual_portfolios: Vec<VirtualPortfolioConfig>,
     pub brokers: Option<BrokersConfig>,
     #[serde(default)]
     pub metrics: MetricsConfig,
+    pub taxpayer: Option<TaxpayerConfig>,
 
+    #[serde(default)]
+    pub quotes: QuotesConfig,
     pub alphavantage: Option<AlphaVantageConfig>,
     pub finnhub: Option<FinnhubConfig>,
     pub twelvedata: Option<TwelveDataConfig>,
@@ -50,15 +57,40 @@ impl Config {
             notify_deposit_closing_days: None,
 
             portfolios: Vec::new(),
+            virtual_portfolios: Vec::new(),
             brokers: Some(BrokersConfig::mock()),
             metrics: Default::default(),
+            taxpayer: None,
 
+            quotes: Default::default(),
             alphavantage: None,
             finnhub: None,
             twelvedata: None,
         }
     }
 
+    /// Returns the configured quote providers chain. The default chain includes only the
+    /// providers which don't require any missing configuration.
+    pub fn get_quote_providers(&self) -> Vec<QuotesProviderType> {
+        if let Some(ref providers) = self.quotes.providers {
+            return providers.clone();
+        }
+
+        let mut providers = Vec::new();
+        if self.quotes.local_file.is_some() {
+            providers.push(QuotesProviderType::Local);
+        }
+        if self.finnhub.is_some() {
+            providers.push(QuotesProviderType::Finnhub);
+        }
+        if self.twelvedata.is_some() {
+            providers.push(QuotesProviderType::TwelveData);
+        }
+        providers.push(QuotesProviderType::Moex);
+
+        providers
+    }
+
     pub fn get_portfolio(&self, name: &str) -> GenericResult<&PortfolioConfig> {
         for portfolio in &self.portfolios {
             if portfolio.name == name {
@@ -68,6 +100,10 @@ impl Config {
 
         Err!("{:?} portfolio is not defined in the configuration file", name)
     }
+
+    pub fn get_virtual_portfolio(&self, name: &str) -> Option<&VirtualPortfolioConfig> {
+        self.virtual_portfolios.iter().find(|portfolio| portfolio.name == name)
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -96,6 +132,11 @@ pub struct PortfolioConfig {
     pub name: String,
     pub broker: Broker,
     pub plan: Option<String>,
+    pub account_number: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_date")]
+    pub account_open_date: Option<Date>,
+    #[serde(default, deserialize_with = "deserialize_optional_date")]
+    pub account_close_date: Option<Date>,
 
     pub statements: String,
     #[serde(default)]
@@ -106,10 +147,13 @@ pub struct PortfolioConfig {
     tax_remapping: Vec<TaxRemappingConfig>,
 
     pub currency: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_currency_rate_source")]
+    currency_rate_source: Option<CurrencyRateSource>,
     pub min_trade_volume: Option<Decimal>,
     pub min_cash_assets: Option<Decimal>,
     pub restrict_buying: Option<bool>,
     pub restrict_selling: Option<bool>,
+    pub tax_aware_rebalancing: Option<TaxAwareRebalancingConfig>,
 
     #[serde(default)]
     pub merge_performance: PerformanceMergingConfig,
@@ -122,6 +166,8 @@ pub struct PortfolioConfig {
 
     #[serde(default, deserialize_with = "deserialize_cash_flows")]
     pub tax_deductions: Vec<(Date, Decimal)>,
+
+    pub iia: Option<IiaConfig>,
 }
 
 impl PortfolioConfig {
@@ -139,6 +185,12 @@ impl PortfolioConfig {
         localities::russia()
     }
 
+    /// Returns source of currency rates for portfolio analysis and rebalancing. Tax statements are
+    /// always generated using official currency rates of the tax country.
+    pub fn get_currency_rate_source(&self) -> CurrencyRateSource {
+        self.currency_rate_source.unwrap_or_else(|| self.get_tax_country().currency_rate_source)
+    }
+
     pub fn get_tax_remapping(&self) -> GenericResult<TaxRemapping> {
         let mut remapping = TaxRemapping::new();
 
@@ -150,6 +202,61 @@ impl PortfolioConfig {
     }
 }
 
+/// Portfolio which consists of several real portfolios (broker accounts) and has its own asset
+/// allocation configuration
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct VirtualPortfolioConfig {
+    pub name: String,
+    pub portfolios: Vec<String>,
+
+    pub currency: String,
+    pub min_trade_volume: Option<Decimal>,
+    pub min_cash_assets: Option<Decimal>,
+    pub restrict_buying: Option<bool>,
+    pub restrict_selling: Option<bool>,
+
+    pub assets: Vec<AssetAllocationConfig>,
+}
+
+/// Tax-aware rebalancing configuration: overweight assets aren't sold if the tax to be paid exceeds
+/// the threshold (relative to the sold volume)
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TaxAwareRebalancingConfig {
+    #[serde(deserialize_with = "deserialize_weight")]
+    pub tax_cost_threshold: Decimal,
+}
+
+/// Taxpayer profile which is used to create new tax statements and reports for the tax inspection
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TaxpayerConfig {
+    pub last_name: String,
+    pub first_name: String,
+    pub middle_name: Option<String>,
+    pub inn: String,
+    pub inspection_code: String,
+    /// Municipality code (ОКТМО) of the taxpayer's place of residence. Required for 3-НДФЛ XML format.
+    pub oktmo: Option<String>,
+}
+
+/// Individual investment account (ИИС) configuration
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct IiaConfig {
+    #[serde(rename = "type")]
+    pub type_: IiaType,
+    #[serde(deserialize_with = "deserialize_date")]
+    pub open_date: Date,
+    #[serde(default, deserialize_with = "deserialize_optional_date")]
+    pub close_date: Option<Date>,
+    /// Personal income tax paid for each year (withheld by employer, for example) which limits
+    /// type A deductions
+    #[serde(default)]
+    pub paid_taxes: HashMap<i32, Decimal>,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
 struct TaxRemappingConfig {
@@ -167,6 +274,10 @@ pub struct AssetAllocationConfig {
 
     #[serde(deserialize_with = "deserialize_weight")]
     pub weight: Decimal,
+    #[serde(default, deserialize_with = "deserialize_optional_weight")]
+    pub tolerance: Option<Decimal>,
+    #[serde(default, deserialize_with = "deserialize_optional_weight")]
+    pub relative_tolerance: Option<Decimal>,
     pub restrict_buying: Option<bool>,
     pub restrict_selling: Option<bool>,
 
@@ -238,6 +349,30 @@ pub struct TransactionCommissionSpec {
     pub fixed_amount: Decimal,
 }
 
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct QuotesConfig {
+    /// Path to *.csv or *.yaml file with manually maintained prices
+    pub local_file: Option<String>,
+    /// Quote providers in priority order
+    pub providers: Option<Vec<QuotesProviderType>>,
+    /// Symbol-specific quote providers in priority order
+    #[serde(default)]
+    pub overrides: HashMap<String, Vec<QuotesProviderType>>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum QuotesProviderType {
+    #[serde(rename = "local")]
+    Local,
+    #[serde(rename = "finnhub")]
+    Finnhub,
+    #[serde(rename = "twelvedata")]
+    TwelveData,
+    #[serde(rename = "moex")]
+    Moex,
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
 pub struct AlphaVantageConfig {
@@ -288,10 +423,9 @@ pub fn load_config(path: &str) -> GenericResult<Config> {
             }
 
             if let Some(ref currency) = portfolio.currency {
-                match currency.as_str() {
-                    "RUB" | "USD" => (),
-                    _ => return Err!("Unsupported portfolio currency: {}", currency),
-                };
+                if !currency::is_supported_currency(currency) {
+                    return Err!("Unsupported portfolio currency: {}", currency);
+                }
             }
 
             for (symbol, mapping) in &portfolio.symbol_remapping {
@@ -303,6 +437,59 @@ pub fn load_config(path: &str) -> GenericResult<Config> {
             }
 
             validate_performance_merging_configuration(&portfolio.merge_performance)?;
+
+            if let Some(ref iia) = portfolio.iia {
+                if let Some(close_date) = iia.close_date {
+                    if close_date < iia.open_date {
+                        return Err!(
+                            "Invalid {:?} portfolio individual investment account dates: {} -> {}",
+                            portfolio.name, formatting::format_date(iia.open_date),
+                            formatting::format_date(close_date));
+                    }
+                }
+
+                // Type A deductions are calculated automatically from the account contributions
+                if iia.type_ == IiaType::A && !portfolio.tax_deductions.is_empty() {
+                    return Err!(
+                        "Invalid {:?} portfolio configuration: Tax deductions can't be specified for type A individual investment account",
+                        portfolio.name);
+                }
+            }
+        }
+    }
+
+    {
+        let mut portfolio_names: HashSet<&String> = config.portfolios.iter()
+            .map(|portfolio| &portfolio.name).collect();
+
+        for portfolio in &config.virtual_portfolios {
+            if !portfolio_names.insert(&portfolio.name) {
+                return Err!("Duplicate portfolio name: {:?}", portfolio.name);
+            }
+
+            if !currency::is_supported_currency(&portfolio.currency) {
+                return Err!("Unsupported portfolio currency: {}", portfolio.currency);
+            }
+
+            if portfolio.portfolios.is_empty() {
+                return Err!("{:?} virtual portfolio has no portfolios", portfolio.name);
+            }
+
+            let mut member_names = HashSet::new();
+
+            for name in &portfolio.portfolios {
+                if !member_names.insert(name) {
+                    return Err!(
+                        "Invalid {:?} virtual portfolio configuration: Duplicated {:?} portfolio",
+                        portfolio.name, name);
+                }
+
+                if config.portfolios.iter().all(|portfolio| &portfolio.name != name) {
+                    return Err!(
+                        "Invalid {:?} virtual portfolio configuration: Unknown {:?} portfolio",
+                        portfolio.name, name);
+                }
+            }
         }
     }
 
@@ -310,11 +497,57 @@ pub fn load_config(path: &str) -> GenericResult<Config> {
         portfolio.statements = shellexpand::tilde(&portfolio.statements).to_string();
     }
 
+    if let Some(ref providers) = config.quotes.providers {
+        validate_quote_providers(providers, config.quotes.local_file.is_some()).map_err(|e| format!(
+            "Invalid quotes configuration: {}", e))?;
+    }
+
+    for (symbol, providers) in &config.quotes.overrides {
+        validate_quote_providers(providers, config.quotes.local_file.is_some()).map_err(|e| format!(
+            "Invalid {} quotes configuration: {}", symbol, e))?;
+    }
+
+    if let Some(ref mut path) = config.quotes.local_file {
+        *path = shellexpand::tilde(path).to_string();
+    }
+
     validate_performance_merging_configuration(&config.metrics.merge_performance)?;
 
+    if let Some(ref taxpayer) = config.taxpayer {
+        let is_number = |value: &str, length: usize| {
+            value.len() == length && value.chars().all(|c| c.is_ascii_digit())
+        };
+
+        if !is_number(&taxpayer.inn, 12) {
+            return Err!("Invalid taxpayer INN: {:?}", taxpayer.inn);
+        }
+
+        if !is_number(&taxpayer.inspection_code, 4) {
+            return Err!("Invalid tax inspection code: {:?}", taxpayer.inspection_code);
+        }
+    }
+
     Ok(config)
 }
 
+fn validate_quote_providers(providers: &[QuotesProviderType], local_file: bool) -> EmptyResult {
+    if providers.is_empty() {
+        return Err!("At least one quote provider must be specified");
+    }
+
+    for (index, provider) in providers.iter().enumerate() {
+        if providers[..index].contains(provider) {
+            return Err!("Duplicated {:?} provider", provider);
+        }
+    }
+
+    if providers.contains(&QuotesProviderType::Local) && !local_file {
+        return Err!("Local price file is not specified");
+    }
+
+    Ok(())
+}
+
 fn default_expire_time() -> Duration {
     Duration::minutes(1)
 }
@@ -343,6 +576,19 @@ fn deserialize_tax_payment_day<'de, D>(deserializer: D) -> Result<TaxPaymentDay,
     }).ok_or_else(|| D::Error::custom(format!("Invalid tax payment day: {:?}", tax_payment_day)))?)
 }
 
+fn deserialize_currency_rate_source<'de, D>(deserializer: D) -> Result<Option<CurrencyRateSource>, D::Error>
+    where D: Deserializer<'de>
+{
+    let source: Option<String> = Deserialize::deserialize(deserializer)?;
+    Ok(match source.as_deref() {
+        Some("cbr") => Some(CurrencyRateSource::Cbr),
+        Some("ecb") => Some(CurrencyRateSource::Ecb),
+        Some("fed") => Some(CurrencyRateSource::Fed),
+        Some(source) => return Err(D::Error::unknown_variant(source, &["cbr", "ecb", "fed"])),
+        None => None,
+    })
+}
+
 fn deserialize_cash_flows<'de, D>(deserializer: D) -> Result<Vec<(Date, Decimal)>, D::Error>
     where D: Deserializer<'de>
 {
@@ -369,20 +615,44 @@ fn deserialize_date<'de, D>(deserializer: D) -> Result<Date, D::Error>
     Ok(util::parse_date(&date, "%d.%m.%Y").map_err(D::Error::custom)?)
 }
 
+fn deserialize_optional_date<'de, D>(deserializer: D) -> Result<Option<Date>, D::Error>
+    where D: Deserializer<'de>
+{
+    let date: Option<String> = Deserialize::deserialize(deserializer)?;
+    Ok(match date {
+        Some(date) => Some(util::parse_date(&date, "%d.%m.%Y").map_err(D::Error::custom)?),
+        None => None,
+    })
+}
+
+fn deserialize_optional_weight<'de, D>(deserializer: D) -> Result<Option<Decimal>, D::Error>
+    where D: Deserializer<'de>
+{
+    let weight: Option<String> = Deserialize::deserialize(deserializer)?;
+    Ok(match weight {
+        Some(weight) => Some(parse_weight(&weight).map_err(D::Error::custom)?),
+        None => None,
+    })
+}
+
 fn deserialize_weight<'de, D>(deserializer: D) -> Result<Decimal, D::Error>
     where D: Deserializer<'de>
 {
     let weight: String = Deserialize::deserialize(deserializer)?;
+    parse_weight(&weight).map_err(D::Error::custom)
+}
+
+fn parse_weight(weight: &str) -> GenericResult<Decimal> {
     if !weight.ends_with('%') {
-        return Err(D::Error::custom(format!("Invalid weight: {}", weight)));
+        return Err!("Invalid weight: {}", weight);
     }
 
-    let weight = match weight[..weight.len() - 1].parse::<u8>().ok() {
-        Some(weight) if weight <= 100 => weight,
-        _ => return Err(D::Error::custom(format!("Invalid weight: {}", weight))),
+    let weight = match util::parse_decimal(&weight[..weight.len() - 1], DecimalRestrictions::PositiveOrZero) {
+        Ok(weight) if weight <= dec!(100) => weight,
+        _ => return Err!("Invalid weight: {}", weight),
     };
 
-    Ok(Decimal::from_u8(weight).unwrap() / dec!(100))
+    Ok(weight / dec!(100))
 }
 
 pub type PerformanceMergingConfig = HashMap<String, HashSet<String>>;
@@ -407,4 +677,22 @@ fn validate_performance_merging_configuration(config: &PerformanceMergingConfig)
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weight_parsing() {
+        assert_eq!(parse_weight("0%").unwrap(), dec!(0));
+        assert_eq!(parse_weight("5%").unwrap(), dec!(0.05));
+        assert_eq!(parse_weight("0.5%").unwrap(), dec!(0.005));
+        assert_eq!(parse_weight("12.25%").unwrap(), dec!(0.1225));
+        assert_eq!(parse_weight("100%").unwrap(), dec!(1));
+
+        for &weight in &["5", "-1%", "100.1%", "%", "a%"] {
+            assert!(parse_weight(weight).is_err());
+        }
+    }
+}
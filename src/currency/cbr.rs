@@ -2,22 +2,50 @@ use std::str::FromStr;
 
 #[cfg(test)] use indoc::indoc;
 use log::debug;
+#[cfg(test)] use matches::assert_matches;
 #[cfg(test)] use mockito::{self, Mock, mock};
 use reqwest::Url;
 use reqwest::blocking::Client;
 use serde::Deserialize;
 
 use crate::core::GenericResult;
-use crate::currency::CurrencyRate;
+use crate::currency::{CurrencyRate, CurrencyRateProvider};
+use crate::db::schema::CurrencyRateSource;
 use crate::formatting;
+use crate::localities;
 use crate::types::{Date, Decimal};
 use crate::util;
 
+/// Official currency rates of the Central Bank of the Russian Federation
+pub struct Cbr;
+
+impl CurrencyRateProvider for Cbr {
+    fn source(&self) -> CurrencyRateSource {
+        CurrencyRateSource::Cbr
+    }
+
+    fn name(&self) -> &'static str {
+        "the Central Bank of the Russian Federation"
+    }
+
+    fn base_currency(&self) -> &'static str {
+        "RUB"
+    }
+
+    // Rates are set for the next day, so they are always published in advance and are missing only
+    // for Russian holidays
+    fn get_min_rate_date(&self, date: Date, _today: Date) -> Date {
+        localities::get_russian_stock_exchange_min_last_working_day(date)
+    }
+
+    fn get_rates(&self, currency: &str, start_date: Date, end_date: Date) -> GenericResult<Vec<CurrencyRate>> {
+        get_rates(currency, start_date, end_date)
+    }
+}
+
 pub fn get_rates(currency: &str, start_date: Date, end_date: Date) -> GenericResult<Vec<CurrencyRate>> {
-    let currency_code = match currency {
-        "USD" => "R01235",
-        _ => return Err!("{} currency is not supported yet.", currency),
-    };
+    let currency_code = get_currency_code(currency).ok_or_else(|| format!(
+        "{} currency is not supported yet.", currency))?;
 
     let date_format = "%d/%m/%Y";
     let start_date_string = start_date.format(date_format).to_string();
@@ -52,6 +80,26 @@ pub fn get_rates(currency: &str, start_date: Date, end_date: Date) -> GenericRes
         "Failed to get currency rates from {}: {}", url, e))?)
 }
 
+pub fn is_supported_currency(currency: &str) -> bool {
+    get_currency_code(currency).is_some()
+}
+
+fn get_currency_code(currency: &str) -> Option<&'static str> {
+    Some(match currency {
+        "AUD" => "R01010",
+        "GBP" => "R01035",
+        "HKD" => "R01200",
+        "EUR" => "R01239",
+        "CAD" => "R01350",
+        "CNY" => "R01375",
+        "SEK" => "R01770",
+        "CHF" => "R01775",
+        "JPY" => "R01820",
+        "USD" => "R01235",
+        _ => return None,
+    })
+}
+
 fn parse_rates(start_date: Date, end_date: Date, data: &str) -> GenericResult<Vec<CurrencyRate>> {
     #[derive(Deserialize)]
     struct Rate {
@@ -124,6 +172,14 @@ mod tests {
         assert_eq!(get_rates("USD", date!(2, 9, 2018), date!(3, 9, 2018)).unwrap(), vec![]);
     }
 
+    #[test]
+    fn unsupported_currency() {
+        assert_matches!(
+            get_rates("XXX", date!(1, 9, 2018), date!(4, 9, 2018)),
+            Err(ref e) if e.to_string() == "XXX currency is not supported yet."
+        );
+    }
+
     #[test]
     fn rates() {
         let _mock = mock_cbr_response(
@@ -155,6 +211,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn hkd_nominal_rates() {
+        let _mock = mock_cbr_response(
+            "/scripts/XML_dynamic.asp?date_req1=01%2F09%2F2018&date_req2=04%2F09%2F2018&VAL_NM_RQ=R01200",
+            indoc!(r#"
+                <?xml version="1.0" encoding="windows-1251"?>
+                <ValCurs ID="R01200" DateRange1="01.09.2018" DateRange2="04.09.2018" name="Foreign Currency Market Dynamic">
+                    <Record Date="01.09.2018" Id="R01200">
+                        <Nominal>10</Nominal>
+                        <Value>86,6845</Value>
+                    </Record>
+                </ValCurs>
+            "#)
+        );
+
+        assert_eq!(
+            get_rates("HKD", date!(1, 9, 2018), date!(4, 9, 2018)).unwrap(),
+            vec![CurrencyRate {
+                date: date!(1, 9, 2018),
+                price: dec!(8.66845),
+            }],
+        );
+    }
+
     fn mock_cbr_response(path: &str, data: &str) -> Mock {
         mock("GET", path)
             .with_status(200)
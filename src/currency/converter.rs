@@ -4,11 +4,10 @@ use chrono::Duration;
 #[cfg(test)] use matches::assert_matches;
 
 use crate::core::GenericResult;
-use crate::currency::{self, Cash, CurrencyRate};
+use crate::currency::{self, Cash, CurrencyRateProvider};
 use crate::currency::rate_cache::{CurrencyRateCache, CurrencyRateCacheResult};
-use crate::db;
+use crate::db::{self, schema::CurrencyRateSource};
 use crate::formatting;
-use crate::localities;
 use crate::quotes::{Quotes, get_currency_pair};
 use crate::types::{Date, Decimal};
 use crate::util;
@@ -30,14 +29,22 @@ use crate::util;
 // and portfolio analysis / sell simulations where all calculations are processed in T+2 mode and
 // forex quotes at conclusion date will be the closest approximation to the future CBR currency rate
 // for trade execution date.
+//
+// Other official currency rate sources (ECB, Federal Reserve) may be used instead of CBR: the
+// source is chosen by the country the calculations are made for or by portfolio configuration. They
+// publish rates for the current day instead of the next one and have their own holiday calendars, so
+// each source determines how far back the converter may look for the last published rate.
 pub struct CurrencyConverter {
     backend: Box<dyn CurrencyConverterBackend>,
 }
 
 impl CurrencyConverter {
-    pub fn new(database: db::Connection, quotes: Option<Rc<Quotes>>, strict_mode: bool) -> CurrencyConverter {
+    pub fn new(
+        database: db::Connection, source: CurrencyRateSource, quotes: Option<Rc<Quotes>>, strict_mode: bool,
+    ) -> CurrencyConverter {
         let rate_cache = CurrencyRateCache::new(database);
-        let backend = CurrencyRateCacheBackend::new(rate_cache, quotes, strict_mode);
+        let provider = currency::get_rate_provider(source);
+        let backend = CurrencyRateCacheBackend::new(rate_cache, provider, quotes, strict_mode);
         CurrencyConverter::new_with_backend(backend)
     }
 
@@ -96,21 +103,27 @@ pub trait CurrencyConverterBackend {
 
 struct CurrencyRateCacheBackend {
     quotes: Option<Rc<Quotes>>,
+    provider: Box<dyn CurrencyRateProvider>,
     rate_cache: CurrencyRateCache,
     strict_mode: bool,
 }
 
 impl CurrencyRateCacheBackend {
-    pub fn new(rate_cache: CurrencyRateCache, quotes: Option<Rc<Quotes>>, strict_mode: bool) -> Box<dyn CurrencyConverterBackend> {
+    pub fn new(
+        rate_cache: CurrencyRateCache, provider: Box<dyn CurrencyRateProvider>, quotes: Option<Rc<Quotes>>,
+        strict_mode: bool,
+    ) -> Box<dyn CurrencyConverterBackend> {
         Box::new(CurrencyRateCacheBackend {
             quotes,
+            provider,
             rate_cache,
             strict_mode,
         })
     }
 
     fn get_price(&self, currency: &str, date: Date, from_cache_only: bool) -> GenericResult<Option<Decimal>> {
-        let cache_result = self.rate_cache.get(currency, date).map_err(|e| format!(
+        let source = self.provider.source();
+        let cache_result = self.rate_cache.get(source, currency, date).map_err(|e| format!(
             "Failed to get currency rate from the currency rate cache: {}", e))?;
 
         Ok(match cache_result {
@@ -123,13 +136,30 @@ impl CurrencyRateCacheBackend {
                         currency, formatting::format_date(date));
                 }
 
-                let currency_rates = get_currency_rates(currency, start_date, end_date)?;
-                self.rate_cache.save(currency, start_date, end_date, currency_rates)?;
+                let currency_rates = self.provider.get_rates(currency, start_date, end_date).map_err(|e| format!(
+                    "Failed to get currency rates from {}: {}", self.provider.name(), e))?;
+                self.rate_cache.save(source, currency, start_date, end_date, currency_rates)?;
 
                 self.get_price(currency, date, true)?
             },
         })
     }
+
+    fn get_rate(&self, currency: &str, date: Date) -> GenericResult<Decimal> {
+        let mut cur_date = date;
+        let min_date = self.provider.get_min_rate_date(cur_date, self.rate_cache.today());
+
+        while cur_date >= min_date {
+            if let Some(price) = self.get_price(currency, cur_date, false)? {
+                return Ok(price);
+            }
+
+            cur_date = cur_date.pred();
+        }
+
+        Err!("Unable to find {} currency rate for {} with {} days precision",
+             currency, formatting::format_date(date), (date - min_date).num_days())
+    }
 }
 
 impl CurrencyConverterBackend for CurrencyRateCacheBackend {
@@ -160,59 +190,69 @@ impl CurrencyConverterBackend for CurrencyRateCacheBackend {
             }
         }
 
-        let (currency, inverse) = match (from, to) {
-            ("USD", "RUB") => ("USD", false),
-            ("RUB", "USD") => ("USD", true),
-            _ => return Err!("Unsupported currency conversion: {} -> {}", from, to),
-        };
+        // Official currency rates are provided only relative to the source's base currency, so cross
+        // rates are calculated through it
+        let base_currency = self.provider.base_currency();
+        let mut result = amount;
 
-        let mut cur_date = date;
-        let min_date = localities::get_russian_stock_exchange_min_last_working_day(cur_date);
-
-        while cur_date >= min_date {
-            if let Some(price) = self.get_price(currency, cur_date, false)? {
-                return Ok(if inverse {
-                    amount / price
-                } else {
-                    price * amount
-                });
-            }
+        if from != base_currency {
+            result *= self.get_rate(from, date)?;
+        }
 
-            cur_date = cur_date.pred();
+        if to != base_currency {
+            result /= self.get_rate(to, date)?;
         }
 
-        Err!("Unable to find {} currency rate for {} with {} days precision",
-             currency, formatting::format_date(date), (date - min_date).num_days())
+        Ok(result)
     }
 }
 
 
-#[cfg(not(test))]
-fn get_currency_rates(currency: &str, start_date: Date, end_date: Date) -> GenericResult<Vec<CurrencyRate>> {
-    Ok(crate::currency::cbr::get_rates(currency, start_date, end_date).map_err(|e| format!(
-        "Failed to get currency rates from the Central Bank of the Russian Federation: {}", e))?)
-}
-
-#[cfg(test)]
-fn get_currency_rates(currency: &str, _start_date: Date, _end_date: Date) -> GenericResult<Vec<CurrencyRate>> {
-    assert_eq!(currency, "USD");
-
-    Ok(vec![
-        CurrencyRate {
-            date: date!(1, 9, 2018),
-            price: dec!(68.0447),
-        },
-        CurrencyRate {
-            date: date!(4, 9, 2018),
-            price: dec!(67.7443),
-        },
-    ])
-}
-
 #[cfg(test)]
 mod tests {
+    use crate::currency::CurrencyRate;
+
     use super::*;
 
+    struct TestProvider;
+
+    impl CurrencyRateProvider for TestProvider {
+        fn source(&self) -> CurrencyRateSource {
+            CurrencyRateSource::Cbr
+        }
+
+        fn name(&self) -> &'static str {
+            "test provider"
+        }
+
+        fn base_currency(&self) -> &'static str {
+            "RUB"
+        }
+
+        fn get_min_rate_date(&self, date: Date, _today: Date) -> Date {
+            date - Duration::days(3)
+        }
+
+        fn get_rates(&self, currency: &str, _start_date: Date, _end_date: Date) -> GenericResult<Vec<CurrencyRate>> {
+            let (first_price, second_price) = match currency {
+                "USD" => (dec!(68.0447), dec!(67.7443)),
+                "EUR" => (dec!(79.0137), dec!(78.7155)),
+                _ => unreachable!(),
+            };
+
+            Ok(vec![
+                CurrencyRate {
+                    date: date!(1, 9, 2018),
+                    price: first_price,
+                },
+                CurrencyRate {
+                    date: date!(4, 9, 2018),
+                    price: second_price,
+                },
+            ])
+        }
+    }
+
     #[test]
     fn convert() {
         let (_database, cache) = CurrencyRateCache::new_temporary();
@@ -220,7 +260,7 @@ mod tests {
         let amount = dec!(3);
         let today = cache.today();
         let converter = CurrencyConverter::new_with_backend(
-            CurrencyRateCacheBackend::new(cache, None, true));
+            CurrencyRateCacheBackend::new(cache, Box::new(TestProvider), None, true));
 
         for currency in ["RUB", "USD"].iter() {
             assert_eq!(converter.convert(currency, currency, today, amount).unwrap(), amount);
@@ -260,4 +300,29 @@ mod tests {
             );
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn convert_cross_rate() {
+        let (_database, cache) = CurrencyRateCache::new_temporary();
+        let converter = CurrencyConverter::new_with_backend(
+            CurrencyRateCacheBackend::new(cache, Box::new(TestProvider), None, true));
+
+        for (date, usd_price, eur_price) in [
+            (date!(3, 9, 2018), dec!(68.0447), dec!(79.0137)),
+            (date!(4, 9, 2018), dec!(67.7443), dec!(78.7155)),
+        ].iter() {
+            assert_eq!(
+                converter.convert("EUR", "RUB", *date, dec!(2)).unwrap(),
+                dec!(2) * eur_price,
+            );
+            assert_eq!(
+                converter.convert("EUR", "USD", *date, dec!(2)).unwrap(),
+                dec!(2) * eur_price / usd_price,
+            );
+            assert_eq!(
+                converter.convert("USD", "EUR", *date, dec!(2)).unwrap(),
+                dec!(2) * usd_price / eur_price,
+            );
+        }
+    }
+}
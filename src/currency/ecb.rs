@@ -0,0 +1,246 @@
+use std::cmp;
+
+use chrono::Duration;
+#[cfg(test)] use indoc::indoc;
+use log::debug;
+#[cfg(test)] use mockito::{self, Mock, mock};
+use reqwest::{StatusCode, Url};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+
+use crate::core::GenericResult;
+use crate::currency::{CurrencyRate, CurrencyRateProvider};
+use crate::db::schema::CurrencyRateSource;
+use crate::formatting;
+use crate::types::{Date, Decimal};
+use crate::util::{self, DecimalRestrictions};
+
+/// Euro foreign exchange reference rates of the European Central Bank
+pub struct Ecb;
+
+impl CurrencyRateProvider for Ecb {
+    fn source(&self) -> CurrencyRateSource {
+        CurrencyRateSource::Ecb
+    }
+
+    fn name(&self) -> &'static str {
+        "the European Central Bank"
+    }
+
+    fn base_currency(&self) -> &'static str {
+        "EUR"
+    }
+
+    // The longest period without rates is Easter holidays (Good Friday and Easter Monday of TARGET
+    // calendar) when the last rate is published on Thursday. Today's rates are published around
+    // 16:00 CET, so the rate may be missing for one more day.
+    fn get_min_rate_date(&self, date: Date, today: Date) -> Date {
+        cmp::min(date - Duration::days(4), today - Duration::days(5))
+    }
+
+    fn get_rates(&self, currency: &str, start_date: Date, end_date: Date) -> GenericResult<Vec<CurrencyRate>> {
+        get_rates(currency, start_date, end_date)
+    }
+}
+
+pub fn get_rates(currency: &str, start_date: Date, end_date: Date) -> GenericResult<Vec<CurrencyRate>> {
+    if !is_supported_currency(currency) {
+        return Err!("{} currency is not supported yet.", currency);
+    }
+
+    let date_format = "%Y-%m-%d";
+    let start_date_string = start_date.format(date_format).to_string();
+    let end_date_string = end_date.format(date_format).to_string();
+
+    #[cfg(not(test))]
+    let base_url = "https://sdw-wsrest.ecb.europa.eu";
+
+    #[cfg(test)]
+    let base_url = mockito::server_url();
+
+    // Daily spot rates of the currency against euro
+    let path = format!("/service/data/EXR/D.{}.EUR.SP00.A", currency);
+
+    let url = Url::parse_with_params(&(base_url.to_owned() + &path), &[
+        ("startPeriod", start_date_string.as_ref()),
+        ("endPeriod", end_date_string.as_ref()),
+        ("format", "csvdata"),
+    ])?;
+
+    let get = |url| -> GenericResult<Vec<CurrencyRate>> {
+        debug!("Getting {} currency rates for {} - {}...", currency,
+               formatting::format_date(start_date), formatting::format_date(end_date));
+
+        let response = Client::new().get(url).send()?;
+
+        // The server returns 404 when there are no rates for the specified period
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(Vec::new());
+        } else if !response.status().is_success() {
+            return Err!("The server returned an error: {}", response.status());
+        }
+
+        Ok(parse_rates(start_date, end_date, &response.text()?).map_err(|e| format!(
+            "Rates info parsing error: {}", e))?)
+    };
+
+    Ok(get(url.as_str()).map_err(|e| format!(
+        "Failed to get currency rates from {}: {}", url, e))?)
+}
+
+fn is_supported_currency(currency: &str) -> bool {
+    match currency {
+        "AUD" | "CAD" | "CHF" | "CNY" | "GBP" | "HKD" | "JPY" | "RUB" | "SEK" | "USD" => true,
+        _ => false,
+    }
+}
+
+fn parse_rates(start_date: Date, end_date: Date, data: &str) -> GenericResult<Vec<CurrencyRate>> {
+    #[derive(Deserialize)]
+    struct Record {
+        #[serde(rename = "TIME_PERIOD")]
+        date: String,
+
+        // Currency units per one euro
+        #[serde(rename = "OBS_VALUE")]
+        price: Decimal,
+    }
+
+    let mut rates = Vec::new();
+
+    for record in csv::Reader::from_reader(data.as_bytes()).deserialize() {
+        let record: Record = record?;
+
+        let date = util::parse_date(&record.date, "%Y-%m-%d")?;
+        if date < start_date || date > end_date {
+            return Err!("The server returned currency rates info for an invalid period");
+        }
+
+        let price = util::validate_named_decimal(
+            "currency rate", record.price, DecimalRestrictions::StrictlyPositive)?;
+
+        rates.push(CurrencyRate {
+            date: date,
+            price: dec!(1) / price,
+        });
+    }
+
+    Ok(rates)
+}
+
+#[cfg(test)]
+mod tests {
+    use matches::assert_matches;
+    use mockito::Matcher;
+
+    use crate::currency::converter::CurrencyConverter;
+    use crate::db;
+
+    use super::*;
+
+    #[test]
+    fn empty_rates() {
+        let path = "/service/data/EXR/D.USD.EUR.SP00.A?startPeriod=2020-01-04&endPeriod=2020-01-05&format=csvdata";
+        let _mock = mock("GET", path)
+            .with_status(404)
+            .with_body("No results found.")
+            .create();
+
+        assert_eq!(get_rates("USD", date!(4, 1, 2020), date!(5, 1, 2020)).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn rates() {
+        let _mock = mock_ecb_response(
+            "/service/data/EXR/D.USD.EUR.SP00.A?startPeriod=2020-01-02&endPeriod=2020-01-06&format=csvdata",
+            indoc!("
+                KEY,FREQ,CURRENCY,CURRENCY_DENOM,EXR_TYPE,EXR_SUFFIX,TIME_PERIOD,OBS_VALUE,OBS_STATUS
+                EXR.D.USD.EUR.SP00.A,D,USD,EUR,SP00,A,2020-01-02,1.1193,A
+                EXR.D.USD.EUR.SP00.A,D,USD,EUR,SP00,A,2020-01-03,1.1147,A
+                EXR.D.USD.EUR.SP00.A,D,USD,EUR,SP00,A,2020-01-06,1.1194,A
+            ")
+        );
+
+        assert_eq!(
+            get_rates("USD", date!(2, 1, 2020), date!(6, 1, 2020)).unwrap(),
+            vec![CurrencyRate {
+                date: date!(2, 1, 2020),
+                price: dec!(1) / dec!(1.1193),
+            }, CurrencyRate {
+                date: date!(3, 1, 2020),
+                price: dec!(1) / dec!(1.1147),
+            }, CurrencyRate {
+                date: date!(6, 1, 2020),
+                price: dec!(1) / dec!(1.1194),
+            }],
+        );
+    }
+
+    #[test]
+    fn conversion() {
+        let (_database, connection) = db::new_temporary();
+        let converter = CurrencyConverter::new(connection, CurrencyRateSource::Ecb, None, true);
+
+        let usd_mock = mock("GET", "/service/data/EXR/D.USD.EUR.SP00.A")
+            .match_query(Matcher::Any)
+            .with_status(200)
+            .with_header("Content-Type", "text/csv")
+            .with_body(indoc!("
+                KEY,FREQ,CURRENCY,CURRENCY_DENOM,EXR_TYPE,EXR_SUFFIX,TIME_PERIOD,OBS_VALUE,OBS_STATUS
+                EXR.D.USD.EUR.SP00.A,D,USD,EUR,SP00,A,2020-01-02,1.1193,A
+                EXR.D.USD.EUR.SP00.A,D,USD,EUR,SP00,A,2020-01-03,1.1147,A
+                EXR.D.USD.EUR.SP00.A,D,USD,EUR,SP00,A,2020-01-06,1.1194,A
+            "))
+            .expect(1)
+            .create();
+
+        let gbp_mock = mock("GET", "/service/data/EXR/D.GBP.EUR.SP00.A")
+            .match_query(Matcher::Any)
+            .with_status(200)
+            .with_header("Content-Type", "text/csv")
+            .with_body(indoc!("
+                KEY,FREQ,CURRENCY,CURRENCY_DENOM,EXR_TYPE,EXR_SUFFIX,TIME_PERIOD,OBS_VALUE,OBS_STATUS
+                EXR.D.GBP.EUR.SP00.A,D,GBP,EUR,SP00,A,2020-01-02,0.84828,A
+                EXR.D.GBP.EUR.SP00.A,D,GBP,EUR,SP00,A,2020-01-03,0.85115,A
+                EXR.D.GBP.EUR.SP00.A,D,GBP,EUR,SP00,A,2020-01-06,0.8505,A
+            "))
+            .expect(1)
+            .create();
+
+        // Friday's rates are used for the weekend
+        for &(date, usd_rate, gbp_rate) in &[
+            (date!(2, 1, 2020), dec!(1.1193), dec!(0.84828)),
+            (date!(3, 1, 2020), dec!(1.1147), dec!(0.85115)),
+            (date!(4, 1, 2020), dec!(1.1147), dec!(0.85115)),
+            (date!(5, 1, 2020), dec!(1.1147), dec!(0.85115)),
+            (date!(6, 1, 2020), dec!(1.1194), dec!(0.8505)),
+        ] {
+            let usd_price = dec!(1) / usd_rate;
+            let gbp_price = dec!(1) / gbp_rate;
+
+            assert_eq!(converter.convert("USD", "EUR", date, dec!(2)).unwrap(), dec!(2) * usd_price);
+            assert_eq!(converter.convert("EUR", "USD", date, dec!(2)).unwrap(), dec!(2) / usd_price);
+            assert_eq!(
+                converter.convert("USD", "GBP", date, dec!(2)).unwrap(),
+                dec!(2) * usd_price / gbp_price);
+        }
+
+        // There are no rates for 28.12.2019 - 01.01.2020 in the test data
+        assert_matches!(
+            converter.convert("USD", "EUR", date!(1, 1, 2020), dec!(2)),
+            Err(ref e) if e.to_string().starts_with("Unable to find USD currency rate")
+        );
+
+        // All rates are taken from the cache after the first request
+        usd_mock.assert();
+        gbp_mock.assert();
+    }
+
+    fn mock_ecb_response(path: &str, data: &str) -> Mock {
+        mock("GET", path)
+            .with_status(200)
+            .with_header("Content-Type", "text/csv")
+            .with_body(data)
+            .create()
+    }
+}
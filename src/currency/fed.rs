@@ -0,0 +1,203 @@
+use std::cmp;
+use std::str::FromStr;
+
+use chrono::Duration;
+#[cfg(test)] use indoc::indoc;
+use log::debug;
+#[cfg(test)] use mockito::{self, Mock, mock};
+use reqwest::Url;
+use reqwest::blocking::Client;
+
+use crate::core::GenericResult;
+use crate::currency::{CurrencyRate, CurrencyRateProvider};
+use crate::db::schema::CurrencyRateSource;
+use crate::formatting;
+use crate::types::{Date, Decimal};
+use crate::util::{self, DecimalRestrictions};
+
+/// Foreign exchange rates from the Federal Reserve H.10 statistical release. The rates are
+/// obtained from FRED (Federal Reserve Economic Data) which republishes H.10 series.
+pub struct Fed;
+
+impl CurrencyRateProvider for Fed {
+    fn source(&self) -> CurrencyRateSource {
+        CurrencyRateSource::Fed
+    }
+
+    fn name(&self) -> &'static str {
+        "the Federal Reserve"
+    }
+
+    fn base_currency(&self) -> &'static str {
+        "USD"
+    }
+
+    // The longest period without rates is a weekend with Friday or Monday holiday. H.10 release is
+    // published weekly on Mondays with rates of the previous week, so the last two weeks may be
+    // not covered yet.
+    fn get_min_rate_date(&self, date: Date, today: Date) -> Date {
+        cmp::min(date - Duration::days(3), today - Duration::days(14))
+    }
+
+    fn get_rates(&self, currency: &str, start_date: Date, end_date: Date) -> GenericResult<Vec<CurrencyRate>> {
+        get_rates(currency, start_date, end_date)
+    }
+}
+
+pub fn get_rates(currency: &str, start_date: Date, end_date: Date) -> GenericResult<Vec<CurrencyRate>> {
+    let (series, inverted) = get_series(currency).ok_or_else(|| format!(
+        "{} currency is not supported yet.", currency))?;
+
+    let date_format = "%Y-%m-%d";
+    let start_date_string = start_date.format(date_format).to_string();
+    let end_date_string = end_date.format(date_format).to_string();
+
+    #[cfg(not(test))]
+    let base_url = "https://fred.stlouisfed.org";
+
+    #[cfg(test)]
+    let base_url = mockito::server_url();
+
+    let url = Url::parse_with_params(&format!("{}/graph/fredgraph.csv", base_url), &[
+        ("id", series),
+        ("cosd", start_date_string.as_ref()),
+        ("coed", end_date_string.as_ref()),
+    ])?;
+
+    let get = |url| -> GenericResult<Vec<CurrencyRate>> {
+        debug!("Getting {} currency rates for {} - {}...", currency,
+               formatting::format_date(start_date), formatting::format_date(end_date));
+
+        let response = Client::new().get(url).send()?;
+        if !response.status().is_success() {
+            return Err!("The server returned an error: {}", response.status());
+        }
+
+        Ok(parse_rates(start_date, end_date, &response.text()?, inverted).map_err(|e| format!(
+            "Rates info parsing error: {}", e))?)
+    };
+
+    Ok(get(url.as_str()).map_err(|e| format!(
+        "Failed to get currency rates from {}: {}", url, e))?)
+}
+
+// H.10 quotes the most of currencies in currency units per one US dollar, but some of them - in US
+// dollars per one currency unit. Returns series ID and whether the rate must be inverted.
+fn get_series(currency: &str) -> Option<(&'static str, bool)> {
+    Some(match currency {
+        "AUD" => ("DEXUSAL", false),
+        "EUR" => ("DEXUSEU", false),
+        "GBP" => ("DEXUSUK", false),
+        "CAD" => ("DEXCAUS", true),
+        "CHF" => ("DEXSZUS", true),
+        "CNY" => ("DEXCHUS", true),
+        "HKD" => ("DEXHKUS", true),
+        "JPY" => ("DEXJPUS", true),
+        "SEK" => ("DEXSDUS", true),
+        _ => return None,
+    })
+}
+
+fn parse_rates(
+    start_date: Date, end_date: Date, data: &str, inverted: bool,
+) -> GenericResult<Vec<CurrencyRate>> {
+    let mut reader = csv::Reader::from_reader(data.as_bytes());
+    if reader.headers()?.len() != 2 {
+        return Err!("Got an unexpected CSV header");
+    }
+
+    let mut rates = Vec::new();
+
+    for record in reader.records() {
+        let record = record?;
+        let (date, price) = (&record[0], &record[1]);
+
+        let date = util::parse_date(date, "%Y-%m-%d")?;
+        if date < start_date || date > end_date {
+            return Err!("The server returned currency rates info for an invalid period");
+        }
+
+        // Holidays are marked with a dot
+        if price == "." {
+            continue;
+        }
+
+        let price = Decimal::from_str(price).ok()
+            .and_then(|price| util::validate_decimal(price, DecimalRestrictions::StrictlyPositive).ok())
+            .ok_or_else(|| format!("Invalid price: {:?}", price))?;
+
+        rates.push(CurrencyRate {
+            date: date,
+            price: if inverted {
+                dec!(1) / price
+            } else {
+                price
+            },
+        });
+    }
+
+    Ok(rates)
+}
+
+#[cfg(test)]
+mod tests {
+    use matches::assert_matches;
+
+    use super::*;
+
+    #[test]
+    fn unsupported_currency() {
+        assert_matches!(
+            get_rates("RUB", date!(1, 9, 2018), date!(4, 9, 2018)),
+            Err(ref e) if e.to_string() == "RUB currency is not supported yet."
+        );
+    }
+
+    #[test]
+    fn rates() {
+        let _mock = mock_fed_response(
+            "/graph/fredgraph.csv?id=DEXUSEU&cosd=2020-01-01&coed=2020-01-03",
+            indoc!("
+                DATE,DEXUSEU
+                2020-01-01,.
+                2020-01-02,1.1218
+                2020-01-03,1.1158
+            ")
+        );
+
+        assert_eq!(
+            get_rates("EUR", date!(1, 1, 2020), date!(3, 1, 2020)).unwrap(),
+            vec![CurrencyRate {
+                date: date!(2, 1, 2020),
+                price: dec!(1.1218),
+            }, CurrencyRate {
+                date: date!(3, 1, 2020),
+                price: dec!(1.1158),
+            }],
+        );
+
+        let _mock = mock_fed_response(
+            "/graph/fredgraph.csv?id=DEXJPUS&cosd=2020-01-02&coed=2020-01-02",
+            indoc!("
+                DATE,DEXJPUS
+                2020-01-02,108.43
+            ")
+        );
+
+        assert_eq!(
+            get_rates("JPY", date!(2, 1, 2020), date!(2, 1, 2020)).unwrap(),
+            vec![CurrencyRate {
+                date: date!(2, 1, 2020),
+                price: dec!(1) / dec!(108.43),
+            }],
+        );
+    }
+
+    fn mock_fed_response(path: &str, data: &str) -> Mock {
+        mock("GET", path)
+            .with_status(200)
+            .with_header("Content-Type", "text/csv")
+            .with_body(data)
+            .create()
+    }
+}
@@ -9,12 +9,15 @@ use num_traits::ToPrimitive;
 use separator::Separatable;
 
 use crate::core::{GenericResult, EmptyResult};
+use crate::db::schema::CurrencyRateSource;
 use crate::types::{Date, Decimal};
 use crate::util;
 
 use self::converter::CurrencyConverter;
 
 mod cbr;
+mod ecb;
+mod fed;
 mod name_cache;
 mod rate_cache;
 
@@ -170,6 +173,27 @@ pub struct CurrencyRate {
     price: Decimal,
 }
 
+/// Source of official currency rates. All rates are specified in its base currency.
+pub trait CurrencyRateProvider {
+    fn source(&self) -> CurrencyRateSource;
+    fn name(&self) -> &'static str;
+    fn base_currency(&self) -> &'static str;
+
+    /// Returns the earliest date which rate may be used for the specified date if there is no rate
+    /// for it: rates aren't published on the source's holidays and may be published with a delay.
+    fn get_min_rate_date(&self, date: Date, today: Date) -> Date;
+
+    fn get_rates(&self, currency: &str, start_date: Date, end_date: Date) -> GenericResult<Vec<CurrencyRate>>;
+}
+
+pub fn get_rate_provider(source: CurrencyRateSource) -> Box<dyn CurrencyRateProvider> {
+    match source {
+        CurrencyRateSource::Cbr => Box::new(cbr::Cbr),
+        CurrencyRateSource::Ecb => Box::new(ecb::Ecb),
+        CurrencyRateSource::Fed => Box::new(fed::Fed),
+    }
+}
+
 #[derive(Debug, Clone)]
 #[cfg_attr(test, derive(PartialEq, Eq))]
 pub struct MultiCurrencyCashAccount {
@@ -239,6 +263,28 @@ pub fn round_to(amount: Decimal, points: u32) -> Decimal {
     util::round(amount, points)
 }
 
+pub fn is_supported_currency(currency: &str) -> bool {
+    currency == "RUB" || cbr::is_supported_currency(currency)
+}
+
+/// Returns ISO 4217 numeric currency code
+pub fn get_numeric_code(currency: &str) -> Option<u32> {
+    Some(match currency {
+        "AUD" => 36,
+        "CAD" => 124,
+        "CHF" => 756,
+        "CNY" => 156,
+        "EUR" => 978,
+        "GBP" => 826,
+        "HKD" => 344,
+        "JPY" => 392,
+        "RUB" => 643,
+        "SEK" => 752,
+        "USD" => 840,
+        _ => return None,
+    })
+}
+
 fn format_currency(currency: &str, mut amount: &str) -> String {
     let mut buffer = String::new();
 
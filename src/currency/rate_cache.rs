@@ -8,7 +8,7 @@ use diesel::{self, prelude::*};
 
 use crate::core::{GenericResult, GenericError, EmptyResult};
 use crate::currency::CurrencyRate;
-use crate::db::{self, schema::currency_rates, models};
+use crate::db::{self, schema::{CurrencyRateSource, currency_rates}, models};
 use crate::formatting;
 use crate::types::{Date, Decimal};
 use crate::util::{self, DecimalRestrictions};
@@ -26,12 +26,17 @@ use crate::util::{self, DecimalRestrictions};
 // no data from sunday for monday, but will be data from monday for tuesday), but don't save
 // tomorrow's currency rates - just in case: we don't actually need them, but by not saving them we
 // can handle a possible corrections, for example.
+//
+// Other sources publish rates for the current day (or even with a delay of several days), so their
+// rates for today and the latest days may be not available yet. Missing dates after the last
+// returned rate are never saved to the database, so such rates will be requested again later. The
+// rates are cached separately for each source.
 pub struct CurrencyRateCache {
     today: Date,
     tomorrow: Date,
 
     db: db::Connection,
-    in_memory_missing: Mutex<HashMap<String, HashSet<Date>>>,
+    in_memory_missing: Mutex<HashMap<(CurrencyRateSource, String), HashSet<Date>>>,
 }
 
 impl CurrencyRateCache {
@@ -56,12 +61,14 @@ impl CurrencyRateCache {
         self.today
     }
 
-    pub fn get(&self, currency: &str, date: Date) -> GenericResult<CurrencyRateCacheResult> {
+    pub fn get(
+        &self, source: CurrencyRateSource, currency: &str, date: Date,
+    ) -> GenericResult<CurrencyRateCacheResult> {
         if date > self.today {
             return Err!("An attempt to get currency rate for the future")
         }
 
-        if let Some(missing) = self.in_memory_missing.lock().unwrap().get(currency) {
+        if let Some(missing) = self.in_memory_missing.lock().unwrap().get(&(source, currency.to_owned())) {
             if missing.get(&date).is_some() {
                 return Ok(CurrencyRateCacheResult::Exists(None));
             }
@@ -70,6 +77,7 @@ impl CurrencyRateCache {
         self.db.transaction::<_, GenericError, _>(|| {
             let result = currency_rates::table
                 .select(currency_rates::price)
+                .filter(currency_rates::source.eq(source))
                 .filter(currency_rates::currency.eq(currency))
                 .filter(currency_rates::date.eq(date))
                 .get_result::<Option<String>>(&*self.db).optional()?;
@@ -86,6 +94,7 @@ impl CurrencyRateCache {
             let start_date = {
                 let result = currency_rates::table
                     .select(currency_rates::date)
+                    .filter(currency_rates::source.eq(source))
                     .filter(currency_rates::currency.eq(currency))
                     .filter(currency_rates::date.lt(date))
                     .order(currency_rates::date.desc())
@@ -101,6 +110,7 @@ impl CurrencyRateCache {
             let end_date = {
                 let result = currency_rates::table
                     .select(currency_rates::date)
+                    .filter(currency_rates::source.eq(source))
                     .filter(currency_rates::currency.eq(currency))
                     .filter(currency_rates::date.gt(date))
                     .filter(currency_rates::price.is_not_null())
@@ -119,7 +129,10 @@ impl CurrencyRateCache {
         })
     }
 
-    pub fn save(&self, currency: &str, start_date: Date, end_date: Date, mut rates: Vec<CurrencyRate>) -> EmptyResult {
+    pub fn save(
+        &self, source: CurrencyRateSource, currency: &str, start_date: Date, end_date: Date,
+        mut rates: Vec<CurrencyRate>,
+    ) -> EmptyResult {
         if start_date > end_date {
             return Err!("Invalid date range: {} - {}",
                 formatting::format_date(start_date), formatting::format_date(end_date));
@@ -146,6 +159,7 @@ impl CurrencyRateCache {
 
                 while date < rate.date {
                     rows.push(models::NewCurrencyRate {
+                        source: source,
                         currency: currency,
                         date: date,
                         price: None,
@@ -161,6 +175,7 @@ impl CurrencyRateCache {
             assert!(rate.date <= self.today);
 
             rows.push(models::NewCurrencyRate {
+                source: source,
                 currency: currency,
                 date: rate.date,
                 price: Some(rate.price.to_string()),
@@ -176,7 +191,7 @@ impl CurrencyRateCache {
 
             while date <= std::cmp::min(end_date, self.today) {
                 self.in_memory_missing.lock().unwrap()
-                    .entry(currency.to_owned())
+                    .entry((source, currency.to_owned()))
                     .or_insert_with(HashSet::new)
                     .insert(date);
                 date = date.succ();
@@ -203,7 +218,7 @@ mod tests {
 
     #[test]
     fn rate_cache() {
-        let currency = "USD";
+        let (source, currency) = (CurrencyRateSource::Cbr, "USD");
         let (_database, mut cache) = CurrencyRateCache::new_temporary();
 
         let today = date!(8, 2, 2018);
@@ -225,15 +240,15 @@ mod tests {
         let cache_end_date = today;
 
         assert_matches!(
-            cache.get(currency, tomorrow),
+            cache.get(source, currency, tomorrow),
             Err(ref e) if e.to_string() == "An attempt to get currency rate for the future"
         );
 
         assert_matches!(
-            cache.get(currency, last_date).unwrap(),
+            cache.get(source, currency, last_date).unwrap(),
             CurrencyRateCacheResult::Missing(from, to) if from == cache_start_date && to == tomorrow
         );
-        cache.save(currency, cache_start_date, tomorrow, currency_rates.clone()).unwrap();
+        cache.save(source, currency, cache_start_date, tomorrow, currency_rates.clone()).unwrap();
 
         for &clear_in_memory_cache in &[false, true] {
             let mut date = cache_start_date.pred();
@@ -242,7 +257,7 @@ mod tests {
             }
 
             assert_matches!(
-                cache.get(currency, date).unwrap(),
+                cache.get(source, currency, date).unwrap(),
                 CurrencyRateCacheResult::Missing(from, to)
                     if from == date - Duration::days(365) && to == first_date
             );
@@ -256,14 +271,14 @@ mod tests {
                 for currency_rate in &currency_rates {
                     if date == currency_rate.date {
                         assert_matches!(
-                            cache.get(currency, date).unwrap(),
+                            cache.get(source, currency, date).unwrap(),
                             CurrencyRateCacheResult::Exists(Some(ref price)) if *price == currency_rate.price
                         );
                         continue 'date_loop;
                     }
                 }
 
-                let result = cache.get(currency, date).unwrap();
+                let result = cache.get(source, currency, date).unwrap();
 
                 if clear_in_memory_cache && last_date < date {
                     assert_matches!(result, CurrencyRateCacheResult::Missing(from, to)
@@ -274,16 +289,21 @@ mod tests {
             }
 
             assert_matches!(
-                cache.get(currency, date),
+                cache.get(source, currency, date),
                 Err(ref e) if e.to_string() == "An attempt to get currency rate for the future"
             );
         }
 
+        assert_matches!(
+            cache.get(CurrencyRateSource::Ecb, currency, last_date).unwrap(),
+            CurrencyRateCacheResult::Missing(..)
+        );
+
         cache.today += Duration::days(10);
         cache.tomorrow += Duration::days(10);
 
         assert_matches!(
-            cache.get(currency, tomorrow).unwrap(),
+            cache.get(source, currency, tomorrow).unwrap(),
             CurrencyRateCacheResult::Missing(from, to)
                 if from == last_date.succ() && to == cache.tomorrow
         );
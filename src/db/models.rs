@@ -1,6 +1,18 @@
-use crate::db::schema::{AssetType, assets, currency_rates, quotes};
+use crate::db::schema::{
+    AccountNotificationType, AssetType, CurrencyRateSource, JournalAction, account_notifications,
+    assets, currency_rates, historical_quotes, journal, quotes, trading_results};
 use crate::types::{Date, DateTime};
 
+#[derive(Insertable, Queryable)]
+#[table_name="account_notifications"]
+pub struct AccountNotification {
+    pub portfolio: String,
+    pub notification_type: AccountNotificationType,
+    pub account_number: String,
+    pub date: Date,
+    pub filing_date: Date,
+}
+
 #[derive(Insertable, Queryable)]
 #[table_name="assets"]
 pub struct Asset {
@@ -13,11 +25,51 @@ pub struct Asset {
 #[derive(Insertable)]
 #[table_name="currency_rates"]
 pub struct NewCurrencyRate<'a> {
+    pub source: CurrencyRateSource,
     pub currency: &'a str,
     pub date: Date,
     pub price: Option<String>,
 }
 
+#[derive(Insertable)]
+#[table_name="historical_quotes"]
+pub struct NewHistoricalQuote<'a> {
+    pub symbol: &'a str,
+    pub date: Date,
+    pub currency: Option<&'a str>,
+    pub price: Option<String>,
+}
+
+#[derive(Insertable)]
+#[table_name="journal"]
+pub struct NewJournalEntry<'a> {
+    pub portfolio: &'a str,
+    pub time: DateTime,
+    pub action: JournalAction,
+    pub symbol: Option<&'a str>,
+    pub shares: Option<String>,
+    pub price: Option<String>,
+    pub cash_assets: Option<String>,
+    pub note: Option<&'a str>,
+    pub undone_id: Option<i32>,
+    pub previous_assets: String,
+}
+
+#[derive(Queryable)]
+pub struct JournalEntry {
+    pub id: i32,
+    pub portfolio: String,
+    pub time: DateTime,
+    pub action: JournalAction,
+    pub symbol: Option<String>,
+    pub shares: Option<String>,
+    pub price: Option<String>,
+    pub cash_assets: Option<String>,
+    pub note: Option<String>,
+    pub undone_id: Option<i32>,
+    pub previous_assets: String,
+}
+
 #[derive(Insertable)]
 #[table_name="quotes"]
 pub struct NewQuote<'a> {
@@ -25,4 +77,15 @@ pub struct NewQuote<'a> {
     pub time: DateTime,
     pub currency: &'a str,
     pub price: String,
+    pub provider: Option<&'a str>,
+    pub quote_time: Option<DateTime>,
+}
+
+#[derive(Insertable, Queryable)]
+#[table_name="trading_results"]
+pub struct TradingResult {
+    pub portfolio: String,
+    pub year: i32,
+    pub profit: String,
+    pub applied_loss: String,
 }
\ No newline at end of file
@@ -1,11 +1,48 @@
 use diesel_derive_enum::DbEnum;
 
+#[derive(DbEnum, Clone, Copy, Debug, PartialEq)]
+pub enum AccountNotificationType {
+    Opening,
+    Change,
+    Closing,
+}
+
 #[derive(DbEnum, Debug)]
 pub enum AssetType {
     Stock,
     Cash,
 }
 
+/// Source of official currency rates
+#[derive(DbEnum, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CurrencyRateSource {
+    Cbr,
+    Ecb,
+    Fed,
+}
+
+#[derive(DbEnum, Clone, Copy, Debug, PartialEq)]
+pub enum JournalAction {
+    Sync,
+    Buy,
+    Sell,
+    Cash,
+    Undo,
+}
+
+table! {
+    use diesel::sql_types::{Date, Text};
+    use super::AccountNotificationTypeMapping;
+
+    account_notifications (portfolio, notification_type, account_number) {
+        portfolio -> Text,
+        notification_type -> AccountNotificationTypeMapping,
+        account_number -> Text,
+        date -> Date,
+        filing_date -> Date,
+    }
+}
+
 table! {
     use diesel::sql_types::Text;
     use super::AssetTypeMapping;
@@ -19,18 +56,61 @@ table! {
 }
 
 table! {
-    currency_rates (currency, date) {
+    use diesel::sql_types::{Date, Nullable, Text};
+    use super::CurrencyRateSourceMapping;
+
+    currency_rates (source, currency, date) {
+        source -> CurrencyRateSourceMapping,
         currency -> Text,
         date -> Date,
         price -> Nullable<Text>,
     }
 }
 
+table! {
+    historical_quotes (symbol, date) {
+        symbol -> Text,
+        date -> Date,
+        currency -> Nullable<Text>,
+        price -> Nullable<Text>,
+    }
+}
+
+table! {
+    use diesel::sql_types::{Integer, Nullable, Text, Timestamp};
+    use super::JournalActionMapping;
+
+    journal (id) {
+        id -> Integer,
+        portfolio -> Text,
+        time -> Timestamp,
+        action -> JournalActionMapping,
+        symbol -> Nullable<Text>,
+        shares -> Nullable<Text>,
+        price -> Nullable<Text>,
+        cash_assets -> Nullable<Text>,
+        note -> Nullable<Text>,
+        undone_id -> Nullable<Integer>,
+        previous_assets -> Text,
+    }
+}
+
 table! {
     quotes (symbol) {
         symbol -> Text,
         time -> Timestamp,
         currency -> Text,
         price -> Text,
+        provider -> Nullable<Text>,
+        quote_time -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    trading_results (portfolio, year) {
+        portfolio -> Text,
+        year -> Integer,
+        profit -> Text,
+        applied_loss -> Text,
     }
 }
\ No newline at end of file
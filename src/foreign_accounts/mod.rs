@@ -0,0 +1,327 @@
+mod xml;
+
+use diesel::{self, prelude::*};
+
+use static_table_derive::StaticTable;
+
+use crate::broker_statement::BrokerStatement;
+use crate::config::{Config, PortfolioConfig};
+use crate::core::{EmptyResult, GenericResult};
+use crate::db::{self, models, schema::{AccountNotificationType, account_notifications}};
+use crate::formatting::table::Style;
+use crate::localities::Jurisdiction;
+use crate::types::Date;
+use crate::util;
+
+/// Foreign broker account details which are reported to the tax inspection
+pub struct AccountInfo {
+    pub organization: &'static str,
+    pub address: &'static str,
+    pub jurisdiction: Jurisdiction,
+    pub number: String,
+    pub open_date: Date,
+}
+
+impl AccountInfo {
+    pub fn new(portfolio: &PortfolioConfig, statement: &BrokerStatement) -> GenericResult<AccountInfo> {
+        let broker = statement.broker.type_;
+        let (jurisdiction, address) = match (broker.get_jurisdiction(), broker.get_address()) {
+            (Some(jurisdiction), Some(address)) => (jurisdiction, address),
+            _ => return Err!(
+                "{} is not a foreign broker: its accounts aren't reported to the tax inspection",
+                statement.broker.name),
+        };
+
+        let number = portfolio.account_number.as_ref()
+            .or_else(|| statement.account_number.as_ref())
+            .ok_or_else(|| format!(
+                "Unable to determine {:?} portfolio account number: specify it in the configuration file",
+                portfolio.name))?;
+
+        // Broker statements may not cover the whole account history, so we can't determine the
+        // opening date from them
+        let open_date = portfolio.account_open_date.ok_or_else(|| format!(
+            "{:?} portfolio account opening date is not specified in the configuration file",
+            portfolio.name))?;
+
+        Ok(AccountInfo {
+            organization: statement.broker.name,
+            address: address,
+            jurisdiction: jurisdiction,
+            number: number.clone(),
+            open_date: open_date,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Notification {
+    pub type_: AccountNotificationType,
+    pub account_number: String,
+    pub previous_account_number: Option<String>,
+    pub date: Date,
+}
+
+/// Stores notifications about foreign accounts which have been already filed to the tax inspection.
+pub struct AccountNotifications {
+    db: db::Connection,
+    portfolio: String,
+}
+
+impl AccountNotifications {
+    pub fn new(connection: db::Connection, portfolio: &str) -> AccountNotifications {
+        AccountNotifications {
+            db: connection,
+            portfolio: portfolio.to_owned(),
+        }
+    }
+
+    pub fn load(&self) -> GenericResult<Vec<models::AccountNotification>> {
+        Ok(account_notifications::table
+            .filter(account_notifications::portfolio.eq(&self.portfolio))
+            .order_by(account_notifications::date.asc())
+            .load::<models::AccountNotification>(&*self.db)?)
+    }
+
+    pub fn save(&self, notification: &Notification, filing_date: Date) -> EmptyResult {
+        diesel::replace_into(account_notifications::table)
+            .values(models::AccountNotification {
+                portfolio: self.portfolio.clone(),
+                notification_type: notification.type_,
+                account_number: notification.account_number.clone(),
+                date: notification.date,
+                filing_date: filing_date,
+            })
+            .execute(&*self.db)?;
+        Ok(())
+    }
+}
+
+pub fn generate_notifications(
+    config: &Config, portfolio_name: &str, xml_dir: Option<&str>, mark_as_filed: bool,
+) -> EmptyResult {
+    let portfolio = config.get_portfolio(portfolio_name)?;
+    let broker = portfolio.broker.get_info(config, portfolio.plan.as_ref())?;
+
+    let statement = BrokerStatement::read(
+        broker, &portfolio.statements, &portfolio.symbol_remapping, &portfolio.instrument_names,
+        portfolio.get_tax_remapping()?, false)?;
+
+    let account = AccountInfo::new(portfolio, &statement)?;
+
+    let database = db::connect(&config.db_path)?;
+    let notifications = AccountNotifications::new(database, &portfolio.name);
+
+    // The account number may be overridden in the configuration file, so the broker statements
+    // can tell when it has been changed only if they contain the same number
+    let change_date = match statement.account_number {
+        Some(ref number) if *number == account.number => statement.account_number_date,
+        _ => None,
+    };
+
+    let filed = notifications.load()?;
+    let required = get_required_notifications(
+        &account, change_date, portfolio.account_close_date, &filed)?;
+
+    print(&account, &filed, &required);
+    let today = util::today();
+
+    if let Some(xml_dir) = xml_dir {
+        let taxpayer = config.taxpayer.as_ref().ok_or(
+            "Taxpayer profile must be configured to export the notifications in XML format")?;
+
+        for notification in &required {
+            let path = xml::write(xml_dir, taxpayer, &account, notification, today)?;
+            println!("{} notification has been saved to {}.", get_type_name(notification.type_), path);
+        }
+    }
+
+    if mark_as_filed {
+        for notification in &required {
+            notifications.save(notification, today)?;
+        }
+    }
+
+    Ok(())
+}
+
+// Account details change is dated by the start of the first broker statement with the new account
+// number, since it's the earliest date when the change is known to have happened
+fn get_required_notifications(
+    account: &AccountInfo, change_date: Option<Date>, close_date: Option<Date>,
+    filed: &[models::AccountNotification],
+) -> GenericResult<Vec<Notification>> {
+    let mut required = Vec::new();
+
+    let is_filed = |type_: AccountNotificationType| {
+        filed.iter().any(|notification| notification.notification_type == type_)
+    };
+
+    let last_number = filed.iter()
+        .filter(|notification| notification.notification_type != AccountNotificationType::Closing)
+        .max_by_key(|notification| notification.date)
+        .map(|notification| notification.account_number.as_str());
+
+    if !is_filed(AccountNotificationType::Opening) {
+        required.push(Notification {
+            type_: AccountNotificationType::Opening,
+            account_number: account.number.clone(),
+            previous_account_number: None,
+            date: account.open_date,
+        });
+    } else if last_number != Some(account.number.as_str()) {
+        let date = change_date.ok_or_else(|| format!(concat!(
+            "Unable to determine the date of account number change to {}: ",
+            "the broker statements don't contain the new account number"), account.number))?;
+
+        required.push(Notification {
+            type_: AccountNotificationType::Change,
+            account_number: account.number.clone(),
+            previous_account_number: last_number.map(ToOwned::to_owned),
+            date: date,
+        });
+    }
+
+    if let Some(close_date) = close_date {
+        if !is_filed(AccountNotificationType::Closing) {
+            required.push(Notification {
+                type_: AccountNotificationType::Closing,
+                account_number: account.number.clone(),
+                previous_account_number: None,
+                date: close_date,
+            });
+        }
+    }
+
+    Ok(required)
+}
+
+#[derive(StaticTable)]
+struct Row {
+    #[column(name="Уведомление")]
+    type_: &'static str,
+    #[column(name="Номер счета")]
+    account_number: String,
+    #[column(name="Дата")]
+    date: Date,
+    #[column(name="Дата подачи")]
+    filing_date: Option<Date>,
+}
+
+fn print(account: &AccountInfo, filed: &[models::AccountNotification], required: &[Notification]) {
+    let mut table = Table::new();
+
+    for notification in filed {
+        let mut row = table.add_row(Row {
+            type_: get_type_name(notification.notification_type),
+            account_number: notification.account_number.clone(),
+            date: notification.date,
+            filing_date: Some(notification.filing_date),
+        });
+
+        let style = Style::new().dimmed();
+        for cell in &mut row {
+            cell.style(style);
+        }
+    }
+
+    for notification in required {
+        table.add_row(Row {
+            type_: get_type_name(notification.type_),
+            account_number: notification.account_number.clone(),
+            date: notification.date,
+            filing_date: None,
+        });
+    }
+
+    table.print(&format!(
+        "Уведомления о счете в {} ({}, код страны {:03})",
+        account.organization, account.jurisdiction.code(), account.jurisdiction.oksm_code()));
+}
+
+fn get_type_name(type_: AccountNotificationType) -> &'static str {
+    match type_ {
+        AccountNotificationType::Opening => "Открытие счета",
+        AccountNotificationType::Change => "Изменение реквизитов",
+        AccountNotificationType::Closing => "Закрытие счета",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notifications() {
+        let (_database, connection) = db::new_temporary();
+        let notifications = AccountNotifications::new(connection.clone(), "portfolio");
+        let other_notifications = AccountNotifications::new(connection.clone(), "other");
+
+        let (open_date, change_date) = (date!(1, 2, 2019), date!(1, 10, 2019));
+        let close_date = date!(1, 6, 2020);
+        let mut account = AccountInfo {
+            organization: "Interactive Brokers LLC",
+            address: "One Pickwick Plaza, Greenwich, CT 06830, USA",
+            jurisdiction: Jurisdiction::Usa,
+            number: s!("U1234567"),
+            open_date: open_date,
+        };
+
+        let get_required = |account: &AccountInfo, close_date: Option<Date>| {
+            get_required_notifications(
+                account, Some(change_date), close_date, &notifications.load().unwrap()).unwrap()
+        };
+
+        let opening = Notification {
+            type_: AccountNotificationType::Opening,
+            account_number: s!("U1234567"),
+            previous_account_number: None,
+            date: open_date,
+        };
+        assert_eq!(get_required(&account, None), vec![opening]);
+
+        for notification in get_required(&account, None) {
+            notifications.save(&notification, date!(1, 3, 2019)).unwrap();
+        }
+        assert_eq!(get_required(&account, None), vec![]);
+
+        other_notifications.save(&Notification {
+            type_: AccountNotificationType::Closing,
+            account_number: s!("U7654321"),
+            previous_account_number: None,
+            date: open_date,
+        }, open_date).unwrap();
+
+        account.number = s!("U7654321");
+        let change = Notification {
+            type_: AccountNotificationType::Change,
+            account_number: s!("U7654321"),
+            previous_account_number: Some(s!("U1234567")),
+            date: change_date,
+        };
+        assert_eq!(get_required(&account, None), vec![change]);
+
+        // The change date is unknown when the broker statements don't contain the new number
+        assert!(get_required_notifications(
+            &account, None, None, &notifications.load().unwrap()).is_err());
+
+        for notification in get_required(&account, None) {
+            notifications.save(&notification, date!(1, 1, 2020)).unwrap();
+        }
+        assert_eq!(get_required(&account, None), vec![]);
+
+        let closing = Notification {
+            type_: AccountNotificationType::Closing,
+            account_number: s!("U7654321"),
+            previous_account_number: None,
+            date: close_date,
+        };
+        assert_eq!(get_required(&account, Some(close_date)), vec![closing]);
+
+        for notification in get_required(&account, Some(close_date)) {
+            notifications.save(&notification, close_date).unwrap();
+        }
+        assert_eq!(get_required(&account, Some(close_date)), vec![]);
+        assert_eq!(notifications.load().unwrap().len(), 3);
+    }
+}
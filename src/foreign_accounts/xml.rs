@@ -0,0 +1,232 @@
+use std::path::Path;
+
+use crate::config::TaxpayerConfig;
+use crate::core::GenericResult;
+use crate::db::schema::AccountNotificationType;
+use crate::formatting::{self, xml::{self, XmlWriter}};
+use crate::types::Date;
+
+use super::{AccountInfo, Notification};
+
+// Form code by КНД (Russian classification of tax documentation) of the notification on opening,
+// closing and changing of details of an account in a foreign bank (financial organization)
+const FORM_CODE: &str = "1120107";
+const FORM_VERSION: &str = "5.01";
+
+// Document type prefix of the file ID
+const FILE_TYPE: &str = "UT_UVOTKRSCHFL";
+
+/// Writes the notification in XML format which is accepted by the tax service to the specified
+/// directory. The file is named by its ID as the tax service requires. Returns path to the file.
+pub fn write(
+    dir: &str, taxpayer: &TaxpayerConfig, account: &AccountInfo, notification: &Notification,
+    date: Date,
+) -> GenericResult<String> {
+    let file_id = xml::get_file_id(FILE_TYPE, taxpayer, date);
+    let data = generate(&file_id, taxpayer, account, notification, date)?;
+
+    let path = Path::new(dir).join(format!("{}.xml", file_id));
+    let path = path.to_str().ok_or_else(|| format!("Invalid path: {:?}", path))?;
+
+    xml::save(&data, path)?;
+    Ok(path.to_owned())
+}
+
+fn generate(
+    file_id: &str, taxpayer: &TaxpayerConfig, account: &AccountInfo, notification: &Notification,
+    date: Date,
+) -> GenericResult<String> {
+    let mut writer = XmlWriter::new()?;
+
+    writer.start("Файл", &[
+        ("ИдФайл", file_id.to_owned()),
+        ("ВерсПрог", format!("investments {}", env!("CARGO_PKG_VERSION"))),
+        ("ВерсФорм", s!(FORM_VERSION)),
+    ])?;
+
+    writer.start("Документ", &[
+        ("КНД", s!(FORM_CODE)),
+        ("ДатаДок", formatting::format_date(date)),
+        ("КодНО", taxpayer.inspection_code.clone()),
+        ("ВидУвед", s!(get_notification_code(notification.type_))),
+    ])?;
+
+    writer.start("СвФЛ", &[("ИННФЛ", taxpayer.inn.clone())])?;
+    writer.empty("ФИО", &xml::get_taxpayer_name(taxpayer))?;
+    writer.end("СвФЛ")?;
+
+    writer.empty("СвОрг", &[
+        ("НаимОрг", s!(account.organization)),
+        ("КодСтр", format!("{:03}", account.jurisdiction.oksm_code())),
+        ("АдрОрг", s!(account.address)),
+    ])?;
+
+    let mut attributes = vec![
+        ("НомСчет", notification.account_number.clone()),
+        ("ДатаОткр", formatting::format_date(account.open_date)),
+    ];
+
+    match notification.type_ {
+        AccountNotificationType::Opening => {},
+        AccountNotificationType::Change => {
+            let previous_number = notification.previous_account_number.clone().ok_or(
+                "The previous account number is required for account details change notification")?;
+
+            attributes.push(("НомСчетПред", previous_number));
+            attributes.push(("ДатаИзм", formatting::format_date(notification.date)));
+        },
+        AccountNotificationType::Closing => {
+            attributes.push(("ДатаЗакр", formatting::format_date(notification.date)));
+        },
+    };
+
+    writer.empty("СвСчет", &attributes)?;
+
+    writer.end("Документ")?;
+    writer.end("Файл")?;
+
+    writer.finish()
+}
+
+fn get_notification_code(type_: AccountNotificationType) -> &'static str {
+    match type_ {
+        AccountNotificationType::Opening => "1",
+        AccountNotificationType::Closing => "2",
+        AccountNotificationType::Change => "3",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::formatting::xml::{
+        ElementSchema, DATE, INN, INSPECTION_CODE, NAME, OKSM_CODE, PROGRAM_VERSION};
+    use crate::localities::Jurisdiction;
+
+    use super::*;
+
+    // Elements and attributes of the notification format (КНД 1120107, version 5.01) which may be
+    // emitted by the writer with their value restrictions
+    const SCHEMA: &[ElementSchema] = &[
+        ElementSchema {
+            name: "Файл", parent: None, required: true, multiple: false,
+            attributes: &[
+                ("ИдФайл", true, concat!(
+                    r"UT_UVOTKRSCHFL_\d{4}_\d{4}_\d{12}_\d{8}_",
+                    r"[0-9A-F]{8}-[0-9A-F]{4}-[0-9A-F]{4}-[0-9A-F]{4}-[0-9A-F]{12}")),
+                ("ВерсПрог", true, PROGRAM_VERSION),
+                ("ВерсФорм", true, r"5\.01"),
+            ],
+        },
+        ElementSchema {
+            name: "Документ", parent: Some("Файл"), required: true, multiple: false,
+            attributes: &[
+                ("КНД", true, "1120107"),
+                ("ДатаДок", true, DATE),
+                ("КодНО", true, INSPECTION_CODE),
+                ("ВидУвед", true, "[123]"),
+            ],
+        },
+        ElementSchema {
+            name: "СвФЛ", parent: Some("Документ"), required: true, multiple: false,
+            attributes: &[
+                ("ИННФЛ", true, INN),
+            ],
+        },
+        ElementSchema {
+            name: "ФИО", parent: Some("СвФЛ"), required: true, multiple: false,
+            attributes: &[
+                ("Фамилия", true, NAME),
+                ("Имя", true, NAME),
+                ("Отчество", false, NAME),
+            ],
+        },
+        ElementSchema {
+            name: "СвОрг", parent: Some("Документ"), required: true, multiple: false,
+            attributes: &[
+                ("НаимОрг", true, r"[^\s].{0,999}"),
+                ("КодСтр", true, OKSM_CODE),
+                ("АдрОрг", true, r"[^\s].{0,999}"),
+            ],
+        },
+        ElementSchema {
+            name: "СвСчет", parent: Some("Документ"), required: true, multiple: false,
+            attributes: &[
+                ("НомСчет", true, r"[^\s].{0,99}"),
+                ("ДатаОткр", true, DATE),
+                ("НомСчетПред", false, r"[^\s].{0,99}"),
+                ("ДатаИзм", false, DATE),
+                ("ДатаЗакр", false, DATE),
+            ],
+        },
+    ];
+
+    #[test]
+    fn generation() {
+        let taxpayer = TaxpayerConfig {
+            last_name: s!("Иванов"),
+            first_name: s!("Иван"),
+            middle_name: None,
+            inn: s!("123456789012"),
+            inspection_code: s!("7701"),
+            oktmo: None,
+        };
+
+        let account = AccountInfo {
+            organization: "Interactive Brokers LLC",
+            address: "One Pickwick Plaza, Greenwich, CT 06830, USA",
+            jurisdiction: Jurisdiction::Usa,
+            number: s!("U7654321"),
+            open_date: date!(1, 2, 2019),
+        };
+
+        for (type_, previous_account_number, code, date_attribute) in [
+            (AccountNotificationType::Opening, None, "1", None),
+            (AccountNotificationType::Change, Some(s!("U1234567")), "3", Some("ДатаИзм")),
+            (AccountNotificationType::Closing, None, "2", Some("ДатаЗакр")),
+        ].iter().cloned() {
+            let notification = Notification {
+                type_: type_,
+                account_number: account.number.clone(),
+                previous_account_number: previous_account_number.clone(),
+                date: date!(1, 10, 2020),
+            };
+
+            let data = generate(
+                "UT_UVOTKRSCHFL_7701_7701_123456789012_20201015_6F9619FF-8B86-4D01-B42D-00CF4FC964FF",
+                &taxpayer, &account, &notification, date!(15, 10, 2020)).unwrap();
+
+            let elements = xml::parse(&data);
+            xml::validate(&elements, SCHEMA);
+
+            let get = |name: &str| xml::get_attributes(&elements, name, 0);
+
+            assert_eq!(get("Документ")["КНД"], "1120107");
+            assert_eq!(get("Документ")["ДатаДок"], "15.10.2020");
+            assert_eq!(get("Документ")["ВидУвед"], code);
+            assert_eq!(get("СвФЛ")["ИННФЛ"], "123456789012");
+            assert!(!get("ФИО").contains_key("Отчество"));
+            assert_eq!(get("СвОрг")["КодСтр"], "840");
+
+            let details = get("СвСчет");
+            assert_eq!(details["НомСчет"], "U7654321");
+            assert_eq!(details["ДатаОткр"], "01.02.2019");
+            assert_eq!(details.get("НомСчетПред"), previous_account_number.as_ref());
+
+            for &attribute in &["ДатаИзм", "ДатаЗакр"] {
+                if Some(attribute) == date_attribute {
+                    assert_eq!(details[attribute], "01.10.2020");
+                } else {
+                    assert!(!details.contains_key(attribute));
+                }
+            }
+        }
+
+        let change = Notification {
+            type_: AccountNotificationType::Change,
+            account_number: account.number.clone(),
+            previous_account_number: None,
+            date: date!(1, 10, 2020),
+        };
+        assert!(generate("id", &taxpayer, &account, &change, date!(15, 10, 2020)).is_err());
+    }
+}
@@ -1,6 +1,7 @@
 use crate::types::Date;
 
 pub mod table;
+pub mod xml;
 
 pub fn format_date(date: Date) -> String {
     date.format("%d.%m.%Y").to_string()
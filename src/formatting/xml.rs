@@ -0,0 +1,235 @@
+use std::fs::File;
+use std::io::Write;
+
+#[cfg(test)] use std::collections::HashMap;
+
+#[cfg(test)] use quick_xml::Reader;
+use quick_xml::Writer;
+use quick_xml::events::{Event, BytesDecl, BytesStart, BytesEnd};
+#[cfg(test)] use regex::Regex;
+
+use crate::config::TaxpayerConfig;
+use crate::core::{EmptyResult, GenericResult};
+use crate::types::Date;
+
+/// A simple writer of Windows-1251 encoded XML documents which are accepted by the tax service.
+pub struct XmlWriter {
+    writer: Writer<Vec<u8>>,
+}
+
+impl XmlWriter {
+    pub fn new() -> GenericResult<XmlWriter> {
+        let mut writer = XmlWriter {
+            writer: Writer::new_with_indent(Vec::new(), b' ', 2),
+        };
+
+        let declaration = BytesDecl::new(b"1.0", Some(b"windows-1251"), None);
+        writer.writer.write_event(Event::Decl(declaration))?;
+
+        Ok(writer)
+    }
+
+    pub fn start(&mut self, name: &str, attributes: &[(&str, String)]) -> EmptyResult {
+        self.writer.write_event(Event::Start(get_element(name, attributes)))?;
+        Ok(())
+    }
+
+    pub fn empty(&mut self, name: &str, attributes: &[(&str, String)]) -> EmptyResult {
+        self.writer.write_event(Event::Empty(get_element(name, attributes)))?;
+        Ok(())
+    }
+
+    pub fn end(&mut self, name: &str) -> EmptyResult {
+        self.writer.write_event(Event::End(BytesEnd::borrowed(name.as_bytes())))?;
+        Ok(())
+    }
+
+    pub fn finish(self) -> GenericResult<String> {
+        Ok(String::from_utf8(self.writer.into_inner())?)
+    }
+}
+
+/// Returns file ID (ИдФайл attribute) for a document of the specified type which is submitted by the
+/// taxpayer to its tax inspection: <type>_<recipient>_<sender>_<INN>_<date>_<GUID>.
+pub fn get_file_id(document_type: &str, taxpayer: &TaxpayerConfig, date: Date) -> String {
+    format!("{type}_{code}_{code}_{inn}_{date}_{guid}",
+            type=document_type, code=taxpayer.inspection_code, inn=taxpayer.inn,
+            date=date.format("%Y%m%d"), guid=generate_guid())
+}
+
+// Generates random (version 4) GUID
+fn generate_guid() -> String {
+    let mut guid: [u8; 16] = rand::random();
+    guid[6] = (guid[6] & 0x0f) | 0x40;
+    guid[8] = (guid[8] & 0x3f) | 0x80;
+
+    let hex: String = guid.iter().map(|byte| format!("{:02X}", byte)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+/// Returns taxpayer's full name attributes (ФИО element).
+pub fn get_taxpayer_name(taxpayer: &TaxpayerConfig) -> Vec<(&'static str, String)> {
+    let mut name = vec![
+        ("Фамилия", taxpayer.last_name.clone()),
+        ("Имя", taxpayer.first_name.clone()),
+    ];
+
+    if let Some(ref middle_name) = taxpayer.middle_name {
+        name.push(("Отчество", middle_name.clone()));
+    }
+
+    name
+}
+
+pub fn save(data: &str, path: &str) -> EmptyResult {
+    let (encoded_data, _, errors) = encoding_rs::WINDOWS_1251.encode(data);
+    if errors {
+        return Err!("Unable to encode the document with Windows-1251 character encoding");
+    }
+
+    let mut file = File::create(path)?;
+    file.write_all(&encoded_data)?;
+
+    Ok(())
+}
+
+fn get_element<'a>(name: &'a str, attributes: &'a [(&str, String)]) -> BytesStart<'a> {
+    let mut element = BytesStart::borrowed_name(name.as_bytes());
+
+    for (key, value) in attributes {
+        element.push_attribute((*key, value.as_str()));
+    }
+
+    element
+}
+
+/// Parsed element of the document
+#[cfg(test)]
+pub struct Element {
+    pub name: String,
+    pub parent: Option<String>,
+    pub attributes: HashMap<String, String>,
+}
+
+/// Parses the document into a flat list of elements with their attributes.
+#[cfg(test)]
+pub fn parse(data: &str) -> Vec<Element> {
+    let mut reader = Reader::from_str(data);
+    reader.trim_text(true);
+
+    let mut elements = Vec::new();
+    let mut path: Vec<String> = Vec::new();
+    let mut buffer = Vec::new();
+
+    loop {
+        let (element, has_children) = match reader.read_event(&mut buffer).unwrap() {
+            Event::Start(ref element) => (element.to_owned(), true),
+            Event::Empty(ref element) => (element.to_owned(), false),
+            Event::End(_) => {
+                path.pop().unwrap();
+                buffer.clear();
+                continue;
+            },
+            Event::Eof => break,
+            _ => {
+                buffer.clear();
+                continue;
+            },
+        };
+
+        let name = String::from_utf8(element.name().to_vec()).unwrap();
+        let mut attributes = HashMap::new();
+
+        for attribute in element.attributes() {
+            let attribute = attribute.unwrap();
+            let key = String::from_utf8(attribute.key.to_vec()).unwrap();
+            let value = String::from_utf8(attribute.unescaped_value().unwrap().to_vec()).unwrap();
+            assert!(attributes.insert(key, value).is_none(), "{} has duplicated attributes", name);
+        }
+
+        elements.push(Element {
+            name: name.clone(),
+            parent: path.last().cloned(),
+            attributes: attributes,
+        });
+
+        if has_children {
+            path.push(name);
+        }
+
+        buffer.clear();
+    }
+
+    assert!(path.is_empty());
+    elements
+}
+
+/// Returns attributes of the element with the specified name and index.
+#[cfg(test)]
+pub fn get_attributes<'a>(elements: &'a [Element], name: &str, index: usize) -> &'a HashMap<String, String> {
+    elements.iter()
+        .filter(|element| element.name == name)
+        .nth(index).map(|element| &element.attributes).unwrap()
+}
+
+/// Element description from the document format specification
+#[cfg(test)]
+pub struct ElementSchema {
+    pub name: &'static str,
+    pub parent: Option<&'static str>,
+    pub required: bool,
+    pub multiple: bool,
+    pub attributes: &'static [AttributeSchema],
+}
+
+/// Attribute description from the document format specification: name, whether the attribute is
+/// required and a regular expression for its value.
+#[cfg(test)]
+pub type AttributeSchema = (&'static str, bool, &'static str);
+
+// Value patterns of common types of the tax service document formats
+#[cfg(test)] pub const DATE: &str = r"(0[1-9]|[12]\d|3[01])\.(0[1-9]|1[0-2])\.\d{4}";
+#[cfg(test)] pub const YEAR: &str = r"\d{4}";
+#[cfg(test)] pub const INN: &str = r"\d{12}";
+#[cfg(test)] pub const INSPECTION_CODE: &str = r"\d{4}";
+#[cfg(test)] pub const OKSM_CODE: &str = r"\d{3}";
+#[cfg(test)] pub const OKV_CODE: &str = r"\d{3}";
+#[cfg(test)] pub const NAME: &str = r"[^\s].{0,59}";
+#[cfg(test)] pub const AMOUNT: &str = r"-?\d{1,15}(\.\d{1,2})?";
+#[cfg(test)] pub const RATE: &str = r"\d{1,6}(\.\d{1,6})?";
+#[cfg(test)] pub const PROGRAM_VERSION: &str = r"[^\s].{0,39}";
+
+/// Checks the document against the format specification: all elements must be known, placed
+/// inside their parents, have all required and no unknown attributes and valid attribute values.
+#[cfg(test)]
+pub fn validate(elements: &[Element], schema: &[ElementSchema]) {
+    for element in elements {
+        let element_schema = schema.iter()
+            .find(|element_schema| element_schema.name == element.name.as_str())
+            .unwrap_or_else(|| panic!("Unexpected element: {}", element.name));
+
+        assert_eq!(element.parent.as_deref(), element_schema.parent,
+                   "{} has an unexpected parent", element.name);
+
+        for (name, value) in &element.attributes {
+            let (_, _, pattern) = element_schema.attributes.iter()
+                .find(|(other_name, _, _)| other_name == name)
+                .unwrap_or_else(|| panic!("{} has an unexpected attribute: {}", element.name, name));
+
+            let regex = Regex::new(&format!("^(?:{})$", pattern)).unwrap();
+            assert!(regex.is_match(value),
+                    "{} has an invalid {} attribute value: {:?}", element.name, name, value);
+        }
+
+        for &(name, required, _) in element_schema.attributes {
+            assert!(!required || element.attributes.contains_key(name),
+                    "{} has no {} attribute", element.name, name);
+        }
+    }
+
+    for element_schema in schema {
+        let count = elements.iter().filter(|element| element.name == element_schema.name).count();
+        assert!(count != 0 || !element_schema.required, "{} element is missing", element_schema.name);
+        assert!(count <= 1 || element_schema.multiple, "{} element is duplicated", element_schema.name);
+    }
+}
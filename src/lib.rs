@@ -14,6 +14,7 @@ pub mod config;
 pub mod currency;
 pub mod db;
 pub mod deposits;
+pub mod foreign_accounts;
 pub mod formatting;
 pub mod localities;
 pub mod metrics;
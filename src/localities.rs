@@ -2,12 +2,16 @@ use chrono::{Datelike, Duration};
 
 use num_traits::Zero;
 
+use crate::core::GenericResult;
 use crate::currency;
+use crate::db::schema::CurrencyRateSource;
 use crate::types::{Date, Decimal};
 
 #[derive(Clone, Copy)]
 pub struct Country {
     pub currency: &'static str,
+    /// Source of official currency rates which are used for tax calculations
+    pub currency_rate_source: CurrencyRateSource,
     tax_rate: Decimal,
     tax_precision: u32,
 }
@@ -62,6 +66,7 @@ impl Country {
 pub fn russia() -> Country {
     Country {
         currency: "RUB",
+        currency_rate_source: CurrencyRateSource::Cbr,
         tax_rate: Decimal::new(13, 2),
         tax_precision: 0,
     }
@@ -70,11 +75,152 @@ pub fn russia() -> Country {
 pub fn us() -> Country {
     Country {
         currency: "USD",
+        currency_rate_source: CurrencyRateSource::Fed,
         tax_rate: Decimal::new(10, 2),
         tax_precision: 2,
     }
 }
 
+/// Country of a foreign income source (security issuer)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Jurisdiction {
+    Germany,
+    Ireland,
+    Netherlands,
+    UnitedKingdom,
+    Usa,
+}
+
+impl Jurisdiction {
+    /// Parses ISO 3166-1 alpha-2 country code
+    pub fn from_code(code: &str) -> GenericResult<Jurisdiction> {
+        Ok(match code {
+            "DE" => Jurisdiction::Germany,
+            "IE" => Jurisdiction::Ireland,
+            "NL" => Jurisdiction::Netherlands,
+            "GB" => Jurisdiction::UnitedKingdom,
+            "US" => Jurisdiction::Usa,
+            _ => return Err!("Unsupported country: {:?}", code),
+        })
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Jurisdiction::Germany => "DE",
+            Jurisdiction::Ireland => "IE",
+            Jurisdiction::Netherlands => "NL",
+            Jurisdiction::UnitedKingdom => "GB",
+            Jurisdiction::Usa => "US",
+        }
+    }
+
+    /// Returns OKSM (Russian classification of countries of the world) code
+    pub fn oksm_code(self) -> u32 {
+        match self {
+            Jurisdiction::Germany => 276,
+            Jurisdiction::Ireland => 372,
+            Jurisdiction::Netherlands => 528,
+            Jurisdiction::UnitedKingdom => 826,
+            Jurisdiction::Usa => 840,
+        }
+    }
+
+    pub fn from_oksm_code(code: u32) -> Option<Jurisdiction> {
+        Some(match code {
+            276 => Jurisdiction::Germany,
+            372 => Jurisdiction::Ireland,
+            528 => Jurisdiction::Netherlands,
+            826 => Jurisdiction::UnitedKingdom,
+            840 => Jurisdiction::Usa,
+            _ => return None,
+        })
+    }
+
+    /// Returns the maximum dividend withholding tax rate established by the double taxation treaty
+    /// with Russia for portfolio investments. Tax withheld above this rate can't be credited.
+    pub fn dividend_treaty_tax_rate(self) -> Decimal {
+        match self {
+            Jurisdiction::Germany => dec!(0.15),
+            Jurisdiction::Ireland => dec!(0.10),
+            Jurisdiction::Netherlands => dec!(0.15),
+            Jurisdiction::UnitedKingdom => dec!(0.10),
+            Jurisdiction::Usa => dec!(0.10),
+        }
+    }
+}
+
+/// Country code which is either a jurisdiction known to the program or an arbitrary country which
+/// is passed through by its OKSM code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CountryCode {
+    Known(Jurisdiction),
+    Unknown(u32),
+}
+
+impl CountryCode {
+    /// Determines issuer country by the first two letters of the security ISIN
+    pub fn from_isin(isin: &str) -> GenericResult<CountryCode> {
+        if isin.len() != 12 || !isin.is_ascii() {
+            return Err!("Invalid ISIN: {:?}", isin);
+        }
+
+        let code = &isin[..2];
+
+        Ok(match Jurisdiction::from_code(code) {
+            Ok(jurisdiction) => CountryCode::Known(jurisdiction),
+            Err(_) => CountryCode::Unknown(get_oksm_code(code).ok_or_else(|| format!(
+                "Unable to determine {} issuer country: Unsupported country: {:?}", isin, code))?),
+        })
+    }
+
+    pub fn from_oksm_code(code: u32) -> CountryCode {
+        match Jurisdiction::from_oksm_code(code) {
+            Some(jurisdiction) => CountryCode::Known(jurisdiction),
+            None => CountryCode::Unknown(code),
+        }
+    }
+
+    pub fn oksm_code(self) -> u32 {
+        match self {
+            CountryCode::Known(jurisdiction) => jurisdiction.oksm_code(),
+            CountryCode::Unknown(code) => code,
+        }
+    }
+
+    pub fn name(self) -> String {
+        match self {
+            CountryCode::Known(jurisdiction) => jurisdiction.code().to_owned(),
+            CountryCode::Unknown(code) => format!("{:03}", code),
+        }
+    }
+
+    /// Returns the maximum dividend withholding tax rate or None if the program doesn't know the
+    /// double taxation treaty terms for the country.
+    pub fn dividend_treaty_tax_rate(self) -> Option<Decimal> {
+        match self {
+            CountryCode::Known(jurisdiction) => Some(jurisdiction.dividend_treaty_tax_rate()),
+            CountryCode::Unknown(_) => None,
+        }
+    }
+}
+
+// Maps ISO 3166-1 alpha-2 code to OKSM code (which matches ISO 3166-1 numeric code) for countries
+// which may appear in security ISINs
+fn get_oksm_code(code: &str) -> Option<u32> {
+    Some(match code {
+        "AE" => 784, "AR" => 32, "AT" => 40, "AU" => 36, "BE" => 56, "BM" => 60, "BR" => 76,
+        "BS" => 44, "CA" => 124, "CH" => 756, "CL" => 152, "CN" => 156, "CO" => 170, "CW" => 531,
+        "CY" => 196, "CZ" => 203, "DE" => 276, "DK" => 208, "ES" => 724, "FI" => 246, "FR" => 250,
+        "GB" => 826, "GG" => 831, "GR" => 300, "HK" => 344, "HU" => 348, "ID" => 360, "IE" => 372,
+        "IL" => 376, "IM" => 833, "IN" => 356, "IS" => 352, "IT" => 380, "JE" => 832, "JP" => 392,
+        "KR" => 410, "KY" => 136, "KZ" => 398, "LR" => 430, "LU" => 442, "MH" => 584, "MT" => 470,
+        "MX" => 484, "MY" => 458, "NL" => 528, "NO" => 578, "NZ" => 554, "PA" => 591, "PE" => 604,
+        "PH" => 608, "PL" => 616, "PR" => 630, "PT" => 620, "RU" => 643, "SA" => 682, "SE" => 752,
+        "SG" => 702, "TH" => 764, "TR" => 792, "TW" => 158, "US" => 840, "VG" => 92, "ZA" => 710,
+        _ => return None,
+    })
+}
+
 pub fn is_valid_execution_date(conclusion: Date, execution: Date) -> bool {
     let expected_execution = conclusion + Duration::days(2);
     conclusion <= execution && get_russian_stock_exchange_min_last_working_day(execution) <= expected_execution
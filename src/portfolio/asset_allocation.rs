@@ -3,7 +3,7 @@ use std::collections::{HashSet, HashMap};
 use num_traits::Zero;
 
 use crate::brokers::BrokerInfo;
-use crate::config::{Config, PortfolioConfig, AssetAllocationConfig};
+use crate::config::{Config, PortfolioConfig, VirtualPortfolioConfig, AssetAllocationConfig};
 use crate::core::{EmptyResult, GenericResult};
 use crate::currency::Cash;
 use crate::currency::converter::CurrencyConverter;
@@ -12,10 +12,11 @@ use crate::types::{Decimal, TradeType};
 use crate::util;
 
 use super::Assets;
+use super::tolerance::{self, Tolerance};
 
 pub struct Portfolio {
     pub name: String,
-    pub broker: BrokerInfo,
+    pub accounts: Vec<Account>,
     pub currency: String,
 
     pub min_trade_volume: Decimal,
@@ -38,30 +39,86 @@ impl Portfolio {
             None => return Err!("The portfolio's currency is not specified in the config"),
         };
 
-        let min_trade_volume = portfolio_config.min_trade_volume.unwrap_or_else(|| dec!(0));
+        let mut account = Account::load(config, portfolio_config, &assets, currency, converter)?;
+
+        // Portfolio-level restrictions are applied to the top-level assets
+        let restrictions = account.restrictions;
+        account.restrictions = (None, None);
+
+        let symbols = portfolio_config.get_stock_symbols().into_iter()
+            .map(|symbol| (symbol, 0)).collect();
+
+        let mut portfolio = Portfolio::new(
+            &portfolio_config.name, currency, portfolio_config.min_trade_volume,
+            portfolio_config.min_cash_assets, vec![account])?;
+
+        portfolio.load_assets(
+            &portfolio_config.assets, restrictions, &symbols, assets.stocks, converter, quotes)?;
+
+        Ok(portfolio)
+    }
+
+    pub fn load_virtual(
+        config: &Config, portfolio_config: &VirtualPortfolioConfig,
+        portfolios: Vec<(&PortfolioConfig, Assets)>, converter: &CurrencyConverter, quotes: &Quotes
+    ) -> GenericResult<Portfolio> {
+        let currency = &portfolio_config.currency;
+
+        let mut accounts = Vec::new();
+        let mut symbols = HashMap::new();
+        let mut stocks = HashMap::new();
+
+        for (index, (account_config, assets)) in portfolios.into_iter().enumerate() {
+            accounts.push(Account::load(config, account_config, &assets, currency, converter)?);
+
+            // Each instrument must be tradable in exactly one account to be able to distribute
+            // trades between the accounts unambiguously
+            for symbol in account_config.get_stock_symbols() {
+                if let Some(&other_index) = symbols.get(&symbol) {
+                    return Err!(
+                        "{} is tradable in several accounts: {:?} and {:?}",
+                        symbol, accounts[other_index].name, account_config.name);
+                }
+                symbols.insert(symbol, index);
+            }
+
+            for (symbol, shares) in assets.stocks {
+                assert!(stocks.insert(symbol, shares).is_none());
+            }
+        }
+
+        let mut portfolio = Portfolio::new(
+            &portfolio_config.name, currency, portfolio_config.min_trade_volume,
+            portfolio_config.min_cash_assets, accounts)?;
+
+        portfolio.load_assets(
+            &portfolio_config.assets,
+            (portfolio_config.restrict_buying, portfolio_config.restrict_selling),
+            &symbols, stocks, converter, quotes)?;
+
+        Ok(portfolio)
+    }
+
+    fn new(
+        name: &str, currency: &str, min_trade_volume: Option<Decimal>,
+        min_cash_assets: Option<Decimal>, accounts: Vec<Account>,
+    ) -> GenericResult<Portfolio> {
+        let min_trade_volume = min_trade_volume.unwrap_or_else(|| dec!(0));
         if min_trade_volume.is_sign_negative() {
             return Err!("Invalid minimum trade volume value")
         }
 
-        let min_cash_assets = portfolio_config.min_cash_assets.unwrap_or_else(|| dec!(0));
+        let min_cash_assets = min_cash_assets.unwrap_or_else(|| dec!(0));
         if min_cash_assets.is_sign_negative() {
             return Err!("Invalid minimum free cash assets value")
         }
 
-        if portfolio_config.assets.is_empty() {
-            return Err!("The portfolio has no asset allocation configuration");
-        }
-
-        for symbol in portfolio_config.get_stock_symbols() {
-            quotes.batch(&symbol);
-        }
+        let cash_assets = accounts.iter().map(|account| account.current_cash_assets).sum();
 
-        let cash_assets = assets.cash.total_assets_real_time(&currency, converter)?;
-
-        let mut portfolio = Portfolio {
-            name: portfolio_config.name.clone(),
-            broker: portfolio_config.broker.get_info(config, portfolio_config.plan.as_ref())?,
-            currency: currency.clone(),
+        Ok(Portfolio {
+            name: name.to_owned(),
+            accounts: accounts,
+            currency: currency.to_owned(),
 
             min_trade_volume: min_trade_volume,
             min_cash_assets: min_cash_assets,
@@ -71,23 +128,36 @@ impl Portfolio {
             target_cash_assets: cash_assets,
             commissions: dec!(0),
             total_value: cash_assets,
-        };
+        })
+    }
 
-        let mut stocks = assets.stocks;
-        let mut symbols = HashSet::new();
+    fn load_assets(
+        &mut self, assets_configs: &[AssetAllocationConfig],
+        (restrict_buying, restrict_selling): (Option<bool>, Option<bool>),
+        symbols: &HashMap<String, usize>, mut stocks: HashMap<String, Decimal>,
+        converter: &CurrencyConverter, quotes: &Quotes,
+    ) -> EmptyResult {
+        if assets_configs.is_empty() {
+            return Err!("The portfolio has no asset allocation configuration");
+        }
+
+        for symbol in symbols.keys() {
+            quotes.batch(symbol);
+        }
+
+        let mut loaded_symbols = HashSet::new();
 
-        for assets_config in &portfolio_config.assets {
+        for assets_config in assets_configs {
             let mut asset_allocation = AssetAllocation::load(
-                &portfolio.broker, assets_config, &currency, &mut symbols, &mut stocks,
-                converter, quotes)?;
+                &self.accounts, symbols, assets_config, &self.currency, &mut loaded_symbols,
+                &mut stocks, converter, quotes)?;
 
-            asset_allocation.apply_restrictions(
-                portfolio_config.restrict_buying, portfolio_config.restrict_selling);
+            asset_allocation.apply_restrictions(restrict_buying, restrict_selling);
 
-            portfolio.total_value += asset_allocation.current_value;
-            portfolio.assets.push(asset_allocation);
+            self.total_value += asset_allocation.current_value;
+            self.assets.push(asset_allocation);
         }
-        check_weights(&portfolio.name, &portfolio.assets)?;
+        check_weights(&self.name, &self.assets)?;
 
         if !stocks.is_empty() {
             let mut missing_symbols: Vec<String> = stocks.keys().cloned().collect();
@@ -98,7 +168,62 @@ impl Portfolio {
                     missing_symbols.join(", "));
         }
 
-        Ok(portfolio)
+        Ok(())
+    }
+
+    /// Emulates cash contribution (positive amount) or withdrawal (negative amount) and allows only
+    /// buying or selling respectively, so the cash flow is distributed between the assets without
+    /// any other changes of the existing positions.
+    ///
+    /// Cash can't be moved between the accounts, so virtual portfolio cash flow must be applied to
+    /// one of its accounts and affects only the assets which are held in it. Assets which stay
+    /// within their tolerance bands aren't traded unless the drifted ones can't absorb the cash
+    /// flow.
+    pub fn apply_cash_flow(&mut self, amount: Decimal, account_name: Option<&str>) -> EmptyResult {
+        let account_index = match account_name {
+            Some(name) => self.accounts.iter().position(|account| account.name == name).ok_or_else(|| format!(
+                "{:?} portfolio has no {:?} account", self.name, name))?,
+            None if self.accounts.len() == 1 => 0,
+            None => return Err!("The account to apply the cash flow to must be specified for virtual portfolios"),
+        };
+
+        let contribution = amount.is_sign_positive();
+        for asset in &mut self.assets {
+            asset.restrict_cash_flow_trading(account_index, contribution);
+        }
+
+        // The withdrawal may be gathered from the account's free cash and the assets which can be
+        // sold
+        let account = &mut self.accounts[account_index];
+        let required_funds = std::cmp::max(
+            account.min_cash_assets - (account.current_cash_assets + amount), dec!(0));
+
+        if get_sellable_value(&self.assets) < required_funds {
+            return Err!("The withdrawal exceeds the account's available funds");
+        }
+
+        account.current_cash_assets += amount;
+        account.target_cash_assets += amount;
+
+        self.current_cash_assets += amount;
+        self.target_cash_assets += amount;
+        self.total_value += amount;
+
+        // The bands are checked against the portfolio value after the cash flow
+        let mut assets = self.assets.clone();
+        tolerance::restrict_in_band(&mut assets, self.total_value - self.min_cash_assets);
+
+        let absorbable = if contribution {
+            has_buyable_assets(&assets)
+        } else {
+            get_sellable_value(&assets) >= required_funds
+        };
+
+        if absorbable {
+            self.assets = assets;
+        }
+
+        Ok(())
     }
 
     pub fn change_commission(&mut self, commission: Decimal) {
@@ -111,11 +236,57 @@ impl Portfolio {
     }
 }
 
+/// Broker account which holds the portfolio assets (virtual portfolios consist of several accounts)
+pub struct Account {
+    pub name: String,
+    pub broker: BrokerInfo,
+    pub currency: String,
+
+    // Restrictions which are applied to all assets of the account
+    restrictions: (Option<bool>, Option<bool>),
+
+    pub min_cash_assets: Decimal,
+    pub current_cash_assets: Decimal,
+    pub target_cash_assets: Decimal,
+    pub commissions: Decimal,
+}
+
+impl Account {
+    fn load(
+        config: &Config, portfolio_config: &PortfolioConfig, assets: &Assets, currency: &str,
+        converter: &CurrencyConverter,
+    ) -> GenericResult<Account> {
+        let account_currency = portfolio_config.currency.as_ref().ok_or_else(|| format!(
+            "{:?} portfolio's currency is not specified in the config", portfolio_config.name))?;
+
+        let cash_assets = assets.cash.total_assets_real_time(currency, converter)?;
+        let min_cash_assets = match portfolio_config.min_cash_assets {
+            Some(amount) => converter.real_time_convert_to(Cash::new(account_currency, amount), currency)?,
+            None => dec!(0),
+        };
+
+        Ok(Account {
+            name: portfolio_config.name.clone(),
+            broker: portfolio_config.broker.get_info(config, portfolio_config.plan.as_ref())?,
+            currency: account_currency.clone(),
+
+            restrictions: (portfolio_config.restrict_buying, portfolio_config.restrict_selling),
+
+            min_cash_assets: min_cash_assets,
+            current_cash_assets: cash_assets,
+            target_cash_assets: cash_assets,
+            commissions: dec!(0),
+        })
+    }
+}
+
+#[derive(Clone)]
 pub enum Holding {
     Stock(StockHolding),
     Group(Vec<AssetAllocation>),
 }
 
+#[derive(Clone)]
 pub struct StockHolding {
     pub symbol: String,
     pub price: Decimal,
@@ -123,6 +294,7 @@ pub struct StockHolding {
     pub current_shares: Decimal,
     pub target_shares: Decimal,
     pub fractional_shares_trading: bool,
+    pub account: usize,
 }
 
 impl StockHolding {
@@ -163,10 +335,12 @@ impl StockHolding {
     }
 }
 
+#[derive(Clone)]
 pub struct AssetAllocation {
     pub name: String,
 
     pub expected_weight: Decimal,
+    pub tolerance: Option<Tolerance>,
     pub restrict_buying: Option<bool>,
     pub restrict_selling: Option<bool>,
 
@@ -183,10 +357,12 @@ pub struct AssetAllocation {
 
 impl AssetAllocation {
     fn load(
-        broker: &BrokerInfo, config: &AssetAllocationConfig, currency: &str,
-        symbols: &mut HashSet<String>, stocks: &mut HashMap<String, Decimal>,
-        converter: &CurrencyConverter, quotes: &Quotes,
+        accounts: &[Account], account_symbols: &HashMap<String, usize>,
+        config: &AssetAllocationConfig, currency: &str, symbols: &mut HashSet<String>,
+        stocks: &mut HashMap<String, Decimal>, converter: &CurrencyConverter, quotes: &Quotes,
     ) -> GenericResult<AssetAllocation> {
+        let mut account_restrictions = (None, None);
+
         let (holding, current_value) = match (&config.symbol, &config.assets) {
             (Some(symbol), None) => {
                 if !symbols.insert(symbol.clone()) {
@@ -194,6 +370,13 @@ impl AssetAllocation {
                         symbol);
                 }
 
+                let account_index = *account_symbols.get(symbol).ok_or_else(|| format!(
+                    "Invalid asset allocation configuration: {} is not tradable in any of the accounts",
+                    symbol))?;
+
+                let account = &accounts[account_index];
+                account_restrictions = account.restrictions;
+
                 let currency_price = quotes.get(symbol)?;
                 let price = converter.real_time_convert_to(currency_price, currency)?;
                 let shares = stocks.remove(symbol).unwrap_or_else(|| dec!(0));
@@ -205,7 +388,8 @@ impl AssetAllocation {
                     currency_price: currency_price,
                     current_shares: shares,
                     target_shares: shares,
-                    fractional_shares_trading: broker.fractional_shares_trading,
+                    fractional_shares_trading: account.broker.fractional_shares_trading,
+                    account: account_index,
                 };
 
                 (Holding::Stock(holding), current_value)
@@ -216,7 +400,8 @@ impl AssetAllocation {
 
                 for asset in assets {
                     let holding = AssetAllocation::load(
-                        broker, asset, currency, symbols, stocks, converter, quotes)?;
+                        accounts, account_symbols, asset, currency, symbols, stocks, converter,
+                        quotes)?;
 
                     current_value += holding.current_value;
                     holdings.push(holding);
@@ -231,10 +416,20 @@ impl AssetAllocation {
                config.name),
         };
 
+        let tolerance = match (config.tolerance, config.relative_tolerance) {
+            (Some(tolerance), None) => Some(Tolerance::Absolute(tolerance)),
+            (None, Some(tolerance)) => Some(Tolerance::Relative(tolerance)),
+            (None, None) => None,
+            (Some(_), Some(_)) => return Err!(
+                "Invalid {:?} assets configuration: tolerance and relative tolerance are mutually exclusive",
+                config.name),
+        };
+
         let mut asset_allocation = AssetAllocation {
             name: config.name.clone(),
 
             expected_weight: config.weight,
+            tolerance: tolerance,
             restrict_buying: None,
             restrict_selling: None,
 
@@ -250,6 +445,7 @@ impl AssetAllocation {
         };
 
         asset_allocation.apply_restrictions(config.restrict_buying, config.restrict_selling);
+        asset_allocation.apply_restrictions(account_restrictions.0, account_restrictions.1);
 
         Ok(asset_allocation)
     }
@@ -299,6 +495,41 @@ impl AssetAllocation {
         }
     }
 
+    fn restrict_cash_flow_trading(&mut self, account: usize, contribution: bool) {
+        let same_account = match self.holding {
+            Holding::Stock(ref holding) => holding.account == account,
+            Holding::Group(ref mut assets) => {
+                for asset in assets {
+                    asset.restrict_cash_flow_trading(account, contribution);
+                }
+                return;
+            },
+        };
+
+        if same_account {
+            self.restrict_trading(!contribution, contribution);
+        } else {
+            self.restrict_trading(true, true);
+        }
+    }
+
+    /// Forbids the specified trades of the asset regardless of the configured restrictions
+    pub fn restrict_trading(&mut self, restrict_buying: bool, restrict_selling: bool) {
+        if restrict_buying {
+            self.restrict_buying = Some(true);
+        }
+
+        if restrict_selling {
+            self.restrict_selling = Some(true);
+        }
+
+        if let Holding::Group(ref mut assets) = self.holding {
+            for asset in assets {
+                asset.restrict_trading(restrict_buying, restrict_selling);
+            }
+        }
+    }
+
     pub fn iterative_trading_granularity(&self, trade_type: TradeType) -> Decimal {
         match self.holding {
             Holding::Stock(ref holding) => holding.iterative_trading_granularity(trade_type),
@@ -320,6 +551,21 @@ impl AssetAllocation {
     }
 }
 
+fn get_sellable_value(assets: &[AssetAllocation]) -> Decimal {
+    assets.iter().map(|asset| match asset.holding {
+        Holding::Stock(_) if asset.restrict_selling.unwrap_or(false) => dec!(0),
+        Holding::Stock(_) => asset.current_value,
+        Holding::Group(ref holdings) => get_sellable_value(holdings),
+    }).sum()
+}
+
+fn has_buyable_assets(assets: &[AssetAllocation]) -> bool {
+    assets.iter().any(|asset| match asset.holding {
+        Holding::Stock(_) => !asset.restrict_buying.unwrap_or(false),
+        Holding::Group(ref holdings) => has_buyable_assets(holdings),
+    })
+}
+
 fn check_weights(name: &str, assets: &[AssetAllocation]) -> EmptyResult {
     let mut weight = dec!(0);
 
@@ -333,4 +579,260 @@ fn check_weights(name: &str, assets: &[AssetAllocation]) -> EmptyResult {
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::brokers::Broker;
+    use crate::config;
+    use crate::currency::MultiCurrencyCashAccount;
+    use crate::currency::converter::CurrencyConverterBackend;
+    use crate::db::{self, schema::CurrencyRateSource};
+    use crate::types::Date;
+    use super::*;
+    use super::super::rebalancing;
+
+    #[test]
+    fn contribution() {
+        let mut portfolio = new_portfolio(&[("account", dec!(100), dec!(50))], vec![
+            new_stock("A", dec!(0.5), 0, dec!(5), None),
+            new_stock("B", dec!(0.5), 0, dec!(30), None),
+        ]);
+
+        // B is overweight, but it can't be sold
+        portfolio.apply_cash_flow(dec!(150), None).unwrap();
+        rebalance(&mut portfolio);
+
+        assert_eq!(get_target_shares(&portfolio), vec![("A", dec!(25)), ("B", dec!(30))]);
+        assert_eq!(portfolio.target_cash_assets, dec!(50));
+    }
+
+    #[test]
+    fn withdrawal() {
+        let assets = || vec![
+            new_stock("A", dec!(0.5), 0, dec!(35), None),
+            new_stock("B", dec!(0.5), 0, dec!(5), None),
+        ];
+
+        // B is underweight, but it can't be bought
+        let mut portfolio = new_portfolio(&[("account", dec!(100), dec!(50))], assets());
+        portfolio.apply_cash_flow(dec!(-250), None).unwrap();
+        rebalance(&mut portfolio);
+
+        assert_eq!(get_target_shares(&portfolio), vec![("A", dec!(15)), ("B", dec!(5))]);
+        assert_eq!(portfolio.target_cash_assets, dec!(50));
+
+        // Free cash above the minimum and all the assets
+        let mut portfolio = new_portfolio(&[("account", dec!(100), dec!(50))], assets());
+        portfolio.apply_cash_flow(dec!(-450), None).unwrap();
+
+        let mut portfolio = new_portfolio(&[("account", dec!(100), dec!(50))], assets());
+        assert!(portfolio.apply_cash_flow(dec!(-451), None).is_err());
+    }
+
+    #[test]
+    fn cash_flow_tolerance() {
+        // A is in its band, so the whole contribution goes to B
+        let mut portfolio = new_portfolio(&[("account", dec!(100), dec!(50))], vec![
+            new_stock("A", dec!(0.5), 0, dec!(20), Some(Tolerance::Absolute(dec!(0.1)))),
+            new_stock("B", dec!(0.5), 0, dec!(20), None),
+        ]);
+
+        portfolio.apply_cash_flow(dec!(50), None).unwrap();
+        rebalance(&mut portfolio);
+        assert_eq!(get_target_shares(&portfolio), vec![("A", dec!(20)), ("B", dec!(30))]);
+
+        // All assets are in their bands, so the contribution is distributed between all of them
+        let mut portfolio = new_portfolio(&[("account", dec!(100), dec!(50))], vec![
+            new_stock("A", dec!(0.5), 0, dec!(20), Some(Tolerance::Absolute(dec!(0.1)))),
+            new_stock("B", dec!(0.5), 0, dec!(20), Some(Tolerance::Absolute(dec!(0.1)))),
+        ]);
+
+        portfolio.apply_cash_flow(dec!(50), None).unwrap();
+        rebalance(&mut portfolio);
+        assert_eq!(get_target_shares(&portfolio), vec![("A", dec!(25)), ("B", dec!(25))]);
+    }
+
+    #[test]
+    fn virtual_portfolio_cash_flow() {
+        let accounts = [("first", dec!(100), dec!(0)), ("second", dec!(100), dec!(0))];
+        let assets = || vec![
+            new_stock("A", dec!(0.5), 0, dec!(10), None),
+            new_stock("B", dec!(0.5), 1, dec!(10), None),
+        ];
+
+        let mut portfolio = new_portfolio(&accounts, assets());
+        assert!(portfolio.apply_cash_flow(dec!(100), None).is_err());
+
+        let mut portfolio = new_portfolio(&accounts, assets());
+        assert!(portfolio.apply_cash_flow(dec!(100), Some("third")).is_err());
+
+        let mut portfolio = new_portfolio(&accounts, assets());
+        portfolio.apply_cash_flow(dec!(100), Some("second")).unwrap();
+
+        let restrictions: Vec<_> = portfolio.assets.iter().map(|asset| {
+            (asset.restrict_buying, asset.restrict_selling)
+        }).collect();
+        assert_eq!(restrictions, vec![(Some(true), Some(true)), (None, Some(true))]);
+
+        assert_eq!(portfolio.accounts[0].current_cash_assets, dec!(100));
+        assert_eq!(portfolio.accounts[1].current_cash_assets, dec!(200));
+    }
+
+    #[test]
+    fn virtual_portfolio_rebalancing() {
+        // The first account has no cash to buy its underweighted asset, so the free cash of the second
+        // account is distributed between its own assets
+        let mut portfolio = new_portfolio(&[("first", dec!(0), dec!(0)), ("second", dec!(100), dec!(0))], vec![
+            new_stock("A", dec!(0.5), 0, dec!(10), None),
+            new_stock("B", dec!(0.25), 1, dec!(10), None),
+            new_stock("C", dec!(0.25), 1, dec!(10), None),
+        ]);
+
+        rebalance(&mut portfolio);
+        assert_eq!(get_target_shares(&portfolio), vec![("A", dec!(10)), ("B", dec!(15)), ("C", dec!(15))]);
+
+        let accounts_cash: Vec<_> = portfolio.accounts.iter().map(|account| {
+            (account.name.as_str(), account.target_cash_assets)
+        }).collect();
+        assert_eq!(accounts_cash, vec![("first", dec!(0)), ("second", dec!(0))]);
+
+        // The overweighted asset is sold to fund the account's buys
+        let mut portfolio = new_portfolio(&[("first", dec!(0), dec!(0)), ("second", dec!(0), dec!(0))], vec![
+            new_stock("A", dec!(0.25), 0, dec!(10), None),
+            new_stock("B", dec!(0.25), 0, dec!(30), None),
+            new_stock("C", dec!(0.5), 1, dec!(40), None),
+        ]);
+
+        rebalance(&mut portfolio);
+        assert_eq!(get_target_shares(&portfolio), vec![("A", dec!(20)), ("B", dec!(20)), ("C", dec!(40))]);
+    }
+
+    #[test]
+    fn portfolio_currency() {
+        struct EurBackend;
+
+        impl CurrencyConverterBackend for EurBackend {
+            fn convert(&self, from: &str, to: &str, _date: Date, amount: Decimal) -> GenericResult<Decimal> {
+                let rate = |currency| match currency {
+                    "EUR" => dec!(1),
+                    "USD" => dec!(0.8),
+                    _ => unreachable!(),
+                };
+                Ok(amount * rate(from) / rate(to))
+            }
+        }
+
+        let testdata = Path::new(file!()).parent().unwrap().join("testdata");
+        assert!(config::load_config(testdata.join("unsupported-currency.yaml").to_str().unwrap()).is_err());
+
+        let config = config::load_config(testdata.join("eur-portfolio.yaml").to_str().unwrap()).unwrap();
+        let portfolio_config = config.get_portfolio("eur").unwrap();
+
+        let (_database_file, database) = db::new_temporary();
+        let quotes = Quotes::new(&config, database).unwrap();
+        let converter = CurrencyConverter::new_with_backend(Box::new(EurBackend));
+
+        let mut cash = MultiCurrencyCashAccount::new();
+        cash.deposit(Cash::new("USD", dec!(500)));
+        cash.deposit(Cash::new("EUR", dec!(600)));
+        let assets = Assets::new(cash, hashmap!{
+            s!("VTI") => dec!(10),
+            s!("BND") => dec!(10),
+        });
+
+        let mut portfolio = Portfolio::load(&config, portfolio_config, assets, &converter, &quotes).unwrap();
+        assert_eq!(portfolio.currency, "EUR");
+        assert_eq!(portfolio.accounts[0].currency, "EUR");
+        assert_eq!(portfolio.current_cash_assets, dec!(1000));
+        assert_eq!(portfolio.min_cash_assets, dec!(200));
+        assert_eq!(portfolio.total_value, dec!(3400));
+
+        // Stock prices are converted from their quote currency to the portfolio currency
+        let prices: Vec<_> = portfolio.assets.iter().map(|asset| match asset.holding {
+            Holding::Stock(ref holding) => (holding.symbol.as_str(), holding.price, holding.currency_price),
+            Holding::Group(_) => unreachable!(),
+        }).collect();
+        assert_eq!(prices, vec![
+            ("VTI", dec!(160), Cash::new("USD", dec!(200))),
+            ("BND", dec!(80), Cash::new("USD", dec!(100))),
+        ]);
+
+        rebalancing::rebalance_portfolio(&mut portfolio, &converter).unwrap();
+        assert_eq!(get_target_shares(&portfolio), vec![("VTI", dec!(10)), ("BND", dec!(20))]);
+        assert_eq!(portfolio.target_cash_assets, dec!(200));
+    }
+
+    fn new_portfolio(accounts: &[(&str, Decimal, Decimal)], assets: Vec<AssetAllocation>) -> Portfolio {
+        let min_cash_assets = accounts.iter().map(|&(_, _, min_cash_assets)| min_cash_assets).sum();
+
+        let accounts = accounts.iter().map(|&(name, cash_assets, min_cash_assets)| Account {
+            name: name.to_owned(),
+            broker: Broker::Firstrade.get_info(&Config::mock(), None).unwrap(),
+            currency: s!("USD"),
+
+            restrictions: (None, None),
+
+            min_cash_assets: min_cash_assets,
+            current_cash_assets: cash_assets,
+            target_cash_assets: cash_assets,
+            commissions: dec!(0),
+        }).collect();
+
+        let mut portfolio = Portfolio::new("test", "USD", None, Some(min_cash_assets), accounts).unwrap();
+
+        for asset in assets {
+            portfolio.total_value += asset.current_value;
+            portfolio.assets.push(asset);
+        }
+
+        portfolio
+    }
+
+    fn new_stock(
+        symbol: &str, weight: Decimal, account: usize, shares: Decimal, tolerance: Option<Tolerance>,
+    ) -> AssetAllocation {
+        let price = dec!(10);
+
+        AssetAllocation {
+            name: symbol.to_owned(),
+
+            expected_weight: weight,
+            tolerance: tolerance,
+            restrict_buying: None,
+            restrict_selling: None,
+
+            holding: Holding::Stock(StockHolding {
+                symbol: symbol.to_owned(),
+                price: price,
+                currency_price: Cash::new("USD", price),
+                current_shares: shares,
+                target_shares: shares,
+                fractional_shares_trading: false,
+                account: account,
+            }),
+            current_value: shares * price,
+            target_value: shares * price,
+
+            min_value: dec!(0),
+            max_value: None,
+
+            buy_blocked: false,
+            sell_blocked: false,
+        }
+    }
+
+    fn rebalance(portfolio: &mut Portfolio) {
+        let (_database_file, database) = db::new_temporary();
+        let converter = CurrencyConverter::new(database, CurrencyRateSource::Cbr, None, false);
+        rebalancing::rebalance_portfolio(portfolio, &converter).unwrap();
+    }
+
+    fn get_target_shares(portfolio: &Portfolio) -> Vec<(&str, Decimal)> {
+        portfolio.assets.iter().map(|asset| match asset.holding {
+            Holding::Stock(ref holding) => (holding.symbol.as_str(), holding.target_shares),
+            Holding::Group(_) => unreachable!(),
+        }).collect()
+    }
+}
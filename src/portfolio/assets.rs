@@ -10,6 +10,7 @@ use crate::db::{self, schema::{AssetType, assets}, models};
 use crate::types::Decimal;
 use crate::util::{self, DecimalRestrictions};
 
+#[derive(Clone)]
 #[cfg_attr(test, derive(Debug, PartialEq, Eq))]
 pub struct Assets {
     pub cash: MultiCurrencyCashAccount,
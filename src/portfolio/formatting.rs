@@ -3,13 +3,20 @@ use std::fmt::Write;
 use ansi_term::{Style, Color, ANSIString};
 use num_traits::Zero;
 
+use crate::core::EmptyResult;
 use crate::currency::Cash;
+use crate::currency::converter::CurrencyConverter;
 use crate::types::Decimal;
 use crate::util;
 
 use super::asset_allocation::{Portfolio, AssetAllocation, Holding};
+use super::tolerance::DriftedAsset;
+
+pub fn print_portfolio(portfolio: Portfolio, flat: bool, converter: &CurrencyConverter) -> EmptyResult {
+    if portfolio.accounts.len() > 1 {
+        print_accounts(&portfolio, converter)?;
+    }
 
-pub fn print_portfolio(portfolio: Portfolio, flat: bool) {
     let mut assets = portfolio.assets;
     if flat {
         assets = flatify(assets, dec!(1));
@@ -31,6 +38,78 @@ pub fn print_portfolio(portfolio: Portfolio, flat: bool) {
         println!("{} {}", colorify_title("Commissions:"),
                  colorify_commission(&format_cash(&portfolio.currency, portfolio.commissions)));
     }
+
+    Ok(())
+}
+
+pub fn print_drifted_assets(portfolio_name: &str, drifted_assets: &[DriftedAsset]) {
+    println!("The following {} portfolio assets have drifted out of their tolerance bands:", portfolio_name);
+
+    for asset in drifted_assets {
+        println!("* {name}: {weight} (expected {expected_weight}, {min_weight} - {max_weight})",
+                 name=asset.name, weight=format_weight(asset.weight),
+                 expected_weight=format_weight(asset.expected_weight),
+                 min_weight=format_weight(asset.band.0), max_weight=format_weight(asset.band.1));
+    }
+}
+
+// Prints the trades of each account in the account's currency
+fn print_accounts(portfolio: &Portfolio, converter: &CurrencyConverter) -> EmptyResult {
+    for (index, account) in portfolio.accounts.iter().enumerate() {
+        let currency = &account.currency;
+        let convert = |amount| converter.real_time_convert_to(Cash::new(&portfolio.currency, amount), currency);
+
+        println!("{} ({}, {}):", colorify_title(&account.name), account.broker.name, account.currency);
+
+        let mut trades = Vec::new();
+        collect_account_trades(&portfolio.assets, index, &mut trades);
+
+        for (name, shares_change, value_change) in trades {
+            let colorify_func = if shares_change.is_sign_positive() {
+                colorify_buy
+            } else {
+                colorify_sell
+            };
+
+            println!("{bullet:>indent$} {name} {changes}", bullet='•', indent=1, name=name,
+                     changes=colorify_func(&format!(
+                         "{shares_change} ({value_change})",
+                         shares_change=format_shares(shares_change, true),
+                         value_change=format_cash(currency, convert(value_change.abs())?))));
+        }
+
+        print!("  Cash assets: {}", format_cash(currency, convert(account.current_cash_assets)?));
+        if account.target_cash_assets != account.current_cash_assets {
+            print!(" -> {}", format_cash(currency, convert(account.target_cash_assets)?));
+        }
+        println!();
+
+        if !account.commissions.is_zero() {
+            println!("  Commissions: {}", colorify_commission(&format_cash(
+                currency, convert(account.commissions)?)));
+        }
+
+        println!();
+    }
+
+    Ok(())
+}
+
+fn collect_account_trades(assets: &[AssetAllocation], account: usize, trades: &mut Vec<(String, Decimal, Decimal)>) {
+    for asset in assets {
+        match asset.holding {
+            Holding::Stock(ref holding) => {
+                if holding.account == account && holding.target_shares != holding.current_shares {
+                    trades.push((
+                        asset.full_name(),
+                        holding.target_shares - holding.current_shares,
+                        asset.target_value - asset.current_value,
+                    ));
+                }
+            },
+            Holding::Group(ref holdings) => collect_account_trades(holdings, account, trades),
+        }
+    }
 }
 
 fn flatify(assets: Vec<AssetAllocation>, expected_weight: Decimal) -> Vec<AssetAllocation> {
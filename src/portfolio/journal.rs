@@ -0,0 +1,421 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::str::FromStr;
+
+use diesel::{self, prelude::*};
+use serde::{Deserialize, Serialize};
+use static_table_derive::StaticTable;
+
+use crate::core::{EmptyResult, GenericResult};
+use crate::currency::{self, Cash, MultiCurrencyCashAccount};
+use crate::db::{self, schema::{JournalAction, journal}, models};
+use crate::types::{DateTime, Decimal};
+use crate::util::{self, DecimalRestrictions};
+
+use super::assets::Assets;
+
+/// Append-only log of all changes of portfolio assets which allows to view their history and to
+/// undo them.
+pub struct Journal {
+    db: db::Connection,
+    portfolio: String,
+}
+
+#[derive(Default)]
+pub struct Operation<'a> {
+    pub symbol: Option<&'a str>,
+    pub shares: Option<Decimal>,
+    pub price: Option<Decimal>,
+    pub cash_assets: Option<Decimal>,
+    pub note: Option<&'a str>,
+}
+
+pub struct Record {
+    pub id: i32,
+    pub time: DateTime,
+    pub action: JournalAction,
+    pub symbol: Option<String>,
+    pub shares: Option<Decimal>,
+    pub price: Option<Decimal>,
+    pub cash_assets: Option<Decimal>,
+    pub note: Option<String>,
+    pub undone_id: Option<i32>,
+    previous_assets: String,
+}
+
+impl Record {
+    /// Returns portfolio assets as they were before the change.
+    pub fn get_previous_assets(&self) -> GenericResult<Assets> {
+        deserialize_assets(&self.previous_assets).map_err(|e| format!(
+            "Got an invalid journal record #{} from the database: {}", self.id, e).into())
+    }
+}
+
+impl Journal {
+    pub fn new(connection: db::Connection, portfolio: &str) -> Journal {
+        Journal {
+            db: connection,
+            portfolio: portfolio.to_owned(),
+        }
+    }
+
+    pub fn record(
+        &self, action: JournalAction, operation: &Operation, previous_assets: &Assets,
+        undone_id: Option<i32>,
+    ) -> EmptyResult {
+        diesel::insert_into(journal::table)
+            .values(models::NewJournalEntry {
+                portfolio: &self.portfolio,
+                time: util::now(),
+                action: action,
+                symbol: operation.symbol,
+                shares: operation.shares.map(|shares| shares.to_string()),
+                price: operation.price.map(|price| price.to_string()),
+                cash_assets: operation.cash_assets.map(|cash_assets| cash_assets.to_string()),
+                note: operation.note,
+                undone_id: undone_id,
+                previous_assets: serialize_assets(previous_assets)?,
+            })
+            .execute(&*self.db)?;
+        Ok(())
+    }
+
+    pub fn load(&self) -> GenericResult<Vec<Record>> {
+        let entries = journal::table
+            .filter(journal::portfolio.eq(&self.portfolio))
+            .order_by(journal::id.asc())
+            .load::<models::JournalEntry>(&*self.db)?;
+
+        let mut records = Vec::with_capacity(entries.len());
+
+        for entry in entries {
+            records.push(Record {
+                id: entry.id,
+                time: entry.time,
+                action: entry.action,
+                symbol: entry.symbol,
+                shares: parse_value(entry.id, entry.shares, DecimalRestrictions::StrictlyPositive)?,
+                price: parse_value(entry.id, entry.price, DecimalRestrictions::PositiveOrZero)?,
+                cash_assets: parse_value(entry.id, entry.cash_assets, DecimalRestrictions::No)?,
+                note: entry.note,
+                undone_id: entry.undone_id,
+                previous_assets: entry.previous_assets,
+            });
+        }
+
+        Ok(records)
+    }
+
+    /// Replays the journal starting from the last sync and returns the stock positions the
+    /// portfolio should have according to the recorded operations.
+    pub fn replay_positions(&self, current_assets: &Assets) -> GenericResult<HashMap<String, Decimal>> {
+        let records = self.load()?;
+        let undone: HashSet<i32> = records.iter().filter_map(|record| record.undone_id).collect();
+
+        let start = records.iter().rposition(|record| {
+            record.action == JournalAction::Sync && !undone.contains(&record.id)
+        }).map_or(0, |index| index + 1);
+
+        // Each record contains the assets as they were before it, so the first record after the
+        // sync holds the positions reported by the broker at the moment of the sync.
+        let mut positions = match records.get(start) {
+            Some(record) => record.get_previous_assets()?.stocks,
+            None => return Ok(current_assets.stocks.clone()),
+        };
+
+        for record in &records[start..] {
+            if undone.contains(&record.id) {
+                continue;
+            }
+
+            let (symbol, shares) = match (record.action, record.symbol.as_ref(), record.shares) {
+                (JournalAction::Buy, Some(symbol), Some(shares)) => (symbol, shares),
+                (JournalAction::Sell, Some(symbol), Some(shares)) => (symbol, -shares),
+                _ => continue,
+            };
+
+            let current_shares = positions.entry(symbol.clone()).or_default();
+            *current_shares = (*current_shares + shares).normalize();
+
+            if current_shares.is_zero() {
+                positions.remove(symbol);
+            }
+        }
+
+        Ok(positions)
+    }
+
+    /// Returns the latest change which hasn't been undone yet.
+    pub fn get_undo_target(&self) -> GenericResult<Option<Record>> {
+        let mut undone = HashSet::new();
+
+        for record in self.load()?.into_iter().rev() {
+            if let Some(undone_id) = record.undone_id {
+                undone.insert(undone_id);
+                continue;
+            }
+
+            if !undone.contains(&record.id) {
+                return Ok(Some(record));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+fn parse_value(
+    id: i32, value: Option<String>, restrictions: DecimalRestrictions,
+) -> GenericResult<Option<Decimal>> {
+    Ok(match value {
+        Some(value) => Some(util::parse_decimal(&value, restrictions).map_err(|_| format!(
+            "Got an invalid journal record #{} from the database: {:?}", id, value))?),
+        None => None,
+    })
+}
+
+#[derive(StaticTable)]
+#[table(name="HistoryTable")]
+struct HistoryRow {
+    #[column(name="#")]
+    id: i32,
+    #[column(name="Time")]
+    time: String,
+    #[column(name="Action")]
+    action: String,
+    #[column(name="Symbol")]
+    symbol: Option<String>,
+    #[column(name="Shares")]
+    shares: Option<Decimal>,
+    #[column(name="Price", align="right")]
+    price: Option<String>,
+    #[column(name="Cash assets")]
+    cash_assets: Option<Decimal>,
+    #[column(name="Note")]
+    note: Option<String>,
+}
+
+pub fn print_history(records: &[Record], portfolio_currency: Option<&str>) {
+    let undone: HashSet<i32> = records.iter().filter_map(|record| record.undone_id).collect();
+    let mut table = HistoryTable::new();
+
+    for record in records {
+        let mut action = match record.action {
+            JournalAction::Sync => s!("sync"),
+            JournalAction::Buy => s!("buy"),
+            JournalAction::Sell => s!("sell"),
+            JournalAction::Cash => s!("cash"),
+            JournalAction::Undo => format!("undo #{}", record.undone_id.unwrap()),
+        };
+
+        if undone.contains(&record.id) {
+            action += " (undone)";
+        }
+
+        table.add_row(HistoryRow {
+            id: record.id,
+            time: record.time.format("%d.%m.%Y %H:%M:%S").to_string(),
+            action: action,
+            symbol: record.symbol.clone(),
+            shares: record.shares,
+            price: format_price(record, portfolio_currency),
+            cash_assets: record.cash_assets,
+            note: record.note.clone(),
+        });
+    }
+
+    table.print("Portfolio history");
+}
+
+// Trade price is stored only when it's specified explicitly. Otherwise it's estimated by cash
+// assets change (including commissions) and marked as estimated.
+fn format_price(record: &Record, portfolio_currency: Option<&str>) -> Option<String> {
+    if let Some(price) = record.price {
+        return Some(price.to_string());
+    }
+
+    let shares = match record.action {
+        JournalAction::Buy | JournalAction::Sell => record.shares?,
+        _ => return None,
+    };
+
+    let previous_assets = record.get_previous_assets().ok()?;
+    if previous_assets.cash.iter().count() != 1 {
+        return None;
+    }
+
+    let previous_cash = previous_assets.cash.get(portfolio_currency?)?;
+    let price = (previous_cash.amount - record.cash_assets?).abs() / shares;
+
+    Some(format!("~{}", currency::round_to(price, 4).normalize()))
+}
+
+#[derive(StaticTable)]
+#[table(name="DriftTable")]
+struct DriftRow {
+    #[column(name="Symbol")]
+    symbol: String,
+    #[column(name="Portfolio")]
+    portfolio: Decimal,
+    #[column(name="Broker")]
+    broker: Decimal,
+    #[column(name="Drift")]
+    drift: Decimal,
+}
+
+/// Compares the positions tracked by the journal with the actual ones reported by the broker and
+/// prints the difference if any.
+pub fn print_drift(tracked: &HashMap<String, Decimal>, actual: &HashMap<String, Decimal>) {
+    let mut symbols = BTreeSet::new();
+    symbols.extend(tracked.keys());
+    symbols.extend(actual.keys());
+
+    let mut table = DriftTable::new();
+
+    for symbol in symbols {
+        let tracked = tracked.get(symbol).cloned().unwrap_or_default();
+        let actual = actual.get(symbol).cloned().unwrap_or_default();
+
+        if tracked != actual {
+            table.add_row(DriftRow {
+                symbol: symbol.clone(),
+                portfolio: tracked,
+                broker: actual,
+                drift: (actual - tracked).normalize(),
+            });
+        }
+    }
+
+    if !table.is_empty() {
+        table.print("Drift between the portfolio and the broker statement");
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct AssetsSnapshot {
+    cash: BTreeMap<String, String>,
+    stocks: BTreeMap<String, String>,
+}
+
+fn serialize_assets(assets: &Assets) -> GenericResult<String> {
+    let snapshot = AssetsSnapshot {
+        cash: assets.cash.iter().map(|cash| (cash.currency.to_owned(), cash.amount.to_string())).collect(),
+        stocks: assets.stocks.iter().map(|(symbol, quantity)| (symbol.clone(), quantity.to_string())).collect(),
+    };
+    Ok(serde_json::to_string(&snapshot)?)
+}
+
+fn deserialize_assets(data: &str) -> GenericResult<Assets> {
+    let snapshot: AssetsSnapshot = serde_json::from_str(data)?;
+
+    let mut cash = MultiCurrencyCashAccount::new();
+    for (currency, amount) in snapshot.cash {
+        let amount = Decimal::from_str(&amount).map_err(|_| format!(
+            "Invalid cash amount: {:?}", amount))?;
+        cash.deposit(Cash::new(&currency, amount));
+    }
+
+    let mut stocks = HashMap::new();
+    for (symbol, quantity) in snapshot.stocks {
+        let quantity = util::parse_decimal(&quantity, DecimalRestrictions::StrictlyPositive)
+            .map_err(|_| format!("Invalid stock quantity: {:?}", quantity))?;
+        stocks.insert(symbol, quantity);
+    }
+
+    Ok(Assets::new(cash, stocks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_assets(cash_assets: Decimal, shares: Option<Decimal>) -> Assets {
+        let mut cash = MultiCurrencyCashAccount::new();
+        cash.deposit(Cash::new("RUB", cash_assets));
+
+        let mut stocks = HashMap::new();
+        if let Some(shares) = shares {
+            stocks.insert(s!("AAA"), shares);
+        }
+
+        Assets::new(cash, stocks)
+    }
+
+    fn trade(shares: Decimal, price: Option<Decimal>, cash_assets: Decimal) -> Operation<'static> {
+        Operation {
+            symbol: Some("AAA"),
+            shares: Some(shares),
+            price: price,
+            cash_assets: Some(cash_assets),
+            note: None,
+        }
+    }
+
+    #[test]
+    fn undo() {
+        let (_database, connection) = db::new_temporary();
+        let journal = Journal::new(connection.clone(), "portfolio");
+        let other_journal = Journal::new(connection.clone(), "other");
+        let buy = |shares, cash_assets| trade(shares, Some(dec!(10)), cash_assets);
+
+        let first = get_assets(dec!(1000), None);
+        let second = get_assets(dec!(900), Some(dec!(10)));
+        let third = get_assets(dec!(800), Some(dec!(20)));
+
+        assert!(journal.get_undo_target().unwrap().is_none());
+
+        journal.record(JournalAction::Buy, &buy(dec!(10), dec!(900)), &first, None).unwrap();
+        other_journal.record(JournalAction::Sync, &Operation::default(), &first, None).unwrap();
+        journal.record(JournalAction::Buy, &buy(dec!(10), dec!(800)), &second, None).unwrap();
+
+        let target = journal.get_undo_target().unwrap().unwrap();
+        assert_eq!(target.id, 3);
+        assert_eq!(target.shares, Some(dec!(10)));
+        assert_eq!(target.cash_assets, Some(dec!(800)));
+        assert_eq!(target.get_previous_assets().unwrap(), second);
+        journal.record(JournalAction::Undo, &Operation::default(), &third, Some(target.id)).unwrap();
+
+        let target = journal.get_undo_target().unwrap().unwrap();
+        assert_eq!(target.id, 1);
+        assert_eq!(target.get_previous_assets().unwrap(), first);
+        journal.record(JournalAction::Undo, &Operation::default(), &second, Some(target.id)).unwrap();
+
+        assert!(journal.get_undo_target().unwrap().is_none());
+        assert_eq!(journal.load().unwrap().len(), 4);
+        assert_eq!(other_journal.get_undo_target().unwrap().unwrap().id, 2);
+    }
+
+    #[test]
+    fn replay() {
+        let (_database, connection) = db::new_temporary();
+        let journal = Journal::new(connection.clone(), "portfolio");
+
+        let current = get_assets(dec!(1000), Some(dec!(1)));
+        assert_eq!(journal.replay_positions(&current).unwrap(), current.stocks);
+
+        let previous = get_assets(dec!(1000), None);
+        journal.record(JournalAction::Buy, &trade(dec!(10), None, dec!(900)), &previous, None).unwrap();
+        assert_eq!(journal.replay_positions(&current).unwrap(), hashmap!{s!("AAA") => dec!(10)});
+
+        let previous = get_assets(dec!(900), Some(dec!(10)));
+        journal.record(JournalAction::Sync, &Operation::default(), &previous, None).unwrap();
+        assert_eq!(journal.replay_positions(&current).unwrap(), current.stocks);
+
+        // The broker has reported 20 shares on sync
+        let previous = get_assets(dec!(900), Some(dec!(20)));
+        journal.record(JournalAction::Buy, &trade(dec!(5), None, dec!(850)), &previous, None).unwrap();
+
+        let previous = get_assets(dec!(850), Some(dec!(25)));
+        journal.record(JournalAction::Sell, &trade(dec!(25), None, dec!(1100)), &previous, None).unwrap();
+        assert!(journal.replay_positions(&current).unwrap().is_empty());
+
+        let previous = get_assets(dec!(1100), None);
+        journal.record(JournalAction::Undo, &Operation::default(), &previous, Some(4)).unwrap();
+        assert_eq!(journal.replay_positions(&current).unwrap(), hashmap!{s!("AAA") => dec!(25)});
+
+        let records = journal.load().unwrap();
+        assert_eq!(records[2].price, None);
+        assert_eq!(format_price(&records[2], Some("RUB")), Some(s!("~10")));
+        assert_eq!(format_price(&records[2], None), None);
+        assert_eq!(format_price(&records[1], Some("RUB")), None);
+    }
+}
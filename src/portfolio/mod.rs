@@ -1,23 +1,30 @@
 use std::collections::hash_map::Entry;
 use std::rc::Rc;
 
+use diesel::Connection as ConnectionTrait;
+
 use crate::broker_statement::BrokerStatement;
 use crate::config::{Config, PortfolioConfig};
-use crate::core::EmptyResult;
+use crate::core::{EmptyResult, GenericError, GenericResult};
 use crate::currency::Cash;
 use crate::currency::converter::CurrencyConverter;
-use crate::db;
+use crate::db::{self, schema::{CurrencyRateSource, JournalAction}};
+use crate::localities;
 use crate::quotes::Quotes;
 use crate::types::Decimal;
 
 use self::asset_allocation::Portfolio;
 use self::assets::Assets;
-use self::formatting::print_portfolio;
+use self::formatting::{print_portfolio, print_drifted_assets};
+use self::journal::{Journal, Operation};
 
 mod asset_allocation;
 mod assets;
 mod formatting;
+mod journal;
 mod rebalancing;
+mod tax_aware;
+mod tolerance;
 
 pub fn sync(config: &Config, portfolio_name: &str) -> EmptyResult {
     let portfolio = config.get_portfolio(portfolio_name)?;
@@ -31,13 +38,34 @@ pub fn sync(config: &Config, portfolio_name: &str) -> EmptyResult {
 
     let assets = Assets::new(statement.cash_assets, statement.open_positions);
     assets.validate(&portfolio)?;
-    assets.save(database, &portfolio.name)?;
 
-    Ok(())
+    let journal = Journal::new(database.clone(), &portfolio.name);
+    let previous_assets = Assets::load(database.clone(), &portfolio.name)?;
+
+    if !previous_assets.cash.is_empty() || !previous_assets.stocks.is_empty() {
+        let tracked_positions = journal.replay_positions(&previous_assets)?;
+        journal::print_drift(&tracked_positions, &assets.stocks);
+    }
+
+    database.transaction::<_, GenericError, _>(|| {
+        assets.save(database.clone(), &portfolio.name)?;
+        journal.record(JournalAction::Sync, &Operation::default(), &previous_assets, None)
+    })
 }
 
-pub fn buy(config: &Config, portfolio_name: &str, shares: Decimal, symbol: &str, cash_assets: Decimal) -> EmptyResult {
-    modify_assets(config, portfolio_name, |portfolio, assets| {
+pub fn buy(
+    config: &Config, portfolio_name: &str, shares: Decimal, symbol: &str, cash_assets: Decimal,
+    price: Option<Decimal>, note: Option<&str>,
+) -> EmptyResult {
+    let operation = Operation {
+        symbol: Some(symbol),
+        shares: Some(shares),
+        price: price,
+        cash_assets: Some(cash_assets),
+        note: note,
+    };
+
+    modify_assets(config, portfolio_name, JournalAction::Buy, operation, |portfolio, assets| {
         if portfolio.get_stock_symbols().get(symbol).is_none() {
             return Err!("Unable to buy {}: it's not specified in asset allocation configuration",
                 symbol);
@@ -51,8 +79,19 @@ pub fn buy(config: &Config, portfolio_name: &str, shares: Decimal, symbol: &str,
     })
 }
 
-pub fn sell(config: &Config, portfolio_name: &str, shares: Decimal, symbol: &str, cash_assets: Decimal) -> EmptyResult {
-    modify_assets(config, portfolio_name, |portfolio, assets| {
+pub fn sell(
+    config: &Config, portfolio_name: &str, shares: Decimal, symbol: &str, cash_assets: Decimal,
+    price: Option<Decimal>, note: Option<&str>,
+) -> EmptyResult {
+    let operation = Operation {
+        symbol: Some(symbol),
+        shares: Some(shares),
+        price: price,
+        cash_assets: Some(cash_assets),
+        note: note,
+    };
+
+    modify_assets(config, portfolio_name, JournalAction::Sell, operation, |portfolio, assets| {
         let mut entry = match assets.stocks.entry(symbol.to_owned()) {
             Entry::Occupied(entry) => entry,
             Entry::Vacant(_) => return Err!("The portfolio has no open {} positions", symbol),
@@ -74,23 +113,68 @@ pub fn sell(config: &Config, portfolio_name: &str, shares: Decimal, symbol: &str
     })
 }
 
-pub fn set_cash_assets(config: &Config, portfolio_name: &str, cash_assets: Decimal) -> EmptyResult {
-    modify_assets(config, portfolio_name, |portfolio, assets| {
+pub fn set_cash_assets(
+    config: &Config, portfolio_name: &str, cash_assets: Decimal, note: Option<&str>,
+) -> EmptyResult {
+    let operation = Operation {
+        cash_assets: Some(cash_assets),
+        note: note,
+        ..Default::default()
+    };
+
+    modify_assets(config, portfolio_name, JournalAction::Cash, operation, |portfolio, assets| {
         set_cash_assets_impl(portfolio, assets, cash_assets)
     })
 }
 
-fn modify_assets<F>(config: &Config, portfolio_name: &str, modify: F) -> EmptyResult
+pub fn history(config: &Config, portfolio_name: &str) -> EmptyResult {
+    let portfolio = config.get_portfolio(portfolio_name)?;
+    let database = db::connect(&config.db_path)?;
+
+    let records = Journal::new(database, &portfolio.name).load()?;
+    if records.is_empty() {
+        return Err!("The portfolio has no history yet");
+    }
+
+    journal::print_history(&records, portfolio.currency.as_deref());
+    Ok(())
+}
+
+pub fn undo(config: &Config, portfolio_name: &str) -> EmptyResult {
+    let portfolio = config.get_portfolio(portfolio_name)?;
+    let database = db::connect(&config.db_path)?;
+    let journal = Journal::new(database.clone(), &portfolio.name);
+
+    let record = journal.get_undo_target()?.ok_or(
+        "The portfolio has no changes to undo")?;
+
+    let current_assets = Assets::load(database.clone(), &portfolio.name)?;
+    let assets = record.get_previous_assets()?;
+    assets.validate(&portfolio)?;
+
+    database.transaction::<_, GenericError, _>(|| {
+        assets.save(database.clone(), &portfolio.name)?;
+        journal.record(JournalAction::Undo, &Operation::default(), &current_assets, Some(record.id))
+    })
+}
+
+fn modify_assets<F>(
+    config: &Config, portfolio_name: &str, action: JournalAction, operation: Operation, modify: F,
+) -> EmptyResult
     where F: Fn(&PortfolioConfig, &mut Assets) -> EmptyResult
 {
     let portfolio = config.get_portfolio(portfolio_name)?;
     let database = db::connect(&config.db_path)?;
+    let journal = Journal::new(database.clone(), &portfolio.name);
 
-    let mut assets = Assets::load(database.clone(), &portfolio.name)?;
+    let previous_assets = Assets::load(database.clone(), &portfolio.name)?;
+    let mut assets = previous_assets.clone();
     modify(portfolio, &mut assets)?;
-    assets.save(database, &portfolio.name)?;
 
-    Ok(())
+    database.transaction::<_, GenericError, _>(|| {
+        assets.save(database.clone(), &portfolio.name)?;
+        journal.record(action, &operation, &previous_assets, None)
+    })
 }
 
 fn set_cash_assets_impl(portfolio: &PortfolioConfig, assets: &mut Assets, cash_assets: Decimal) -> EmptyResult {
@@ -104,29 +188,253 @@ fn set_cash_assets_impl(portfolio: &PortfolioConfig, assets: &mut Assets, cash_a
 }
 
 pub fn show(config: &Config, portfolio_name: &str, flat: bool) -> EmptyResult {
-    process(config, portfolio_name, false, flat)
+    process(config, portfolio_name, false, None, flat)
 }
 
-pub fn rebalance(config: &Config, portfolio_name: &str, flat: bool) -> EmptyResult {
-    process(config, portfolio_name, true, flat)
+/// Rebalances the portfolio. If cash flow is specified, only distributes the contribution
+/// (positive amount) or withdrawal (negative amount) between the assets.
+pub fn rebalance(
+    config: &Config, portfolio_name: &str, cash_flow: Option<Decimal>, account: Option<&str>,
+    flat: bool,
+) -> EmptyResult {
+    process(config, portfolio_name, true, cash_flow.map(|amount| (amount, account)), flat)
+}
+
+/// Checks whether any of the portfolio assets has drifted out of its tolerance band. Returns false
+/// and prints the drifted assets if so.
+pub fn check(config: &Config, portfolio_name: &str) -> GenericResult<bool> {
+    let database = db::connect(&config.db_path)?;
+
+    let source = get_currency_rate_source(config, portfolio_name)?;
+    let quotes = Rc::new(Quotes::new(&config, database.clone())?);
+    let converter = CurrencyConverter::new(database.clone(), source, Some(quotes.clone()), false);
+
+    let (portfolio, _) = load_portfolio(config, portfolio_name, database, &converter, &quotes)?;
+
+    let drifted_assets = tolerance::find_drifted_assets(&portfolio);
+    if drifted_assets.is_empty() {
+        return Ok(true);
+    }
+
+    print_drifted_assets(&portfolio.name, &drifted_assets);
+    Ok(false)
 }
 
-fn process(config: &Config, portfolio_name: &str, rebalance: bool, flat: bool) -> EmptyResult {
-    let portfolio_config = config.get_portfolio(portfolio_name)?;
+fn process(
+    config: &Config, portfolio_name: &str, rebalance: bool, cash_flow: Option<(Decimal, Option<&str>)>,
+    flat: bool,
+) -> EmptyResult {
     let database = db::connect(&config.db_path)?;
 
+    let source = get_currency_rate_source(config, portfolio_name)?;
     let quotes = Rc::new(Quotes::new(&config, database.clone())?);
-    let converter = CurrencyConverter::new(database.clone(), Some(quotes.clone()), false);
+    let converter = CurrencyConverter::new(database.clone(), source, Some(quotes.clone()), false);
 
-    let assets = Assets::load(database, &portfolio_config.name)?;
-    assets.validate(&portfolio_config)?;
+    let (mut portfolio, portfolio_configs) = load_portfolio(
+        config, portfolio_name, database, &converter, &quotes)?;
 
-    let mut portfolio = Portfolio::load(config, portfolio_config, assets, &converter, &quotes)?;
     if rebalance {
+        match cash_flow {
+            Some((amount, account)) => portfolio.apply_cash_flow(amount, account)?,
+            None => tolerance::restrict_in_band_assets(&mut portfolio),
+        };
+
+        if portfolio_configs.iter().any(|portfolio| portfolio.tax_aware_rebalancing.is_some()) {
+            tax_aware::restrict_taxable_sells(config, &mut portfolio, &portfolio_configs, &converter)?;
+        }
+
         rebalancing::rebalance_portfolio(&mut portfolio, &converter)?;
     }
 
-    print_portfolio(portfolio, flat);
+    print_portfolio(portfolio, flat, &converter)
+}
 
-    Ok(())
-}
\ No newline at end of file
+// Virtual portfolio's accounts may belong to different tax countries, so currency rate source is
+// determined by all its accounts
+fn get_currency_rate_source(config: &Config, portfolio_name: &str) -> GenericResult<CurrencyRateSource> {
+    let portfolio_configs = match config.get_virtual_portfolio(portfolio_name) {
+        Some(virtual_portfolio_config) => {
+            let mut portfolio_configs = Vec::new();
+            for name in &virtual_portfolio_config.portfolios {
+                portfolio_configs.push(config.get_portfolio(name)?);
+            }
+            portfolio_configs
+        },
+        None => vec![config.get_portfolio(portfolio_name)?],
+    };
+
+    let mut source = None;
+
+    for portfolio_config in portfolio_configs {
+        let account_source = portfolio_config.get_currency_rate_source();
+
+        match source {
+            Some(source) if source != account_source => return Err!(
+                "{:?} portfolio consists of accounts with different currency rate sources",
+                portfolio_name),
+            _ => source = Some(account_source),
+        }
+    }
+
+    Ok(source.unwrap_or_else(|| localities::russia().currency_rate_source))
+}
+
+// Loads the portfolio (real or virtual) and configs of all its accounts
+fn load_portfolio<'a>(
+    config: &'a Config, portfolio_name: &str, database: db::Connection,
+    converter: &CurrencyConverter, quotes: &Quotes,
+) -> GenericResult<(Portfolio, Vec<&'a PortfolioConfig>)> {
+    Ok(match config.get_virtual_portfolio(portfolio_name) {
+        Some(virtual_portfolio_config) => {
+            let mut portfolios = Vec::new();
+            let mut portfolio_configs = Vec::new();
+
+            for name in &virtual_portfolio_config.portfolios {
+                let portfolio_config = config.get_portfolio(name)?;
+
+                let assets = Assets::load(database.clone(), &portfolio_config.name)?;
+                assets.validate(&portfolio_config)?;
+
+                portfolios.push((portfolio_config, assets));
+                portfolio_configs.push(portfolio_config);
+            }
+
+            let portfolio = Portfolio::load_virtual(
+                config, virtual_portfolio_config, portfolios, converter, quotes)?;
+
+            (portfolio, portfolio_configs)
+        },
+        None => {
+            let portfolio_config = config.get_portfolio(portfolio_name)?;
+
+            let assets = Assets::load(database, &portfolio_config.name)?;
+            assets.validate(&portfolio_config)?;
+
+            let portfolio = Portfolio::load(config, portfolio_config, assets, converter, quotes)?;
+            (portfolio, vec![portfolio_config])
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs::File;
+    use std::io::Write;
+
+    use indoc::indoc;
+
+    use crate::config::QuotesProviderType;
+    use crate::currency::MultiCurrencyCashAccount;
+
+    use super::*;
+
+    #[test]
+    fn virtual_portfolio() {
+        let (database_file, database) = db::new_temporary();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let prices_path = temp_dir.path().join("prices.yaml");
+        File::create(&prices_path).unwrap().write_all(indoc!("
+            FXUS: {price: 3320, currency: RUB}
+            FXRU: {price: 1040, currency: RUB}
+        ").as_bytes()).unwrap();
+
+        let mut config = Config::mock();
+        config.db_path = database_file.path().to_str().unwrap().to_owned();
+        config.quotes.local_file = Some(prices_path.to_str().unwrap().to_owned());
+        config.quotes.providers = Some(vec![QuotesProviderType::Local]);
+
+        config.portfolios = serde_yaml::from_str(indoc!("
+            - name: first
+              broker: tinkoff
+              statements: /mock
+              currency: RUB
+              assets:
+                - {name: Stocks, symbol: FXUS, weight: 100%}
+
+            - name: second
+              broker: open-broker
+              statements: /mock
+              currency: RUB
+              assets:
+                - {name: Stocks, symbol: FXRU, weight: 100%}
+        ")).unwrap();
+
+        config.virtual_portfolios = serde_yaml::from_str(indoc!("
+            - name: virtual
+              portfolios: [first, second]
+              currency: RUB
+              assets:
+                - {name: US stocks, symbol: FXUS, weight: 50%}
+                - {name: Russian stocks, symbol: FXRU, weight: 50%}
+        ")).unwrap();
+
+        for (name, symbol) in &[("first", "FXUS"), ("second", "FXRU")] {
+            let mut cash = MultiCurrencyCashAccount::new();
+            cash.deposit(Cash::new("RUB", dec!(100000)));
+
+            let mut stocks = HashMap::new();
+            stocks.insert(symbol.to_string(), dec!(10));
+
+            Assets::new(cash, stocks).save(database.clone(), name).unwrap();
+        }
+
+        assert_eq!(
+            get_currency_rate_source(&config, "virtual").unwrap(),
+            localities::russia().currency_rate_source);
+
+        process(&config, "virtual", false, None, false).unwrap();
+        process(&config, "virtual", true, None, false).unwrap();
+    }
+
+    #[test]
+    fn currency_rate_source() {
+        let mut config = Config::mock();
+
+        config.portfolios = serde_yaml::from_str(indoc!("
+            - name: first
+              broker: interactive-brokers
+              statements: /mock
+              currency_rate_source: ecb
+
+            - name: second
+              broker: firstrade
+              statements: /mock
+              currency_rate_source: ecb
+
+            - name: third
+              broker: tinkoff
+              statements: /mock
+        ")).unwrap();
+
+        config.virtual_portfolios = serde_yaml::from_str(indoc!("
+            - name: same
+              portfolios: [first, second]
+              currency: EUR
+              assets: []
+
+            - name: different
+              portfolios: [first, third]
+              currency: EUR
+              assets: []
+        ")).unwrap();
+
+        for &(name, source) in &[
+            ("first", CurrencyRateSource::Ecb),
+            ("third", CurrencyRateSource::Cbr),
+            ("same", CurrencyRateSource::Ecb),
+        ] {
+            assert_eq!(get_currency_rate_source(&config, name).unwrap(), source);
+        }
+
+        assert!(get_currency_rate_source(&config, "different").is_err());
+
+        assert!(serde_yaml::from_str::<Vec<PortfolioConfig>>(indoc!("
+            - name: invalid
+              broker: tinkoff
+              statements: /mock
+              currency_rate_source: xxx
+        ")).is_err());
+    }
+}
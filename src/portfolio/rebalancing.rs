@@ -3,14 +3,14 @@ use std::collections::HashSet;
 use log::{self, log_enabled, debug};
 use num_traits::Zero;
 
-use crate::brokers::BrokerInfo;
 use crate::commissions::CommissionCalc;
 use crate::core::{GenericResult, EmptyResult};
+use crate::currency::Cash;
 use crate::currency::converter::CurrencyConverter;
 use crate::types::{Decimal, TradeType};
 use crate::util;
 
-use super::asset_allocation::{Portfolio, AssetAllocation, Holding, StockHolding};
+use super::asset_allocation::{Portfolio, Account, AssetAllocation, Holding, StockHolding};
 
 pub fn rebalance_portfolio(portfolio: &mut Portfolio, converter: &CurrencyConverter) -> EmptyResult {
     // The first step is bottom-up and calculates strict limits on asset min/max value
@@ -26,7 +26,7 @@ pub fn rebalance_portfolio(portfolio: &mut Portfolio, converter: &CurrencyConver
 
     // The next step is bottom-up and calculates the result of the previous step
     let target_value = calculate_result_value(
-        &mut portfolio.assets, &portfolio.broker, &portfolio.currency, converter)?;
+        &mut portfolio.assets, &portfolio.accounts, &portfolio.currency, converter)?;
     portfolio.target_cash_assets = portfolio.total_value - target_value;
 
     let (interim_trade_commissions, interim_additional_commissions) =
@@ -35,12 +35,14 @@ pub fn rebalance_portfolio(portfolio: &mut Portfolio, converter: &CurrencyConver
     let interim_total_commissions = interim_trade_commissions + interim_additional_commissions;
     assert!(portfolio.commissions.is_zero());
     portfolio.change_commission(interim_total_commissions);
+    calculate_accounts_result(portfolio, converter)?;
 
     // The rebalancing logic is relatively inaccurate because it distributes funds only inside of
     // one group which leads to accumulation of free cash / debt from each asset group. Also it
     // can't take into account accumulated commissions. The following step operates on all levels of
     // asset tree and distributes accumulated free cash / debt in the optimal way according to asset
-    // allocation configuration.
+    // allocation configuration. Cash can't be moved between the accounts, so it also covers debt
+    // of each account by selling its own assets and buys assets only for the account's free cash.
     distribute_cash_assets(portfolio, converter)?;
 
     let (trade_commissions, additional_commissions) = calculate_total_commissions(portfolio, converter)?;
@@ -50,7 +52,8 @@ pub fn rebalance_portfolio(portfolio: &mut Portfolio, converter: &CurrencyConver
     );
     portfolio.change_commission(additional_commissions - interim_additional_commissions);
 
-    Ok(())
+    calculate_accounts_result(portfolio, converter)?;
+    check_accounts_cash(portfolio)
 }
 
 fn calculate_restrictions(assets: &mut Vec<AssetAllocation>) -> (Decimal, Option<Decimal>) {
@@ -344,7 +347,7 @@ struct AssetGroupRebalancingState {
 }
 
 fn calculate_result_value(
-    assets: &mut Vec<AssetAllocation>, broker: &BrokerInfo,
+    assets: &mut Vec<AssetAllocation>, accounts: &[Account],
     currency: &str, converter: &CurrencyConverter
 ) -> GenericResult<Decimal> {
     let mut total_value = dec!(0);
@@ -355,11 +358,11 @@ fn calculate_result_value(
         total_value += match asset.holding {
             Holding::Stock(ref mut holding) => {
                 assert_eq!(holding.target_shares, holding.current_shares);
-                change_to(&name, holding, asset.target_value, broker, currency, converter)?;
+                change_to(&name, holding, asset.target_value, accounts, currency, converter)?;
                 asset.target_value
             },
             Holding::Group(ref mut holdings) => {
-                calculate_result_value(holdings, broker, currency, converter)?
+                calculate_result_value(holdings, accounts, currency, converter)?
             },
         };
     }
@@ -371,49 +374,96 @@ fn distribute_cash_assets(portfolio: &mut Portfolio, converter: &CurrencyConvert
     debug!("");
     debug!("Cash assets distribution:");
 
-    for trade_type in [TradeType::Sell, TradeType::Buy].iter().cloned() {
+    // At first cover debt of the whole portfolio and then debt of each of its accounts which can
+    // be covered only by selling the account's own assets
+    let mut sell_scopes = vec![None];
+    if portfolio.accounts.len() > 1 {
+        sell_scopes.extend((0..portfolio.accounts.len()).map(Some));
+    }
+
+    for account in sell_scopes {
         loop {
-            let free_cash_assets = portfolio.target_cash_assets - portfolio.min_cash_assets;
+            let free_cash_assets = match account {
+                Some(index) => get_account_free_cash_assets(&portfolio.accounts[index]),
+                None => portfolio.target_cash_assets - portfolio.min_cash_assets,
+            };
 
-            if !match trade_type {
-                TradeType::Sell => free_cash_assets.is_sign_negative(),
-                TradeType::Buy => free_cash_assets.is_sign_positive(),
-            } {
+            if !free_cash_assets.is_sign_negative() {
                 break;
             }
 
             let expected_total_value = portfolio.total_value - portfolio.min_cash_assets;
 
             let trade = find_assets_for_cash_distribution(
-                trade_type, &portfolio.assets, expected_total_value, free_cash_assets,
-                portfolio.min_trade_volume);
+                TradeType::Sell, &portfolio.assets, &portfolio.accounts, account,
+                expected_total_value, free_cash_assets, portfolio.min_trade_volume);
 
-            let trade = match trade {
-                Some(trade) => trade,
+            match trade {
+                Some(trade) => apply_trade(portfolio, trade, converter)?,
                 None => break,
             };
+        }
+    }
 
-            portfolio.target_cash_assets -= trade.volume;
+    loop {
+        let free_cash_assets = portfolio.target_cash_assets - portfolio.min_cash_assets;
+        if !free_cash_assets.is_sign_positive() {
+            break;
+        }
 
-            let commission = process_trade(
-                &mut portfolio.assets, trade, &portfolio.broker, &portfolio.currency, converter)?;
+        let expected_total_value = portfolio.total_value - portfolio.min_cash_assets;
 
-            portfolio.change_commission(commission);
-        }
+        let trade = find_assets_for_cash_distribution(
+            TradeType::Buy, &portfolio.assets, &portfolio.accounts, None,
+            expected_total_value, free_cash_assets, portfolio.min_trade_volume);
+
+        match trade {
+            Some(trade) => apply_trade(portfolio, trade, converter)?,
+            None => break,
+        };
     }
 
     Ok(())
 }
 
+fn apply_trade(portfolio: &mut Portfolio, trade: PossibleTrade, converter: &CurrencyConverter) -> EmptyResult {
+    let account = get_trade_account(&portfolio.assets, &trade.path);
+    let volume = trade.volume;
+
+    let commission = process_trade(
+        &mut portfolio.assets, trade, &portfolio.accounts, &portfolio.currency, converter)?;
+
+    portfolio.target_cash_assets -= volume;
+    portfolio.change_commission(commission);
+    portfolio.accounts[account].target_cash_assets -= volume + commission;
+
+    Ok(())
+}
+
+fn get_trade_account(assets: &[AssetAllocation], path: &[usize]) -> usize {
+    let (&index, path) = path.split_last().unwrap();
+
+    match assets[index].holding {
+        Holding::Stock(ref holding) => holding.account,
+        Holding::Group(ref holdings) => get_trade_account(holdings, path),
+    }
+}
+
+fn get_account_free_cash_assets(account: &Account) -> Decimal {
+    account.target_cash_assets - account.min_cash_assets
+}
+
 struct PossibleTrade {
     path: Vec<usize>,
     volume: Decimal,
     result: Decimal,
 }
 
+// Finds the best trade for cash distribution. The trades may be limited to the specified account.
+// Buying is also limited by free cash of the account which holds the asset.
 fn find_assets_for_cash_distribution(
-    trade_type: TradeType, assets: &[AssetAllocation], expected_total_value: Decimal,
-    cash_assets: Decimal, min_trade_volume: Decimal
+    trade_type: TradeType, assets: &[AssetAllocation], accounts: &[Account], account: Option<usize>,
+    expected_total_value: Decimal, cash_assets: Decimal, min_trade_volume: Decimal
 ) -> Option<PossibleTrade> {
     let mut best_trade: Option<PossibleTrade> = None;
 
@@ -421,13 +471,21 @@ fn find_assets_for_cash_distribution(
         let expected_value = expected_total_value * asset.expected_weight;
 
         let trade = match asset.holding {
-            Holding::Stock(_) => {
+            Holding::Stock(ref holding) => {
+                if account.is_some() && account != Some(holding.account) {
+                    continue;
+                }
+
+                let cash_assets = std::cmp::min(
+                    cash_assets, get_account_free_cash_assets(&accounts[holding.account]));
+
                 calculate_min_trade_volume(
                     trade_type, asset, expected_value, cash_assets, min_trade_volume)
             },
             Holding::Group(ref holdings) => {
                 let mut trade = find_assets_for_cash_distribution(
-                    trade_type, holdings, expected_value, cash_assets, min_trade_volume);
+                    trade_type, holdings, accounts, account, expected_value, cash_assets,
+                    min_trade_volume);
 
                 if let Some(ref mut trade) = trade {
                     trade.result = calculate_trade_result(
@@ -454,7 +512,7 @@ fn find_assets_for_cash_distribution(
 
 fn process_trade(
     assets: &mut Vec<AssetAllocation>, mut trade: PossibleTrade,
-    broker: &BrokerInfo, currency: &str, converter: &CurrencyConverter
+    accounts: &[Account], currency: &str, converter: &CurrencyConverter
 ) -> GenericResult<Decimal> {
     let index = trade.path.pop().unwrap();
     let asset = &mut assets[index];
@@ -470,10 +528,10 @@ fn process_trade(
                    name=name, prev_target_value=asset.target_value.normalize(),
                    target_value=target_value.normalize());
 
-            change_to(&name, holding, target_value, broker, currency, converter)?
+            change_to(&name, holding, target_value, accounts, currency, converter)?
         },
         Holding::Group(ref mut holdings) => {
-            process_trade(holdings, trade, broker, currency, converter)?
+            process_trade(holdings, trade, accounts, currency, converter)?
         },
     };
 
@@ -551,8 +609,10 @@ fn get_best_trade(trade_type: TradeType, best_trade: Option<PossibleTrade>, trad
 
 fn change_to(
     name: &str, holding: &mut StockHolding, target_value: Decimal,
-    broker: &BrokerInfo, currency: &str, converter: &CurrencyConverter
+    accounts: &[Account], currency: &str, converter: &CurrencyConverter
 ) -> GenericResult<Decimal> {
+    let broker = &accounts[holding.account].broker;
+
     let calculate_commission = |target_shares| -> GenericResult<Decimal> {
         // We use a temporary calculator because we can work only with non-accumulated commissions
         // here since the returned commission difference can't be calculated for accumulated
@@ -595,43 +655,113 @@ fn calculate_target_commission(
 }
 
 fn calculate_total_commissions(portfolio: &Portfolio, converter: &CurrencyConverter) -> GenericResult<(Decimal, Decimal)> {
-    let mut commission_calc = CommissionCalc::new(portfolio.broker.commission_spec.clone());
+    let mut total_trade_commissions = dec!(0);
+    let mut total_additional_commissions = dec!(0);
+
+    for (trade_commissions, additional_commissions) in calculate_commissions(portfolio, converter)? {
+        total_trade_commissions += trade_commissions;
+        total_additional_commissions += additional_commissions;
+    }
+
+    Ok((total_trade_commissions, total_additional_commissions))
+}
+
+// Calculates trade and additional commissions for each of the portfolio accounts
+fn calculate_commissions(portfolio: &Portfolio, converter: &CurrencyConverter) -> GenericResult<Vec<(Decimal, Decimal)>> {
+    let mut commission_calcs: Vec<CommissionCalc> = portfolio.accounts.iter()
+        .map(|account| CommissionCalc::new(account.broker.commission_spec.clone()))
+        .collect();
 
-    let trade_commissions = calculate_trade_commissions(
-        &portfolio.assets, &mut commission_calc, &portfolio.currency, converter)?;
+    let mut trade_commissions = vec![dec!(0); portfolio.accounts.len()];
+    calculate_trade_commissions(
+        &portfolio.assets, &mut commission_calcs, &mut trade_commissions, &portfolio.currency,
+        converter)?;
 
     let date = util::today_trade_conclusion_date();
-    let mut additional_commissions = dec!(0);
+    let mut commissions = Vec::new();
 
-    for &commission in commission_calc.calculate().values() {
-        additional_commissions += converter.convert_to(date, commission, &portfolio.currency)?;
+    for (commission_calc, trade_commissions) in commission_calcs.iter_mut().zip(trade_commissions) {
+        let mut additional_commissions = dec!(0);
+
+        for &commission in commission_calc.calculate().values() {
+            additional_commissions += converter.convert_to(date, commission, &portfolio.currency)?;
+        }
+
+        commissions.push((trade_commissions, additional_commissions));
     }
 
-    Ok((trade_commissions, additional_commissions))
+    Ok(commissions)
 }
 
 fn calculate_trade_commissions(
-    assets: &[AssetAllocation], commission_calc: &mut CommissionCalc,
-    currency: &str, converter: &CurrencyConverter,
-) -> GenericResult<Decimal> {
-    let mut trade_commissions = dec!(0);
-
+    assets: &[AssetAllocation], commission_calcs: &mut [CommissionCalc],
+    trade_commissions: &mut [Decimal], currency: &str, converter: &CurrencyConverter,
+) -> EmptyResult {
     for asset in assets {
         match &asset.holding {
             Holding::Stock(holding) => {
-                trade_commissions += calculate_target_commission(
-                    &asset.full_name(), holding, holding.target_shares, commission_calc,
-                    currency, converter,
+                trade_commissions[holding.account] += calculate_target_commission(
+                    &asset.full_name(), holding, holding.target_shares,
+                    &mut commission_calcs[holding.account], currency, converter,
                 )?;
             },
             Holding::Group(assets) => {
-                trade_commissions += calculate_trade_commissions(
-                    assets, commission_calc, currency, converter)?;
+                calculate_trade_commissions(
+                    assets, commission_calcs, trade_commissions, currency, converter)?;
             },
         }
     }
 
-    Ok(trade_commissions)
+    Ok(())
+}
+
+// Distributes the rebalancing result between the portfolio accounts
+fn calculate_accounts_result(portfolio: &mut Portfolio, converter: &CurrencyConverter) -> EmptyResult {
+    let commissions = calculate_commissions(portfolio, converter)?;
+
+    for (account, (trade_commissions, additional_commissions)) in portfolio.accounts.iter_mut().zip(commissions) {
+        account.commissions = trade_commissions + additional_commissions;
+        account.target_cash_assets = account.current_cash_assets - account.commissions;
+    }
+
+    calculate_accounts_trades(&portfolio.assets, &mut portfolio.accounts);
+
+    Ok(())
+}
+
+// Cash distribution sells the account's assets to cover its debt, but it may be impossible due to
+// the restrictions
+fn check_accounts_cash(portfolio: &Portfolio) -> EmptyResult {
+    if portfolio.accounts.len() < 2 {
+        return Ok(());
+    }
+
+    for account in &portfolio.accounts {
+        if account.target_cash_assets < account.min_cash_assets {
+            let shortage = Cash::new(
+                &portfolio.currency, account.min_cash_assets - account.target_cash_assets);
+
+            return Err!(concat!(
+                "Unable to rebalance {:?} portfolio: {:?} account lacks {} of cash assets to ",
+                "perform the trades. Add funds to the account or allow selling in it."
+            ), portfolio.name, account.name, shortage.round());
+        }
+    }
+
+    Ok(())
+}
+
+fn calculate_accounts_trades(assets: &[AssetAllocation], accounts: &mut [Account]) {
+    for asset in assets {
+        match &asset.holding {
+            Holding::Stock(holding) => {
+                accounts[holding.account].target_cash_assets -= asset.target_value - asset.current_value;
+            },
+            Holding::Group(assets) => {
+                calculate_accounts_trades(assets, accounts);
+            },
+        }
+    }
 }
 
 fn calculate_min_sell_volume(asset: &AssetAllocation, min_trade_volume: Decimal) -> Option<Decimal> {
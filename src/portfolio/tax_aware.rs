@@ -0,0 +1,325 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::Datelike;
+use log::debug;
+
+use crate::broker_statement::BrokerStatement;
+use crate::commissions::CommissionCalc;
+use crate::config::{Config, PortfolioConfig};
+use crate::core::{EmptyResult, GenericResult};
+use crate::currency::Cash;
+use crate::currency::converter::CurrencyConverter;
+use crate::db;
+use crate::localities::Country;
+use crate::tax_statement::LossCarryforward;
+use crate::taxes::iia;
+use crate::types::Decimal;
+use crate::util;
+
+use super::asset_allocation::{Portfolio, AssetAllocation, Holding};
+
+struct SellCandidate {
+    account: usize,
+    symbol: String,
+    shares: Decimal,
+    price: Cash,
+}
+
+// Emulated sell results in local currency
+#[derive(Debug)]
+struct EmulatedSell {
+    symbol: String,
+    volume: Decimal,
+    profit: Decimal,
+}
+
+/// Restricts selling of overweight stocks which leads to tax payment exceeding the configured
+/// threshold. This way rebalancing prefers buying of underweight assets over selling of overweight
+/// ones.
+pub fn restrict_taxable_sells(
+    config: &Config, portfolio: &mut Portfolio, portfolio_configs: &[&PortfolioConfig],
+    converter: &CurrencyConverter,
+) -> EmptyResult {
+    assert_eq!(portfolio_configs.len(), portfolio.accounts.len());
+
+    let mut candidates = Vec::new();
+    find_sell_candidates(
+        &portfolio.assets, portfolio.total_value - portfolio.min_cash_assets, portfolio_configs,
+        &mut candidates);
+
+    let database = db::connect(&config.db_path)?;
+    let today = util::today();
+    let mut taxable_symbols = HashSet::new();
+
+    for (account, portfolio_config) in portfolio_configs.iter().enumerate() {
+        let tax_cost_threshold = match portfolio_config.tax_aware_rebalancing {
+            Some(ref config) => config.tax_cost_threshold,
+            None => continue,
+        };
+
+        let account_candidates: Vec<&SellCandidate> = candidates.iter()
+            .filter(|candidate| candidate.account == account)
+            .collect();
+
+        if account_candidates.is_empty() {
+            continue;
+        }
+
+        // Assume that individual investment account is going to be closed right now
+        if portfolio_config.iia.as_ref().map_or(false, |iia| {
+            iia::is_trading_income_exempt(iia, iia.close_date.unwrap_or(today))
+        }) {
+            continue;
+        }
+
+        let country = portfolio_config.get_tax_country();
+        let (tax_base, sells) = emulate_sells(config, portfolio_config, &account_candidates, converter)?;
+
+        // Tax agents calculate the tax for each account separately, so the carried forward losses
+        // are available only for the income which is declared by the taxpayer
+        let broker = portfolio_config.broker.get_info(config, portfolio_config.plan.as_ref())?;
+        let available_loss = if broker.type_.is_tax_agent() {
+            dec!(0)
+        } else {
+            LossCarryforward::new(database.clone(), &portfolio_config.name)
+                .get_available_loss(today.year())?
+        };
+
+        for symbol in find_taxable_sells(&country, tax_base - available_loss, &sells, tax_cost_threshold) {
+            debug!("Selling of {} leads to tax payment exceeding the threshold: restricting it.", symbol);
+            taxable_symbols.insert(symbol);
+        }
+    }
+
+    restrict_selling(&mut portfolio.assets, &taxable_symbols);
+    Ok(())
+}
+
+fn find_sell_candidates(
+    assets: &[AssetAllocation], expected_total_value: Decimal,
+    portfolio_configs: &[&PortfolioConfig], candidates: &mut Vec<SellCandidate>,
+) {
+    for asset in assets {
+        let expected_value = expected_total_value * asset.expected_weight;
+
+        match asset.holding {
+            Holding::Stock(ref holding) => {
+                if portfolio_configs[holding.account].tax_aware_rebalancing.is_none() {
+                    continue;
+                }
+
+                if asset.restrict_selling.unwrap_or(false) {
+                    continue;
+                }
+
+                let precision = if holding.fractional_shares_trading {
+                    util::decimal_precision(holding.current_shares)
+                } else {
+                    0
+                };
+
+                let shares = calculate_excess_shares(
+                    holding.current_shares, holding.price, precision, expected_value);
+
+                if let Some(shares) = shares {
+                    candidates.push(SellCandidate {
+                        account: holding.account,
+                        symbol: holding.symbol.clone(),
+                        shares: shares,
+                        price: holding.currency_price,
+                    });
+                }
+            },
+            Holding::Group(ref holdings) => {
+                find_sell_candidates(holdings, expected_value, portfolio_configs, candidates);
+            },
+        }
+    }
+}
+
+// Returns the number of shares which are to be sold to bring the asset to its expected value
+fn calculate_excess_shares(
+    current_shares: Decimal, price: Decimal, precision: u32, expected_value: Decimal,
+) -> Option<Decimal> {
+    let current_value = current_shares * price;
+
+    // Zero expected value means a deprecated asset which must be sold regardless of taxes
+    if expected_value.is_zero() || current_value <= expected_value {
+        return None;
+    }
+
+    let excess_value = current_value - expected_value;
+
+    let shares = std::cmp::min(util::round(excess_value / price, precision), current_shares);
+    if shares.is_zero() {
+        return None;
+    }
+
+    Some(shares.normalize())
+}
+
+// Emulates selling of the specified positions. Returns the current year tax base (trading results
+// which have been already received this year) and the emulated sells.
+fn emulate_sells(
+    config: &Config, portfolio_config: &PortfolioConfig, candidates: &[&SellCandidate],
+    converter: &CurrencyConverter,
+) -> GenericResult<(Decimal, Vec<EmulatedSell>)> {
+    let broker = portfolio_config.broker.get_info(config, portfolio_config.plan.as_ref())?;
+    let country = portfolio_config.get_tax_country();
+    let year = util::today().year();
+
+    let mut statement = BrokerStatement::read(
+        broker, &portfolio_config.statements, &portfolio_config.symbol_remapping,
+        &portfolio_config.instrument_names, portfolio_config.get_tax_remapping()?, false)?;
+
+    let mut commission_calc = CommissionCalc::new(statement.broker.commission_spec.clone());
+
+    for candidate in candidates {
+        // Positions that aren't reflected in the broker statement yet have unknown cost, so we
+        // can estimate the tax only for the positions which are
+        let available = match statement.open_positions.get(&candidate.symbol) {
+            Some(&available) => available,
+            None => continue,
+        };
+
+        let shares = std::cmp::min(candidate.shares, available);
+        statement.emulate_sell(&candidate.symbol, shares, candidate.price, &mut commission_calc)?;
+    }
+
+    statement.process_trades()?;
+
+    let mut tax_base = dec!(0);
+    let mut sells = Vec::new();
+
+    for trade in &statement.stock_sells {
+        if !trade.emulation && trade.execution_date.year() != year {
+            continue;
+        }
+
+        let details = trade.calculate(&country, converter)?;
+        let profit = details.local_profit.amount - details.long_term_ownership.deduction();
+
+        if trade.emulation {
+            sells.push(EmulatedSell {
+                symbol: trade.symbol.clone(),
+                volume: details.local_revenue.amount,
+                profit: profit,
+            });
+        } else {
+            tax_base += profit;
+        }
+    }
+
+    // Bonds form the same tax base as stocks
+    for trade in &statement.bond_sells {
+        if !trade.emulation && trade.execution_date.year() != year {
+            continue;
+        }
+
+        let details = trade.calculate(&country, converter)?;
+
+        if trade.emulation {
+            sells.push(EmulatedSell {
+                symbol: trade.symbol.clone(),
+                volume: details.local_revenue.amount,
+                profit: details.local_profit.amount,
+            });
+        } else {
+            tax_base += details.local_profit.amount;
+        }
+    }
+
+    Ok((tax_base, sells))
+}
+
+// Returns symbols which selling leads to tax payment exceeding the threshold. Each sell is estimated
+// independently: its tax is the increase of the tax on the current year's tax base, so losses which
+// have been already received this year or carried forward from the previous ones are taken into
+// account.
+fn find_taxable_sells(
+    country: &Country, tax_base: Decimal, sells: &[EmulatedSell], tax_cost_threshold: Decimal,
+) -> Vec<String> {
+    let mut taxes: HashMap<&str, (Decimal, Decimal)> = HashMap::new();
+
+    for sell in sells {
+        let tax = country.tax_to_pay(tax_base + sell.profit, None) - country.tax_to_pay(tax_base, None);
+        let (volume, total_tax) = taxes.entry(sell.symbol.as_str()).or_default();
+        *volume += sell.volume;
+        *total_tax += tax;
+    }
+
+    let mut symbols: Vec<String> = taxes.into_iter().filter_map(|(symbol, (volume, tax))| {
+        if tax > volume * tax_cost_threshold {
+            Some(symbol.to_owned())
+        } else {
+            None
+        }
+    }).collect();
+
+    symbols.sort();
+    symbols
+}
+
+// Each instrument is tradable in exactly one account, so the symbols are unambiguous
+fn restrict_selling(assets: &mut Vec<AssetAllocation>, symbols: &HashSet<String>) {
+    for asset in assets {
+        match asset.holding {
+            Holding::Stock(ref holding) => {
+                if symbols.contains(&holding.symbol) {
+                    asset.restrict_selling = Some(true);
+                }
+            },
+            Holding::Group(ref mut holdings) => restrict_selling(holdings, symbols),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::localities;
+    use super::*;
+
+    #[test]
+    fn excess_shares() {
+        // Underweight and deprecated assets
+        assert_eq!(calculate_excess_shares(dec!(10), dec!(10), 0, dec!(100)), None);
+        assert_eq!(calculate_excess_shares(dec!(10), dec!(10), 0, dec!(150)), None);
+        assert_eq!(calculate_excess_shares(dec!(10), dec!(10), 0, dec!(0)), None);
+
+        // Overweight assets
+        assert_eq!(calculate_excess_shares(dec!(12), dec!(10), 0, dec!(100)), Some(dec!(2)));
+        assert_eq!(calculate_excess_shares(dec!(10.5), dec!(10), 1, dec!(100)), Some(dec!(0.5)));
+        assert_eq!(calculate_excess_shares(dec!(10.4), dec!(10), 0, dec!(100)), None);
+        assert_eq!(calculate_excess_shares(dec!(13), dec!(10), 0, dec!(100)), Some(dec!(3)));
+    }
+
+    #[test]
+    fn taxable_sells() {
+        let country = localities::russia();
+        let threshold = dec!(0.01);
+
+        let sell = |symbol: &str, volume, profit| EmulatedSell {
+            symbol: symbol.to_owned(),
+            volume: volume,
+            profit: profit,
+        };
+
+        let sells = [
+            sell("LOSS", dec!(10000), dec!(-500)),
+            sell("SMALL", dec!(10000), dec!(500)), // 65 tax
+            sell("BIG", dec!(10000), dec!(1000)), // 130 tax
+        ];
+
+        // No losses
+        assert_eq!(find_taxable_sells(&country, dec!(0), &sells, threshold), vec![s!("BIG")]);
+        assert_eq!(find_taxable_sells(&country, dec!(0), &sells, dec!(0)), vec![s!("BIG"), s!("SMALL")]);
+
+        // Profit received this year doesn't change the marginal tax
+        assert_eq!(find_taxable_sells(&country, dec!(5000), &sells, threshold), vec![s!("BIG")]);
+
+        // Losses received this year or carried forward offset the profit
+        assert_eq!(find_taxable_sells(&country, dec!(-500), &sells, dec!(0)), vec![s!("BIG")]);
+        assert!(find_taxable_sells(&country, dec!(-500), &sells, threshold).is_empty());
+        assert!(find_taxable_sells(&country, dec!(-1000), &sells, dec!(0)).is_empty());
+    }
+}
@@ -0,0 +1,232 @@
+use crate::types::Decimal;
+
+use super::asset_allocation::{Portfolio, AssetAllocation, Holding};
+
+/// Tolerance band around the expected asset weight: the asset isn't rebalanced while its weight
+/// stays within the band
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tolerance {
+    Absolute(Decimal),
+    Relative(Decimal),
+}
+
+impl Tolerance {
+    pub fn get_band(self, weight: Decimal) -> (Decimal, Decimal) {
+        let deviation = match self {
+            Tolerance::Absolute(deviation) => deviation,
+            Tolerance::Relative(deviation) => weight * deviation,
+        };
+
+        let min_weight = std::cmp::max(weight - deviation, dec!(0));
+        let max_weight = std::cmp::min(weight + deviation, dec!(1));
+
+        (min_weight, max_weight)
+    }
+}
+
+pub struct DriftedAsset {
+    pub name: String,
+    pub weight: Decimal,
+    pub expected_weight: Decimal,
+    pub band: (Decimal, Decimal),
+}
+
+/// Forbids trading of assets which weight is within their tolerance band, so rebalancing proposes
+/// trades only for the assets which have drifted out of it.
+pub fn restrict_in_band_assets(portfolio: &mut Portfolio) {
+    let expected_total_value = portfolio.total_value - portfolio.min_cash_assets;
+    restrict_in_band(&mut portfolio.assets, expected_total_value);
+}
+
+pub fn restrict_in_band(assets: &mut Vec<AssetAllocation>, expected_total_value: Decimal) {
+    for asset in assets {
+        let expected_value = expected_total_value * asset.expected_weight;
+
+        if is_in_band(asset, expected_total_value) == Some(true) {
+            asset.restrict_trading(true, true);
+        } else if let Holding::Group(ref mut holdings) = asset.holding {
+            restrict_in_band(holdings, expected_value);
+        }
+    }
+}
+
+/// Returns all assets which weight is out of their tolerance band.
+pub fn find_drifted_assets(portfolio: &Portfolio) -> Vec<DriftedAsset> {
+    let mut drifted_assets = Vec::new();
+    let expected_total_value = portfolio.total_value - portfolio.min_cash_assets;
+    find_drifted_assets_impl(&portfolio.assets, expected_total_value, None, &mut drifted_assets);
+    drifted_assets
+}
+
+fn find_drifted_assets_impl(
+    assets: &[AssetAllocation], expected_total_value: Decimal, parent_name: Option<&str>,
+    drifted_assets: &mut Vec<DriftedAsset>,
+) {
+    for asset in assets {
+        let name = match parent_name {
+            Some(parent_name) => format!("{} / {}", parent_name, asset.full_name()),
+            None => asset.full_name(),
+        };
+
+        if let Some(tolerance) = asset.tolerance {
+            if is_in_band(asset, expected_total_value) == Some(false) {
+                drifted_assets.push(DriftedAsset {
+                    name: name.clone(),
+                    weight: get_weight(asset.current_value, expected_total_value),
+                    expected_weight: asset.expected_weight,
+                    band: tolerance.get_band(asset.expected_weight),
+                });
+            }
+        }
+
+        if let Holding::Group(ref holdings) = asset.holding {
+            let expected_value = expected_total_value * asset.expected_weight;
+            find_drifted_assets_impl(holdings, expected_value, Some(&name), drifted_assets);
+        }
+    }
+}
+
+// Returns None if the asset has no tolerance band
+fn is_in_band(asset: &AssetAllocation, expected_total_value: Decimal) -> Option<bool> {
+    let (min_weight, max_weight) = asset.tolerance?.get_band(asset.expected_weight);
+    let weight = get_weight(asset.current_value, expected_total_value);
+    Some(min_weight <= weight && weight <= max_weight)
+}
+
+fn get_weight(value: Decimal, expected_total_value: Decimal) -> Decimal {
+    if expected_total_value.is_zero() {
+        if value.is_zero() {
+            dec!(0)
+        } else {
+            Decimal::max_value()
+        }
+    } else {
+        value / expected_total_value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::currency::Cash;
+    use super::*;
+    use super::super::asset_allocation::StockHolding;
+
+    #[test]
+    fn tolerance_bands() {
+        assert_eq!(Tolerance::Absolute(dec!(0.05)).get_band(dec!(0.2)), (dec!(0.15), dec!(0.25)));
+        assert_eq!(Tolerance::Absolute(dec!(0.05)).get_band(dec!(0.03)), (dec!(0), dec!(0.08)));
+        assert_eq!(Tolerance::Absolute(dec!(0.05)).get_band(dec!(0.98)), (dec!(0.93), dec!(1)));
+        assert_eq!(Tolerance::Relative(dec!(0.25)).get_band(dec!(0.2)), (dec!(0.15), dec!(0.25)));
+    }
+
+    #[test]
+    fn in_band_assets() {
+        let mut assets = get_assets();
+        restrict_in_band(&mut assets, dec!(1000));
+
+        let restrictions = |asset: &AssetAllocation| (asset.restrict_buying, asset.restrict_selling);
+        let (stocks, bonds) = (&assets[0], &assets[1]);
+        let holdings = match stocks.holding {
+            Holding::Group(ref holdings) => holdings,
+            Holding::Stock(_) => unreachable!(),
+        };
+
+        // Stocks are within 60% ± 5%, so nothing inside of them is traded even though VXUS has drifted
+        assert_eq!(restrictions(stocks), (Some(true), Some(true)));
+        assert_eq!(restrictions(&holdings[0]), (Some(true), Some(true)));
+        assert_eq!(restrictions(&holdings[1]), (Some(true), Some(true)));
+
+        // Bonds have no tolerance band
+        assert_eq!(restrictions(bonds), (None, None));
+
+        // Stocks drift out of the band, so only in-band VTI is restricted
+        let mut assets = get_assets();
+        restrict_in_band(&mut assets, dec!(1200));
+
+        let (stocks, bonds) = (&assets[0], &assets[1]);
+        let holdings = match stocks.holding {
+            Holding::Group(ref holdings) => holdings,
+            Holding::Stock(_) => unreachable!(),
+        };
+
+        assert_eq!(restrictions(stocks), (None, None));
+        assert_eq!(restrictions(&holdings[0]), (Some(true), Some(true)));
+        assert_eq!(restrictions(&holdings[1]), (None, None));
+        assert_eq!(restrictions(bonds), (None, None));
+    }
+
+    #[test]
+    fn drifted_assets() {
+        let assets = get_assets();
+
+        let mut drifted_assets = Vec::new();
+        find_drifted_assets_impl(&assets, dec!(1000), None, &mut drifted_assets);
+
+        let drifted: Vec<_> = drifted_assets.iter().map(|asset| {
+            (asset.name.as_str(), asset.weight, asset.expected_weight, asset.band)
+        }).collect();
+
+        assert_eq!(drifted, vec![
+            ("Stocks / International stocks (VXUS)", dec!(0.25), dec!(0.2), (dec!(0.195), dec!(0.205))),
+        ]);
+
+        let mut drifted_assets = Vec::new();
+        find_drifted_assets_impl(&assets, dec!(1200), None, &mut drifted_assets);
+
+        let drifted: Vec<_> = drifted_assets.iter().map(|asset| asset.name.as_str()).collect();
+        assert_eq!(drifted, vec!["Stocks", "Stocks / International stocks (VXUS)"]);
+    }
+
+    // Stocks: 600 of expected 60% ± 5%
+    //   VTI: 450 of expected 80% ± 25% (relative)
+    //   VXUS: 150 of expected 20% ± 0.5%
+    // Bonds: 400 of expected 40% with no band
+    fn get_assets() -> Vec<AssetAllocation> {
+        let stocks = vec![
+            get_asset("US stocks", Some("VTI"), dec!(0.8), Some(Tolerance::Relative(dec!(0.25))), dec!(450)),
+            get_asset("International stocks", Some("VXUS"), dec!(0.2),
+                      Some(Tolerance::Absolute(dec!(0.005))), dec!(150)),
+        ];
+
+        let mut stocks_group = get_asset("Stocks", None, dec!(0.6), Some(Tolerance::Absolute(dec!(0.05))), dec!(600));
+        stocks_group.holding = Holding::Group(stocks);
+
+        vec![stocks_group, get_asset("Bonds", Some("BND"), dec!(0.4), None, dec!(400))]
+    }
+
+    fn get_asset(
+        name: &str, symbol: Option<&str>, weight: Decimal, tolerance: Option<Tolerance>, value: Decimal,
+    ) -> AssetAllocation {
+        let holding = match symbol {
+            Some(symbol) => Holding::Stock(StockHolding {
+                symbol: symbol.to_owned(),
+                price: dec!(1),
+                currency_price: Cash::new("USD", dec!(1)),
+                current_shares: value,
+                target_shares: value,
+                fractional_shares_trading: false,
+                account: 0,
+            }),
+            None => Holding::Group(Vec::new()),
+        };
+
+        AssetAllocation {
+            name: name.to_owned(),
+
+            expected_weight: weight,
+            tolerance: tolerance,
+            restrict_buying: None,
+            restrict_selling: None,
+
+            holding: holding,
+            current_value: value,
+            target_value: value,
+
+            min_value: dec!(0),
+            max_value: None,
+
+            buy_blocked: false,
+            sell_blocked: false,
+        }
+    }
+}
@@ -13,7 +13,7 @@ use crate::core::GenericResult;
 use crate::currency::Cash;
 use crate::util::{self, DecimalRestrictions};
 
-use super::{QuotesMap, QuotesProvider};
+use super::{Quote, QuotesMap, QuotesProvider};
 
 pub struct AlphaVantage {
     api_key: String,
@@ -115,7 +115,7 @@ fn parse_quotes(response: Response) -> GenericResult<HashMap<String, Cash>> {
         let price = util::parse_decimal(&quote.price, DecimalRestrictions::StrictlyPositive)
             .map_err(|_| format!("Invalid price: {:?}", quote.price))?.normalize();
 
-        quotes.insert(quote.symbol, Cash::new("USD", price));
+        quotes.insert(quote.symbol, Quote::new(Cash::new("USD", price), Some(time.naive_utc())));
     };
 
     if !outdated.is_empty() {
@@ -197,8 +197,10 @@ mod tests {
         let client = AlphaVantage::new("mock");
 
         let mut quotes = HashMap::new();
-        quotes.insert(s!("BND"), Cash::new("USD", dec!(77.8650)));
-        quotes.insert(s!("BNDX"), Cash::new("USD", dec!(54.5450)));
+        quotes.insert(s!("BND"), Quote::new(
+            Cash::new("USD", dec!(77.8650)), Some(date!(31, 10, 2018).and_hms(20, 0, 5))));
+        quotes.insert(s!("BNDX"), Quote::new(
+            Cash::new("USD", dec!(54.5450)), Some(date!(31, 10, 2018).and_hms(20, 0, 8))));
         assert_eq!(client.get_quotes(&["BND", "BNDX", "OUTDATED", "INVALID"]).unwrap(), quotes);
     }
 
@@ -1,17 +1,28 @@
 use chrono::Duration;
-use diesel::{self, prelude::*};
+use diesel::{self, prelude::*, dsl::{max, min}};
 #[cfg(test)] use tempfile::NamedTempFile;
 
-use crate::core::{GenericResult, EmptyResult};
+use crate::core::{GenericResult, GenericError, EmptyResult};
 use crate::currency::Cash;
-use crate::db::{self, schema::quotes, models};
+use crate::db::{self, schema::{historical_quotes, quotes}, models};
+use crate::formatting;
+use crate::types::{Date, DateTime};
 use crate::util::{self, DecimalRestrictions};
 
+use super::HistoryMap;
+
 pub struct Cache {
     db: db::Connection,
     expire_time: Duration,
 }
 
+pub struct CachedQuote {
+    pub price: Cash,
+    pub provider: Option<String>,
+    pub time: DateTime,
+    pub quote_time: Option<DateTime>,
+}
+
 impl Cache {
     pub fn new(connection: db::Connection, expire_time: Duration) -> Cache {
         Cache {
@@ -26,16 +37,35 @@ impl Cache {
         (database, Cache::new(connection, Duration::minutes(1)))
     }
 
-    pub fn get(&self, symbol: &str) -> GenericResult<Option<Cash>> {
+    /// Returns the actual quote if it has been received from one of the specified providers.
+    pub fn get(&self, symbol: &str, providers: &[&str]) -> GenericResult<Option<Cash>> {
         let expire_time = util::now() - self.expire_time;
 
         let result = quotes::table
-            .select((quotes::currency, quotes::price))
+            .select((quotes::currency, quotes::price, quotes::provider))
             .filter(quotes::symbol.eq(symbol))
             .filter(quotes::time.gt(&expire_time))
-            .get_result::<(String, String)>(&*self.db).optional()?;
+            .get_result::<(String, String, Option<String>)>(&*self.db).optional()?;
 
         let (currency, price) = match result {
+            Some((currency, price, Some(provider))) if providers.contains(&provider.as_str()) => (currency, price),
+            _ => return Ok(None),
+        };
+
+        let price = util::parse_decimal(&price, DecimalRestrictions::StrictlyPositive).map_err(|_| format!(
+            "Got an invalid price from the database: {:?}", price))?;
+
+        Ok(Some(Cash::new(&currency, price)))
+    }
+
+    /// Returns the last received quote regardless of its expiration time.
+    pub fn get_info(&self, symbol: &str) -> GenericResult<Option<CachedQuote>> {
+        let result = quotes::table
+            .select((quotes::time, quotes::currency, quotes::price, quotes::provider, quotes::quote_time))
+            .filter(quotes::symbol.eq(symbol))
+            .get_result::<(DateTime, String, String, Option<String>, Option<DateTime>)>(&*self.db).optional()?;
+
+        let (time, currency, price, provider, quote_time) = match result {
             Some(result) => result,
             None => return Ok(None),
         };
@@ -43,20 +73,104 @@ impl Cache {
         let price = util::parse_decimal(&price, DecimalRestrictions::StrictlyPositive).map_err(|_| format!(
             "Got an invalid price from the database: {:?}", price))?;
 
-        Ok(Some(Cash::new(&currency, price)))
+        Ok(Some(CachedQuote {
+            price: Cash::new(&currency, price),
+            provider: provider,
+            time: time,
+            quote_time: quote_time,
+        }))
     }
 
-    pub fn save(&self, symbol: &str, price: Cash) -> EmptyResult {
+    pub fn save(&self, symbol: &str, price: Cash, quote_time: Option<DateTime>, provider: &str) -> EmptyResult {
         diesel::replace_into(quotes::table)
             .values(models::NewQuote {
                 symbol: symbol,
                 time: util::now(),
                 currency: price.currency,
                 price: price.amount.to_string(),
+                provider: Some(provider),
+                quote_time: quote_time,
             })
             .execute(&*self.db)?;
         Ok(())
     }
+
+    /// Returns the date range for which historical quotes of the symbol have been cached.
+    pub fn get_history_range(&self, symbol: &str) -> GenericResult<Option<(Date, Date)>> {
+        let result = historical_quotes::table
+            .select((min(historical_quotes::date), max(historical_quotes::date)))
+            .filter(historical_quotes::symbol.eq(symbol))
+            .get_result::<(Option<Date>, Option<Date>)>(&*self.db)?;
+
+        Ok(match result {
+            (Some(first_date), Some(last_date)) => Some((first_date, last_date)),
+            _ => None,
+        })
+    }
+
+    pub fn get_history(&self, symbol: &str, start_date: Date, end_date: Date) -> GenericResult<HistoryMap> {
+        let results = historical_quotes::table
+            .select((historical_quotes::date, historical_quotes::currency, historical_quotes::price))
+            .filter(historical_quotes::symbol.eq(symbol))
+            .filter(historical_quotes::date.ge(start_date))
+            .filter(historical_quotes::date.le(end_date))
+            .filter(historical_quotes::price.is_not_null())
+            .load::<(Date, Option<String>, Option<String>)>(&*self.db)?;
+
+        let mut history = HistoryMap::new();
+
+        for result in results {
+            let (date, currency, price) = match result {
+                (date, Some(currency), Some(price)) => (date, currency, price),
+                _ => return Err!("Got an invalid historical quote from the database"),
+            };
+
+            let price = util::parse_decimal(&price, DecimalRestrictions::StrictlyPositive).map_err(|_| format!(
+                "Got an invalid price from the database: {:?}", price))?;
+
+            history.insert(date, Cash::new(&currency, price));
+        }
+
+        Ok(history)
+    }
+
+    /// Saves historical quotes for the specified date range. Days without quotes are saved as well,
+    /// so the whole range is considered as cached.
+    pub fn save_history(
+        &self, symbol: &str, start_date: Date, end_date: Date, history: &HistoryMap,
+    ) -> EmptyResult {
+        if start_date > end_date {
+            return Err!("Invalid date range: {} - {}",
+                formatting::format_date(start_date), formatting::format_date(end_date));
+        }
+
+        if history.keys().any(|&date| date < start_date || date > end_date) {
+            return Err!("The specified historical quotes don't match the specified date range");
+        }
+
+        let mut rows = Vec::new();
+        let mut date = start_date;
+
+        while date <= end_date {
+            let price = history.get(&date);
+
+            rows.push(models::NewHistoricalQuote {
+                symbol: symbol,
+                date: date,
+                currency: price.map(|price| price.currency),
+                price: price.map(|price| price.amount.to_string()),
+            });
+
+            date = date.succ();
+        }
+
+        self.db.transaction::<_, GenericError, _>(|| {
+            diesel::replace_into(historical_quotes::table)
+                .values(rows)
+                .execute(&*self.db)?;
+            Ok(())
+        })
+    }
 }
 
 #[cfg(test)]
@@ -72,6 +186,7 @@ mod tests {
 
         let other_symbol = "FXRU";
         let other_price = Cash::new("RUB", dec!(1234.56));
+        let other_time = date!(3, 2, 2020).and_hms(10, 30, 0);
 
         diesel::replace_into(quotes::table)
             .values(models::NewQuote {
@@ -79,22 +194,57 @@ mod tests {
                 time: util::now() - cache.expire_time,
                 currency: "EUR",
                 price: s!("12.34"),
+                provider: None,
+                quote_time: None,
             })
             .execute(&*cache.db).unwrap();
 
-        assert_eq!(cache.get(symbol).unwrap(), None);
-        assert_eq!(cache.get(other_symbol).unwrap(), None);
+        let providers = ["provider", "other-provider"];
+        assert_eq!(cache.get(symbol, &providers).unwrap(), None);
+        assert_eq!(cache.get(other_symbol, &providers).unwrap(), None);
 
-        cache.save(symbol, price).unwrap();
-        assert_eq!(cache.get(symbol).unwrap(), Some(price));
-        assert_eq!(cache.get(other_symbol).unwrap(), None);
+        cache.save(symbol, price, None, "provider").unwrap();
+        assert_eq!(cache.get(symbol, &providers).unwrap(), Some(price));
+        assert_eq!(cache.get(symbol, &["other-provider"]).unwrap(), None);
+        assert_eq!(cache.get(other_symbol, &providers).unwrap(), None);
 
-        cache.save(other_symbol, other_price).unwrap();
-        assert_eq!(cache.get(symbol).unwrap(), Some(price));
-        assert_eq!(cache.get(other_symbol).unwrap(), Some(other_price));
+        cache.save(other_symbol, other_price, Some(other_time), "other-provider").unwrap();
+        assert_eq!(cache.get(symbol, &providers).unwrap(), Some(price));
+        assert_eq!(cache.get(other_symbol, &providers).unwrap(), Some(other_price));
 
         cache.expire_time = Duration::seconds(0);
-        assert_eq!(cache.get(symbol).unwrap(), None);
-        assert_eq!(cache.get(other_symbol).unwrap(), None);
+        assert_eq!(cache.get(symbol, &providers).unwrap(), None);
+        assert_eq!(cache.get(other_symbol, &providers).unwrap(), None);
+
+        let info = cache.get_info(other_symbol).unwrap().unwrap();
+        assert_eq!(info.price, other_price);
+        assert_eq!(info.provider.as_deref(), Some("other-provider"));
+        assert_eq!(info.quote_time, Some(other_time));
+    }
+
+    #[test]
+    fn history() {
+        let (_database, cache) = Cache::new_temporary();
+
+        let symbol = "FXUS";
+        let (start_date, end_date) = (date!(3, 2, 2020), date!(9, 2, 2020));
+
+        let mut history = HistoryMap::new();
+        history.insert(date!(3, 2, 2020), Cash::new("RUB", dec!(3320)));
+        history.insert(date!(4, 2, 2020), Cash::new("RUB", dec!(3350.5)));
+        history.insert(date!(7, 2, 2020), Cash::new("RUB", dec!(3401)));
+
+        assert_eq!(cache.get_history_range(symbol).unwrap(), None);
+        assert!(cache.save_history(symbol, start_date, date!(5, 2, 2020), &history).is_err());
+
+        cache.save_history(symbol, start_date, end_date, &history).unwrap();
+        assert_eq!(cache.get_history_range(symbol).unwrap(), Some((start_date, end_date)));
+        assert_eq!(cache.get_history_range("FXIT").unwrap(), None);
+
+        assert_eq!(cache.get_history(symbol, start_date, end_date).unwrap(), history);
+        assert_eq!(cache.get_history(symbol, date!(5, 2, 2020), date!(6, 2, 2020)).unwrap(), HistoryMap::new());
+
+        let history = cache.get_history(symbol, date!(4, 2, 2020), date!(10, 2, 2020)).unwrap();
+        assert_eq!(history.keys().cloned().collect::<Vec<_>>(), vec![date!(4, 2, 2020), date!(7, 2, 2020)]);
     }
 }
\ No newline at end of file
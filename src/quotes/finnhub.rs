@@ -19,7 +19,7 @@ use crate::rate_limiter::RateLimiter;
 use crate::util::{self, DecimalRestrictions};
 use crate::types::Decimal;
 
-use super::{QuotesMap, QuotesProvider};
+use super::{Quote, QuotesMap, QuotesProvider};
 
 pub struct Finnhub {
     token: String,
@@ -38,7 +38,7 @@ impl Finnhub {
         }
     }
 
-    fn get_quote(&self, symbol: &str) -> GenericResult<Option<Cash>> {
+    fn get_quote(&self, symbol: &str) -> GenericResult<Option<Quote>> {
         #[derive(Deserialize)]
         struct Quote {
             #[serde(rename = "t")]
@@ -62,6 +62,9 @@ impl Finnhub {
             return Ok(None);
         }
 
+        let time = NaiveDateTime::from_timestamp_opt(time, 0).ok_or_else(|| format!(
+            "Got an invalid {} quote time: {}", symbol, time))?;
+
         let price = util::validate_decimal(price, DecimalRestrictions::StrictlyPositive)
             .map_err(|_| format!("Got an invalid {} price: {:?}", symbol, price))?;
 
@@ -82,7 +85,7 @@ impl Finnhub {
             s!("USD")
         };
 
-        Ok(Some(Cash::new(&currency, price)))
+        Ok(Some(Quote::new(Cash::new(&currency, price), Some(time))))
     }
 
     fn query<T: DeserializeOwned>(&self, method: &str, symbol: &str) -> GenericResult<Option<T>> {
@@ -135,9 +138,9 @@ impl QuotesProvider for Finnhub {
         let quotes = Mutex::new(HashMap::new());
 
         if let Some(error) = symbols.par_iter().map(|&symbol| -> EmptyResult {
-            if let Some(price) = self.get_quote(symbol)? {
+            if let Some(quote) = self.get_quote(symbol)? {
                 let mut quotes = quotes.lock().unwrap();
-                quotes.insert(symbol.to_owned(), price);
+                quotes.insert(symbol.to_owned(), quote);
             }
             Ok(())
         }).find_map_any(|result| match result {
@@ -270,9 +273,11 @@ mod tests {
 
         let client = Finnhub::new("mock");
 
+        let time = Some(date!(21, 2, 2020).and_hms(14, 30, 0));
+
         let mut quotes = HashMap::new();
-        quotes.insert(s!("BND"), Cash::new("USD", dec!(85.80000305175781)));
-        quotes.insert(s!("FXRL.ME"), Cash::new("RUB", dec!(2758.5)));
+        quotes.insert(s!("BND"), Quote::new(Cash::new("USD", dec!(85.80000305175781)), time));
+        quotes.insert(s!("FXRL.ME"), Quote::new(Cash::new("RUB", dec!(2758.5)), time));
         assert_eq!(client.get_quotes(&[
             "BND", "AMZN", "UNKNOWN", "UNKNOWN_OLD_1", "UNKNOWN_OLD_2", "FXRL.ME",
         ]).unwrap(), quotes);
@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::core::GenericResult;
+use crate::currency::{self, Cash};
+use crate::types::Decimal;
+use crate::util::{self, DecimalRestrictions};
+
+use super::{Quote, QuotesMap, QuotesProvider, parse_currency_pair};
+
+/// Quotes from a manually maintained price file. Allows to use the program without network quote
+/// providers or override their prices.
+pub struct LocalQuotes {
+    quotes: QuotesMap,
+}
+
+impl LocalQuotes {
+    pub fn load(path: &str) -> GenericResult<LocalQuotes> {
+        let quotes = read_prices(path).map_err(|e| format!(
+            "Error while reading {:?} price file: {}", path, e))?;
+
+        Ok(LocalQuotes {quotes})
+    }
+}
+
+impl QuotesProvider for LocalQuotes {
+    fn name(&self) -> &'static str {
+        "local price file"
+    }
+
+    // The file may be changed at any moment, so its prices must always be actual
+    fn cacheable(&self) -> bool {
+        false
+    }
+
+    fn get_quotes(&self, symbols: &[&str]) -> GenericResult<QuotesMap> {
+        Ok(symbols.iter().filter_map(|&symbol| {
+            self.quotes.get(symbol).cloned().map(|quote| (symbol.to_owned(), quote))
+        }).collect())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Price {
+    price: Decimal,
+    currency: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CsvPrice {
+    symbol: String,
+    price: Decimal,
+    currency: String,
+}
+
+// *.csv file has `symbol,price,currency` header. *.yaml file is a mapping of symbols to
+// {price, currency} objects.
+fn read_prices(path: &str) -> GenericResult<QuotesMap> {
+    let extension = Path::new(path).extension().and_then(|extension| extension.to_str());
+
+    let prices: Vec<(String, Price)> = match extension {
+        Some("csv") => {
+            let mut prices = Vec::new();
+
+            for price in csv::Reader::from_path(path)?.deserialize() {
+                let price: CsvPrice = price?;
+                prices.push((price.symbol, Price {
+                    price: price.price,
+                    currency: price.currency,
+                }));
+            }
+
+            prices
+        },
+        Some("yaml") | Some("yml") => {
+            let mut data = Vec::new();
+            File::open(path)?.read_to_end(&mut data)?;
+
+            let prices: BTreeMap<String, Price> = serde_yaml::from_slice(&data)?;
+            prices.into_iter().collect()
+        },
+        _ => return Err!("Unsupported file format: *.csv or *.yaml file is expected"),
+    };
+
+    let mut quotes = QuotesMap::new();
+
+    for (symbol, price) in prices {
+        let amount = util::validate_named_decimal(
+            &format!("{} price", symbol), price.price, DecimalRestrictions::StrictlyPositive)?;
+
+        if !currency::is_supported_currency(&price.currency) {
+            return Err!("{} price has an unsupported currency: {}", symbol, price.currency);
+        }
+
+        if let Ok((_base_currency, quote_currency)) = parse_currency_pair(&symbol) {
+            if price.currency != quote_currency {
+                return Err!("Invalid {} forex pair price currency: {}", symbol, price.currency);
+            }
+        }
+
+        // The file doesn't contain quote times
+        let quote = Quote::new(Cash::new(&price.currency, amount), None);
+
+        if quotes.insert(symbol.clone(), quote).is_some() {
+            return Err!("Duplicated {} price", symbol);
+        }
+    }
+
+    Ok(quotes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn local_quotes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let csv_path = temp_dir.path().join("prices.csv");
+        File::create(&csv_path).unwrap().write_all(indoc!("
+            symbol,price,currency
+            VTI,170.5,USD
+            FXUS,3320,RUB
+            USD/RUB,73.6573,RUB
+        ").as_bytes()).unwrap();
+
+        let yaml_path = temp_dir.path().join("prices.yaml");
+        File::create(&yaml_path).unwrap().write_all(indoc!("
+            VTI: {price: 170.5, currency: USD}
+            FXUS: {price: 3320, currency: RUB}
+            USD/RUB:
+              price: 73.6573
+              currency: RUB
+        ").as_bytes()).unwrap();
+
+        let mut quotes = QuotesMap::new();
+        quotes.insert(s!("VTI"), Quote::new(Cash::new("USD", dec!(170.5)), None));
+        quotes.insert(s!("USD/RUB"), Quote::new(Cash::new("RUB", dec!(73.6573)), None));
+
+        for path in &[csv_path, yaml_path] {
+            let provider = LocalQuotes::load(path.to_str().unwrap()).unwrap();
+            assert_eq!(provider.get_quotes(&["VTI", "USD/RUB", "BND"]).unwrap(), quotes);
+        }
+
+        let invalid_path = temp_dir.path().join("invalid.csv");
+        File::create(&invalid_path).unwrap().write_all(indoc!("
+            symbol,price,currency
+            USD/RUB,73.6573,USD
+        ").as_bytes()).unwrap();
+        assert!(LocalQuotes::load(invalid_path.to_str().unwrap()).is_err());
+    }
+}
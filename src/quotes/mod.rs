@@ -1,53 +1,110 @@
 use std::cell::RefCell;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
-#[cfg(not(test))] use chrono::{DateTime, TimeZone};
+#[cfg(not(test))] use chrono::TimeZone;
 use lazy_static::lazy_static;
-use log::debug;
+use log::{debug, warn};
 use regex::Regex;
 
-use crate::config::Config;
-use crate::core::GenericResult;
+use crate::config::{Config, QuotesProviderType};
+use crate::core::{EmptyResult, GenericResult};
 use crate::currency::Cash;
 use crate::db;
-#[cfg(not(test))] use crate::util;
+use crate::formatting;
+use crate::types::{Date, DateTime};
+use crate::util;
 
 use self::cache::Cache;
 use self::finnhub::Finnhub;
+use self::local::LocalQuotes;
 use self::moex::Moex;
 use self::twelvedata::TwelveData;
 
+pub use self::report::print_quotes;
+
 mod alphavantage;
 mod cache;
 mod finnhub;
+mod local;
 mod moex;
+mod report;
 mod twelvedata;
 
 pub struct Quotes {
     cache: Cache,
     providers: Vec<Box<dyn QuotesProvider>>,
+
+    // Provider chains are stored as indices in the providers list
+    chain: Vec<usize>,
+    overrides: HashMap<String, Vec<usize>>,
+
+    failures: RefCell<BTreeMap<&'static str, String>>,
     batched_symbols: RefCell<HashSet<String>>,
 }
 
 impl Quotes {
     pub fn new(config: &Config, database: db::Connection) -> GenericResult<Quotes> {
-        let finnhub = config.finnhub.as_ref().ok_or(
-            "Finnhub configuration is not set in the configuration file")?;
-
-        let twelvedata = config.twelvedata.as_ref().ok_or(
-            "Twelve Data configuration is not set in the configuration file")?;
+        let mut provider_types = Vec::new();
+
+        let chain: Vec<usize> = config.get_quote_providers().into_iter().map(|provider_type| {
+            get_provider_index(&mut provider_types, provider_type)
+        }).collect();
+
+        let overrides = config.quotes.overrides.iter().map(|(symbol, chain)| {
+            let chain: Vec<usize> = chain.iter().map(|&provider_type| {
+                get_provider_index(&mut provider_types, provider_type)
+            }).collect();
+            (symbol.clone(), chain)
+        }).collect();
+
+        let mut providers: Vec<Box<dyn QuotesProvider>> = Vec::new();
+
+        for provider_type in provider_types {
+            let provider: Box<dyn QuotesProvider> = match provider_type {
+                QuotesProviderType::Local => {
+                    let path = config.quotes.local_file.as_ref().ok_or(
+                        "Local price file is not set in the configuration file")?;
+
+                    Box::new(LocalQuotes::load(path)?)
+                },
+                QuotesProviderType::Finnhub => {
+                    let finnhub = config.finnhub.as_ref().ok_or(
+                        "Finnhub configuration is not set in the configuration file")?;
+
+                    Box::new(Finnhub::new(&finnhub.token))
+                },
+                QuotesProviderType::TwelveData => {
+                    let twelvedata = config.twelvedata.as_ref().ok_or(
+                        "Twelve Data configuration is not set in the configuration file")?;
+
+                    Box::new(TwelveData::new(&twelvedata.token))
+                },
+                QuotesProviderType::Moex => Box::new(Moex::new()),
+            };
+            providers.push(provider);
+        }
 
-        Ok(Quotes::new_with(Cache::new(database, config.cache_expire_time), vec![
-            Box::new(Finnhub::new(&finnhub.token)),
-            Box::new(TwelveData::new(&twelvedata.token)),
-            Box::new(Moex::new()),
-        ]))
+        let cache = Cache::new(database, config.cache_expire_time);
+        Ok(Quotes::new_with_chains(cache, providers, chain, overrides))
     }
 
     fn new_with(cache: Cache, providers: Vec<Box<dyn QuotesProvider>>) -> Quotes {
+        let chain = (0..providers.len()).collect();
+        Quotes::new_with_chains(cache, providers, chain, HashMap::new())
+    }
+
+    fn new_with_chains(
+        cache: Cache, providers: Vec<Box<dyn QuotesProvider>>, chain: Vec<usize>,
+        overrides: HashMap<String, Vec<usize>>,
+    ) -> Quotes {
         Quotes {
             cache: cache,
             providers: providers,
+
+            chain: chain,
+            overrides: overrides,
+
+            failures: RefCell::new(BTreeMap::new()),
             batched_symbols: RefCell::new(HashSet::new()),
         }
     }
@@ -57,93 +114,249 @@ impl Quotes {
     }
 
     pub fn get(&self, symbol: &str) -> GenericResult<Cash> {
-        if let Some(price) = self.cache.get(symbol)? {
+        if let Some((quote, _provider)) = self.get_local(symbol)? {
+            return Ok(quote.price);
+        }
+
+        // The cached price is used only if it has been received from one of the symbol's providers
+        let providers: Vec<&str> = self.get_chain(symbol).iter().map(|&index| {
+            self.providers[index].name()
+        }).collect();
+
+        if let Some(price) = self.cache.get(symbol, &providers)? {
             return Ok(price);
         }
 
         self.batch(symbol);
         let mut batched_symbols = self.batched_symbols.borrow_mut();
 
+        // Each symbol walks through its own provider chain: symbols which haven't been found by
+        // the current provider (or if the provider has failed) fall back to the next one.
+        let mut positions: HashMap<String, usize> = HashMap::new();
+
         let mut price = None;
 
-        for provider in &self.providers {
-            let quotes = {
-                let symbols: Vec<&str> = batched_symbols.iter().filter_map(|symbol| {
-                    let is_currency_pair = is_currency_pair(&symbol);
-
-                    if
-                        provider.supports_stocks() && !is_currency_pair ||
-                        provider.supports_forex() && is_currency_pair
-                    {
-                        Some(symbol.as_str())
-                    } else {
-                        None
-                    }
-                }).collect();
+        loop {
+            let mut requests: BTreeMap<usize, Vec<String>> = BTreeMap::new();
 
-                if symbols.is_empty() {
-                    continue;
+            for symbol in batched_symbols.iter() {
+                let chain = self.get_chain(symbol);
+                let position = positions.entry(symbol.clone()).or_insert(0);
+
+                while let Some(&index) = chain.get(*position) {
+                    *position += 1;
+
+                    if is_supported(self.providers[index].as_ref(), symbol) {
+                        requests.entry(index).or_default().push(symbol.clone());
+                        break;
+                    }
                 }
+            }
+
+            if requests.is_empty() {
+                break;
+            }
+
+            for (index, symbols) in requests {
+                let provider = &self.providers[index];
+                let symbols: Vec<&str> = symbols.iter().map(String::as_str).collect();
 
                 debug!("Getting quotes from {} for the following symbols: {}...",
                        provider.name(), symbols.join(", "));
 
-                provider.get_quotes(&symbols).map_err(|e| format!(
-                    "Failed to get quotes from {}: {}", provider.name(), e))?
-            };
+                let quotes = match provider.get_quotes(&symbols) {
+                    Ok(quotes) => {
+                        self.failures.borrow_mut().remove(provider.name());
+                        quotes
+                    },
+                    Err(e) => {
+                        warn!("Failed to get quotes from {}: {}.", provider.name(), e);
+                        self.failures.borrow_mut().insert(provider.name(), e.to_string());
+                        continue;
+                    },
+                };
 
-            for (other_symbol, other_price) in quotes.iter() {
-                let mut other_price = *other_price;
+                for (other_symbol, other_quote) in quotes.iter() {
+                    let mut other_price = other_quote.price;
 
-                // Some providers return stock quotes with unnecessary very high precision, so add
-                // rounding here. But don't round Forex pairs since we always round conversion
-                // result + reverse pairs always need high precision.
-                if provider.high_precision() && !is_currency_pair(other_symbol) {
-                    let rounded_price = other_price.round();
-                    let round_precision =
-                        (other_price.amount - rounded_price.amount).abs() / other_price.amount;
+                    // Some providers return stock quotes with unnecessary very high precision, so
+                    // add rounding here. But don't round Forex pairs since we always round
+                    // conversion result + reverse pairs always need high precision.
+                    if provider.high_precision() && !is_currency_pair(other_symbol) {
+                        let rounded_price = other_price.round();
+                        let round_precision =
+                            (other_price.amount - rounded_price.amount).abs() / other_price.amount;
 
-                    if round_precision < dec!(0.0001) {
-                        other_price = rounded_price;
+                        if round_precision < dec!(0.0001) {
+                            other_price = rounded_price;
+                        }
+                    };
+
+                    if *other_symbol == symbol {
+                        price.replace(other_price);
                     }
-                };
 
-                if *other_symbol == symbol {
-                    price.replace(other_price);
+                    if provider.cacheable() {
+                        self.cache.save(&other_symbol, other_price, other_quote.time, provider.name())?;
+                    }
+                    batched_symbols.remove(other_symbol);
                 }
-
-                self.cache.save(&other_symbol, other_price)?;
-                batched_symbols.remove(other_symbol);
             }
+        }
+
+        // All the symbols left in the batch have exhausted their provider chains, so drop them to
+        // not request them again along with every next symbol
+        batched_symbols.clear();
+
+        match price {
+            Some(price) => Ok(price),
+            None => Err!("Unable to find quotes for {}", symbol),
+        }
+    }
 
-            if batched_symbols.is_empty() {
+    /// Returns the price from the local providers which precede all the network ones in the
+    /// symbol's chain. Local prices aren't cached, so they must be checked before the cache.
+    fn get_local(&self, symbol: &str) -> GenericResult<Option<(Quote, &'static str)>> {
+        for &index in self.get_chain(symbol) {
+            let provider = &self.providers[index];
+            if provider.cacheable() {
                 break;
+            } else if !is_supported(provider.as_ref(), symbol) {
+                continue;
+            }
+
+            if let Some(&quote) = provider.get_quotes(&[symbol])?.get(symbol) {
+                return Ok(Some((quote, provider.name())));
             }
         }
 
-        if !batched_symbols.is_empty() {
-            let symbols = batched_symbols.iter().cloned().collect::<Vec<String>>();
-            return Err!("Unable to find quotes for following symbols: {}", symbols.join(", "));
+        Ok(None)
+    }
+
+    /// Returns end-of-day quotes for the specified period. The history is cached, so only the days
+    /// which haven't been fetched yet are requested from the providers.
+    pub fn get_history(
+        &self, symbol: &str, start_date: Date, end_date: Date,
+    ) -> GenericResult<BTreeMap<Date, Cash>> {
+        if start_date > end_date {
+            return Err!("Invalid date range: {} - {}",
+                formatting::format_date(start_date), formatting::format_date(end_date));
+        } else if end_date >= util::today() {
+            return Err!("Historical quotes are available only for the past days");
         }
 
-        Ok(price.unwrap())
+        let missing_range = match self.cache.get_history_range(symbol)? {
+            Some((first_date, last_date)) => {
+                if start_date >= first_date && end_date <= last_date {
+                    None
+                } else if start_date >= first_date {
+                    Some((last_date.succ(), end_date))
+                } else if end_date <= last_date {
+                    Some((start_date, first_date.pred()))
+                } else {
+                    Some((start_date, end_date))
+                }
+            },
+            None => Some((start_date, end_date)),
+        };
+
+        if let Some((start_date, end_date)) = missing_range {
+            self.fetch_history(symbol, start_date, end_date)?;
+        }
+
+        self.cache.get_history(symbol, start_date, end_date)
+    }
+
+    fn fetch_history(&self, symbol: &str, start_date: Date, end_date: Date) -> EmptyResult {
+        for &index in self.get_chain(symbol) {
+            let provider = &self.providers[index];
+
+            debug!("Getting historical quotes for {} from {} ({} - {})...", symbol, provider.name(),
+                   formatting::format_date(start_date), formatting::format_date(end_date));
+
+            let history = match provider.get_history(symbol, start_date, end_date) {
+                Ok(history) => {
+                    self.failures.borrow_mut().remove(provider.name());
+                    history
+                },
+                Err(e) => {
+                    warn!("Failed to get historical quotes from {}: {}.", provider.name(), e);
+                    self.failures.borrow_mut().insert(provider.name(), e.to_string());
+                    continue;
+                },
+            };
+
+            if let Some(history) = history {
+                return self.cache.save_history(symbol, start_date, end_date, &history);
+            }
+        }
+
+        Err!("Unable to find historical quotes for {}", symbol)
+    }
+
+    fn get_chain(&self, symbol: &str) -> &[usize] {
+        self.overrides.get(symbol).unwrap_or(&self.chain)
+    }
+}
+
+fn get_provider_index(
+    provider_types: &mut Vec<QuotesProviderType>, provider_type: QuotesProviderType,
+) -> usize {
+    match provider_types.iter().position(|&other| other == provider_type) {
+        Some(index) => index,
+        None => {
+            provider_types.push(provider_type);
+            provider_types.len() - 1
+        },
     }
 }
 
-type QuotesMap = HashMap<String, Cash>;
+/// Price with the time it has been actual at according to the provider (if known)
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Quote {
+    price: Cash,
+    time: Option<DateTime>,
+}
+
+impl Quote {
+    fn new(price: Cash, time: Option<DateTime>) -> Quote {
+        Quote {price, time}
+    }
+}
+
+type QuotesMap = HashMap<String, Quote>;
+type HistoryMap = BTreeMap<Date, Cash>;
 
 trait QuotesProvider {
     fn name(&self) -> &'static str;
     fn supports_stocks(&self) -> bool {true}
     fn supports_forex(&self) -> bool {true}
     fn high_precision(&self) -> bool {false}
+    fn cacheable(&self) -> bool {true}
     fn get_quotes(&self, symbols: &[&str]) -> GenericResult<QuotesMap>;
+
+    /// Returns end-of-day quotes for the specified period or None if the provider doesn't know
+    /// the symbol or doesn't provide historical quotes at all. An empty history means that the
+    /// symbol is known, but hasn't been traded during the period.
+    fn get_history(
+        &self, _symbol: &str, _start_date: Date, _end_date: Date,
+    ) -> GenericResult<Option<HistoryMap>> {
+        Ok(None)
+    }
 }
 
 pub fn get_currency_pair(base: &str, quote: &str) -> String {
     format!("{}/{}", base, quote)
 }
 
+fn is_supported(provider: &dyn QuotesProvider, symbol: &str) -> bool {
+    if is_currency_pair(symbol) {
+        provider.supports_forex()
+    } else {
+        provider.supports_stocks()
+    }
+}
+
 fn is_currency_pair(symbol: &str) -> bool {
     parse_currency_pair(symbol).is_ok()
 }
@@ -163,12 +376,20 @@ fn parse_currency_pair(pair: &str) -> GenericResult<(&str, &str)> {
 }
 
 #[cfg(not(test))]
-fn is_outdated_quote<T: TimeZone>(date_time: DateTime<T>) -> bool {
+fn is_outdated_quote<T: TimeZone>(date_time: chrono::DateTime<T>) -> bool {
     (util::utc_now() - date_time.naive_utc()).num_days() >= 5
 }
 
 #[cfg(test)]
 mod tests {
+    use std::fs::File;
+    use std::io::Write;
+    use std::rc::Rc;
+
+    use chrono::Datelike;
+
+    use crate::types::Decimal;
+
     use super::*;
 
     #[test]
@@ -191,8 +412,8 @@ mod tests {
                 *self.request_id.borrow_mut() += 1;
 
                 let mut quotes = HashMap::new();
-                quotes.insert(s!("BND"), Cash::new("USD", dec!(12.34)));
-                quotes.insert(s!("VTI"), Cash::new("USD", dec!(56.78)));
+                quotes.insert(s!("BND"), Quote::new(Cash::new("USD", dec!(12.34)), None));
+                quotes.insert(s!("VTI"), Quote::new(Cash::new("USD", dec!(56.78)), None));
                 Ok(quotes)
             }
         }
@@ -212,7 +433,7 @@ mod tests {
                 *self.request_id.borrow_mut() += 1;
 
                 let mut quotes = HashMap::new();
-                quotes.insert(s!("BNDX"), Cash::new("USD", dec!(90.12)));
+                quotes.insert(s!("BNDX"), Quote::new(Cash::new("USD", dec!(90.12)), None));
                 Ok(quotes)
             }
         }
@@ -232,4 +453,262 @@ mod tests {
         assert_eq!(quotes.get("VTI").unwrap(), Cash::new("USD", dec!(56.78)));
         assert_eq!(quotes.get("BNDX").unwrap(), Cash::new("USD", dec!(90.12)));
     }
+
+    #[test]
+    fn fallback() {
+        struct FailingProvider;
+
+        impl QuotesProvider for FailingProvider {
+            fn name(&self) -> &'static str {
+                "failing-provider"
+            }
+
+            fn get_quotes(&self, _symbols: &[&str]) -> GenericResult<QuotesMap> {
+                Err!("Service is unavailable")
+            }
+        }
+
+        struct Provider {
+            name: &'static str,
+            requests: Rc<RefCell<Vec<Vec<String>>>>,
+        }
+
+        impl QuotesProvider for Provider {
+            fn name(&self) -> &'static str {
+                self.name
+            }
+
+            fn get_quotes(&self, symbols: &[&str]) -> GenericResult<QuotesMap> {
+                let mut symbols = symbols.to_vec();
+                symbols.sort();
+
+                self.requests.borrow_mut().push(symbols.iter().map(|&symbol| symbol.to_owned()).collect());
+
+                Ok(symbols.iter().map(|&symbol| {
+                    (symbol.to_owned(), Quote::new(Cash::new("USD", dec!(12.34)), None))
+                }).collect())
+            }
+        }
+
+        let requests = Rc::new(RefCell::new(Vec::new()));
+        let other_requests = Rc::new(RefCell::new(Vec::new()));
+
+        let mut overrides = HashMap::new();
+        overrides.insert(s!("SBER"), vec![2]);
+
+        let (_database, cache) = Cache::new_temporary();
+        let quotes = Quotes::new_with_chains(cache, vec![
+            Box::new(FailingProvider),
+            Box::new(Provider {name: "provider", requests: requests.clone()}),
+            Box::new(Provider {name: "other-provider", requests: other_requests.clone()}),
+        ], vec![0, 1], overrides);
+
+        quotes.batch("BND");
+        quotes.batch("SBER");
+        assert_eq!(quotes.get("VTI").unwrap(), Cash::new("USD", dec!(12.34)));
+        assert_eq!(quotes.get("SBER").unwrap(), Cash::new("USD", dec!(12.34)));
+
+        assert_eq!(*requests.borrow(), vec![vec![s!("BND"), s!("VTI")]]);
+        assert_eq!(*other_requests.borrow(), vec![vec![s!("SBER")]]);
+
+        assert_eq!(quotes.cache.get_info("VTI").unwrap().unwrap().provider, Some(s!("provider")));
+        assert_eq!(quotes.cache.get_info("SBER").unwrap().unwrap().provider, Some(s!("other-provider")));
+        assert_eq!(*quotes.failures.borrow(), vec![
+            ("failing-provider", s!("Service is unavailable")),
+        ].into_iter().collect::<BTreeMap<_, _>>());
+    }
+
+    #[test]
+    fn failed_symbols() {
+        struct Provider {
+            name: &'static str,
+            requests: Rc<RefCell<Vec<Vec<String>>>>,
+        }
+
+        impl QuotesProvider for Provider {
+            fn name(&self) -> &'static str {
+                self.name
+            }
+
+            fn get_quotes(&self, symbols: &[&str]) -> GenericResult<QuotesMap> {
+                let mut symbols = symbols.to_vec();
+                symbols.sort();
+
+                self.requests.borrow_mut().push(symbols.iter().map(|&symbol| symbol.to_owned()).collect());
+
+                Ok(symbols.iter().filter(|&&symbol| symbol != "UNKNOWN").map(|&symbol| {
+                    (symbol.to_owned(), Quote::new(Cash::new("USD", dec!(12.34)), None))
+                }).collect())
+            }
+        }
+
+        let requests = Rc::new(RefCell::new(Vec::new()));
+        let other_requests = Rc::new(RefCell::new(Vec::new()));
+
+        let mut overrides = HashMap::new();
+        overrides.insert(s!("SBER"), vec![1]);
+
+        let (_database, cache) = Cache::new_temporary();
+        let quotes = Quotes::new_with_chains(cache, vec![
+            Box::new(Provider {name: "provider", requests: requests.clone()}),
+            Box::new(Provider {name: "other-provider", requests: other_requests.clone()}),
+        ], vec![0], overrides);
+
+        quotes.batch("UNKNOWN");
+        assert_eq!(quotes.get("VTI").unwrap(), Cash::new("USD", dec!(12.34)));
+        assert_eq!(quotes.get("BND").unwrap(), Cash::new("USD", dec!(12.34)));
+        assert_eq!(quotes.get("UNKNOWN").unwrap_err().to_string(), "Unable to find quotes for UNKNOWN");
+
+        assert_eq!(*requests.borrow(), vec![
+            vec![s!("UNKNOWN"), s!("VTI")],
+            vec![s!("BND")],
+            vec![s!("UNKNOWN")],
+        ]);
+
+        // The price cached from the default chain mustn't be used for the overridden symbol
+        quotes.cache.save("SBER", Cash::new("USD", dec!(1)), None, "provider").unwrap();
+        assert_eq!(quotes.get("SBER").unwrap(), Cash::new("USD", dec!(12.34)));
+        assert_eq!(*other_requests.borrow(), vec![vec![s!("SBER")]]);
+    }
+
+    #[test]
+    fn local() {
+        struct LocalProvider {
+            price: Cash,
+        }
+
+        impl QuotesProvider for LocalProvider {
+            fn name(&self) -> &'static str {
+                "local"
+            }
+
+            fn cacheable(&self) -> bool {
+                false
+            }
+
+            fn get_quotes(&self, symbols: &[&str]) -> GenericResult<QuotesMap> {
+                Ok(symbols.iter().filter(|&&symbol| symbol == "VTI").map(|&symbol| {
+                    (symbol.to_owned(), Quote::new(self.price, None))
+                }).collect())
+            }
+        }
+
+        struct Provider;
+
+        impl QuotesProvider for Provider {
+            fn name(&self) -> &'static str {
+                "provider"
+            }
+
+            fn get_quotes(&self, symbols: &[&str]) -> GenericResult<QuotesMap> {
+                let time = date!(1, 1, 2020).and_hms(12, 0, 0);
+                Ok(symbols.iter().map(|&symbol| {
+                    (symbol.to_owned(), Quote::new(Cash::new("USD", dec!(12.34)), Some(time)))
+                }).collect())
+            }
+        }
+
+        let (_database, cache) = Cache::new_temporary();
+        let local = LocalProvider {price: Cash::new("USD", dec!(1))};
+        let quotes = Quotes::new_with(cache, vec![Box::new(local), Box::new(Provider)]);
+
+        assert_eq!(quotes.get("BND").unwrap(), Cash::new("USD", dec!(12.34)));
+        assert_eq!(quotes.get("VTI").unwrap(), Cash::new("USD", dec!(1)));
+        assert!(quotes.cache.get_info("VTI").unwrap().is_none());
+        assert_eq!(quotes.cache.get_info("BND").unwrap().unwrap().quote_time,
+                   Some(date!(1, 1, 2020).and_hms(12, 0, 0)));
+
+        // The cached price of the network provider mustn't hide the local one
+        quotes.cache.save("VTI", Cash::new("USD", dec!(12.34)), None, "provider").unwrap();
+        assert_eq!(quotes.get("VTI").unwrap(), Cash::new("USD", dec!(1)));
+    }
+
+    #[test]
+    fn default_providers() {
+        let (_database_file, database) = db::new_temporary();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let prices_path = temp_dir.path().join("prices.yaml");
+        File::create(&prices_path).unwrap().write_all(b"VTI: {price: 200, currency: USD}\n").unwrap();
+
+        let mut config = Config::mock();
+        config.quotes.local_file = Some(prices_path.to_str().unwrap().to_owned());
+        assert_eq!(config.get_quote_providers(), vec![QuotesProviderType::Local, QuotesProviderType::Moex]);
+
+        let quotes = Quotes::new(&config, database).unwrap();
+        assert_eq!(quotes.get("VTI").unwrap(), Cash::new("USD", dec!(200)));
+    }
+
+    #[test]
+    fn history() {
+        struct Provider {
+            requests: Rc<RefCell<Vec<(Date, Date)>>>,
+        }
+
+        impl QuotesProvider for Provider {
+            fn name(&self) -> &'static str {
+                "provider"
+            }
+
+            fn get_quotes(&self, _symbols: &[&str]) -> GenericResult<QuotesMap> {
+                unreachable!()
+            }
+
+            fn get_history(&self, symbol: &str, start_date: Date, end_date: Date) -> GenericResult<Option<HistoryMap>> {
+                if symbol != "BND" && symbol != "CLOSED" {
+                    return Ok(None);
+                }
+
+                self.requests.borrow_mut().push((start_date, end_date));
+                if symbol == "CLOSED" {
+                    return Ok(Some(HistoryMap::new()));
+                }
+
+                let mut history = HistoryMap::new();
+                let mut date = start_date;
+
+                while date <= end_date {
+                    history.insert(date, Cash::new("USD", Decimal::from(date.day())));
+                    date = date.succ();
+                }
+
+                Ok(Some(history))
+            }
+        }
+
+        let requests = Rc::new(RefCell::new(Vec::new()));
+        let (_database, cache) = Cache::new_temporary();
+        let quotes = Quotes::new_with(cache, vec![
+            Box::new(Provider {requests: requests.clone()}),
+        ]);
+
+        let history = quotes.get_history("BND", date!(10, 1, 2020), date!(20, 1, 2020)).unwrap();
+        assert_eq!(history.len(), 11);
+        assert_eq!(history[&date!(15, 1, 2020)], Cash::new("USD", dec!(15)));
+
+        let history = quotes.get_history("BND", date!(12, 1, 2020), date!(15, 1, 2020)).unwrap();
+        assert_eq!(history.len(), 4);
+
+        let history = quotes.get_history("BND", date!(15, 1, 2020), date!(25, 1, 2020)).unwrap();
+        assert_eq!(history.len(), 11);
+
+        let history = quotes.get_history("BND", date!(1, 1, 2020), date!(5, 1, 2020)).unwrap();
+        assert_eq!(history.len(), 5);
+
+        assert_eq!(*requests.borrow(), vec![
+            (date!(10, 1, 2020), date!(20, 1, 2020)),
+            (date!(21, 1, 2020), date!(25, 1, 2020)),
+            (date!(1, 1, 2020), date!(9, 1, 2020)),
+        ]);
+
+        assert!(quotes.get_history("BNDX", date!(1, 1, 2020), date!(5, 1, 2020)).is_err());
+        assert!(quotes.get_history("BND", date!(5, 1, 2020), date!(1, 1, 2020)).is_err());
+
+        requests.borrow_mut().clear();
+        for _ in 0..2 {
+            let history = quotes.get_history("CLOSED", date!(1, 1, 2020), date!(5, 1, 2020)).unwrap();
+            assert!(history.is_empty());
+        }
+        assert_eq!(*requests.borrow(), vec![(date!(1, 1, 2020), date!(5, 1, 2020))]);
+    }
 }
\ No newline at end of file
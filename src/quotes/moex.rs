@@ -1,7 +1,9 @@
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
 use std::str::FromStr;
 
-use log::{error, trace};
+use chrono_tz::Europe::Moscow;
+use log::{debug, error, trace};
 use num_traits::Zero;
 use reqwest::Url;
 use reqwest::blocking::Client;
@@ -14,14 +16,48 @@ use crate::currency::Cash;
 use crate::types::{Decimal, Date};
 use crate::util;
 
-use super::{QuotesMap, QuotesProvider};
+use super::{HistoryMap, Quote, QuotesMap, QuotesProvider, is_currency_pair};
+
+// Currency pairs are quoted by their instruments with TOM settlement on the currency market
+const CURRENCY_PAIRS: &[(&str, &str)] = &[
+    ("USD/RUB", "USD000UTSTOM"),
+    ("EUR/RUB", "EUR_RUB__TOM"),
+];
 
 pub struct Moex {
+    boards: RefCell<HashMap<String, Option<Board>>>,
 }
 
 impl Moex {
     pub fn new() -> Moex {
-        Moex {}
+        Moex {
+            boards: RefCell::new(HashMap::new()),
+        }
+    }
+
+    // Returns security ID and the board which the security should be quoted on
+    fn resolve(&self, symbol: &str) -> GenericResult<Option<(String, Board)>> {
+        if is_currency_pair(symbol) {
+            return Ok(CURRENCY_PAIRS.iter()
+                .find(|&&(pair, _)| pair == symbol)
+                .map(|&(_, security_id)| (security_id.to_owned(), Board::currency())));
+        }
+
+        if let Some(board) = self.boards.borrow().get(symbol) {
+            return Ok(board.clone().map(|board| (symbol.to_owned(), board)));
+        }
+
+        let url = Url::parse_with_params(
+            &format!("{}/iss/securities/{}.xml", get_base_url(), symbol),
+            &[("iss.only", "boards")],
+        )?;
+
+        let board = send_request(&url).and_then(|data| {
+            Ok(parse_boards(symbol, &data).map_err(|e| format!("Boards info parsing error: {}", e))?)
+        }).map_err(|e| format!("Failed to get {} boards info from {}: {}", symbol, url, e))?;
+
+        self.boards.borrow_mut().insert(symbol.to_owned(), board.clone());
+        Ok(board.map(|board| (symbol.to_owned(), board)))
     }
 }
 
@@ -30,38 +66,213 @@ impl QuotesProvider for Moex {
         "Moscow Exchange"
     }
 
-    fn supports_forex(&self) -> bool {
-        false
-    }
-
     fn get_quotes(&self, symbols: &[&str]) -> GenericResult<QuotesMap> {
-        #[cfg(not(test))] let base_url = "https://iss.moex.com";
-        #[cfg(test)] let base_url = mockito::server_url();
+        let mut boards: BTreeMap<Board, Vec<(String, &str)>> = BTreeMap::new();
 
-        let url = Url::parse_with_params(
-            &format!("{}/iss/engines/stock/markets/shares/boards/TQTF/securities.xml", base_url),
-            &[("securities", symbols.join(",").as_str())],
-        )?;
+        for &symbol in symbols {
+            if let Some((security_id, board)) = self.resolve(symbol)? {
+                boards.entry(board).or_default().push((security_id, symbol));
+            }
+        }
+
+        let mut quotes = QuotesMap::new();
+
+        for (board, securities) in boards {
+            let market_type = board.market_type()?;
+            let security_ids: Vec<&str> = securities.iter()
+                .map(|(security_id, _)| security_id.as_str()).collect();
+
+            let url = Url::parse_with_params(
+                &format!("{}/iss/{}/securities.xml", get_base_url(), board.path()),
+                &[("securities", security_ids.join(",").as_str())],
+            )?;
 
-        let get = |url| -> GenericResult<HashMap<String, Cash>> {
-            trace!("Sending request to {}...", url);
-            let response = Client::new().get(url).send()?;
-            trace!("Got response from {}.", url);
+            let board_quotes = send_request(&url).and_then(|data| {
+                Ok(parse_quotes(&data, market_type).map_err(|e| format!(
+                    "Quotes info parsing error: {}", e))?)
+            }).map_err(|e| format!("Failed to get quotes from {}: {}", url, e))?;
 
-            if !response.status().is_success() {
-                return Err!("The server returned an error: {}", response.status());
+            for (security_id, symbol) in securities {
+                if let Some(&quote) = board_quotes.get(&security_id) {
+                    quotes.insert(symbol.to_owned(), quote);
+                }
             }
+        }
+
+        Ok(quotes)
+    }
 
-            Ok(parse_quotes(&response.text()?).map_err(|e| format!(
-                "Quotes info parsing error: {}", e))?)
+    fn get_history(&self, symbol: &str, start_date: Date, end_date: Date) -> GenericResult<Option<HistoryMap>> {
+        let (security_id, board) = match self.resolve(symbol)? {
+            Some(security) => security,
+            None => return Ok(None),
         };
+        let market_type = board.market_type()?;
+
+        let mut history = HistoryMap::new();
+        let mut start = 0;
+
+        loop {
+            let url = Url::parse_with_params(
+                &format!("{}/iss/history/{}/securities/{}.xml",
+                         get_base_url(), board.path(), security_id),
+                &[
+                    ("from", start_date.format("%Y-%m-%d").to_string().as_str()),
+                    ("till", end_date.format("%Y-%m-%d").to_string().as_str()),
+                    ("start", start.to_string().as_str()),
+                ],
+            )?;
+
+            let cursor = send_request(&url).and_then(|data| {
+                Ok(parse_history(&data, market_type, &mut history).map_err(|e| format!(
+                    "Historical quotes parsing error: {}", e))?)
+            }).map_err(|e| format!("Failed to get historical quotes from {}: {}", url, e))?;
+
+            start = cursor.index + cursor.page_size;
+            if cursor.page_size == 0 || start >= cursor.total {
+                break;
+            }
+        }
+
+        // Exchange holidays have no history rows
+        Ok(Some(history))
+    }
+}
+
+/// Trading board which security is quoted on
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Board {
+    engine: String,
+    market: String,
+    board: String,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum MarketType {
+    Shares,
+    Bonds,
+    Currency,
+}
+
+impl Board {
+    fn new(engine: &str, market: &str, board: &str) -> Board {
+        Board {
+            engine: engine.to_owned(),
+            market: market.to_owned(),
+            board: board.to_owned(),
+        }
+    }
+
+    fn currency() -> Board {
+        Board::new("currency", "selt", "CETS")
+    }
+
+    fn market_type(&self) -> GenericResult<MarketType> {
+        Ok(match (self.engine.as_str(), self.market.as_str()) {
+            ("stock", "shares") => MarketType::Shares,
+            ("stock", "bonds") => MarketType::Bonds,
+            ("currency", "selt") => MarketType::Currency,
+            _ => return Err!("Unsupported market: {}/{}", self.engine, self.market),
+        })
+    }
+
+    fn path(&self) -> String {
+        format!("engines/{}/markets/{}/boards/{}", self.engine, self.market, self.board)
+    }
+}
+
+fn get_base_url() -> String {
+    #[cfg(not(test))] let base_url = s!("https://iss.moex.com");
+    #[cfg(test)] let base_url = mockito::server_url();
+    base_url
+}
 
-        Ok(get(url.as_str()).map_err(|e| format!(
-            "Failed to get quotes from {}: {}", url, e))?)
+fn send_request(url: &Url) -> GenericResult<String> {
+    trace!("Sending request to {}...", url);
+    let response = Client::new().get(url.as_str()).send()?;
+    trace!("Got response from {}.", url);
+
+    if !response.status().is_success() {
+        return Err!("The server returned an error: {}", response.status());
     }
+
+    Ok(response.text()?)
 }
 
-fn parse_quotes(data: &str) -> GenericResult<HashMap<String, Cash>> {
+// Returns the primary board of the security or None if the security is unknown or traded on an
+// unsupported market
+fn parse_boards(symbol: &str, data: &str) -> GenericResult<Option<Board>> {
+    #[derive(Deserialize)]
+    struct Document {
+        data: Vec<Data>,
+    }
+
+    #[derive(Deserialize)]
+    struct Data {
+        id: String,
+
+        #[serde(rename = "rows")]
+        table: Table,
+    }
+
+    #[derive(Deserialize)]
+    struct Table {
+        #[serde(rename = "row", default)]
+        rows: Vec<Row>,
+    }
+
+    #[derive(Deserialize)]
+    struct Row {
+        #[serde(rename = "engine")]
+        engine: Option<String>,
+
+        #[serde(rename = "market")]
+        market: Option<String>,
+
+        #[serde(rename = "boardid")]
+        board: Option<String>,
+
+        #[serde(rename = "is_primary")]
+        is_primary: Option<u8>,
+    }
+
+    let result: Document = serde_xml_rs::from_str(data).map_err(|e| e.to_string())?;
+    let mut boards = None;
+
+    for data in result.data {
+        if data.id == "boards" && boards.replace(data.table.rows).is_some() {
+            return Err!("Duplicated {:?} data", data.id);
+        }
+    }
+
+    let boards = boards.ok_or("Unable to find boards info in server response")?;
+    let mut primary_board = None;
+
+    for row in boards {
+        if get_value(row.is_primary)? == 0 {
+            continue;
+        }
+
+        let board = Board::new(&get_value(row.engine)?, &get_value(row.market)?, &get_value(row.board)?);
+        if primary_board.replace(board).is_some() {
+            return Err!("{} has several primary boards", symbol);
+        }
+    }
+
+    let board = match primary_board {
+        Some(board) => board,
+        None => return Ok(None),
+    };
+
+    if let Err(e) = board.market_type() {
+        debug!("{}: {}.", symbol, e);
+        return Ok(None);
+    }
+
+    Ok(Some(board))
+}
+
+fn parse_quotes(data: &str, market_type: MarketType) -> GenericResult<HashMap<String, Quote>> {
     #[derive(Deserialize)]
     struct Document {
         data: Vec<Data>,
@@ -90,9 +301,6 @@ fn parse_quotes(data: &str) -> GenericResult<HashMap<String, Cash>> {
 
         // Security fields
 
-        #[serde(rename = "LOTSIZE")]
-        lot_size: Option<u32>,
-
         #[serde(rename = "CURRENCYID")]
         currency: Option<String>,
 
@@ -100,10 +308,22 @@ fn parse_quotes(data: &str) -> GenericResult<HashMap<String, Cash>> {
         #[serde(rename = "PREVDATE")]
         prev_date: Option<String>,
 
-        /// Previous trade day close price
-        #[serde(rename = "PREVLEGALCLOSEPRICE")]
+        /// Previous trade day close price (is missing on currency market)
+        #[serde(default, rename = "PREVLEGALCLOSEPRICE", deserialize_with = "deserialize_optional_decimal")]
+        prev_close_price: Option<Decimal>,
+
+        /// Previous trade day last price
+        #[serde(default, rename = "PREVPRICE", deserialize_with = "deserialize_optional_decimal")]
         prev_price: Option<Decimal>,
 
+        /// Bond face value
+        #[serde(default, rename = "FACEVALUE", deserialize_with = "deserialize_optional_decimal")]
+        face_value: Option<Decimal>,
+
+        /// Bond accrued interest
+        #[serde(default, rename = "ACCRUEDINT", deserialize_with = "deserialize_optional_decimal")]
+        accrued_interest: Option<Decimal>,
+
         // Market data fields
 
         #[serde(rename = "NUMTRADES")]
@@ -121,6 +341,9 @@ fn parse_quotes(data: &str) -> GenericResult<HashMap<String, Cash>> {
         // SYSTIME - data fetch time
         #[serde(rename = "SYSTIME")]
         time: Option<String>,
+
+        #[serde(rename = "TIME")]
+        trade_time: Option<String>,
     }
 
     let result: Document = serde_xml_rs::from_str(data).map_err(|e| e.to_string())?;
@@ -143,30 +366,35 @@ fn parse_quotes(data: &str) -> GenericResult<HashMap<String, Cash>> {
         _ => return Err!("Unable to find securities info in server response"),
     };
 
+    struct Security {
+        currency: &'static str,
+        prev_date: Date,
+        prev_price: Decimal,
+        bond: Option<(Decimal, Decimal)>,
+    }
+
     let mut symbols = HashMap::new();
 
+    // Prices are quoted per security (not per lot), so lot size doesn't matter here
     for row in securities {
         let symbol = get_value(row.symbol)?;
-        let lot_size = get_value(row.lot_size)?;
         let currency = get_value(row.currency)?;
         let prev_date = get_value(row.prev_date)?;
-        let prev_price = get_value(row.prev_price)?;
+        let prev_price = get_value(row.prev_close_price.or(row.prev_price))?;
 
-        if lot_size != 1 {
-            return Err!("{} has lot = {} which is not supported yet", symbol, lot_size);
-        }
-
-        let currency = match currency.as_str() {
-            "SUR" => "RUB",
-            _ => return Err!("{} is nominated in an unsupported currency: {}", symbol, currency),
-        };
+        let currency = parse_currency(&symbol, &currency)?;
 
         let prev_date = util::parse_date(&prev_date, "%Y-%m-%d")?;
         if prev_price.is_zero() || prev_price.is_sign_negative() {
             return Err!("Invalid price: {}", prev_price);
         }
 
-        if symbols.insert(symbol.clone(), (currency, prev_date, prev_price)).is_some() {
+        let bond = match market_type {
+            MarketType::Bonds => Some((get_value(row.face_value)?, get_value(row.accrued_interest)?)),
+            _ => None,
+        };
+
+        if symbols.insert(symbol.clone(), Security {currency, prev_date, prev_price, bond}).is_some() {
             return Err!("Duplicated symbol: {}", symbol);
         }
     }
@@ -184,32 +412,46 @@ fn parse_quotes(data: &str) -> GenericResult<HashMap<String, Cash>> {
         }
 
         let trades = get_value(row.trades)?;
-        let &(currency, prev_date, prev_price) = symbols.get(&symbol).ok_or_else(|| format!(
+        let security = symbols.get(&symbol).ok_or_else(|| format!(
             "There is market data for {} but security info is missing", symbol))?;
 
-        let price = match row.price {
+        let (price, time) = match row.price {
             Some(price) => {
                 if price.is_zero() || price.is_sign_negative() {
                     return Err!("Invalid price: {}", price);
                 }
 
-                price
+                let time = match row.trade_time {
+                    Some(ref time) if !time.is_empty() => {
+                        let time = format!("{} {}", date.format("%Y-%m-%d"), time);
+                        Some(util::parse_tz_date_time(&time, "%Y-%m-%d %H:%M:%S", Moscow, false)?.naive_utc())
+                    },
+                    _ => None,
+                };
+
+                (price, time)
             },
             None => {
                 if trades != 0 {
                     return Err!("There is no last price for {}", symbol);
                 }
 
-                if is_outdated(prev_date) {
+                if is_outdated(security.prev_date) {
                     outdated.push(symbol);
                     continue;
                 }
 
-                prev_price
+                // Previous trade day close time is unknown
+                (security.prev_price, None)
             },
         };
 
-        if quotes.insert(symbol.clone(), Cash::new(currency, price)).is_some() {
+        let price = match security.bond {
+            Some((face_value, accrued_interest)) => get_bond_price(price, face_value, accrued_interest),
+            None => price,
+        };
+
+        if quotes.insert(symbol.clone(), Quote::new(Cash::new(security.currency, price), time)).is_some() {
             return Err!("Duplicated symbol: {}", symbol);
         }
     }
@@ -221,6 +463,152 @@ fn parse_quotes(data: &str) -> GenericResult<HashMap<String, Cash>> {
     Ok(quotes)
 }
 
+struct Cursor {
+    index: u32,
+    total: u32,
+    page_size: u32,
+}
+
+fn parse_history(data: &str, market_type: MarketType, history: &mut HistoryMap) -> GenericResult<Cursor> {
+    #[derive(Deserialize)]
+    struct Document {
+        data: Vec<Data>,
+    }
+
+    #[derive(Deserialize)]
+    struct Data {
+        id: String,
+
+        #[serde(rename = "rows")]
+        table: Table,
+    }
+
+    #[derive(Deserialize)]
+    struct Table {
+        #[serde(rename = "row", default)]
+        rows: Vec<Row>,
+    }
+
+    #[derive(Deserialize)]
+    struct Row {
+        // History fields
+
+        #[serde(rename = "SECID")]
+        symbol: Option<String>,
+
+        #[serde(rename = "TRADEDATE")]
+        date: Option<String>,
+
+        /// Is missing on currency market
+        #[serde(rename = "CURRENCYID")]
+        currency: Option<String>,
+
+        /// Close price (is empty if there were no trades during the day)
+        #[serde(default, rename = "LEGALCLOSEPRICE", deserialize_with = "deserialize_optional_decimal")]
+        close_price: Option<Decimal>,
+
+        /// Last price (is used on currency market where there is no close price)
+        #[serde(default, rename = "CLOSE", deserialize_with = "deserialize_optional_decimal")]
+        last_price: Option<Decimal>,
+
+        /// Bond face value
+        #[serde(default, rename = "FACEVALUE", deserialize_with = "deserialize_optional_decimal")]
+        face_value: Option<Decimal>,
+
+        /// Bond accrued interest
+        #[serde(default, rename = "ACCINT", deserialize_with = "deserialize_optional_decimal")]
+        accrued_interest: Option<Decimal>,
+
+        // Cursor fields
+
+        #[serde(rename = "INDEX")]
+        index: Option<u32>,
+
+        #[serde(rename = "TOTAL")]
+        total: Option<u32>,
+
+        #[serde(rename = "PAGESIZE")]
+        page_size: Option<u32>,
+    }
+
+    let result: Document = serde_xml_rs::from_str(data).map_err(|e| e.to_string())?;
+    let (mut rows, mut cursor) = (None, None);
+
+    for data in result.data {
+        let data_ref = match data.id.as_str() {
+            "history" => &mut rows,
+            "history.cursor" => &mut cursor,
+            _ => continue,
+        };
+
+        if data_ref.replace(data.table.rows).is_some() {
+            return Err!("Duplicated {:?} data", data.id);
+        }
+    }
+
+    let (rows, cursor) = match (rows, cursor) {
+        (Some(rows), Some(cursor)) => (rows, cursor),
+        _ => return Err!("Unable to find historical quotes in server response"),
+    };
+
+    for row in rows {
+        let symbol = get_value(row.symbol)?;
+        let date = util::parse_date(&get_value(row.date)?, "%Y-%m-%d")?;
+        let currency = match row.currency {
+            Some(currency) => parse_currency(&symbol, &currency)?,
+            None if market_type == MarketType::Currency => "RUB",
+            None => return Err!("Got an unexpected response from server"),
+        };
+
+        let price = match market_type {
+            MarketType::Currency => row.last_price,
+            _ => row.close_price,
+        };
+
+        let price = match price {
+            Some(price) => price,
+            None => continue,
+        };
+
+        if price.is_zero() || price.is_sign_negative() {
+            return Err!("Invalid price: {}", price);
+        }
+
+        let price = match market_type {
+            MarketType::Bonds => get_bond_price(
+                price, get_value(row.face_value)?, get_value(row.accrued_interest)?),
+            _ => price,
+        };
+
+        if history.insert(date, Cash::new(currency, price)).is_some() {
+            return Err!("Duplicated quotes for {}", date);
+        }
+    }
+
+    let cursor = match cursor.as_slice() {
+        [cursor] => cursor,
+        _ => return Err!("Got an unexpected response from server"),
+    };
+
+    Ok(Cursor {
+        index: get_value(cursor.index)?,
+        total: get_value(cursor.total)?,
+        page_size: get_value(cursor.page_size)?,
+    })
+}
+
+// Bonds are quoted in percents of their face value
+fn get_bond_price(price: Decimal, face_value: Decimal, accrued_interest: Decimal) -> Decimal {
+    (price / dec!(100) * face_value + accrued_interest).normalize()
+}
+
+fn parse_currency(symbol: &str, currency: &str) -> GenericResult<&'static str> {
+    Ok(match currency {
+        "SUR" | "RUB" => "RUB",
+        _ => return Err!("{} is nominated in an unsupported currency: {}", symbol, currency),
+    })
+}
+
 fn get_value<T>(value: Option<T>) -> GenericResult<T> {
     Ok(value.ok_or_else(|| "Got an unexpected response from server")?)
 }
@@ -260,23 +648,76 @@ mod tests {
 
     use super::*;
 
+    const ETF_BOARD: &str = "engines/stock/markets/shares/boards/TQTF";
+
     #[test]
     fn no_quotes() {
-        let _mock = mock_response(&["FXUS", "FXIT"], "moex-empty.xml");
+        let _boards_mock = mock_boards(&["FXUS", "FXIT"], ETF_BOARD);
+        let _mock = mock_response(ETF_BOARD, &["FXUS", "FXIT"], "moex-empty.xml");
         assert_eq!(Moex::new().get_quotes(&["FXUS", "FXIT"]).unwrap(), HashMap::new());
     }
 
     #[test]
     fn quotes() {
-        let _mock = mock_response(&["FXUS", "FXIT", "INVALID"], "moex.xml");
+        let _boards_mock = mock_boards(&["FXUS", "FXIT"], ETF_BOARD);
+        let _unknown_board_mock = mock_unknown_board("INVALID");
+        let _mock = mock_response(ETF_BOARD, &["FXUS", "FXIT"], "moex.xml");
 
         let mut quotes = HashMap::new();
-        quotes.insert(s!("FXUS"), Cash::new("RUB", dec!(3320)));
-        quotes.insert(s!("FXIT"), Cash::new("RUB", dec!(4612)));
+        quotes.insert(s!("FXUS"), Quote::new(
+            Cash::new("RUB", dec!(3320)), Some(date!(9, 11, 2018).and_hms(15, 41, 7))));
+        quotes.insert(s!("FXIT"), Quote::new(
+            Cash::new("RUB", dec!(4612)), Some(date!(9, 11, 2018).and_hms(15, 39, 19))));
 
         assert_eq!(Moex::new().get_quotes(&["FXUS", "FXIT", "INVALID"]).unwrap(), quotes);
     }
 
+    #[test]
+    fn board_quotes() {
+        let shares_board = "engines/stock/markets/shares/boards/TQBR";
+        let bonds_board = "engines/stock/markets/bonds/boards/TQOB";
+        let currency_board = "engines/currency/markets/selt/boards/CETS";
+
+        let _shares_board_mock = mock_boards(&["SBER"], shares_board);
+        let _bonds_board_mock = mock_boards(&["SU26209RMFS5"], bonds_board);
+
+        let _shares_mock = mock_response(shares_board, &["SBER"], "moex-shares.xml");
+        let _bonds_mock = mock_response(bonds_board, &["SU26209RMFS5"], "moex-bonds.xml");
+        let _currency_mock = mock_response(
+            currency_board, &["USD000UTSTOM", "EUR_RUB__TOM"], "moex-currency.xml");
+
+        let mut quotes = HashMap::new();
+        quotes.insert(s!("SBER"), Quote::new(Cash::new("RUB", dec!(271.37)), None));
+        quotes.insert(s!("SU26209RMFS5"), Quote::new(Cash::new("RUB", dec!(1059.75)), None));
+        quotes.insert(s!("USD/RUB"), Quote::new(Cash::new("RUB", dec!(75.9525)), None));
+        quotes.insert(s!("EUR/RUB"), Quote::new(Cash::new("RUB", dec!(90.705)), None));
+
+        let moex = Moex::new();
+        assert_eq!(moex.get_quotes(&["SBER", "USD/RUB", "SU26209RMFS5", "EUR/RUB", "GBP/RUB"]).unwrap(), quotes);
+    }
+
+    #[test]
+    fn no_history() {
+        let _board_mock = mock_boards(&["FXUS"], ETF_BOARD);
+        let _mock = mock_history_response(
+            ETF_BOARD, "FXUS", "2020-01-01", "2020-01-05", "moex-history-empty.xml");
+        assert_eq!(Moex::new().get_history("FXUS", date!(1, 1, 2020), date!(5, 1, 2020)).unwrap(),
+                   Some(HistoryMap::new()));
+    }
+
+    #[test]
+    fn history() {
+        let _board_mock = mock_boards(&["FXUS"], ETF_BOARD);
+        let _mock = mock_history_response(
+            ETF_BOARD, "FXUS", "2020-01-01", "2020-01-10", "moex-history.xml");
+
+        let mut history = HistoryMap::new();
+        history.insert(date!(3, 1, 2020), Cash::new("RUB", dec!(3320)));
+        history.insert(date!(6, 1, 2020), Cash::new("RUB", dec!(3350.5)));
+
+        assert_eq!(Moex::new().get_history("FXUS", date!(1, 1, 2020), date!(10, 1, 2020)).unwrap(), Some(history));
+    }
+
     #[test]
     fn exchange_closed() {
         test_exchange_status("closed")
@@ -294,7 +735,8 @@ mod tests {
 
     fn test_exchange_status(status: &str) {
         let securities = ["FXAU", "FXCN", "FXDE", "FXIT", "FXJP", "FXRB", "FXRL", "FXRU", "FXUK", "FXUS"];
-        let _mock = mock_response(&securities, &format!("moex-{}.xml", status));
+        let _boards_mock = mock_boards(&securities, ETF_BOARD);
+        let _mock = mock_response(ETF_BOARD, &securities, &format!("moex-{}.xml", status));
         let quotes = Moex::new().get_quotes(&securities).unwrap();
         assert_eq!(
             HashSet::from_iter(quotes.keys().map(String::as_str)),
@@ -302,20 +744,62 @@ mod tests {
         );
     }
 
-    fn mock_response(securities: &[&str], body_path: &str) -> Mock {
+    fn mock_boards(securities: &[&str], board_path: &str) -> Vec<Mock> {
+        let board: Vec<&str> = board_path.split('/').collect();
+        let (engine, market, board) = (board[1], board[3], board[5]);
+
+        securities.iter().map(|symbol| {
+            mock_boards_response(symbol, &format!(concat!(
+                r#"<row secid="{symbol}" boardid="SMAL" market="{market}" engine="{engine}" is_primary="0" />"#,
+                r#"<row secid="{symbol}" boardid="{board}" market="{market}" engine="{engine}" is_primary="1" />"#,
+            ), symbol=symbol, engine=engine, market=market, board=board))
+        }).collect()
+    }
+
+    fn mock_unknown_board(symbol: &str) -> Mock {
+        mock_boards_response(symbol, "")
+    }
+
+    fn mock_boards_response(symbol: &str, rows: &str) -> Mock {
+        let body = format!(concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            r#"<document><data id="boards"><rows>{}</rows></data></document>"#,
+        ), rows);
+
+        mock("GET", format!("/iss/securities/{}.xml?iss.only=boards", symbol).as_str())
+            .with_status(200)
+            .with_header("Content-Type", "application/xml; charset=utf-8")
+            .with_body(body)
+            .create()
+    }
+
+    fn mock_response(board_path: &str, securities: &[&str], body_path: &str) -> Mock {
         let path = format!(
-            "/iss/engines/stock/markets/shares/boards/TQTF/securities.xml?securities={}",
+            "/iss/{}/securities.xml?securities={}", board_path,
             url::form_urlencoded::byte_serialize(securities.join(",").as_bytes()).collect::<String>()
         );
+        mock_file_response(&path, body_path)
+    }
+
+    fn mock_history_response(
+        board_path: &str, symbol: &str, start_date: &str, end_date: &str, body_path: &str,
+    ) -> Mock {
+        let path = format!(
+            "/iss/history/{}/securities/{}.xml?from={}&till={}&start=0",
+            board_path, symbol, start_date, end_date);
+        mock_file_response(&path, body_path)
+    }
+
+    fn mock_file_response(path: &str, body_path: &str) -> Mock {
         let body_path = Path::new(file!()).parent().unwrap().join("testdata").join(body_path);
 
         let mut body = String::new();
         File::open(body_path).unwrap().read_to_string(&mut body).unwrap();
 
-        mock("GET", path.as_str())
+        mock("GET", path)
             .with_status(200)
             .with_header("Content-Type", "application/xml; charset=utf-8")
             .with_body(body)
             .create()
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,97 @@
+use chrono::Duration;
+use log::warn;
+use static_table_derive::StaticTable;
+
+use crate::config::Config;
+use crate::core::EmptyResult;
+use crate::currency::Cash;
+use crate::db;
+use crate::util;
+
+use super::Quotes;
+
+#[derive(StaticTable)]
+#[table(name="QuotesTable")]
+struct QuoteRow {
+    #[column(name="Symbol")]
+    symbol: String,
+    #[column(name="Price")]
+    price: Option<Cash>,
+    #[column(name="Provider")]
+    provider: Option<String>,
+    #[column(name="Age")]
+    age: Option<String>,
+}
+
+#[derive(StaticTable)]
+#[table(name="FailuresTable")]
+struct FailureRow {
+    #[column(name="Provider")]
+    provider: &'static str,
+    #[column(name="Error")]
+    error: String,
+}
+
+/// Gets quotes for the specified symbols and prints which provider has served each of them and
+/// how old the quote is.
+pub fn print_quotes(config: &Config, symbols: &[String]) -> EmptyResult {
+    let quotes = Quotes::new(config, db::connect(&config.db_path)?)?;
+    let mut table = QuotesTable::new();
+
+    for symbol in symbols {
+        if let Err(e) = quotes.get(symbol) {
+            warn!("{}.", e);
+        }
+
+        let mut row = QuoteRow {
+            symbol: symbol.clone(),
+            price: None,
+            provider: None,
+            age: None,
+        };
+
+        // Age is calculated from the time reported by the provider, so it's unknown for the
+        // providers which don't report it
+        if let Some((quote, provider)) = quotes.get_local(symbol)? {
+            row.price = Some(quote.price);
+            row.provider = Some(provider.to_owned());
+            row.age = quote.time.map(|time| format_age(util::utc_now() - time));
+        } else if let Some(quote) = quotes.cache.get_info(symbol)? {
+            row.price = Some(quote.price);
+            row.provider = quote.provider;
+            row.age = quote.quote_time.map(|time| format_age(util::utc_now() - time));
+        }
+
+        table.add_row(row);
+    }
+
+    table.print("Quotes");
+
+    let failures = quotes.failures.borrow();
+    if !failures.is_empty() {
+        let mut table = FailuresTable::new();
+
+        for (&provider, error) in failures.iter() {
+            table.add_row(FailureRow {
+                provider: provider,
+                error: error.clone(),
+            });
+        }
+
+        table.print("Failed quote providers");
+    }
+
+    Ok(())
+}
+
+fn format_age(age: Duration) -> String {
+    if age.num_days() > 0 {
+        format!("{}d", age.num_days())
+    } else if age.num_hours() > 0 {
+        format!("{}h", age.num_hours())
+    } else if age.num_minutes() > 0 {
+        format!("{}m", age.num_minutes())
+    } else {
+        format!("{}s", age.num_seconds().max(0))
+    }
+}
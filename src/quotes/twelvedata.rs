@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use std::sync::Mutex;
 
-use chrono::{DateTime, TimeZone, Utc};
+use chrono::{DateTime, Duration, TimeZone, Utc};
 #[cfg(test)] use chrono::NaiveDate;
 #[cfg(test)] use indoc::indoc;
 use log::{debug, trace};
@@ -14,9 +14,9 @@ use serde::{Deserialize, de::DeserializeOwned};
 use crate::core::{GenericResult, EmptyResult};
 use crate::currency::Cash;
 use crate::util::{self, DecimalRestrictions};
-use crate::types::Decimal;
+use crate::types::{Date, Decimal};
 
-use super::{QuotesMap, QuotesProvider, parse_currency_pair};
+use super::{HistoryMap, Quote, QuotesMap, QuotesProvider, parse_currency_pair};
 
 pub struct TwelveData {
     token: String,
@@ -31,7 +31,7 @@ impl TwelveData {
         }
     }
 
-    fn get_quote(&self, symbol: &str) -> GenericResult<Option<Cash>> {
+    fn get_quote(&self, symbol: &str) -> GenericResult<Option<Quote>> {
         #[cfg(not(test))] let base_url = "https://api.twelvedata.com";
         #[cfg(test)] let base_url = mockito::server_url();
 
@@ -58,6 +58,37 @@ impl TwelveData {
         Ok(get(url.as_str()).map_err(|e| format!(
             "Failed to get quotes from {}: {}", url, e))?)
     }
+
+    fn get_daily_history(&self, symbol: &str, start_date: Date, end_date: Date) -> GenericResult<Option<HistoryMap>> {
+        #[cfg(not(test))] let base_url = "https://api.twelvedata.com";
+        #[cfg(test)] let base_url = mockito::server_url();
+
+        // End date is exclusive
+        let url = Url::parse_with_params(&format!("{}/time_series", base_url), &[
+            ("symbol", symbol),
+            ("interval", "1day"),
+            ("start_date", start_date.format("%Y-%m-%d").to_string().as_str()),
+            ("end_date", (end_date + Duration::days(1)).format("%Y-%m-%d").to_string().as_str()),
+            ("outputsize", "5000"),
+            ("timezone", "UTC"),
+            ("apikey", self.token.as_ref()),
+        ])?;
+
+        let get = |url| {
+            trace!("Sending request to {}...", url);
+            let response = self.client.get(url).send()?;
+            trace!("Got response from {}.", url);
+
+            if !response.status().is_success() {
+                return Err!("Server returned an error: {}", response.status());
+            }
+
+            get_history(symbol, start_date, end_date, response)
+        };
+
+        Ok(get(url.as_str()).map_err(|e| format!(
+            "Failed to get historical quotes from {}: {}", url, e))?)
+    }
 }
 
 impl QuotesProvider for TwelveData {
@@ -74,9 +105,9 @@ impl QuotesProvider for TwelveData {
         let quotes = Mutex::new(HashMap::new());
 
         if let Some(error) = symbols.par_iter().map(|&symbol| -> EmptyResult {
-            if let Some(price) = self.get_quote(symbol)? {
+            if let Some(quote) = self.get_quote(symbol)? {
                 let mut quotes = quotes.lock().unwrap();
-                quotes.insert(symbol.to_owned(), price);
+                quotes.insert(symbol.to_owned(), quote);
             }
             Ok(())
         }).find_map_any(|result| match result {
@@ -88,48 +119,57 @@ impl QuotesProvider for TwelveData {
 
         Ok(quotes.into_inner().unwrap())
     }
-}
 
-fn get_quote(symbol: &str, response: Response) -> GenericResult<Option<Cash>> {
-    #[derive(Deserialize)]
-    struct GenericResponse {
-        status: String,
+    fn get_history(&self, symbol: &str, start_date: Date, end_date: Date) -> GenericResult<Option<HistoryMap>> {
+        self.get_daily_history(symbol, start_date, end_date)
     }
+}
 
-    #[derive(Deserialize)]
-    struct ErrorResponse {
-        message: String,
-    }
+#[derive(Deserialize)]
+struct GenericResponse {
+    status: String,
+}
 
-    #[derive(Deserialize)]
-    struct QuoteResponse {
-        meta: Meta,
-        values: Vec<Value>,
-    }
+#[derive(Deserialize)]
+struct ErrorResponse {
+    message: String,
+}
 
-    #[derive(Deserialize)]
-    struct Meta {
-        currency: Option<String>,
-    }
+#[derive(Deserialize)]
+struct TimeSeriesResponse {
+    meta: Meta,
+    values: Vec<Value>,
+}
 
-    #[derive(Deserialize)]
-    struct Value {
-        datetime: String,
-        close: Decimal,
-    }
+#[derive(Deserialize)]
+struct Meta {
+    currency: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Value {
+    datetime: String,
+    close: Decimal,
+}
 
+// Returns the error if the server returned an error (unknown symbol, for example)
+fn parse_time_series(
+    symbol: &str, response: Response,
+) -> GenericResult<Result<TimeSeriesResponse, ErrorResponse>> {
     let response = response.text()?;
 
     if parse_response::<GenericResponse>(&response)?.status != "ok" {
         let error: ErrorResponse = parse_response(&response)?;
         debug!("{}: Server returned an error: {}.", symbol, error.message);
-        return Ok(None)
+        return Ok(Err(error))
     }
 
-    let quote: QuoteResponse = parse_response(&response)?;
+    Ok(Ok(parse_response(&response)?))
+}
 
-    let currency = if let Ok((_base_currency, quote_currency)) = parse_currency_pair(symbol) {
-        if let Some(currency) = quote.meta.currency {
+fn get_currency<'a>(symbol: &'a str, meta: &'a Meta) -> GenericResult<&'a str> {
+    Ok(if let Ok((_base_currency, quote_currency)) = parse_currency_pair(symbol) {
+        if let Some(ref currency) = meta.currency {
             if currency != quote_currency {
                 return Err!(
                     "Got an unexpected currency for {} forex pair: {}", symbol, currency);
@@ -138,9 +178,17 @@ fn get_quote(symbol: &str, response: Response) -> GenericResult<Option<Cash>> {
 
         quote_currency
     } else {
-        quote.meta.currency.as_ref().ok_or_else(||
+        meta.currency.as_ref().ok_or_else(||
             "Got an unexpected response from server: missing quote currency")?.as_str()
+    })
+}
+
+fn get_quote(symbol: &str, response: Response) -> GenericResult<Option<Quote>> {
+    let quote = match parse_time_series(symbol, response)? {
+        Ok(quote) => quote,
+        Err(_) => return Ok(None),
     };
+    let currency = get_currency(symbol, &quote.meta)?;
 
     let value = match quote.values.first() {
         Some(value) => value,
@@ -156,7 +204,39 @@ fn get_quote(symbol: &str, response: Response) -> GenericResult<Option<Cash>> {
     let price = util::validate_named_decimal(
         "price", value.close, DecimalRestrictions::StrictlyPositive)?;
 
-    Ok(Some(Cash::new(currency, price)))
+    Ok(Some(Quote::new(Cash::new(currency, price), Some(time.naive_utc()))))
+}
+
+fn get_history(
+    symbol: &str, start_date: Date, end_date: Date, response: Response,
+) -> GenericResult<Option<HistoryMap>> {
+    let time_series = match parse_time_series(symbol, response)? {
+        Ok(time_series) => time_series,
+        // The symbol is known, but there are no quotes for the period (holidays, for example)
+        Err(error) if error.message.starts_with("No data is available") => {
+            return Ok(Some(HistoryMap::new()));
+        },
+        Err(_) => return Ok(None),
+    };
+    let currency = get_currency(symbol, &time_series.meta)?;
+
+    let mut history = HistoryMap::new();
+
+    for value in &time_series.values {
+        let date = util::parse_date(&value.datetime, "%Y-%m-%d")?;
+        if date < start_date || date > end_date {
+            continue;
+        }
+
+        let price = util::validate_named_decimal(
+            "price", value.close, DecimalRestrictions::StrictlyPositive)?;
+
+        if history.insert(date, Cash::new(currency, price)).is_some() {
+            return Err!("Got duplicated quotes for {}", date);
+        }
+    }
+
+    Ok(Some(history))
 }
 
 fn parse_response<T: DeserializeOwned>(response: &str) -> GenericResult<T> {
@@ -260,11 +340,75 @@ mod tests {
         let client = TwelveData::new("mock");
 
         let mut quotes = HashMap::new();
-        quotes.insert(s!("USD/RUB"), Cash::new("RUB", dec!(63.97370)));
-        quotes.insert(s!("AMZN"), Cash::new("USD", dec!(2007.76001)));
+        quotes.insert(s!("USD/RUB"), Quote::new(
+            Cash::new("RUB", dec!(63.97370)), Some(date!(31, 1, 2020).and_hms(21, 58, 0))));
+        quotes.insert(s!("AMZN"), Quote::new(
+            Cash::new("USD", dec!(2007.76001)), Some(date!(31, 1, 2020).and_hms(20, 59, 0))));
         assert_eq!(client.get_quotes(&["USD/RUB", "UNKNOWN", "AMZN", "AAPL"]).unwrap(), quotes);
     }
 
+    #[test]
+    fn history() {
+        let _stock_history_mock = mock_response("/time_series?symbol=AMZN&interval=1day&start_date=2020-01-27&end_date=2020-02-01&outputsize=5000&timezone=UTC&apikey=mock", indoc!(r#"
+            {
+                "meta": {
+                    "currency": "USD",
+                    "exchange": "NASDAQ",
+                    "exchange_timezone": "America/New_York",
+                    "interval": "1day",
+                    "symbol": "AMZN",
+                    "type": "Common Stock"
+                },
+                "status": "ok",
+                "values": [
+                    {
+                        "close": "2008.71997",
+                        "datetime": "2020-01-31",
+                        "high": "2055.71997",
+                        "low": "2002.27002",
+                        "open": "2051.46997",
+                        "volume": "15567283"
+                    },
+                    {
+                        "close": "1870.68005",
+                        "datetime": "2020-01-30",
+                        "high": "1874.69995",
+                        "low": "1840.63000",
+                        "open": "1858.00000",
+                        "volume": "6286963"
+                    }
+                ]
+            }
+        "#));
+
+        let _unknown_history_mock = mock_response("/time_series?symbol=UNKNOWN&interval=1day&start_date=2020-01-27&end_date=2020-02-01&outputsize=5000&timezone=UTC&apikey=mock", indoc!(r#"
+            {
+                "code": 400,
+                "message": "symbol_ticker not found",
+                "status": "error"
+            }
+        "#));
+
+        let _no_history_mock = mock_response("/time_series?symbol=BND&interval=1day&start_date=2020-01-27&end_date=2020-02-01&outputsize=5000&timezone=UTC&apikey=mock", indoc!(r#"
+            {
+                "code": 400,
+                "message": "No data is available on the specified dates. Try setting different start/end dates.",
+                "status": "error"
+            }
+        "#));
+
+        let client = TwelveData::new("mock");
+        let (start_date, end_date) = (date!(27, 1, 2020), date!(31, 1, 2020));
+
+        let mut history = HistoryMap::new();
+        history.insert(date!(30, 1, 2020), Cash::new("USD", dec!(1870.68005)));
+        history.insert(date!(31, 1, 2020), Cash::new("USD", dec!(2008.71997)));
+
+        assert_eq!(client.get_history("AMZN", start_date, end_date).unwrap(), Some(history));
+        assert_eq!(client.get_history("BND", start_date, end_date).unwrap(), Some(HistoryMap::new()));
+        assert_eq!(client.get_history("UNKNOWN", start_date, end_date).unwrap(), None);
+    }
+
     fn mock_response(path: &str, data: &str) -> Mock {
         mock("GET", path)
             .with_status(200)
@@ -0,0 +1,225 @@
+use chrono::Datelike;
+use static_table_derive::StaticTable;
+
+use crate::broker_statement::BrokerStatement;
+use crate::config::PortfolioConfig;
+use crate::core::EmptyResult;
+use crate::currency::{Cash, MultiCurrencyCashAccount};
+use crate::currency::converter::CurrencyConverter;
+use crate::formatting;
+use crate::types::{Date, Decimal};
+
+use super::statement::TaxStatement;
+
+#[derive(StaticTable)]
+#[table(name="TradesTable")]
+struct TradeRow {
+    #[column(name="Дата сделки")]
+    conclusion_date: Date,
+    #[column(name="Дата расчета")]
+    execution_date: Date,
+    #[column(name="Облигация")]
+    security: String,
+    #[column(name="Кол.")]
+    quantity: Decimal,
+    #[column(name="Цена")]
+    price: Cash,
+    #[column(name="НКД")]
+    accrued_interest: Cash,
+    #[column(name="Доход от\nпродажи")]
+    revenue: Cash,
+    #[column(name="Доход от\nпродажи (руб)")]
+    local_revenue: Cash,
+    #[column(name="Комиссия\n(руб)")]
+    local_commission: Cash,
+    #[column(name="Затраты на\nпокупку")]
+    purchase_local_cost: Cash,
+    #[column(name="Общие\nзатраты")]
+    total_local_cost: Cash,
+    #[column(name="Прибыль")]
+    local_profit: Cash,
+    #[column(name="Налог")]
+    tax_to_pay: Cash,
+}
+
+#[derive(StaticTable)]
+#[table(name="CouponsTable")]
+struct CouponRow {
+    #[column(name="Дата")]
+    date: Date,
+    #[column(name="Облигация")]
+    security: String,
+    #[column(name="Валюта", align="center")]
+    currency: String,
+    #[column(name="Сумма")]
+    foreign_amount: Cash,
+    #[column(name="Курс руб.")]
+    currency_rate: Decimal,
+    #[column(name="Сумма (руб)")]
+    amount: Cash,
+    #[column(name="Уплачено (руб)")]
+    paid_tax: Cash,
+    #[column(name="К уплате")]
+    tax_to_pay: Cash,
+    #[column(name="Реальный доход")]
+    income: Cash,
+}
+
+pub fn process_income(
+    portfolio: &PortfolioConfig, broker_statement: &BrokerStatement, year: Option<i32>,
+    mut tax_statement: Option<&mut TaxStatement>, converter: &CurrencyConverter,
+) -> EmptyResult {
+    let country = portfolio.get_tax_country();
+    let broker_name = &broker_statement.broker.name;
+
+    // Tax on income from bonds is withheld by tax agents, so such income is shown for information
+    // purposes only and isn't declared
+    if broker_statement.broker.type_.is_tax_agent() {
+        tax_statement = None;
+    }
+
+    let mut trades_table = TradesTable::new();
+    let mut total_local_profit = Cash::new(country.currency, dec!(0));
+    let mut total_tax_to_pay = Cash::new(country.currency, dec!(0));
+
+    for trade in &broker_statement.bond_sells {
+        if let Some(year) = year {
+            if trade.execution_date.year() != year {
+                continue;
+            }
+        }
+
+        let security = broker_statement.get_instrument_name(&trade.symbol);
+        let details = trade.calculate(&country, converter)?;
+
+        total_local_profit.add_assign(details.local_profit).unwrap();
+        total_tax_to_pay.add_assign(details.tax_to_pay).unwrap();
+
+        trades_table.add_row(TradeRow {
+            conclusion_date: trade.conclusion_date,
+            execution_date: trade.execution_date,
+            security: security.clone(),
+            quantity: trade.quantity,
+            price: trade.price,
+            accrued_interest: trade.accrued_interest,
+            revenue: details.revenue,
+            local_revenue: details.local_revenue,
+            local_commission: details.local_commission,
+            purchase_local_cost: details.purchase_local_cost,
+            total_local_cost: details.total_local_cost,
+            local_profit: details.local_profit,
+            tax_to_pay: details.tax_to_pay,
+        });
+
+        if let Some(ref mut tax_statement) = tax_statement {
+            let description = format!("{}: {} {}", broker_name, if trade.redemption {
+                "Погашение"
+            } else {
+                "Продажа"
+            }, security);
+
+            let issuer_country = broker_statement.get_bond_issuer(&trade.symbol)?;
+            let precise_currency_rate = converter.precise_currency_rate(
+                trade.execution_date, details.revenue.currency, country.currency)?;
+
+            tax_statement.add_stock_income(
+                &description, issuer_country, trade.execution_date, details.revenue.currency,
+                precise_currency_rate, details.revenue.amount, details.local_revenue.amount,
+                details.total_local_cost.amount,
+            ).map_err(|e| format!(
+                "Unable to add income from selling {} on {} to the tax statement: {}",
+                trade.symbol, formatting::format_date(trade.execution_date), e
+            ))?;
+        }
+    }
+
+    if !trades_table.is_empty() {
+        let mut totals = trades_table.add_empty_row();
+        totals.set_local_profit(total_local_profit);
+        totals.set_tax_to_pay(total_tax_to_pay);
+
+        trades_table.print(&format!(
+            "Расчет прибыли от продажи и погашения облигаций, полученной через {}", broker_name));
+    }
+
+    let mut coupons_table = CouponsTable::new();
+
+    let mut total_foreign_amount = MultiCurrencyCashAccount::new();
+    let mut total_amount = dec!(0);
+    let mut total_paid_tax = dec!(0);
+    let mut total_tax_to_pay = dec!(0);
+    let mut total_income = dec!(0);
+
+    for coupon in &broker_statement.coupons {
+        if let Some(year) = year {
+            if coupon.date.year() != year {
+                continue;
+            }
+        }
+
+        // Accrued interest paid on bond buying is reported by some brokers as a negative coupon
+        // payment. It can't be declared as a negative income, so skip it as we do for interest.
+        if coupon.amount.is_negative() {
+            continue;
+        }
+
+        let security = broker_statement.get_instrument_name(&coupon.symbol);
+
+        let foreign_amount = coupon.amount.round();
+        total_foreign_amount.deposit(foreign_amount);
+
+        let precise_currency_rate = converter.precise_currency_rate(
+            coupon.date, foreign_amount.currency, country.currency)?;
+
+        let amount = converter.convert_to_rounding(coupon.date, foreign_amount, country.currency)?;
+        total_amount += amount;
+
+        let foreign_paid_tax = coupon.paid_tax.round();
+        let paid_tax = converter.convert_to_rounding(coupon.date, foreign_paid_tax, country.currency)?;
+        total_paid_tax += paid_tax;
+
+        let tax_to_pay = coupon.tax_to_pay(&country, converter)?;
+        total_tax_to_pay += tax_to_pay;
+
+        let income = amount - paid_tax - tax_to_pay;
+        total_income += income;
+
+        coupons_table.add_row(CouponRow {
+            date: coupon.date,
+            security: security.clone(),
+            currency: foreign_amount.currency.to_owned(),
+            foreign_amount: foreign_amount,
+            currency_rate: precise_currency_rate,
+            amount: Cash::new(country.currency, amount),
+            paid_tax: Cash::new(country.currency, paid_tax),
+            tax_to_pay: Cash::new(country.currency, tax_to_pay),
+            income: Cash::new(country.currency, income),
+        });
+
+        if let Some(ref mut tax_statement) = tax_statement {
+            let description = format!("{}: Купонный доход по {}", broker_name, security);
+
+            tax_statement.add_coupon_income(
+                &description, coupon.issuer_country, coupon.date, foreign_amount.currency,
+                precise_currency_rate, foreign_amount.amount, foreign_paid_tax.amount,
+                amount, paid_tax,
+            ).map_err(|e| format!(
+                "Unable to add {} to the tax statement: {}", coupon.description(), e
+            ))?;
+        }
+    }
+
+    if !coupons_table.is_empty() {
+        let mut totals = coupons_table.add_empty_row();
+        totals.set_foreign_amount(total_foreign_amount);
+        totals.set_amount(Cash::new(country.currency, total_amount));
+        totals.set_paid_tax(Cash::new(country.currency, total_paid_tax));
+        totals.set_tax_to_pay(Cash::new(country.currency, total_tax_to_pay));
+        totals.set_income(Cash::new(country.currency, total_income));
+
+        coupons_table.print(&format!(
+            "Расчет купонного дохода по облигациям, полученного через {}", broker_name));
+    }
+
+    Ok(())
+}
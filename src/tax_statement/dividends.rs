@@ -17,6 +17,8 @@ struct Row {
     date: Date,
     #[column(name="Эмитент")]
     issuer: String,
+    #[column(name="Страна", align="center")]
+    country: String,
     #[column(name="Валюта", align="center")]
     currency: String,
 
@@ -89,7 +91,12 @@ pub fn process_income(
         let tax_to_pay = dividend.tax_to_pay(&country, converter)?;
         total_tax_to_pay += tax_to_pay;
 
-        let tax_deduction = country.round_tax(paid_tax);
+        // Tax paid above the rate established by the double taxation treaty can't be credited
+        let foreign_creditable_tax = dividend.creditable_tax()?;
+        let creditable_tax = converter.convert_to_rounding(
+            dividend.date, foreign_creditable_tax, country.currency)?;
+
+        let tax_deduction = country.round_tax(creditable_tax);
         if !tax_to_pay.is_zero() {
             assert_eq!(tax_deduction, tax - tax_to_pay);
         }
@@ -101,6 +108,7 @@ pub fn process_income(
         table.add_row(Row {
             date: dividend.date,
             issuer: issuer.to_owned(),
+            country: dividend.issuer_country.name(),
             currency: foreign_amount.currency.to_owned(),
 
             foreign_amount: foreign_amount,
@@ -118,15 +126,10 @@ pub fn process_income(
         if let Some(ref mut tax_statement) = tax_statement {
             let description = format!("{}: Дивиденд от {}", broker_statement.broker.name, issuer);
 
-            if foreign_paid_tax.currency != foreign_amount.currency {
-                return Err!(
-                    "{}: Tax currency is different from dividend currency: {} vs {}",
-                    dividend.description(), foreign_paid_tax.currency, foreign_amount.currency);
-            }
-
             tax_statement.add_dividend_income(
-                &description, dividend.date, foreign_amount.currency, precise_currency_rate,
-                foreign_amount.amount, foreign_paid_tax.amount, amount, paid_tax
+                &description, dividend.issuer_country, dividend.date, foreign_amount.currency,
+                precise_currency_rate, foreign_amount.amount, foreign_creditable_tax.amount, amount,
+                creditable_tax,
             ).map_err(|e| format!(
                 "Unable to add {} to the tax statement: {}", dividend.description(), e
             ))?;
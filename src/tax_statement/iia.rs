@@ -0,0 +1,89 @@
+use log::warn;
+use static_table_derive::StaticTable;
+
+use crate::broker_statement::BrokerStatement;
+use crate::config::PortfolioConfig;
+use crate::core::EmptyResult;
+use crate::currency::Cash;
+use crate::taxes::iia;
+
+use super::statement::TaxStatement;
+
+#[derive(StaticTable)]
+#[table(name="DeductionsTable")]
+struct DeductionRow {
+    #[column(name="Год")]
+    year: i32,
+    #[column(name="Взносы")]
+    contributions: Cash,
+    #[column(name="Налоговая база")]
+    base: Cash,
+    #[column(name="Вычет")]
+    deduction: Cash,
+}
+
+/// Returns true if income from trading operations is exempt from taxation due to type B individual
+/// investment account closing.
+pub fn is_trading_income_exempt(portfolio: &PortfolioConfig) -> bool {
+    portfolio.iia.as_ref().and_then(|iia| {
+        iia.close_date.map(|close_date| iia::is_trading_income_exempt(iia, close_date))
+    }).unwrap_or(false)
+}
+
+pub fn process_deductions(
+    portfolio: &PortfolioConfig, broker_statement: &BrokerStatement, year: Option<i32>,
+    mut tax_statement: Option<&mut TaxStatement>,
+) -> EmptyResult {
+    let iia = match portfolio.iia {
+        Some(ref iia) => iia,
+        None => return Ok(()),
+    };
+
+    let country = portfolio.get_tax_country();
+    let mut table = DeductionsTable::new();
+    let mut total_deduction = Cash::new(country.currency, dec!(0));
+
+    for deduction in iia::get_contribution_deductions(iia, &country, &broker_statement.cash_flows)? {
+        if let Some(year) = year {
+            if deduction.year != year {
+                continue;
+            }
+        }
+
+        if !deduction.deduction.is_zero() && !iia.paid_taxes.contains_key(&deduction.year) {
+            warn!(concat!(
+                "Personal income tax paid in {} is not specified in the individual investment ",
+                "account configuration. The deduction can't exceed it."), deduction.year);
+        }
+
+        // The tax statement declares the deducted base, the refund is calculated from it
+        if let Some(ref mut tax_statement) = tax_statement {
+            if !deduction.base.is_zero() {
+                tax_statement.add_iia_contributions_deduction(deduction.base)?;
+            }
+        }
+
+        let deduction = DeductionRow {
+            year: deduction.year,
+            contributions: Cash::new(country.currency, deduction.contributions),
+            base: Cash::new(country.currency, deduction.base),
+            deduction: Cash::new(country.currency, deduction.deduction),
+        };
+
+        total_deduction.add_assign(deduction.deduction).unwrap();
+        table.add_row(deduction);
+    }
+
+    if !table.is_empty() {
+        if year.is_none() {
+            let mut totals = table.add_empty_row();
+            totals.set_deduction(total_deduction);
+        }
+
+        table.print(&format!(
+            "Инвестиционный вычет на взносы на ИИС типа А, открытый через {}",
+            broker_statement.broker.name));
+    }
+
+    Ok(())
+}
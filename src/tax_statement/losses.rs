@@ -0,0 +1,192 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use diesel::{self, prelude::*};
+
+use crate::core::{EmptyResult, GenericResult};
+use crate::db::{self, schema::trading_results, models};
+use crate::types::Decimal;
+use crate::util::{self, DecimalRestrictions};
+
+// Losses may be carried forward for up to 10 years following the year when they've been received
+const LOSS_CARRYFORWARD_YEARS: i32 = 10;
+
+/// Stores stock trading results of previous years to be able to carry losses forward. Losses are
+/// carried forward by the taxpayer as a whole, so the results are stored for each portfolio, but
+/// are summed up across all of them.
+pub struct LossCarryforward {
+    db: db::Connection,
+    portfolio: String,
+}
+
+struct TradingResult {
+    portfolio: String,
+    year: i32,
+    profit: Decimal,
+    applied_loss: Decimal,
+}
+
+impl LossCarryforward {
+    pub fn new(connection: db::Connection, portfolio: &str) -> LossCarryforward {
+        LossCarryforward {
+            db: connection,
+            portfolio: portfolio.to_owned(),
+        }
+    }
+
+    fn load(&self) -> GenericResult<Vec<TradingResult>> {
+        let results = trading_results::table.load::<models::TradingResult>(&*self.db)?;
+        let parse = |value: &str| util::parse_decimal(value, DecimalRestrictions::No).map_err(|_| format!(
+            "Got an invalid trading result from the database: {:?}", value));
+
+        let mut parsed_results = Vec::new();
+
+        for result in results {
+            parsed_results.push(TradingResult {
+                profit: parse(&result.profit)?,
+                applied_loss: parse(&result.applied_loss)?,
+                portfolio: result.portfolio,
+                year: result.year,
+            });
+        }
+
+        Ok(parsed_results)
+    }
+
+    pub fn save(&self, year: i32, profit: Decimal) -> EmptyResult {
+        let updated = diesel::update(trading_results::table
+                .filter(trading_results::portfolio.eq(&self.portfolio))
+                .filter(trading_results::year.eq(year)))
+            .set(trading_results::profit.eq(profit.to_string()))
+            .execute(&*self.db)?;
+
+        if updated == 0 {
+            self.insert(year, profit, dec!(0))?;
+        }
+
+        Ok(())
+    }
+
+    /// Saves the loss which has been applied to the portfolio's result of the specified year.
+    pub fn save_applied_loss(&self, year: i32, loss: Decimal) -> EmptyResult {
+        let updated = diesel::update(trading_results::table
+                .filter(trading_results::portfolio.eq(&self.portfolio))
+                .filter(trading_results::year.eq(year)))
+            .set(trading_results::applied_loss.eq(loss.to_string()))
+            .execute(&*self.db)?;
+
+        if updated == 0 {
+            self.insert(year, dec!(0), loss)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns losses of the previous years which may be applied to the specified year: the
+    /// taxpayer's carried forward loss without the part which has been already applied by other
+    /// portfolios.
+    pub fn get_available_loss(&self, year: i32) -> GenericResult<Decimal> {
+        let mut profits = BTreeMap::new();
+        let mut applied_loss = dec!(0);
+
+        for result in self.load()? {
+            *profits.entry(result.year).or_default() += result.profit;
+
+            if result.year == year && result.portfolio != self.portfolio {
+                applied_loss += result.applied_loss;
+            }
+        }
+
+        let available_loss = calculate_available_loss(&profits, year) - applied_loss;
+        Ok(std::cmp::max(available_loss, dec!(0)))
+    }
+
+    fn insert(&self, year: i32, profit: Decimal, applied_loss: Decimal) -> EmptyResult {
+        diesel::insert_into(trading_results::table)
+            .values(models::TradingResult {
+                portfolio: self.portfolio.clone(),
+                year: year,
+                profit: profit.to_string(),
+                applied_loss: applied_loss.to_string(),
+            })
+            .execute(&*self.db)?;
+        Ok(())
+    }
+}
+
+// Losses are applied in the order they've been received
+fn calculate_available_loss(profits: &BTreeMap<i32, Decimal>, year: i32) -> Decimal {
+    let mut losses: VecDeque<(i32, Decimal)> = VecDeque::new();
+
+    let expire = |losses: &mut VecDeque<(i32, Decimal)>, year: i32| {
+        while let Some(&(loss_year, _)) = losses.front() {
+            if year - loss_year <= LOSS_CARRYFORWARD_YEARS {
+                break;
+            }
+            losses.pop_front();
+        }
+    };
+
+    for (&profit_year, &profit) in profits.range(..year) {
+        if profit.is_sign_negative() {
+            losses.push_back((profit_year, -profit));
+            continue;
+        }
+
+        expire(&mut losses, profit_year);
+        let mut profit = profit;
+
+        while !profit.is_zero() {
+            let loss = match losses.front_mut() {
+                Some((_, loss)) => loss,
+                None => break,
+            };
+
+            let applied = std::cmp::min(*loss, profit);
+            *loss -= applied;
+            profit -= applied;
+
+            if loss.is_zero() {
+                losses.pop_front();
+            }
+        }
+    }
+
+    expire(&mut losses, year);
+    losses.iter().map(|&(_, loss)| loss).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loss_carryforward() {
+        let (_database, connection) = db::new_temporary();
+        let ledger = LossCarryforward::new(connection.clone(), "portfolio");
+        let other_ledger = LossCarryforward::new(connection.clone(), "other");
+
+        ledger.save(2008, dec!(-50)).unwrap();
+        ledger.save(2010, dec!(-100)).unwrap();
+        ledger.save(2011, dec!(30)).unwrap();
+        ledger.save(2012, dec!(-20)).unwrap();
+
+        assert_eq!(ledger.get_available_loss(2010).unwrap(), dec!(50));
+        assert_eq!(ledger.get_available_loss(2013).unwrap(), dec!(140));
+        assert_eq!(ledger.get_available_loss(2019).unwrap(), dec!(120));
+        assert_eq!(ledger.get_available_loss(2021).unwrap(), dec!(20));
+        assert_eq!(ledger.get_available_loss(2023).unwrap(), dec!(0));
+
+        // Results of all the taxpayer's portfolios are summed up
+        other_ledger.save(2012, dec!(-1000)).unwrap();
+        assert_eq!(ledger.get_available_loss(2013).unwrap(), dec!(1140));
+
+        // The loss can't be applied twice by different portfolios
+        other_ledger.save_applied_loss(2013, dec!(1000)).unwrap();
+        assert_eq!(ledger.get_available_loss(2013).unwrap(), dec!(140));
+        assert_eq!(other_ledger.get_available_loss(2013).unwrap(), dec!(1140));
+
+        ledger.save(2011, dec!(200)).unwrap();
+        assert_eq!(ledger.get_available_loss(2013).unwrap(), dec!(20));
+        assert_eq!(other_ledger.get_available_loss(2013).unwrap(), dec!(1020));
+    }
+}
@@ -4,15 +4,20 @@ use crate::core::EmptyResult;
 use crate::currency::converter::CurrencyConverter;
 use crate::db;
 
-pub use self::statement::TaxStatement;
+pub use self::losses::LossCarryforward;
+pub use self::statement::{TaxStatement, ExportedStatement};
 
+mod bonds;
 mod dividends;
+mod iia;
 mod interest;
+mod losses;
 mod statement;
 mod trades;
 
 pub fn generate_tax_statement(
-    config: &Config, portfolio_name: &str, year: Option<i32>, tax_statement_path: Option<&str>
+    config: &Config, portfolio_name: &str, year: Option<i32>, tax_statement_path: Option<&str>,
+    create_tax_statement: bool,
 ) -> EmptyResult {
     let portfolio = config.get_portfolio(portfolio_name)?;
     let broker = portfolio.broker.get_info(config, portfolio.plan.as_ref())?;
@@ -30,7 +35,14 @@ pub fn generate_tax_statement(
             let year = year.ok_or_else(||
                 "Tax year must be specified when tax statement is specified")?;
 
-            let statement = TaxStatement::read(path)?;
+            let statement = if create_tax_statement || TaxStatement::is_xml(path) {
+                let taxpayer = config.taxpayer.as_ref().ok_or(
+                    "Taxpayer profile must be configured to create a new tax statement")?;
+                TaxStatement::create(path, year, taxpayer)?
+            } else {
+                TaxStatement::read(path)?
+            };
+
             if statement.year != year {
                 return Err!("Tax statement year ({}) doesn't match the requested year {}",
                             statement.year, year);
@@ -42,10 +54,40 @@ pub fn generate_tax_statement(
     };
 
     let database = db::connect(&config.db_path)?;
-    let converter = CurrencyConverter::new(database, None, true);
+    let converter = CurrencyConverter::new(
+        database.clone(), portfolio.get_tax_country().currency_rate_source, None, true);
+    let loss_carryforward = LossCarryforward::new(database, &portfolio.name);
+
+    // Income from trading operations on type B individual investment account is exempt from
+    // taxation, so it's shown for information purposes only and isn't declared
+    let trading_income_exempt = iia::is_trading_income_exempt(&portfolio);
+
+    {
+        // Exempt results mustn't affect the loss ledger either. Income received through tax agents
+        // is taxed by them, so it's not accounted in the taxpayer's ledger.
+        let (tax_statement, loss_carryforward) = if trading_income_exempt {
+            (None, None)
+        } else if broker_statement.broker.type_.is_tax_agent() {
+            (tax_statement.as_mut(), None)
+        } else {
+            (tax_statement.as_mut(), Some(&loss_carryforward))
+        };
+
+        trades::process_income(
+            &portfolio, &broker_statement, year, tax_statement, &converter, loss_carryforward,
+        ).map_err(|e| format!("Failed to process income from stock trading: {}", e))?;
+    }
+
+    {
+        let tax_statement = if trading_income_exempt {
+            None
+        } else {
+            tax_statement.as_mut()
+        };
 
-    trades::process_income(&portfolio, &broker_statement, year, tax_statement.as_mut(), &converter)
-        .map_err(|e| format!("Failed to process income from stock trading: {}", e))?;
+        bonds::process_income(&portfolio, &broker_statement, year, tax_statement, &converter)
+            .map_err(|e| format!("Failed to process income from bonds: {}", e))?;
+    }
 
     dividends::process_income(&portfolio, &broker_statement, year, tax_statement.as_mut(), &converter)
         .map_err(|e| format!("Failed to process dividend income: {}", e))?;
@@ -53,6 +95,9 @@ pub fn generate_tax_statement(
     interest::process_income(&portfolio, &broker_statement, year, tax_statement.as_mut(), &converter)
         .map_err(|e| format!("Failed to process income from idle cash interest: {}", e))?;
 
+    iia::process_deductions(&portfolio, &broker_statement, year, tax_statement.as_mut())
+        .map_err(|e| format!("Failed to process individual investment account deductions: {}", e))?;
+
     if let Some(ref tax_statement) = tax_statement {
         tax_statement.save()?;
     }
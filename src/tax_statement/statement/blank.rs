@@ -0,0 +1,56 @@
+use crate::config::TaxpayerConfig;
+use crate::core::GenericResult;
+use crate::types::Date;
+
+use super::encoding::TaxStatementPrimitiveType;
+use super::deductions::{InvestmentDeductions, SecuritiesDeductions};
+use super::foreign_income::ForeignIncome;
+use super::record::{Record, UnknownRecord};
+
+/// Synthesizes records of a blank tax statement the same way as Декларация program does it when
+/// creates a new statement with enabled foreign income.
+pub fn create_records(
+    taxpayer: &TaxpayerConfig, creation_date: Date,
+) -> GenericResult<Vec<Box<dyn Record>>> {
+    let mut records: Vec<Box<dyn Record>> = Vec::new();
+
+    let mut date = String::new();
+    creation_date.encode(&mut date)?;
+
+    // Inspection code, creation date and enabled income types (domestic + foreign)
+    records.push(record("@DeclInfo", &[
+        taxpayer.inspection_code.as_str(), "", "0", "0", date.as_str(), "0", "5", "0", "3",
+        "0", "0", "0", "0", "0", "0", "0", "0", "0", "0", "0", "", "0", "", "", "0",
+    ]));
+
+    records.push(record("@PersonName", &[
+        taxpayer.last_name.as_str(), taxpayer.first_name.as_str(),
+        taxpayer.middle_name.as_deref().unwrap_or_default(), taxpayer.inn.as_str(), "", "0",
+    ]));
+
+    // Декларация program fills birth and document issue dates with placeholders, but we don't know
+    // the real ones, so leave them for the user.
+    records.push(record("@PersonDocument", &["0", "", "", "", "", "", "", "0"]));
+    records.push(record("@Foreigner", &["", "РОССИЯ", "", "643"]));
+    records.push(record("@PhoneForeignerHome", &["", ""]));
+    records.push(record("@PhoneForeignerWork", &["", ""]));
+    records.push(record("@PersonAddress", &["0", "", "0", "", "", "", "", "", "", "", "", ""]));
+    records.push(record("@HomePhone", &["", ""]));
+    records.push(record("@WorkPhone", &["", ""]));
+    records.push(record("@DeclInquiry", &["0", "0", "0", "0", "0"]));
+    records.push(Box::new(ForeignIncome {incomes: Vec::new()}));
+
+    for &name in &["@StandartDeduct", "@SocialDeduct", "@ConstructionDeduct"] {
+        records.push(record(name, &["0"]));
+    }
+
+    records.push(Box::new(SecuritiesDeductions {losses: Vec::new()}));
+    records.push(Box::new(InvestmentDeductions {deductions: Vec::new()}));
+
+    Ok(records)
+}
+
+fn record(name: &str, fields: &[&str]) -> Box<dyn Record> {
+    Box::new(UnknownRecord::new(
+        name.to_owned(), fields.iter().map(|&field| field.to_owned()).collect()))
+}
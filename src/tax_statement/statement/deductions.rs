@@ -0,0 +1,119 @@
+use std::any::Any;
+
+use crate::core::{EmptyResult, GenericResult};
+use crate::types::Decimal;
+
+use super::parser::{TaxStatementReader, TaxStatementWriter};
+use super::record::Record;
+use super::types::Integer;
+
+/// Investment tax deductions (ст. 219.1 НК РФ) which are applied to the whole tax base instead of a
+/// particular income.
+#[derive(Debug)]
+pub struct InvestmentDeductions {
+    pub deductions: Vec<InvestmentDeduction>,
+}
+
+impl InvestmentDeductions {
+    pub const RECORD_NAME: &'static str = "@InvDeduct";
+
+    pub fn read(reader: &mut TaxStatementReader) -> GenericResult<InvestmentDeductions> {
+        let number: usize = reader.read_value()?;
+        let mut deductions = Vec::with_capacity(number);
+
+        for index in 0..number {
+            deductions.push(InvestmentDeduction::read(reader, index)?);
+        }
+
+        Ok(InvestmentDeductions {deductions: deductions})
+    }
+}
+
+impl Record for InvestmentDeductions {
+    fn name(&self) -> &str {
+        InvestmentDeductions::RECORD_NAME
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_mut_any(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn write(&self, writer: &mut TaxStatementWriter) -> EmptyResult {
+        writer.write_data(InvestmentDeductions::RECORD_NAME)?;
+        writer.write_value(&self.deductions.len())?;
+
+        for (index, deduction) in self.deductions.iter().enumerate() {
+            deduction.write(writer, index)?;
+        }
+
+        Ok(())
+    }
+}
+
+tax_statement_array_record!(InvestmentDeduction {
+    code: Integer,
+    amount: Decimal,
+}, index_length=2);
+
+impl InvestmentDeduction {
+    // Long-term ownership deduction (ЛДВ)
+    pub const LONG_TERM_OWNERSHIP_CODE: Integer = 618;
+
+    // Deduction on contributions to type A individual investment account
+    pub const IIA_CONTRIBUTIONS_CODE: Integer = 619;
+}
+
+/// Losses from operations with securities of the previous years which are carried forward to the
+/// current tax year (ст. 220.1 НК РФ).
+#[derive(Debug)]
+pub struct SecuritiesDeductions {
+    pub losses: Vec<CarriedForwardLoss>,
+}
+
+impl SecuritiesDeductions {
+    pub const RECORD_NAME: &'static str = "@CBDeduct";
+
+    pub fn read(reader: &mut TaxStatementReader) -> GenericResult<SecuritiesDeductions> {
+        let number: usize = reader.read_value()?;
+        let mut losses = Vec::with_capacity(number);
+
+        for index in 0..number {
+            losses.push(CarriedForwardLoss::read(reader, index)?);
+        }
+
+        Ok(SecuritiesDeductions {losses: losses})
+    }
+}
+
+impl Record for SecuritiesDeductions {
+    fn name(&self) -> &str {
+        SecuritiesDeductions::RECORD_NAME
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_mut_any(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn write(&self, writer: &mut TaxStatementWriter) -> EmptyResult {
+        writer.write_data(SecuritiesDeductions::RECORD_NAME)?;
+        writer.write_value(&self.losses.len())?;
+
+        for (index, loss) in self.losses.iter().enumerate() {
+            loss.write(writer, index)?;
+        }
+
+        Ok(())
+    }
+}
+
+tax_statement_array_record!(CarriedForwardLoss {
+    amount: Decimal,
+}, index_length=2);
@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::GenericResult;
+
+use super::TaxStatement;
+use super::record::{Record, UnknownRecord, is_record_name};
+
+/// Tax statement records in a format-independent form which may be serialized to JSON/YAML,
+/// edited and written back to *.dcX file.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ExportedStatement {
+    pub year: i32,
+    pub records: Vec<ExportedRecord>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ExportedRecord {
+    pub name: String,
+    pub fields: Vec<String>,
+}
+
+impl ExportedStatement {
+    /// Compares the statements record by record and returns a human-readable list of differences.
+    pub fn diff(&self, other: &ExportedStatement) -> Vec<String> {
+        let mut differences = Vec::new();
+
+        if self.year != other.year {
+            differences.push(format!("Year: {} -> {}", self.year, other.year));
+        }
+
+        let records = index_records(&self.records);
+        let other_records = index_records(&other.records);
+
+        for (key, record) in &records {
+            let name = get_record_label(key);
+
+            let other_record = match other_records.iter().find(|(other_key, _)| other_key == key) {
+                Some((_, other_record)) => other_record,
+                None => {
+                    differences.push(format!("- {}", name));
+                    continue;
+                },
+            };
+
+            for index in 0..std::cmp::max(record.fields.len(), other_record.fields.len()) {
+                match (record.fields.get(index), other_record.fields.get(index)) {
+                    (Some(value), Some(other_value)) if value != other_value => {
+                        differences.push(format!(
+                            "~ {}[{}]: {:?} -> {:?}", name, index, value, other_value));
+                    },
+                    (Some(value), None) => {
+                        differences.push(format!("~ {}[{}]: {:?} is removed", name, index, value));
+                    },
+                    (None, Some(other_value)) => {
+                        differences.push(format!("~ {}[{}]: {:?} is added", name, index, other_value));
+                    },
+                    _ => {},
+                }
+            }
+        }
+
+        for (key, _) in &other_records {
+            if records.iter().all(|(other_key, _)| other_key != key) {
+                differences.push(format!("+ {}", get_record_label(key)));
+            }
+        }
+
+        differences
+    }
+}
+
+pub fn export(statement: &TaxStatement) -> GenericResult<ExportedStatement> {
+    let mut records = Vec::with_capacity(statement.records.len());
+
+    for record in &statement.records {
+        let fields = record.as_any().downcast_ref::<UnknownRecord>().ok_or_else(|| format!(
+            "Unable to export {} record: it's not a raw record", record.name()))?.fields();
+
+        records.push(ExportedRecord {
+            name: record.name().to_owned(),
+            fields: fields.to_vec(),
+        });
+    }
+
+    Ok(ExportedStatement {
+        year: statement.year,
+        records: records,
+    })
+}
+
+pub fn import(statement: &ExportedStatement) -> GenericResult<Vec<Box<dyn Record>>> {
+    let mut records: Vec<Box<dyn Record>> = Vec::with_capacity(statement.records.len());
+
+    for record in &statement.records {
+        if !is_record_name(&record.name) {
+            return Err!("Got an invalid record name: {:?}", record.name);
+        }
+
+        // Field value which looks like a record name will be read as a record name
+        if let Some(field) = record.fields.iter().find(|field| is_record_name(field)) {
+            return Err!("{} record has an invalid field value: {:?}", record.name, field);
+        }
+
+        records.push(Box::new(UnknownRecord::new(record.name.clone(), record.fields.clone())));
+    }
+
+    if records.is_empty() {
+        return Err!("The tax statement has no records");
+    }
+
+    Ok(records)
+}
+
+// Identifies records by their name and occurrence number
+fn index_records(records: &[ExportedRecord]) -> Vec<((&str, usize), &ExportedRecord)> {
+    let mut occurrences: HashMap<&str, usize> = HashMap::new();
+
+    records.iter().map(|record| {
+        let occurrence = occurrences.entry(&record.name).or_default();
+        *occurrence += 1;
+        ((record.name.as_str(), *occurrence), record)
+    }).collect()
+}
+
+fn get_record_label(&(name, occurrence): &(&str, usize)) -> String {
+    if occurrence == 1 {
+        name.to_owned()
+    } else {
+        format!("{} #{}", name, occurrence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::Read;
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let path = Path::new(file!()).parent().unwrap().join("testdata/filled.dc9");
+        let path = path.to_str().unwrap();
+
+        let exported = TaxStatement::export(path).unwrap();
+        assert_eq!(exported.year, 2019);
+        assert_eq!(exported.records.len(), 20);
+
+        let data = serde_json::to_string_pretty(&exported).unwrap();
+        let imported: ExportedStatement = serde_json::from_str(&data).unwrap();
+        assert_eq!(imported, exported);
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let new_path = temp_dir.path().join("statement.dc9");
+        let new_path = new_path.to_str().unwrap();
+
+        TaxStatement::import(new_path, &imported).unwrap().save().unwrap();
+        assert_eq!(get_contents(new_path), get_contents(path));
+        assert!(TaxStatement::import(new_path, &imported).is_err());
+
+        let other_path = temp_dir.path().join("statement.dc8");
+        assert!(TaxStatement::import(other_path.to_str().unwrap(), &imported).is_err());
+    }
+
+    #[test]
+    fn diff() {
+        let record = |name: &str, fields: &[&str]| ExportedRecord {
+            name: name.to_owned(),
+            fields: fields.iter().map(|&field| field.to_owned()).collect(),
+        };
+
+        let statement = ExportedStatement {
+            year: 2019,
+            records: vec![
+                record("@DeclInfo", &["", "0"]),
+                record("@CurrencyIncome", &["1010", "100"]),
+                record("@CurrencyIncome", &["1011", "200"]),
+                record("@InvDeduct", &["0"]),
+            ],
+        };
+        assert!(statement.diff(&statement).is_empty());
+
+        let other = ExportedStatement {
+            year: 2019,
+            records: vec![
+                record("@DeclInfo", &["7701", "0", "1"]),
+                record("@CurrencyIncome", &["1010", "100"]),
+                record("@CurrencyIncome", &["1011", "300"]),
+                record("@CBDeduct", &["0"]),
+            ],
+        };
+
+        assert_eq!(statement.diff(&other), vec![
+            r#"~ @DeclInfo[0]: "" -> "7701""#,
+            r#"~ @DeclInfo[2]: "1" is added"#,
+            r#"~ @CurrencyIncome #2[1]: "200" -> "300""#,
+            "- @InvDeduct",
+            "+ @CBDeduct",
+        ]);
+    }
+
+    fn get_contents(path: &str) -> Vec<u8> {
+        let mut data = Vec::new();
+        File::open(path).unwrap().read_to_end(&mut data).unwrap();
+        data
+    }
+}
@@ -1,7 +1,9 @@
 use std::any::Any;
+use std::convert::TryFrom;
 
 use crate::core::{EmptyResult, GenericResult};
 use crate::currency;
+use crate::localities::CountryCode;
 use crate::types::{Date, Decimal};
 
 use super::encoding::TaxStatementType;
@@ -34,6 +36,10 @@ impl Record for ForeignIncome {
         ForeignIncome::RECORD_NAME
     }
 
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
     fn as_mut_any(&mut self) -> &mut dyn Any {
         self
     }
@@ -88,6 +94,15 @@ impl CurrencyInfo {
         let (currency_code, currency_name, currency_rate_units) = match currency {
             "RUB" => (643, "Российский рубль", 1000),
             "USD" => (840, "Доллар США", 100),
+            "EUR" => (978, "Евро", 100),
+            "GBP" => (826, "Фунт стерлингов", 100),
+            "HKD" => (344, "Гонконгский доллар", 100),
+            "CNY" => (156, "Юань", 100),
+            "AUD" => (36, "Австралийский доллар", 100),
+            "CAD" => (124, "Канадский доллар", 100),
+            "SEK" => (752, "Шведская крона", 100),
+            "CHF" => (756, "Швейцарский франк", 100),
+            "JPY" => (392, "Японская иена", 100),
             _ => return Err!("{} currency is not supported yet", currency),
         };
         let currency_rate = currency::round(precise_currency_rate * Decimal::from(currency_rate_units));
@@ -145,15 +160,21 @@ pub enum IncomeType {
     Dividend,
     Interest,
     Stock,
+    Derivative,
     Unknown {unknown: Integer, code: Integer, name: String},
 }
 
 impl IncomeType {
+    pub fn code(&self) -> Integer {
+        self.decouple().1
+    }
+
     fn decouple(&self) -> (Integer, Integer, String) {
         let (unknown, code, name) = match self {
             IncomeType::Dividend => (14, 1010, "Дивиденды"),
             IncomeType::Interest => (13, 1011, "Проценты (за исключением процентов по облигациям с ипотечным покрытием, эмитированным до 01.01.2007)"),
             IncomeType::Stock => (13, 1530, "(01)Доходы от реализации ЦБ (обращ-ся на орг. рынке ЦБ)"),
+            IncomeType::Derivative => (13, 1532, "(02)Доходы по операциям с ПФИ (обращ-ся на орг. рынке ЦБ)"),
             IncomeType::Unknown {unknown, code, name} => return (*unknown, *code, name.clone()),
         };
 
@@ -167,7 +188,9 @@ impl TaxStatementType for IncomeType {
         let code = reader.read_value()?;
         let name = reader.read_value()?;
 
-        for income_type in &[IncomeType::Dividend, IncomeType::Interest, IncomeType::Stock] {
+        for income_type in &[
+            IncomeType::Dividend, IncomeType::Interest, IncomeType::Stock, IncomeType::Derivative,
+        ] {
             let (other_unknown, other_code, other_name) = income_type.decouple();
             if unknown == other_unknown && code == other_code && name == other_name {
                 return Ok(income_type.clone());
@@ -190,27 +213,34 @@ impl TaxStatementType for IncomeType {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-#[cfg_attr(test, derive(PartialEq, Eq))]
-pub enum CountryCode {
-    Usa,
-    Unknown(Integer),
-}
-
 impl TaxStatementType for CountryCode {
     fn read(reader: &mut TaxStatementReader) -> GenericResult<CountryCode> {
-        Ok(match reader.read_value()? {
-            840 => CountryCode::Usa,
-            code => CountryCode::Unknown(code),
-        })
+        let code: Integer = reader.read_value()?;
+        let code = u32::try_from(code).map_err(|_| format!("Invalid country code: {}", code))?;
+        Ok(CountryCode::from_oksm_code(code))
     }
 
     fn write(&self, writer: &mut TaxStatementWriter) -> EmptyResult {
-        let code = match *self {
-            CountryCode::Usa => 840,
-            CountryCode::Unknown(code) => code,
-        };
-        writer.write_value(&code)?;
+        writer.write_value(&(self.oksm_code() as Integer))?;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn currency_info() {
+        for &currency in &["USD", "EUR", "GBP", "HKD", "CNY", "AUD", "CAD", "SEK", "CHF", "JPY"] {
+            CurrencyInfo::new(currency, dec!(1)).unwrap();
+        }
+
+        let info = CurrencyInfo::new("JPY", dec!(0.692315)).unwrap();
+        assert_eq!(info.code, 392);
+        assert_eq!(info.income_date_rate, dec!(69.23));
+        assert_eq!(info.income_date_units, 100);
+
+        assert!(CurrencyInfo::new("XYZ", dec!(1)).is_err());
+    }
+}
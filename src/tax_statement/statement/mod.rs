@@ -1,36 +1,135 @@
 use std::fs;
+use std::path::Path;
 
+use crate::config::TaxpayerConfig;
 use crate::core::{EmptyResult, GenericResult};
+use crate::localities::{CountryCode, Jurisdiction};
 use crate::types::{Date, Decimal};
+use crate::util;
 
-use self::foreign_income::{ForeignIncome, CurrencyIncome, CountryCode, CurrencyInfo, DeductionInfo,
-                           IncomeType, ControlledForeignCompanyInfo};
+pub use self::export::{ExportedStatement, ExportedRecord};
+
+use self::deductions::{InvestmentDeductions, InvestmentDeduction, SecuritiesDeductions,
+                       CarriedForwardLoss};
+use self::foreign_income::{ForeignIncome, CurrencyIncome, CurrencyInfo, DeductionInfo, IncomeType,
+                           ControlledForeignCompanyInfo};
 use self::record::Record;
+use self::types::Integer;
 use self::parser::{TaxStatementReader, TaxStatementWriter};
 
 #[macro_use] mod record;
+mod blank;
+mod deductions;
 mod encoding;
+mod export;
 mod foreign_income;
 mod parser;
 mod types;
+mod xml;
 
 #[derive(Debug)]
 pub struct TaxStatement {
     path: String,
     pub year: i32,
+    format: Format,
     records: Vec<Box<dyn Record>>,
 }
 
+#[derive(Debug)]
+enum Format {
+    // *.dcX file of Декларация program
+    Declaration,
+    // XML format published by the tax service
+    Xml(TaxpayerConfig),
+}
+
 impl TaxStatement {
     pub fn read(path: &str) -> GenericResult<TaxStatement> {
-        Ok(TaxStatementReader::read(path).map_err(|e| format!(
+        TaxStatement::read_impl(path, false)
+    }
+
+    /// Exports all statement records as is (with their raw fields) to be able to view or edit them.
+    pub fn export(path: &str) -> GenericResult<ExportedStatement> {
+        let statement = TaxStatement::read_impl(path, true)?;
+        export::export(&statement)
+    }
+
+    /// Creates the statement from the previously exported (and possibly edited) records. The
+    /// statement is written to the disk only on save. The path must point to a new file to not lose
+    /// the original statement.
+    pub fn import(path: &str, exported: &ExportedStatement) -> GenericResult<TaxStatement> {
+        if Path::new(path).exists() {
+            return Err!("Unable to import {:?} tax statement: the file already exists", path);
+        }
+
+        let year = parser::get_year(path)?;
+        if year != exported.year {
+            return Err!(
+                "Tax statement file extension ({}) doesn't match the exported statement year {}",
+                year, exported.year);
+        }
+
+        Ok(TaxStatement {
+            path: path.to_owned(),
+            year: year,
+            format: Format::Declaration,
+            records: export::import(exported)?,
+        })
+    }
+
+    fn read_impl(path: &str, raw: bool) -> GenericResult<TaxStatement> {
+        Ok(TaxStatementReader::read(path, raw).map_err(|e| format!(
             "Error while reading {:?} tax statement: {}", path, e))?)
     }
 
+    /// Creates a new tax statement for the specified taxpayer without any template file. The
+    /// statement is written to the disk only on save: *.xml path means 3-НДФЛ XML format, *.dcX
+    /// file of Декларация program is created otherwise.
+    pub fn create(path: &str, year: i32, taxpayer: &TaxpayerConfig) -> GenericResult<TaxStatement> {
+        if Path::new(path).exists() {
+            return Err!("Unable to create {:?} tax statement: the file already exists", path);
+        }
+
+        let format = if TaxStatement::is_xml(path) {
+            Format::Xml(taxpayer.clone())
+        } else {
+            let statement_year = parser::get_year(path)?;
+            if statement_year != year {
+                return Err!("Tax statement file extension ({}) doesn't match the requested year {}",
+                            statement_year, year);
+            }
+
+            Format::Declaration
+        };
+
+        TaxStatement::new(path, year, taxpayer, format, util::today())
+    }
+
+    /// XML tax statements can't be altered, so they are always created from scratch.
+    pub fn is_xml(path: &str) -> bool {
+        path.ends_with(".xml")
+    }
+
+    fn new(
+        path: &str, year: i32, taxpayer: &TaxpayerConfig, format: Format, date: Date,
+    ) -> GenericResult<TaxStatement> {
+        Ok(TaxStatement {
+            path: path.to_owned(),
+            year: year,
+            format: format,
+            records: blank::create_records(taxpayer, date)?,
+        })
+    }
+
     pub fn save(&self) -> EmptyResult {
         let temp_path = format!("{}.new", self.path);
 
-        TaxStatementWriter::write(self, &temp_path).map_err(|e| {
+        let result = match self.format {
+            Format::Declaration => TaxStatementWriter::write(self, &temp_path),
+            Format::Xml(ref taxpayer) => xml::write(self, taxpayer, util::today(), &temp_path),
+        };
+
+        result.map_err(|e| {
             let _ = fs::remove_file(&temp_path);
             format!("Failed to save the tax statement to {:?}: {}", temp_path, e)
         })?;
@@ -44,13 +143,14 @@ impl TaxStatement {
     }
 
     pub fn add_dividend_income(
-        &mut self, description: &str, date: Date, currency: &str, currency_rate: Decimal,
-        amount: Decimal, paid_tax: Decimal, local_amount: Decimal, local_paid_tax: Decimal,
+        &mut self, description: &str, issuer_country: CountryCode, date: Date, currency: &str,
+        currency_rate: Decimal, amount: Decimal, paid_tax: Decimal, local_amount: Decimal,
+        local_paid_tax: Decimal,
     ) -> EmptyResult {
         self.get_foreign_incomes()?.push(CurrencyIncome {
             type_: IncomeType::Dividend,
             description: description.to_owned(),
-            county_code: CountryCode::Usa,
+            county_code: issuer_country,
 
             date: date,
             tax_payment_date: date,
@@ -76,7 +176,7 @@ impl TaxStatement {
         self.get_foreign_incomes()?.push(CurrencyIncome {
             type_: IncomeType::Interest,
             description: description.to_owned(),
-            county_code: CountryCode::Usa,
+            county_code: CountryCode::Known(Jurisdiction::Usa),
 
             date: date,
             tax_payment_date: date,
@@ -95,14 +195,41 @@ impl TaxStatement {
         Ok(())
     }
 
+    pub fn add_coupon_income(
+        &mut self, description: &str, issuer_country: CountryCode, date: Date, currency: &str,
+        currency_rate: Decimal, amount: Decimal, paid_tax: Decimal, local_amount: Decimal,
+        local_paid_tax: Decimal,
+    ) -> EmptyResult {
+        self.get_foreign_incomes()?.push(CurrencyIncome {
+            type_: IncomeType::Interest,
+            description: description.to_owned(),
+            county_code: issuer_country,
+
+            date: date,
+            tax_payment_date: date,
+            currency: CurrencyInfo::new(currency, currency_rate)?,
+
+            amount: amount,
+            local_amount: local_amount,
+
+            paid_tax: paid_tax,
+            local_paid_tax: local_paid_tax,
+            deduction: DeductionInfo::new_none(),
+
+            controlled_foreign_company: ControlledForeignCompanyInfo::new_none(),
+        });
+
+        Ok(())
+    }
+
     pub fn add_stock_income(
-        &mut self, description: &str, date: Date, currency: &str, currency_rate: Decimal,
-        amount: Decimal, local_amount: Decimal, purchase_local_cost: Decimal,
+        &mut self, description: &str, issuer_country: CountryCode, date: Date, currency: &str,
+        currency_rate: Decimal, amount: Decimal, local_amount: Decimal, purchase_local_cost: Decimal,
     ) -> EmptyResult {
         self.get_foreign_incomes()?.push(CurrencyIncome {
             type_: IncomeType::Stock,
             description: description.to_owned(),
-            county_code: CountryCode::Usa,
+            county_code: issuer_country,
 
             date: date,
             tax_payment_date: date,
@@ -124,12 +251,106 @@ impl TaxStatement {
         Ok(())
     }
 
+    pub fn add_derivative_income(
+        &mut self, description: &str, date: Date, currency: &str, currency_rate: Decimal,
+        amount: Decimal, local_amount: Decimal, local_cost: Decimal,
+    ) -> EmptyResult {
+        self.get_foreign_incomes()?.push(CurrencyIncome {
+            type_: IncomeType::Derivative,
+            description: description.to_owned(),
+            county_code: CountryCode::Known(Jurisdiction::Usa),
+
+            date: date,
+            tax_payment_date: date,
+            currency: CurrencyInfo::new(currency, currency_rate)?,
+
+            amount: amount,
+            local_amount: local_amount,
+
+            paid_tax: dec!(0),
+            local_paid_tax: dec!(0),
+            deduction: DeductionInfo {
+                code: 202,
+                amount: local_cost,
+            },
+
+            controlled_foreign_company: ControlledForeignCompanyInfo::new_none(),
+        });
+
+        Ok(())
+    }
+
+    pub fn add_long_term_ownership_deduction(&mut self, amount: Decimal) -> EmptyResult {
+        self.add_investment_deduction(InvestmentDeduction::LONG_TERM_OWNERSHIP_CODE, amount)
+    }
+
+    pub fn add_iia_contributions_deduction(&mut self, amount: Decimal) -> EmptyResult {
+        self.add_investment_deduction(InvestmentDeduction::IIA_CONTRIBUTIONS_CODE, amount)
+    }
+
+    fn add_investment_deduction(&mut self, code: Integer, amount: Decimal) -> EmptyResult {
+        self.get_mut_record(InvestmentDeductions::RECORD_NAME)?
+            .map(|record: &mut InvestmentDeductions| &mut record.deductions)
+            .ok_or("Investment deductions must be enabled in the tax statement")?
+            .push(InvestmentDeduction {code, amount});
+        Ok(())
+    }
+
+    pub fn add_loss_carryforward(&mut self, amount: Decimal) -> EmptyResult {
+        self.get_mut_record(SecuritiesDeductions::RECORD_NAME)?
+            .map(|record: &mut SecuritiesDeductions| &mut record.losses)
+            .ok_or("Securities deductions must be enabled in the tax statement")?
+            .push(CarriedForwardLoss {amount});
+        Ok(())
+    }
+
+    fn investment_deductions(&self) -> GenericResult<&Vec<InvestmentDeduction>> {
+        Ok(self.get_record(InvestmentDeductions::RECORD_NAME)?
+            .map(|record: &InvestmentDeductions| &record.deductions)
+            .ok_or("Investment deductions must be enabled in the tax statement")?)
+    }
+
+    fn carried_forward_losses(&self) -> GenericResult<&Vec<CarriedForwardLoss>> {
+        Ok(self.get_record(SecuritiesDeductions::RECORD_NAME)?
+            .map(|record: &SecuritiesDeductions| &record.losses)
+            .ok_or("Securities deductions must be enabled in the tax statement")?)
+    }
+
+    fn foreign_incomes(&self) -> GenericResult<&Vec<CurrencyIncome>> {
+        Ok(self.get_record(ForeignIncome::RECORD_NAME)?
+            .map(|record: &ForeignIncome| &record.incomes)
+            .ok_or("Foreign income must be enabled in the tax statement")?)
+    }
+
     fn get_foreign_incomes(&mut self) -> GenericResult<&mut Vec<CurrencyIncome>> {
         Ok(self.get_mut_record(ForeignIncome::RECORD_NAME)?
             .map(|record: &mut ForeignIncome| &mut record.incomes)
             .ok_or("Foreign income must be enabled in the tax statement")?)
     }
 
+    fn get_record<T: 'static + Record>(&self, name: &str) -> GenericResult<Option<&T>> {
+        let mut found_record = None;
+
+        for record in &self.records {
+            if record.name() != name {
+                continue;
+            }
+
+            if found_record.is_some() {
+                return Err!("The statement has several {} records", name);
+            }
+
+            found_record = Some(record);
+        }
+
+        Ok(match found_record {
+            Some(record) => Some(
+                record.as_any().downcast_ref::<T>().ok_or_else(|| format!(
+                    "Failed to cast {} record to the underlaying type", name))?),
+            None => None,
+        })
+    }
+
     fn get_mut_record<T: 'static + Record>(&mut self, name: &str) -> GenericResult<Option<&mut T>> {
         let mut found_record = None;
 
@@ -13,9 +13,11 @@ use regex::Regex;
 use crate::core::{EmptyResult, GenericResult};
 #[cfg(test)] use crate::util;
 
-use super::TaxStatement;
+use super::{TaxStatement, Format};
 use super::record::{Record, UnknownRecord, is_record_name};
 use super::encoding::{TaxStatementType, TaxStatementPrimitiveType};
+#[cfg(test)] use super::deductions::{InvestmentDeduction, CarriedForwardLoss};
+use super::deductions::{InvestmentDeductions, SecuritiesDeductions};
 use super::foreign_income::ForeignIncome;
 
 const SUPPORTED_YEAR: i32 = 2019;
@@ -26,23 +28,8 @@ pub struct TaxStatementReader {
 }
 
 impl TaxStatementReader {
-    pub fn read(path: &str) -> GenericResult<TaxStatement> {
-        lazy_static! {
-            static ref EXTENSION_REGEX: Regex = Regex::new(r"\.dc(\d)$").unwrap();
-        }
-
-        let year = EXTENSION_REGEX.captures(path)
-            .and_then(|captures| captures.get(1).unwrap().as_str().parse::<u8>().ok())
-            .ok_or_else(||"Invalid tax statement file extension: *.dcX is expected")?;
-        let year = 2010 + i32::from(year);
-
-        if year != SUPPORTED_YEAR {
-            warn!(concat!(
-                "Only *{} tax statements ({} year) are supported by the program. ",
-                "Reading or writing tax statements for other years may have issues or won't work ",
-                "at all."
-            ), get_extension(SUPPORTED_YEAR), SUPPORTED_YEAR);
-        }
+    pub fn read(path: &str, raw: bool) -> GenericResult<TaxStatement> {
+        let year = get_year(path)?;
 
         let mut reader = TaxStatementReader {
             file: BufReader::new(File::open(path)?),
@@ -75,7 +62,9 @@ impl TaxStatementReader {
             };
 
             let record: Box<dyn Record> = match record_name.as_str() {
-                ForeignIncome::RECORD_NAME => Box::new(ForeignIncome::read(&mut reader)?),
+                ForeignIncome::RECORD_NAME if !raw => Box::new(ForeignIncome::read(&mut reader)?),
+                SecuritiesDeductions::RECORD_NAME if !raw => Box::new(SecuritiesDeductions::read(&mut reader)?),
+                InvestmentDeductions::RECORD_NAME if !raw => Box::new(InvestmentDeductions::read(&mut reader)?),
                 _ => {
                     let (record, read_next_record_name) = UnknownRecord::read(&mut reader, record_name)?;
                     next_record_name = read_next_record_name;
@@ -93,6 +82,7 @@ impl TaxStatementReader {
         let statement = TaxStatement {
             path: path.to_owned(),
             year: year,
+            format: Format::Declaration,
             records: records,
         };
         debug!("Read statement:\n{:#?}", statement);
@@ -210,6 +200,28 @@ impl TaxStatementWriter {
     }
 }
 
+/// Returns tax year of the statement by its file extension.
+pub fn get_year(path: &str) -> GenericResult<i32> {
+    lazy_static! {
+        static ref EXTENSION_REGEX: Regex = Regex::new(r"\.dc(\d)$").unwrap();
+    }
+
+    let year = EXTENSION_REGEX.captures(path)
+        .and_then(|captures| captures.get(1).unwrap().as_str().parse::<u8>().ok())
+        .ok_or_else(||"Invalid tax statement file extension: *.dcX is expected")?;
+    let year = 2010 + i32::from(year);
+
+    if year != SUPPORTED_YEAR {
+        warn!(concat!(
+            "Only *{} tax statements ({} year) are supported by the program. ",
+            "Reading or writing tax statements for other years may have issues or won't work ",
+            "at all."
+        ), get_extension(SUPPORTED_YEAR), SUPPORTED_YEAR);
+    }
+
+    Ok(year)
+}
+
 fn get_extension(year: i32) -> String {
     format!(".dc{}", year - 2010)
 }
@@ -229,6 +241,8 @@ fn encode(data: &str) -> GenericResult<Cow<[u8]>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::TaxpayerConfig;
+    use crate::localities::{CountryCode, Jurisdiction};
 
     #[test]
     fn parse_empty() {
@@ -243,12 +257,95 @@ mod tests {
 
         let data = get_contents(&path);
         let mut statement = test_parsing(&path);
-        let year = statement.year;
 
         let mut incomes = Vec::new();
         incomes.extend(statement.get_foreign_incomes().unwrap().drain(..));
         assert_eq!(incomes.len(), 4);
 
+        fill(&mut statement);
+        assert_eq!(*statement.get_foreign_incomes().unwrap(), incomes);
+        compare_to(&statement, &data);
+    }
+
+    #[test]
+    fn create() {
+        let path = Path::new(file!()).parent().unwrap().join(get_path("filled"))
+            .to_str().unwrap().to_owned();
+
+        let taxpayer = TaxpayerConfig {
+            last_name: String::new(),
+            first_name: String::new(),
+            middle_name: None,
+            inn: String::new(),
+            inspection_code: String::new(),
+            oktmo: None,
+        };
+
+        let mut statement = TaxStatement::new(
+            "mock.dc9", SUPPORTED_YEAR, &taxpayer, Format::Declaration,
+            date!(30, 12, SUPPORTED_YEAR)).unwrap();
+        assert!(statement.get_foreign_incomes().unwrap().is_empty());
+
+        fill(&mut statement);
+
+        // The test file has been created by Декларация program which fills personal document dates
+        // with placeholders
+        let data = get_contents(&path);
+        let expected_data = data.replacen(
+            "@PersonDocument00010000000000005328740005281260000000000010",
+            "@PersonDocument0001000000000000000000000000000010", 1);
+        assert_ne!(expected_data, data);
+
+        compare_to(&statement, &expected_data);
+    }
+
+    #[test]
+    fn deductions() {
+        let taxpayer = TaxpayerConfig {
+            last_name: String::new(),
+            first_name: String::new(),
+            middle_name: None,
+            inn: String::new(),
+            inspection_code: String::new(),
+            oktmo: None,
+        };
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join(format!("statement{}", get_extension(SUPPORTED_YEAR)));
+        let path = path.to_str().unwrap();
+
+        let mut statement = TaxStatement::new(
+            path, SUPPORTED_YEAR, &taxpayer, Format::Declaration,
+            date!(30, 12, SUPPORTED_YEAR)).unwrap();
+
+        statement.add_long_term_ownership_deduction(dec!(3000000)).unwrap();
+        statement.add_iia_contributions_deduction(dec!(52000)).unwrap();
+        statement.add_loss_carryforward(dec!(1000)).unwrap();
+        statement.save().unwrap();
+
+        let statement = TaxStatementReader::read(path, false).unwrap();
+
+        assert_eq!(
+            statement.get_record(InvestmentDeductions::RECORD_NAME).unwrap()
+                .map(|record: &InvestmentDeductions| &record.deductions).unwrap(),
+            &vec![InvestmentDeduction {
+                code: InvestmentDeduction::LONG_TERM_OWNERSHIP_CODE,
+                amount: dec!(3000000),
+            }, InvestmentDeduction {
+                code: InvestmentDeduction::IIA_CONTRIBUTIONS_CODE,
+                amount: dec!(52000),
+            }],
+        );
+
+        assert_eq!(
+            statement.get_record(SecuritiesDeductions::RECORD_NAME).unwrap()
+                .map(|record: &SecuritiesDeductions| &record.losses).unwrap(),
+            &vec![CarriedForwardLoss {amount: dec!(1000)}],
+        );
+    }
+
+    fn fill(statement: &mut TaxStatement) {
+        let year = statement.year;
         let date = date!(1, 1, year);
         let currency = "USD"; // 840 - Доллар США
 
@@ -261,12 +358,13 @@ mod tests {
 
         // 1010 - Дивиденды
         statement.add_dividend_income(
-            "Дивиденд", date, currency, currency_rate,
+            "Дивиденд", CountryCode::Known(Jurisdiction::Usa), date, currency, currency_rate,
             amount, paid_tax, local_amount, local_paid_tax).unwrap();
 
         // 1530 - (01)Доходы от реализации ЦБ (обращ-ся на орг. рынке ЦБ)
         statement.add_stock_income(
-            "Акции", date, currency, currency_rate, amount, local_amount, purchase_local_cost).unwrap();
+            "Акции", CountryCode::Known(Jurisdiction::Usa), date, currency, currency_rate, amount,
+            local_amount, purchase_local_cost).unwrap();
 
         // 1011 - Проценты (за исключением процентов по облигациям с ипотечным покрытием, эмитированным до 01.01.2007)
         statement.add_interest_income(
@@ -276,9 +374,6 @@ mod tests {
         // 643 - Российский рубль
         statement.add_interest_income(
             "Проценты в рублях", date, "RUB", dec!(1), amount, amount).unwrap();
-
-        assert_eq!(*statement.get_foreign_incomes().unwrap(), incomes);
-        compare_to(&statement, &data);
     }
 
     #[test]
@@ -289,7 +384,7 @@ mod tests {
     fn test_parsing(path: &str) -> TaxStatement {
         let data = get_contents(path);
 
-        let statement = TaxStatementReader::read(path).unwrap();
+        let statement = TaxStatementReader::read(path, false).unwrap();
         assert_eq!(statement.year, SUPPORTED_YEAR);
         compare_to(&statement, &data);
 
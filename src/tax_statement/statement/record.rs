@@ -7,6 +7,7 @@ use super::parser::{TaxStatementReader, TaxStatementWriter};
 
 pub trait Record: Debug {
     fn name(&self) -> &str;
+    fn as_any(&self) -> &dyn Any;
     fn as_mut_any(&mut self) -> &mut dyn Any;
     fn write(&self, writer: &mut TaxStatementWriter) -> EmptyResult;
 }
@@ -18,6 +19,14 @@ pub struct UnknownRecord {
 }
 
 impl UnknownRecord {
+    pub fn new(name: String, fields: Vec<String>) -> UnknownRecord {
+        UnknownRecord {name, fields}
+    }
+
+    pub fn fields(&self) -> &[String] {
+        &self.fields
+    }
+
     pub fn read(reader: &mut TaxStatementReader, name: String) -> GenericResult<(UnknownRecord, Option<String>)> {
         let mut fields = Vec::new();
         let mut next_record_name = None;
@@ -47,6 +56,10 @@ impl Record for UnknownRecord {
         &self.name
     }
 
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
     fn as_mut_any(&mut self) -> &mut dyn Any {
         self
     }
@@ -92,6 +105,10 @@ macro_rules! tax_statement_record {
                 $name::RECORD_NAME
             }
 
+            fn as_any(&self) -> &::std::any::Any {
+                self
+            }
+
             fn as_mut_any(&mut self) -> &mut ::std::any::Any {
                 self
             }
@@ -0,0 +1,492 @@
+use std::cmp;
+
+use crate::config::TaxpayerConfig;
+use crate::core::{EmptyResult, GenericResult};
+use crate::currency;
+use crate::formatting::{self, xml::{self, XmlWriter}};
+use crate::localities::{self, Country};
+use crate::types::{Date, Decimal};
+
+use super::TaxStatement;
+use super::deductions::{CarriedForwardLoss, InvestmentDeduction};
+use super::foreign_income::{CurrencyIncome, IncomeType};
+
+// 3-НДФЛ form code by КНД (Russian classification of tax documentation)
+const FORM_CODE: &str = "1151020";
+const FORM_VERSION: &str = "5.05";
+
+// Document type prefix of the file ID
+const FILE_TYPE: &str = "NO_NDFL3";
+
+// Tax period code for a calendar year
+const TAX_PERIOD_CODE: &str = "34";
+
+// Statement is submitted to the tax inspection at the taxpayer's place of residence
+const SUBMISSION_PLACE_CODE: &str = "760";
+
+// Budget classification code (КБК) of personal income tax which is declared by the taxpayer
+// (ст. 228 НК РФ)
+const BUDGET_CLASSIFICATION_CODE: &str = "18210102030011000110";
+
+const TAX_RATE: &str = "13";
+
+/// Writes the statement in 3-НДФЛ XML format which is accepted by the tax service (for example,
+/// via taxpayer's personal account).
+pub fn write(
+    statement: &TaxStatement, taxpayer: &TaxpayerConfig, date: Date, path: &str,
+) -> EmptyResult {
+    let data = generate(statement, taxpayer, date)?;
+    xml::save(&data, path)
+}
+
+fn generate(statement: &TaxStatement, taxpayer: &TaxpayerConfig, date: Date) -> GenericResult<String> {
+    let oktmo = taxpayer.oktmo.clone().ok_or(
+        "Municipality code (ОКТМО) must be specified in taxpayer profile to generate 3-НДФЛ XML")?;
+
+    let incomes = statement.foreign_incomes()?;
+    let investment_deductions = statement.investment_deductions()?;
+    let losses = statement.carried_forward_losses()?;
+    let tax = calculate_tax(&localities::russia(), incomes, investment_deductions, losses);
+
+    let mut writer = XmlWriter::new()?;
+
+    writer.start("Файл", &[
+        ("ИдФайл", xml::get_file_id(FILE_TYPE, taxpayer, date)),
+        ("ВерсПрог", format!("investments {}", env!("CARGO_PKG_VERSION"))),
+        ("ВерсФорм", s!(FORM_VERSION)),
+    ])?;
+
+    writer.start("Документ", &[
+        ("КНД", s!(FORM_CODE)),
+        ("ДатаДок", formatting::format_date(date)),
+        ("НомКорр", s!("0")),
+        ("ОтчетГод", statement.year.to_string()),
+        ("Период", s!(TAX_PERIOD_CODE)),
+        ("КодНО", taxpayer.inspection_code.clone()),
+        ("ПоМесту", s!(SUBMISSION_PLACE_CODE)),
+    ])?;
+
+    writer.start("СвНП", &[("ИННФЛ", taxpayer.inn.clone())])?;
+    writer.empty("ФИО", &xml::get_taxpayer_name(taxpayer))?;
+    writer.end("СвНП")?;
+
+    writer.empty("Подписант", &[("ПрПодп", s!("1"))])?;
+
+    writer.start("НДФЛ3", &[])?;
+
+    // Раздел 1: tax to be paid to (or refunded from) the budget
+    writer.empty("Раздел1", &[
+        ("КБК", s!(BUDGET_CLASSIFICATION_CODE)),
+        ("ОКТМО", oktmo),
+        ("НалДопл", format_amount(tax.tax_to_pay)),
+        ("НалВозвр", s!("0")),
+    ])?;
+
+    // Раздел 2: tax base and tax calculation
+    writer.empty("Раздел2", &[
+        ("Ставка", s!(TAX_RATE)),
+        ("СумДох", format_amount(tax.income)),
+        ("СумВыч", format_amount(tax.deduction)),
+        ("НалБаза", format_amount(tax.tax_base)),
+        ("НалИсчисл", format_amount(tax.calculated_tax)),
+        ("НалУплИнГос", format_amount(tax.credited_tax)),
+        ("НалДопл", format_amount(tax.tax_to_pay)),
+    ])?;
+
+    let (mut total_income, mut total_deduction, mut total_paid_tax) = (dec!(0), dec!(0), dec!(0));
+
+    for income in incomes {
+        writer.empty("ДохИнГос", &get_income_attributes(income))?;
+
+        total_income += income.local_amount;
+        total_deduction += income.deduction.amount;
+        total_paid_tax += income.local_paid_tax;
+    }
+
+    writer.empty("ИтогДохИнГос", &[
+        ("КолДох", incomes.len().to_string()),
+        ("СумДох", format_amount(total_income)),
+        ("СумВыч", format_amount(total_deduction)),
+        ("НалУплРуб", format_amount(total_paid_tax)),
+    ])?;
+
+    for deduction in investment_deductions {
+        writer.empty("ИнвВыч", &[
+            ("КодВыч", deduction.code.to_string()),
+            ("СумВыч", format_amount(deduction.amount)),
+        ])?;
+    }
+
+    for loss in losses {
+        writer.empty("УбытПрошлЦБ", &[("СумУбыт", format_amount(loss.amount))])?;
+    }
+
+    writer.end("НДФЛ3")?;
+    writer.end("Документ")?;
+    writer.end("Файл")?;
+
+    writer.finish()
+}
+
+struct TaxCalculation {
+    income: Decimal,
+    deduction: Decimal,
+    tax_base: Decimal,
+    calculated_tax: Decimal,
+    credited_tax: Decimal,
+    tax_to_pay: Decimal,
+}
+
+impl TaxCalculation {
+    fn add(&mut self, country: &Country, tax_base: Decimal, paid_tax: Decimal) {
+        let tax_base = cmp::max(tax_base, dec!(0));
+        let calculated_tax = country.tax_to_pay(tax_base, None);
+        let tax_to_pay = country.tax_to_pay(tax_base, Some(paid_tax));
+
+        self.tax_base += tax_base;
+        self.calculated_tax += calculated_tax;
+        self.credited_tax += calculated_tax - tax_to_pay;
+        self.tax_to_pay += tax_to_pay;
+    }
+}
+
+// Profit of securities and derivatives trading is calculated as a whole, so losses of some trades
+// offset profit of others, and only this profit may be reduced by investment deductions and losses
+// carried forward from the previous years. Other incomes are taxed separately with credit of tax
+// paid abroad.
+fn calculate_tax(
+    country: &Country, incomes: &[CurrencyIncome], investment_deductions: &[InvestmentDeduction],
+    losses: &[CarriedForwardLoss],
+) -> TaxCalculation {
+    let mut tax = TaxCalculation {
+        income: dec!(0),
+        deduction: dec!(0),
+        tax_base: dec!(0),
+        calculated_tax: dec!(0),
+        credited_tax: dec!(0),
+        tax_to_pay: dec!(0),
+    };
+
+    let (mut trading_profit, mut trading_paid_tax) = (dec!(0), dec!(0));
+
+    for income in incomes {
+        let profit = income.local_amount - income.deduction.amount;
+        tax.income += income.local_amount;
+
+        match income.type_ {
+            IncomeType::Stock | IncomeType::Derivative => {
+                trading_profit += profit;
+                trading_paid_tax += income.local_paid_tax;
+            },
+            _ => tax.add(country, profit, income.local_paid_tax),
+        };
+    }
+
+    trading_profit -= investment_deductions.iter().map(|deduction| deduction.amount).sum::<Decimal>();
+    trading_profit -= losses.iter().map(|loss| loss.amount).sum::<Decimal>();
+    tax.add(country, trading_profit, trading_paid_tax);
+
+    tax.deduction = tax.income - tax.tax_base;
+    tax
+}
+
+fn get_income_attributes(income: &CurrencyIncome) -> Vec<(&'static str, String)> {
+    let country_code = income.county_code.oksm_code();
+
+    let currency = &income.currency;
+    let income_date_rate = currency.income_date_rate / Decimal::from(currency.income_date_units);
+    let tax_payment_date_rate =
+        currency.tax_payment_date_rate / Decimal::from(currency.tax_payment_date_units);
+
+    let mut attributes = vec![
+        ("КодДох", income.type_.code().to_string()),
+        ("НаимИст", income.description.clone()),
+        ("КодСтранИст", format!("{:03}", country_code)),
+        ("ДатаДох", formatting::format_date(income.date)),
+        ("ДатаУплНал", formatting::format_date(income.tax_payment_date)),
+        ("КодВал", format!("{:03}", currency.code)),
+        ("КурсДох", income_date_rate.normalize().to_string()),
+        ("КурсНал", tax_payment_date_rate.normalize().to_string()),
+        ("СумДохВал", format_amount(income.amount)),
+        ("СумДохРуб", format_amount(income.local_amount)),
+        ("НалУплВал", format_amount(income.paid_tax)),
+        ("НалУплРуб", format_amount(income.local_paid_tax)),
+    ];
+
+    if income.deduction.code != 0 {
+        attributes.push(("КодВыч", income.deduction.code.to_string()));
+        attributes.push(("СумВыч", format_amount(income.deduction.amount)));
+    }
+
+    attributes
+}
+
+fn format_amount(amount: Decimal) -> String {
+    currency::round(amount).normalize().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::formatting::xml::{
+        ElementSchema, AMOUNT, DATE, INN, INSPECTION_CODE, NAME, OKSM_CODE, OKV_CODE,
+        PROGRAM_VERSION, RATE, YEAR};
+    use crate::localities::{CountryCode, Jurisdiction};
+
+    use super::*;
+    use super::super::Format;
+
+    // Elements and attributes of 3-НДФЛ format (КНД 1151020, version 5.05) which may be emitted by
+    // the writer with their value restrictions. The published XSD can't be downloaded by the tests
+    // (they must work offline), so it's transcribed here and must be kept in sync with the format
+    // updates.
+    const SCHEMA: &[ElementSchema] = &[
+        ElementSchema {
+            name: "Файл", parent: None, required: true, multiple: false,
+            attributes: &[
+                ("ИдФайл", true, concat!(
+                    r"NO_NDFL3_\d{4}_\d{4}_\d{12}_\d{8}_",
+                    r"[0-9A-F]{8}-[0-9A-F]{4}-[0-9A-F]{4}-[0-9A-F]{4}-[0-9A-F]{12}")),
+                ("ВерсПрог", true, PROGRAM_VERSION),
+                ("ВерсФорм", true, r"5\.05"),
+            ],
+        },
+        ElementSchema {
+            name: "Документ", parent: Some("Файл"), required: true, multiple: false,
+            attributes: &[
+                ("КНД", true, "1151020"),
+                ("ДатаДок", true, DATE),
+                ("НомКорр", true, r"\d{1,3}"),
+                ("ОтчетГод", true, YEAR),
+                ("Период", true, "34"),
+                ("КодНО", true, INSPECTION_CODE),
+                ("ПоМесту", true, "760"),
+            ],
+        },
+        ElementSchema {
+            name: "СвНП", parent: Some("Документ"), required: true, multiple: false,
+            attributes: &[
+                ("ИННФЛ", true, INN),
+            ],
+        },
+        ElementSchema {
+            name: "ФИО", parent: Some("СвНП"), required: true, multiple: false,
+            attributes: &[
+                ("Фамилия", true, NAME),
+                ("Имя", true, NAME),
+                ("Отчество", false, NAME),
+            ],
+        },
+        ElementSchema {
+            name: "Подписант", parent: Some("Документ"), required: true, multiple: false,
+            attributes: &[
+                ("ПрПодп", true, "[12]"),
+            ],
+        },
+        ElementSchema {
+            name: "НДФЛ3", parent: Some("Документ"), required: true, multiple: false,
+            attributes: &[],
+        },
+        ElementSchema {
+            name: "Раздел1", parent: Some("НДФЛ3"), required: true, multiple: false,
+            attributes: &[
+                ("КБК", true, r"\d{20}"),
+                ("ОКТМО", true, r"\d{8}|\d{11}"),
+                ("НалДопл", true, r"\d{1,15}"),
+                ("НалВозвр", true, r"\d{1,15}"),
+            ],
+        },
+        ElementSchema {
+            name: "Раздел2", parent: Some("НДФЛ3"), required: true, multiple: false,
+            attributes: &[
+                ("Ставка", true, r"\d{1,2}"),
+                ("СумДох", true, AMOUNT),
+                ("СумВыч", true, AMOUNT),
+                ("НалБаза", true, AMOUNT),
+                ("НалИсчисл", true, r"\d{1,15}"),
+                ("НалУплИнГос", true, r"\d{1,15}"),
+                ("НалДопл", true, r"\d{1,15}"),
+            ],
+        },
+        ElementSchema {
+            name: "ДохИнГос", parent: Some("НДФЛ3"), required: false, multiple: true,
+            attributes: &[
+                ("КодДох", true, r"\d{4}"),
+                ("НаимИст", true, r"[^\s].{0,999}"),
+                ("КодСтранИст", true, OKSM_CODE),
+                ("ДатаДох", true, DATE),
+                ("ДатаУплНал", true, DATE),
+                ("КодВал", true, OKV_CODE),
+                ("КурсДох", true, RATE),
+                ("КурсНал", true, RATE),
+                ("СумДохВал", true, AMOUNT),
+                ("СумДохРуб", true, AMOUNT),
+                ("НалУплВал", true, AMOUNT),
+                ("НалУплРуб", true, AMOUNT),
+                ("КодВыч", false, r"\d{3}"),
+                ("СумВыч", false, AMOUNT),
+            ],
+        },
+        ElementSchema {
+            name: "ИтогДохИнГос", parent: Some("НДФЛ3"), required: true, multiple: false,
+            attributes: &[
+                ("КолДох", true, r"\d{1,5}"),
+                ("СумДох", true, AMOUNT),
+                ("СумВыч", true, AMOUNT),
+                ("НалУплРуб", true, AMOUNT),
+            ],
+        },
+        ElementSchema {
+            name: "ИнвВыч", parent: Some("НДФЛ3"), required: false, multiple: true,
+            attributes: &[
+                ("КодВыч", true, r"\d{3}"),
+                ("СумВыч", true, AMOUNT),
+            ],
+        },
+        ElementSchema {
+            name: "УбытПрошлЦБ", parent: Some("НДФЛ3"), required: false, multiple: true,
+            attributes: &[
+                ("СумУбыт", true, AMOUNT),
+            ],
+        },
+    ];
+
+    #[test]
+    fn generation() {
+        let taxpayer = TaxpayerConfig {
+            last_name: s!("Иванов"),
+            first_name: s!("Иван"),
+            middle_name: None,
+            inn: s!("123456789012"),
+            inspection_code: s!("7701"),
+            oktmo: Some(s!("45383000")),
+        };
+
+        let year = 2019;
+        let date = date!(1, 1, year);
+        let mut statement = TaxStatement::new(
+            "mock.xml", year, &taxpayer, Format::Xml(taxpayer.clone()), date).unwrap();
+
+        statement.add_dividend_income(
+            "Дивиденд", CountryCode::Known(Jurisdiction::Usa), date, "USD", dec!(69.4706),
+            dec!(100), dec!(10), dec!(6947.06), dec!(694.71)).unwrap();
+
+        statement.add_stock_income(
+            "Акции", CountryCode::Known(Jurisdiction::Usa), date, "USD", dec!(69.4706), dec!(100),
+            dec!(6947.06), dec!(10)).unwrap();
+
+        statement.add_long_term_ownership_deduction(dec!(1000)).unwrap();
+        statement.add_loss_carryforward(dec!(500)).unwrap();
+
+        let data = generate(&statement, &taxpayer, date!(15, 1, year + 1)).unwrap();
+        let elements = xml::parse(&data);
+        xml::validate(&elements, SCHEMA);
+
+        let get = |name: &str, index: usize| xml::get_attributes(&elements, name, index);
+
+        assert!(get("Файл", 0)["ИдФайл"].starts_with("NO_NDFL3_7701_7701_123456789012_20200115_"));
+        assert_eq!(get("Документ", 0)["ОтчетГод"], "2019");
+        assert_eq!(get("Документ", 0)["ДатаДок"], "15.01.2020");
+        assert_eq!(get("ФИО", 0)["Фамилия"], "Иванов");
+        assert!(!get("ФИО", 0).contains_key("Отчество"));
+
+        let payment = get("Раздел1", 0);
+        assert_eq!(payment["КБК"], "18210102030011000110");
+        assert_eq!(payment["ОКТМО"], "45383000");
+        assert_eq!(payment["НалДопл"], "915");
+        assert_eq!(payment["НалВозвр"], "0");
+
+        // Dividend: 903 calculated - 695 paid abroad = 208
+        // Stock: 6947.06 - 10 - 1000 - 500 = 5437.06 -> 707
+        let calculation = get("Раздел2", 0);
+        assert_eq!(calculation["СумДох"], "13894.12");
+        assert_eq!(calculation["СумВыч"], "1510");
+        assert_eq!(calculation["НалБаза"], "12384.12");
+        assert_eq!(calculation["НалИсчисл"], "1610");
+        assert_eq!(calculation["НалУплИнГос"], "695");
+        assert_eq!(calculation["НалДопл"], "915");
+
+        let dividend = get("ДохИнГос", 0);
+        assert_eq!(dividend["КодДох"], "1010");
+        assert_eq!(dividend["КодСтранИст"], "840");
+        assert_eq!(dividend["ДатаДох"], "01.01.2019");
+        assert_eq!(dividend["КодВал"], "840");
+        assert_eq!(dividend["КурсДох"], "69.4706");
+        assert_eq!(dividend["СумДохРуб"], "6947.06");
+        assert_eq!(dividend["НалУплРуб"], "694.71");
+        assert!(!dividend.contains_key("КодВыч"));
+
+        let stock = get("ДохИнГос", 1);
+        assert_eq!(stock["КодДох"], "1530");
+        assert_eq!(stock["КодВыч"], "201");
+        assert_eq!(stock["СумВыч"], "10");
+
+        let total = get("ИтогДохИнГос", 0);
+        assert_eq!(total["КолДох"], "2");
+        assert_eq!(total["СумДох"], "13894.12");
+        assert_eq!(total["СумВыч"], "10");
+        assert_eq!(total["НалУплРуб"], "694.71");
+
+        let deduction = get("ИнвВыч", 0);
+        assert_eq!(deduction["КодВыч"], "618");
+        assert_eq!(deduction["СумВыч"], "1000");
+        assert_eq!(get("УбытПрошлЦБ", 0)["СумУбыт"], "500");
+
+        let taxpayer = TaxpayerConfig {oktmo: None, ..taxpayer};
+        assert!(generate(&statement, &taxpayer, date!(15, 1, year + 1)).is_err());
+    }
+
+    #[test]
+    fn trading_loss() {
+        let taxpayer = TaxpayerConfig {
+            last_name: s!("Иванов"),
+            first_name: s!("Иван"),
+            middle_name: None,
+            inn: s!("123456789012"),
+            inspection_code: s!("7701"),
+            oktmo: Some(s!("45383000")),
+        };
+
+        let year = 2019;
+        let date = date!(1, 1, year);
+        let mut statement = TaxStatement::new(
+            "mock.xml", year, &taxpayer, Format::Xml(taxpayer.clone()), date).unwrap();
+
+        statement.add_stock_income(
+            "Акции", CountryCode::Known(Jurisdiction::Usa), date, "USD", dec!(70), dec!(100),
+            dec!(7000), dec!(10000)).unwrap();
+
+        statement.add_derivative_income(
+            "ПФИ", date, "USD", dec!(70), dec!(100), dec!(7000), dec!(1000)).unwrap();
+
+        let data = generate(&statement, &taxpayer, date!(15, 1, year + 1)).unwrap();
+        let elements = xml::parse(&data);
+        xml::validate(&elements, SCHEMA);
+
+        // Derivatives profit is offset by the stock trading loss
+        let calculation = xml::get_attributes(&elements, "Раздел2", 0);
+        assert_eq!(calculation["СумДох"], "14000");
+        assert_eq!(calculation["СумВыч"], "11000");
+        assert_eq!(calculation["НалБаза"], "3000");
+        assert_eq!(calculation["НалИсчисл"], "390");
+        assert_eq!(calculation["НалДопл"], "390");
+    }
+
+    #[test]
+    fn existing_file() {
+        let taxpayer = TaxpayerConfig {
+            last_name: s!("Иванов"),
+            first_name: s!("Иван"),
+            middle_name: None,
+            inn: s!("123456789012"),
+            inspection_code: s!("7701"),
+            oktmo: Some(s!("45383000")),
+        };
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("statement.xml");
+        let path = path.to_str().unwrap();
+
+        TaxStatement::create(path, 2019, &taxpayer).unwrap().save().unwrap();
+        assert!(TaxStatement::create(path, 2019, &taxpayer).is_err());
+    }
+}
@@ -1,21 +1,26 @@
+use std::collections::BTreeMap;
+
 use chrono::Datelike;
 use static_table_derive::StaticTable;
 
-use crate::broker_statement::{BrokerStatement, StockSell, SellDetails, FifoDetails};
+use crate::broker_statement::{
+    BrokerStatement, StockSell, SellDetails, FifoDetails, DerivativeType};
 use crate::config::PortfolioConfig;
 use crate::core::EmptyResult;
 use crate::currency::Cash;
 use crate::currency::converter::CurrencyConverter;
 use crate::formatting::{self, table::Cell};
-use crate::localities::Country;
-use crate::taxes::TaxPaymentDay;
+use crate::localities::{Country, CountryCode, Jurisdiction};
+use crate::taxes::{TaxPaymentDay, LongTermOwnershipDeduction};
 use crate::types::{Date, Decimal};
 
+use super::losses::LossCarryforward;
 use super::statement::TaxStatement;
 
 pub fn process_income(
     portfolio: &PortfolioConfig, broker_statement: &BrokerStatement, year: Option<i32>,
     mut tax_statement: Option<&mut TaxStatement>, converter: &CurrencyConverter,
+    loss_carryforward: Option<&LossCarryforward>,
 ) -> EmptyResult {
     let country = portfolio.get_tax_country();
     let mut processor = TradesProcessor {
@@ -33,31 +38,210 @@ pub fn process_income(
         same_currency: true,
         stock_splits: false,
         total_local_profit: Cash::new(country.currency, dec!(0)),
+        bonds_local_profit: Cash::new(country.currency, dec!(0)),
+        long_term_ownership: LongTermOwnershipDeduction::default(),
+        applied_loss: Cash::new(country.currency, dec!(0)),
     };
 
-    let mut trade_id = 0;
+    let mut trades = Vec::new();
+    let mut yearly_results: BTreeMap<i32, (Decimal, LongTermOwnershipDeduction)> = BTreeMap::new();
 
     for trade in &broker_statement.stock_sells {
+        let details = trade.calculate(&country, converter)?;
+        let trade_year = trade.execution_date.year();
+
+        {
+            let (profit, long_term_ownership) = yearly_results.entry(trade_year).or_default();
+            *profit += details.local_profit.amount;
+            long_term_ownership.merge(&details.long_term_ownership);
+        }
+
         if let Some(year) = year {
-            if trade.execution_date.year() != year {
+            if trade_year != year {
                 continue;
             }
         }
 
+        trades.push((trade, details));
+    }
+
+    // Bonds form the same tax base as stocks, so their results are accounted in the loss ledger and
+    // the tax base totals too
+    for trade in &broker_statement.bond_sells {
         let details = trade.calculate(&country, converter)?;
-        processor.process_trade(trade_id, trade, &details)?;
+        let trade_year = trade.execution_date.year();
+
+        let (profit, _) = yearly_results.entry(trade_year).or_default();
+        *profit += details.local_profit.amount;
+
+        if year.is_none() || year == Some(trade_year) {
+            processor.bonds_local_profit.add_assign(details.local_profit).unwrap();
+        }
+    }
+
+    // Long-term ownership deduction is calculated for the whole tax year and losses are carried
+    // forward only when we calculate a particular tax year. The ledger is updated with results of
+    // all years which are covered by the broker statement.
+    let mut long_term_ownership_deduction = dec!(0);
+
+    if let Some(year) = year {
+        if let Some(loss_carryforward) = loss_carryforward {
+            for (&profit_year, (profit, long_term_ownership)) in yearly_results.range(..=year) {
+                let deduction = get_long_term_ownership_deduction(*profit, long_term_ownership);
+                loss_carryforward.save(profit_year, profit - deduction)?;
+            }
+        }
+
+        let (mut profit, long_term_ownership) = yearly_results.get(&year).copied().unwrap_or_default();
+        processor.long_term_ownership = long_term_ownership;
+
+        long_term_ownership_deduction = get_long_term_ownership_deduction(profit, &long_term_ownership);
+        profit -= long_term_ownership_deduction;
+
+        if let Some(loss_carryforward) = loss_carryforward {
+            if profit.is_sign_positive() {
+                let available_loss = loss_carryforward.get_available_loss(year)?;
+                processor.applied_loss.amount = std::cmp::min(available_loss, profit);
+            }
+            loss_carryforward.save_applied_loss(year, processor.applied_loss.amount)?;
+        }
+    }
+
+    for (trade_id, (trade, details)) in trades.iter().enumerate() {
+        processor.process_trade(trade_id, trade, details)?;
 
         if let Some(ref mut tax_statement) = tax_statement {
-            processor.add_income(tax_statement, trade, &details)?;
+            processor.add_income(tax_statement, trade, details)?;
+        }
+    }
+
+    // Long-term ownership deduction and the carried forward loss are applied to the whole tax base,
+    // so they are declared separately from the incomes
+    if let Some(ref mut tax_statement) = tax_statement {
+        if !long_term_ownership_deduction.is_zero() {
+            tax_statement.add_long_term_ownership_deduction(long_term_ownership_deduction)?;
         }
 
-        trade_id += 1;
+        if !processor.applied_loss.is_zero() {
+            tax_statement.add_loss_carryforward(processor.applied_loss.amount)?;
+        }
     }
 
-    if trade_id != 0 {
+    if !trades.is_empty() || !processor.applied_loss.is_zero() {
         processor.print();
     }
 
+    process_derivatives(portfolio, broker_statement, year, tax_statement, converter)
+}
+
+// The deduction can't make the total result negative
+fn get_long_term_ownership_deduction(profit: Decimal, long_term_ownership: &LongTermOwnershipDeduction) -> Decimal {
+    if profit.is_sign_negative() {
+        return dec!(0);
+    }
+    std::cmp::min(long_term_ownership.deduction(), profit)
+}
+
+#[derive(StaticTable)]
+#[table(name="DerivativesTable")]
+struct DerivativeRow {
+    #[column(name="Дата сделки")]
+    conclusion_date: Date,
+    #[column(name="Дата расчета")]
+    execution_date: Date,
+    #[column(name="Инструмент")]
+    security: String,
+    #[column(name="Тип")]
+    type_: String,
+    #[column(name="Кол.")]
+    quantity: Decimal,
+    #[column(name="Цена")]
+    price: Cash,
+    #[column(name="Доход")]
+    revenue: Cash,
+    #[column(name="Доход (руб)")]
+    local_revenue: Cash,
+    #[column(name="Комиссия\n(руб)")]
+    local_commission: Cash,
+    #[column(name="Общие\nзатраты")]
+    total_local_cost: Cash,
+    #[column(name="Прибыль")]
+    local_profit: Cash,
+    #[column(name="Налог")]
+    tax_to_pay: Cash,
+}
+
+// Derivatives (ПФИ) form a separate tax base, so they are reported separately from stocks and their
+// losses aren't offset against stock trading profit
+fn process_derivatives(
+    portfolio: &PortfolioConfig, broker_statement: &BrokerStatement, year: Option<i32>,
+    mut tax_statement: Option<&mut TaxStatement>, converter: &CurrencyConverter,
+) -> EmptyResult {
+    let country = portfolio.get_tax_country();
+    let broker_name = &broker_statement.broker.name;
+
+    let mut table = DerivativesTable::new();
+    let mut total_local_profit = Cash::new(country.currency, dec!(0));
+
+    for trade in &broker_statement.derivative_trades {
+        if let Some(year) = year {
+            if trade.execution_date.year() != year {
+                continue;
+            }
+        }
+
+        if trade.get_closed().is_zero() {
+            continue;
+        }
+
+        let security = broker_statement.get_instrument_name(&trade.symbol);
+        let details = trade.calculate(&country, converter)?;
+        total_local_profit.add_assign(details.local_profit).unwrap();
+
+        table.add_row(DerivativeRow {
+            conclusion_date: trade.conclusion_date,
+            execution_date: trade.execution_date,
+            security: security.clone(),
+            type_: match trade.type_ {
+                DerivativeType::Option => "Опцион",
+                DerivativeType::Future => "Фьючерс",
+            }.to_owned(),
+            quantity: trade.get_closed(),
+            price: trade.price,
+            revenue: details.revenue,
+            local_revenue: details.local_revenue,
+            local_commission: details.local_commission,
+            total_local_cost: details.total_local_cost,
+            local_profit: details.local_profit,
+            tax_to_pay: details.tax_to_pay,
+        });
+
+        if let Some(ref mut tax_statement) = tax_statement {
+            let description = format!("{}: Закрытие позиции по {}", broker_name, security);
+
+            let precise_currency_rate = converter.precise_currency_rate(
+                trade.execution_date, details.revenue.currency, country.currency)?;
+
+            tax_statement.add_derivative_income(
+                &description, trade.execution_date, details.revenue.currency, precise_currency_rate,
+                details.revenue.amount, details.local_revenue.amount, details.total_local_cost.amount,
+            ).map_err(|e| format!(
+                "Unable to add income from {} position closing on {} to the tax statement: {}",
+                trade.symbol, formatting::format_date(trade.execution_date), e
+            ))?;
+        }
+    }
+
+    if !table.is_empty() {
+        let mut totals = table.add_empty_row();
+        totals.set_local_profit(total_local_profit);
+        totals.set_tax_to_pay(Cash::new(
+            country.currency, country.tax_to_pay(total_local_profit.amount, None)));
+
+        table.print(&format!(
+            "Расчет прибыли по операциям с ПФИ, полученной через {}", broker_name));
+    }
+
     Ok(())
 }
 
@@ -76,6 +260,9 @@ struct TradesProcessor<'a> {
     same_currency: bool,
     stock_splits: bool,
     total_local_profit: Cash,
+    bonds_local_profit: Cash,
+    long_term_ownership: LongTermOwnershipDeduction,
+    applied_loss: Cash,
 }
 
 #[derive(StaticTable)]
@@ -150,20 +337,26 @@ struct FifoRow {
     local_commission: Cash,
     #[column(name="Общие затраты")]
     total_local_cost: Cash,
+    #[column(name="ЛДВ")]
+    long_term_ownership: Option<String>,
 }
 
 impl<'a> TradesProcessor<'a> {
-    fn add_income(&self, tax_statement: &mut TaxStatement, trade: &StockSell, details: &SellDetails) -> EmptyResult {
+    fn add_income(
+        &self, tax_statement: &mut TaxStatement, trade: &StockSell, details: &SellDetails,
+    ) -> EmptyResult {
         let name = self.broker_statement.get_instrument_name(&trade.symbol);
         let description = format!("{}: Продажа {}", self.broker_statement.broker.name, name);
 
         let precise_currency_rate = self.converter.precise_currency_rate(
             trade.execution_date, details.revenue.currency, self.country.currency)?;
 
+        // Stock issuer country isn't provided by broker statements, so stocks are declared as
+        // US ones
         tax_statement.add_stock_income(
-            &description, trade.execution_date, details.revenue.currency, precise_currency_rate,
-            details.revenue.amount, details.local_revenue.amount,
-            details.total_local_cost.amount
+            &description, CountryCode::Known(Jurisdiction::Usa), trade.execution_date,
+            details.revenue.currency, precise_currency_rate, details.revenue.amount,
+            details.local_revenue.amount, details.total_local_cost.amount,
         ).map_err(|e| format!(
             "Unable to add income from selling {} on {} to the tax statement: {}",
             trade.symbol, formatting::format_date(trade.execution_date), e
@@ -245,6 +438,11 @@ impl<'a> TradesProcessor<'a> {
             commission: buy_trade.commission,
             local_commission: buy_trade.local_commission,
             total_local_cost: buy_trade.total_local_cost,
+            long_term_ownership: if buy_trade.long_term_ownership {
+                Some(format!("{} г.", buy_trade.ownership_years))
+            } else {
+                None
+            },
         });
 
         Ok(())
@@ -278,8 +476,39 @@ impl<'a> TradesProcessor<'a> {
             self.fifo_table.hide_multiplier()
         }
 
+        let mut total_local_profit = self.total_local_profit;
+
+        // Long-term ownership deduction and the carried forward loss are applied to the tax base
+        // which includes bonds, so they are shown here to make the totals match
+        if !self.bonds_local_profit.is_zero() {
+            let mut bonds = self.trades_table.add_empty_row();
+            bonds.set_security("Облигации");
+            bonds.set_local_profit(self.bonds_local_profit);
+            total_local_profit.add_assign(self.bonds_local_profit).unwrap();
+        }
+
+        if self.long_term_ownership.is_empty() {
+            self.fifo_table.hide_long_term_ownership();
+        } else {
+            let deduction = get_long_term_ownership_deduction(
+                total_local_profit.amount, &self.long_term_ownership);
+
+            let mut row = self.trades_table.add_empty_row();
+            row.set_security(format!(
+                "Вычет ЛДВ (лимит {})", Cash::new(self.country.currency, self.long_term_ownership.limit())));
+            row.set_local_profit(Cash::new(self.country.currency, -deduction));
+            total_local_profit.amount -= deduction;
+        }
+
+        if !self.applied_loss.is_zero() {
+            let mut loss = self.trades_table.add_empty_row();
+            loss.set_security("Убытки прошлых лет");
+            loss.set_local_profit(-self.applied_loss);
+            total_local_profit.sub_assign(self.applied_loss).unwrap();
+        }
+
         let mut totals = self.trades_table.add_empty_row();
-        totals.set_local_profit(self.total_local_profit);
+        totals.set_local_profit(total_local_profit);
 
         let show_net_tax = match self.portfolio.tax_payment_day {
             TaxPaymentDay::Day {..} => self.year.is_some(),
@@ -287,7 +516,7 @@ impl<'a> TradesProcessor<'a> {
         };
 
         if show_net_tax {
-            let tax_to_pay = self.country.tax_to_pay(self.total_local_profit.amount, None);
+            let tax_to_pay = self.country.tax_to_pay(total_local_profit.amount, None);
             totals.set_tax_to_pay(Cash::new(self.country.currency, tax_to_pay));
         }
 
@@ -295,6 +524,8 @@ impl<'a> TradesProcessor<'a> {
             "Расчет прибыли от продажи ценных бумаг, полученной через {}",
             self.broker_statement.broker.name));
 
-        self.fifo_table.print("Детализация расчета сделок по ФИФО");
+        if !self.fifo_table.is_empty() {
+            self.fifo_table.print("Детализация расчета сделок по ФИФО");
+        }
     }
 }
\ No newline at end of file
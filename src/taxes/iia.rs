@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+
+use chrono::Datelike;
+use serde::Deserialize;
+
+use crate::config::IiaConfig;
+use crate::core::GenericResult;
+use crate::currency::CashAssets;
+use crate::formatting;
+use crate::localities::Country;
+use crate::types::{Date, Decimal};
+
+// Individual investment account (ИИС) must be owned for at least 3 years to get any of the
+// deductions
+const IIA_MIN_YEARS: i32 = 3;
+
+// Maximum annual contributions amount which may be used as a base for type A deduction
+const IIA_TYPE_A_DEDUCTION_BASE_LIMIT: u32 = 400_000;
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum IiaType {
+    // Deduction for the contributions
+    #[serde(rename = "A")]
+    A,
+
+    // Deduction for the income from trading operations
+    #[serde(rename = "B")]
+    B,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ContributionDeduction {
+    pub year: i32,
+    pub contributions: Decimal,
+    pub base: Decimal,
+    pub deduction: Decimal,
+}
+
+impl ContributionDeduction {
+    /// Returns an approximate date when the deduction is going to be received: tax statement is
+    /// submitted at the beginning of the next year, checked during up to 3 months and the tax is
+    /// refunded during a month after that.
+    pub fn get_payment_date(&self) -> Date {
+        Date::from_ymd(self.year + 1, 6, 1)
+    }
+}
+
+/// Returns the minimum date when the account may be closed without losing the deductions.
+pub fn get_min_close_date(iia: &IiaConfig) -> Date {
+    let year = iia.open_date.year() + IIA_MIN_YEARS;
+
+    match Date::from_ymd_opt(year, iia.open_date.month(), iia.open_date.day()) {
+        Some(date) => date,
+        None => Date::from_ymd(year, 3, 1), // February 29
+    }
+}
+
+/// Returns true if income from trading operations of the account closed (or going to be closed)
+/// at the specified date is exempt from taxation.
+pub fn is_trading_income_exempt(iia: &IiaConfig, close_date: Date) -> bool {
+    iia.type_ == IiaType::B && close_date >= get_min_close_date(iia)
+}
+
+/// Calculates type A deductions for the account contributions. The deductions are returned only
+/// if they are not going to be lost due to early account closing.
+pub fn get_contribution_deductions(
+    iia: &IiaConfig, country: &Country, cash_flows: &[CashAssets],
+) -> GenericResult<Vec<ContributionDeduction>> {
+    if iia.type_ != IiaType::A {
+        return Ok(Vec::new());
+    }
+
+    if let Some(close_date) = iia.close_date {
+        if close_date < get_min_close_date(iia) {
+            return Ok(Vec::new());
+        }
+    }
+
+    let mut yearly_contributions: BTreeMap<i32, Decimal> = BTreeMap::new();
+
+    for cash_flow in cash_flows {
+        // Partial withdrawals are prohibited, so the withdrawal is only possible on account closing
+        if !cash_flow.cash.is_positive() {
+            continue;
+        }
+
+        if cash_flow.cash.currency != country.currency {
+            return Err!(
+                "Got an unexpected individual investment account contribution from {}: {}",
+                formatting::format_date(cash_flow.date), cash_flow.cash);
+        }
+
+        *yearly_contributions.entry(cash_flow.date.year()).or_default() += cash_flow.cash.amount;
+    }
+
+    Ok(yearly_contributions.into_iter().map(|(year, contributions)| {
+        let base = std::cmp::min(contributions, Decimal::from(IIA_TYPE_A_DEDUCTION_BASE_LIMIT));
+        let mut deduction = country.tax_to_pay(base, None);
+
+        // The deduction is a refund of the paid tax, so it can't exceed it
+        if let Some(&paid_tax) = iia.paid_taxes.get(&year) {
+            deduction = std::cmp::min(deduction, paid_tax);
+        }
+
+        ContributionDeduction {year, contributions, base, deduction}
+    }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::*;
+    use crate::localities;
+
+    #[test]
+    fn contribution_deductions() {
+        let country = localities::russia();
+        let cash_flows = [
+            CashAssets::new(date!(1, 3, 2017), "RUB", dec!(100000)),
+            CashAssets::new(date!(1, 6, 2017), "RUB", dec!(50000)),
+            CashAssets::new(date!(1, 2, 2018), "RUB", dec!(1000000)),
+            CashAssets::new(date!(1, 4, 2020), "RUB", dec!(-1300000)),
+        ];
+
+        let mut iia = IiaConfig {
+            type_: IiaType::A,
+            open_date: date!(1, 3, 2017),
+            close_date: None,
+            paid_taxes: HashMap::new(),
+        };
+
+        assert_eq!(get_contribution_deductions(&iia, &country, &cash_flows).unwrap(), vec![
+            ContributionDeduction {
+                year: 2017, contributions: dec!(150000), base: dec!(150000), deduction: dec!(19500),
+            },
+            ContributionDeduction {
+                year: 2018, contributions: dec!(1000000), base: dec!(400000), deduction: dec!(52000),
+            },
+        ]);
+
+        iia.close_date = Some(date!(28, 2, 2020));
+        assert!(get_contribution_deductions(&iia, &country, &cash_flows).unwrap().is_empty());
+
+        iia.close_date = Some(date!(1, 3, 2020));
+        assert_eq!(get_contribution_deductions(&iia, &country, &cash_flows).unwrap().len(), 2);
+
+        iia.paid_taxes.insert(2018, dec!(30000));
+        assert_eq!(get_contribution_deductions(&iia, &country, &cash_flows).unwrap()[1], ContributionDeduction {
+            year: 2018, contributions: dec!(1000000), base: dec!(400000), deduction: dec!(30000),
+        });
+    }
+
+    #[test]
+    fn trading_income_exemption() {
+        let mut iia = IiaConfig {
+            type_: IiaType::B,
+            open_date: date!(29, 2, 2016),
+            close_date: None,
+            paid_taxes: HashMap::new(),
+        };
+
+        assert!(!is_trading_income_exempt(&iia, date!(28, 2, 2019)));
+        assert!(is_trading_income_exempt(&iia, date!(1, 3, 2019)));
+
+        iia.type_ = IiaType::A;
+        assert!(!is_trading_income_exempt(&iia, date!(1, 3, 2019)));
+    }
+}
@@ -0,0 +1,229 @@
+use std::collections::{HashMap, HashSet};
+use std::default::Default;
+
+use chrono::Datelike;
+use lazy_static::lazy_static;
+
+use crate::core::EmptyResult;
+use crate::currency;
+use crate::formatting::format_date;
+use crate::localities::Country;
+use crate::types::{Date, Decimal};
+use crate::util;
+
+pub mod iia;
+
+#[derive(Debug, Clone, Copy)]
+pub enum TaxPaymentDay {
+    Day {month: u32, day: u32},
+    OnClose,
+}
+
+impl Default for TaxPaymentDay {
+    fn default() -> TaxPaymentDay {
+        TaxPaymentDay::Day {
+            month: 3,
+            day: 15,
+        }
+    }
+}
+
+impl TaxPaymentDay {
+    /// Returns an approximate date when tax is going to be paid for the specified income
+    pub fn get(&self, income_date: Date) -> Date {
+        lazy_static! {
+            static ref ACCOUNT_CLOSE_DATE: Date = Date::from_ymd(util::today().year() + 10, 1, 1);
+        }
+
+        match *self {
+            TaxPaymentDay::Day {month, day} => Date::from_ymd(income_date.year() + 1, month, day),
+            TaxPaymentDay::OnClose => *ACCOUNT_CLOSE_DATE,
+        }
+    }
+}
+
+pub struct TaxRemapping {
+    remapping: HashMap<(Date, String), (Date, bool)>
+}
+
+impl TaxRemapping {
+    pub fn new() -> TaxRemapping {
+        TaxRemapping {
+            remapping: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, date: Date, description: &str, to_date: Date) -> EmptyResult {
+        if self.remapping.insert((date, description.to_owned()), (to_date, false)).is_some() {
+            return Err!(
+                "Invalid tax remapping configuration: Duplicated match: {} - {:?}",
+                format_date(date), description);
+        }
+        Ok(())
+    }
+
+    pub fn map(&mut self, date: Date, description: &str) -> Date {
+        if let Some((to_date, mapped)) = self.remapping.get_mut(&(date, description.to_owned())) {
+            *mapped = true;
+            *to_date
+        } else {
+            date
+        }
+    }
+
+    pub fn ensure_all_mapped(&self) -> EmptyResult {
+        for ((date, description), (_, mapped)) in self.remapping.iter() {
+            if !mapped {
+                return Err!(
+                    "The following tax remapping rule hasn't been mapped to any tax: {} - {:?}",
+                    format_date(*date), description)
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct NetTaxCalculator {
+    country: Country,
+    tax_payment_day: TaxPaymentDay,
+    profit: HashMap<Date, Decimal>,
+}
+
+impl NetTaxCalculator {
+    pub fn new(country: Country, tax_payment_day: TaxPaymentDay) -> NetTaxCalculator {
+        NetTaxCalculator {
+            country,
+            tax_payment_day,
+            profit: HashMap::new(),
+        }
+    }
+
+    pub fn add_profit(&mut self, date: Date, amount: Decimal) {
+        let amount = currency::round(amount);
+        self.profit.entry(self.tax_payment_day.get(date))
+            .and_modify(|profit| *profit += amount)
+            .or_insert(amount);
+    }
+
+    pub fn get_taxes(&self) -> HashMap<Date, Decimal> {
+        let mut taxes = HashMap::new();
+        let mut years = HashSet::new();
+
+        for (&tax_payment_date, &profit) in self.profit.iter() {
+            let year = tax_payment_date.year();
+            assert!(years.insert(year)); // Ensure that we have only one tax payment date per year
+
+            let tax_to_pay = self.country.tax_to_pay(profit, None);
+            assert_eq!(taxes.insert(tax_payment_date, tax_to_pay), None);
+        }
+
+        taxes
+    }
+}
+
+// Long-term ownership deduction (ЛДВ) is applicable only to securities that have been acquired
+// starting from 2014 and owned for more than 3 years
+const LONG_TERM_OWNERSHIP_MIN_YEARS: i32 = 3;
+const LONG_TERM_OWNERSHIP_YEAR_LIMIT: u32 = 3_000_000;
+
+/// Returns the number of full years of ownership for the security.
+pub fn get_ownership_years(buy_date: Date, sell_date: Date) -> u32 {
+    let mut years = sell_date.year() - buy_date.year();
+    if (sell_date.month(), sell_date.day()) < (buy_date.month(), buy_date.day()) {
+        years -= 1;
+    }
+    std::cmp::max(years, 0) as u32
+}
+
+pub fn is_long_term_ownership(buy_date: Date, sell_date: Date) -> bool {
+    match get_long_term_ownership_date(buy_date) {
+        Some(date) => sell_date >= date,
+        None => false,
+    }
+}
+
+/// Returns the date starting from which the security becomes eligible for long-term ownership
+/// deduction or `None` if it can't be eligible at all.
+pub fn get_long_term_ownership_date(buy_date: Date) -> Option<Date> {
+    if buy_date < date!(1, 1, 2014) {
+        return None;
+    }
+
+    let year = buy_date.year() + LONG_TERM_OWNERSHIP_MIN_YEARS;
+
+    Some(match Date::from_ymd_opt(year, buy_date.month(), buy_date.day()) {
+        Some(date) => date.succ(),
+        None => Date::from_ymd(year, 3, 1), // February 29
+    })
+}
+
+/// Accumulates information about securities eligible for long-term ownership deduction.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LongTermOwnershipDeduction {
+    pub revenue: Decimal,
+    pub ownership_years: Decimal, // Sum of full ownership years multiplied by revenue
+    pub profit: Decimal,
+}
+
+impl LongTermOwnershipDeduction {
+    pub fn is_empty(&self) -> bool {
+        self.revenue.is_zero()
+    }
+
+    pub fn add(&mut self, revenue: Decimal, years: u32, profit: Decimal) {
+        self.revenue += revenue;
+        self.ownership_years += revenue * Decimal::from(years);
+        self.profit += profit;
+    }
+
+    pub fn merge(&mut self, other: &LongTermOwnershipDeduction) {
+        self.revenue += other.revenue;
+        self.ownership_years += other.ownership_years;
+        self.profit += other.profit;
+    }
+
+    /// Returns the deduction limit (in local currency): 3 million multiplied by the average number
+    /// of full ownership years weighted by revenue from selling of the securities.
+    pub fn limit(&self) -> Decimal {
+        if self.is_empty() {
+            return dec!(0);
+        }
+
+        currency::round(
+            Decimal::from(LONG_TERM_OWNERSHIP_YEAR_LIMIT) * self.ownership_years / self.revenue)
+    }
+
+    pub fn deduction(&self) -> Decimal {
+        if self.profit.is_sign_negative() {
+            return dec!(0);
+        }
+
+        std::cmp::min(self.limit(), self.profit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_term_ownership() {
+        assert_eq!(get_ownership_years(date!(10, 2, 2015), date!(9, 2, 2018)), 2);
+        assert_eq!(get_ownership_years(date!(10, 2, 2015), date!(10, 2, 2018)), 3);
+
+        assert!(!is_long_term_ownership(date!(10, 2, 2013), date!(10, 2, 2020)));
+        assert!(!is_long_term_ownership(date!(10, 2, 2015), date!(10, 2, 2018)));
+        assert!(is_long_term_ownership(date!(10, 2, 2015), date!(11, 2, 2018)));
+
+        assert_eq!(get_long_term_ownership_date(date!(10, 2, 2013)), None);
+        assert_eq!(get_long_term_ownership_date(date!(10, 2, 2015)), Some(date!(11, 2, 2018)));
+        assert_eq!(get_long_term_ownership_date(date!(29, 2, 2016)), Some(date!(1, 3, 2019)));
+
+        let mut deduction = LongTermOwnershipDeduction::default();
+        deduction.add(dec!(6000000), 3, dec!(5000000));
+        deduction.add(dec!(18000000), 4, dec!(6000000));
+        assert_eq!(deduction.limit(), dec!(11250000));
+        assert_eq!(deduction.deduction(), dec!(11000000));
+    }
+}